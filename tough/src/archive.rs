@@ -0,0 +1,186 @@
+//! Provides a [`Transport`] that reads a repository from a tar archive.
+
+use crate::error::{self, Result};
+use crate::{Transport, TransportError, TransportErrorKind};
+use snafu::ResultExt;
+use std::collections::HashMap;
+use std::fmt::{self, Debug, Formatter};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use url::Url;
+
+/// The first two bytes of a gzip stream.
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// The first four bytes of a zstd frame.
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+/// A [`Transport`] that serves files from a tar archive, such as one written by
+/// [`Repository::cache_to_tar`](crate::Repository::cache_to_tar), without extracting it to disk.
+///
+/// The archive is indexed once, when the `TarTransport` is created: the position and size of each
+/// file within it are kept in memory, and a file's contents are read from the archive when it is
+/// fetched. Files are requested with `tar://` URLs whose path is the path of the member within the
+/// archive, so the metadata and targets base URLs of a repository cached with `cache_to_tar` are
+/// [`TarTransport::metadata_url`] and [`TarTransport::targets_url`]. Because lookups are by member
+/// name, the filenames used by consistent snapshots work like any other. A file that is not in the
+/// archive results in a [`TransportErrorKind::FileNotFound`] error.
+#[derive(Clone)]
+pub struct TarTransport {
+    archive: Arc<Mutex<File>>,
+    index: Arc<HashMap<String, Member>>,
+}
+
+/// Where the contents of an archive member are within the archive.
+#[derive(Debug, Clone, Copy)]
+struct Member {
+    offset: u64,
+    size: u64,
+}
+
+impl TarTransport {
+    /// Reads an uncompressed tar archive from `reader`. Since `reader` can't be read again when
+    /// files are fetched, the archive is copied to a temporary file first.
+    pub fn from_reader<R: Read>(mut reader: R) -> Result<Self> {
+        let mut file = tempfile::tempfile().context(error::TarTransportRead)?;
+        io::copy(&mut reader, &mut file).context(error::TarTransportRead)?;
+        Self::from_file(file)
+    }
+
+    /// Reads a tar archive from the file at `path`. The archive may be compressed with gzip or
+    /// zstd, which is detected automatically; a compressed archive is decompressed to a temporary
+    /// file first. zstd needs tough's `zstd` feature, which the `http` feature also enables.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let file = File::open(path).context(error::TarTransportOpen { path })?;
+        let mut reader = BufReader::new(file);
+        let magic = reader
+            .fill_buf()
+            .context(error::TarTransportOpen { path })?;
+        if magic.starts_with(&GZIP_MAGIC) {
+            Self::from_reader(flate2::read::GzDecoder::new(reader))
+        } else if magic.starts_with(&ZSTD_MAGIC) {
+            Self::from_zstd(path, reader)
+        } else {
+            Self::from_file(reader.into_inner())
+        }
+    }
+
+    #[cfg(feature = "zstd")]
+    fn from_zstd(path: &Path, reader: BufReader<File>) -> Result<Self> {
+        Self::from_reader(
+            zstd::stream::read::Decoder::with_buffer(reader)
+                .context(error::TarTransportOpen { path })?,
+        )
+    }
+
+    #[cfg(not(feature = "zstd"))]
+    fn from_zstd(path: &Path, _reader: BufReader<File>) -> Result<Self> {
+        error::TarTransportZstd { path }.fail()
+    }
+
+    /// Indexes the uncompressed tar archive in `file`.
+    fn from_file(mut file: File) -> Result<Self> {
+        file.seek(SeekFrom::Start(0))
+            .context(error::TarTransportRead)?;
+        let mut index = HashMap::new();
+        let mut archive = tar::Archive::new(file);
+        for entry in archive.entries().context(error::TarTransportRead)? {
+            let entry = entry.context(error::TarTransportRead)?;
+            if !entry.header().entry_type().is_file() {
+                continue;
+            }
+            let path = entry.path().context(error::TarTransportRead)?;
+            let name = path.to_string_lossy().trim_start_matches("./").to_owned();
+            index.insert(
+                name,
+                Member {
+                    offset: entry.raw_file_position(),
+                    size: entry.size(),
+                },
+            );
+        }
+        Ok(Self {
+            archive: Arc::new(Mutex::new(archive.into_inner())),
+            index: Arc::new(index),
+        })
+    }
+
+    /// The metadata base URL of a repository archived with `cache_to_tar`.
+    #[allow(clippy::missing_panics_doc)]
+    pub fn metadata_url() -> Url {
+        // This is a constant, valid URL.
+        Url::parse("tar:///metadata/").unwrap()
+    }
+
+    /// The targets base URL of a repository archived with `cache_to_tar`.
+    #[allow(clippy::missing_panics_doc)]
+    pub fn targets_url() -> Url {
+        // This is a constant, valid URL.
+        Url::parse("tar:///targets/").unwrap()
+    }
+}
+
+impl Debug for TarTransport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TarTransport")
+            .field("files", &self.index.len())
+            .finish_non_exhaustive()
+    }
+}
+
+impl Transport for TarTransport {
+    fn fetch(&self, url: Url) -> std::result::Result<Box<dyn Read + Send>, TransportError> {
+        if url.scheme() != "tar" {
+            return Err(TransportError::new(
+                TransportErrorKind::UnsupportedUrlScheme,
+                url,
+            ));
+        }
+        let name = percent_encoding::percent_decode_str(url.path().trim_start_matches('/'))
+            .decode_utf8_lossy();
+        match self.index.get(name.as_ref()) {
+            Some(member) => Ok(Box::new(MemberReader {
+                archive: Arc::clone(&self.archive),
+                position: member.offset,
+                end: member.offset + member.size,
+            })),
+            None => Err(TransportError::new(TransportErrorKind::FileNotFound, url)),
+        }
+    }
+}
+
+/// Reads the contents of an archive member. The archive is shared by every reader the transport
+/// returns, so each read seeks to the reader's own position first.
+struct MemberReader {
+    archive: Arc<Mutex<File>>,
+    position: u64,
+    end: u64,
+}
+
+impl Read for MemberReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let remaining = self.end - self.position;
+        if remaining == 0 || buf.is_empty() {
+            return Ok(0);
+        }
+        // A reader that panicked while holding the lock can't have left the file in a state that
+        // matters, since every read seeks first.
+        let mut archive = self
+            .archive
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        archive.seek(SeekFrom::Start(self.position))?;
+        let n = (&mut *archive).take(remaining).read(buf)?;
+        if n == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "tar archive ended within a member",
+            ));
+        }
+        self.position += n as u64;
+        Ok(n)
+    }
+}
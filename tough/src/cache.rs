@@ -1,11 +1,106 @@
 use crate::error::{self, Result};
-use crate::fetch::{fetch_max_size, fetch_sha256};
-use crate::schema::{RoleType, Target};
-use crate::Repository;
-use snafu::{OptionExt, ResultExt};
+use crate::fetch::{fetch_hashes, fetch_max_size, GzipTransport};
+use crate::schema::decoded::{Decoded, Hex};
+use crate::schema::{Hashes, RoleType, Target};
+use crate::{safe_target_path, Repository, Transport};
+use percent_encoding::{utf8_percent_encode, AsciiSet, CONTROLS};
+use snafu::{ensure, OptionExt, ResultExt};
 use std::fs::OpenOptions;
 use std::io::{Read, Write};
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use url::Url;
+
+/// The characters that are percent-encoded in each `/`-separated part of a target's name to form its
+/// URL: those that the URL standard encodes in a path segment, plus `%`, so that it isn't read as
+/// an escape, `:`, so that the name isn't read as a URL scheme, and `\`, which some URL schemes
+/// treat as `/`.
+const TARGET_PATH_SEGMENT: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b':')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'\\')
+    .add(b'`')
+    .add(b'{')
+    .add(b'}');
+
+/// The destination of the files written by [`Repository::cache`] and its variants, so that all of
+/// them can share the logic that decides which files belong in the cached repository.
+trait CacheSink {
+    /// Writes a metadata file, which has already been read into memory.
+    fn write_metadata(&mut self, filename: &str, data: &[u8]) -> Result<()>;
+
+    /// Writes a target file of `length` bytes, streaming it from `reader`.
+    fn write_target(&mut self, filename: &str, length: u64, reader: &mut dyn Read) -> Result<()>;
+}
+
+/// Writes the cached repository to directories on the local filesystem.
+struct DirSink<'a> {
+    metadata_outdir: &'a Path,
+    targets_outdir: &'a Path,
+}
+
+impl CacheSink for DirSink<'_> {
+    fn write_metadata(&mut self, filename: &str, data: &[u8]) -> Result<()> {
+        let outpath = self.metadata_outdir.join(filename);
+        let mut file = std::fs::File::create(&outpath).context(error::CacheFileWrite {
+            path: outpath.clone(),
+        })?;
+        file.write_all(data)
+            .context(error::CacheFileWrite { path: outpath })
+    }
+
+    fn write_target(&mut self, filename: &str, _length: u64, reader: &mut dyn Read) -> Result<()> {
+        let path = self.targets_outdir.join(filename);
+        // Target names may contain path separators, so create any intermediate directories.
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .context(error::CacheDirectoryCreate { path: parent })?;
+        }
+        let mut f = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .open(&path)
+            .context(error::CacheTargetWrite { path: path.clone() })?;
+        let _ = std::io::copy(reader, &mut f).context(error::CacheTargetWrite { path })?;
+        Ok(())
+    }
+}
+
+/// Writes the cached repository as `metadata/` and `targets/` entries of a tar archive.
+struct TarSink<W: Write> {
+    builder: tar::Builder<W>,
+}
+
+impl<W: Write> TarSink<W> {
+    fn append(&mut self, path: PathBuf, size: u64, data: &mut dyn Read) -> Result<()> {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(size);
+        header.set_mode(0o644);
+        self.builder
+            .append_data(&mut header, &path, data)
+            .context(error::CacheTarWrite { path })
+    }
+}
+
+impl<W: Write> CacheSink for TarSink<W> {
+    fn write_metadata(&mut self, filename: &str, data: &[u8]) -> Result<()> {
+        let mut data = data;
+        self.append(
+            Path::new("metadata").join(filename),
+            data.len() as u64,
+            &mut data,
+        )
+    }
+
+    fn write_target(&mut self, filename: &str, length: u64, reader: &mut dyn Read) -> Result<()> {
+        self.append(Path::new("targets").join(filename), length, reader)
+    }
+}
 
 impl Repository {
     /// Cache an entire or partial repository to disk, including all required metadata.
@@ -36,23 +131,73 @@ impl Repository {
             path: targets_outdir.as_ref(),
         })?;
 
-        // Fetch targets and save them to the outdir
+        self.cache_impl(
+            &mut DirSink {
+                metadata_outdir: metadata_outdir.as_ref(),
+                targets_outdir: targets_outdir.as_ref(),
+            },
+            targets_subset,
+            cache_root_chain,
+        )
+    }
+
+    /// Cache an entire or partial repository to a tar archive, including all required metadata.
+    /// The archive contains `metadata/` and `targets/` directories laid out exactly like the
+    /// directories written by [`Repository::cache`], so once extracted it can be loaded with a
+    /// [`RepositoryLoader`](crate::RepositoryLoader) using filesystem URLs.
+    ///
+    /// Each target is verified against the repository metadata as it is added to the archive.
+    ///
+    /// The archive is not compressed; to compress it, wrap `writer` in an encoder of your choice.
+    /// `writer` is returned once the archive is complete so that such an encoder can be finished.
+    ///
+    /// * `targets_subset` is the list of targets to include in the archive. If no subset is
+    /// specified (`None`), then *all* targets are included.
+    /// * `cache_root_chain` specifies whether or not we will include all versions of `root.json`.
+    pub fn cache_to_tar<W, S>(
+        &self,
+        writer: W,
+        targets_subset: Option<&[S]>,
+        cache_root_chain: bool,
+    ) -> Result<W>
+    where
+        W: Write,
+        S: AsRef<str>,
+    {
+        let mut sink = TarSink {
+            builder: tar::Builder::new(writer),
+        };
+        self.cache_impl(&mut sink, targets_subset, cache_root_chain)?;
+        sink.builder.into_inner().context(error::CacheTarFinish)
+    }
+
+    /// Writes the requested targets and all metadata to `sink`.
+    fn cache_impl<S>(
+        &self,
+        sink: &mut dyn CacheSink,
+        targets_subset: Option<&[S]>,
+        cache_root_chain: bool,
+    ) -> Result<()>
+    where
+        S: AsRef<str>,
+    {
+        // Fetch targets and save them to the sink
         if let Some(target_list) = targets_subset {
             for target_name in target_list.iter() {
-                self.cache_target(&targets_outdir, target_name.as_ref())?;
+                self.cache_target(sink, target_name.as_ref())?;
             }
         } else {
             let targets = &self.targets.signed.targets_map();
             for target_name in targets.keys() {
-                self.cache_target(&targets_outdir, target_name)?;
+                self.cache_target(sink, target_name)?;
             }
         }
 
         // Cache all metadata
-        self.cache_metadata_impl(&metadata_outdir)?;
+        self.cache_metadata_impl(sink)?;
 
         if cache_root_chain {
-            self.cache_root_chain(&metadata_outdir)?;
+            self.cache_root_chain(sink)?;
         }
         Ok(())
     }
@@ -71,36 +216,38 @@ impl Repository {
             path: metadata_outdir.as_ref(),
         })?;
 
-        self.cache_metadata_impl(&metadata_outdir)?;
+        // Only metadata is written, so there is no targets directory.
+        let mut sink = DirSink {
+            metadata_outdir: metadata_outdir.as_ref(),
+            targets_outdir: metadata_outdir.as_ref(),
+        };
+        self.cache_metadata_impl(&mut sink)?;
 
         if cache_root_chain {
-            self.cache_root_chain(metadata_outdir)?;
+            self.cache_root_chain(&mut sink)?;
         }
         Ok(())
     }
 
     /// Cache repository metadata files, including delegated targets metadata
-    fn cache_metadata_impl<P>(&self, metadata_outdir: P) -> Result<()>
-    where
-        P: AsRef<Path>,
-    {
+    fn cache_metadata_impl(&self, sink: &mut dyn CacheSink) -> Result<()> {
         self.cache_file_from_transport(
             self.snapshot_filename().as_str(),
             self.max_snapshot_size()?,
             "timestamp.json",
-            &metadata_outdir,
+            sink,
         )?;
         self.cache_file_from_transport(
             self.targets_filename().as_str(),
             self.limits.max_targets_size,
             "max_targets_size argument",
-            &metadata_outdir,
+            sink,
         )?;
         self.cache_file_from_transport(
             "timestamp.json",
             self.limits.max_timestamp_size,
             "max_timestamp_size argument",
-            &metadata_outdir,
+            sink,
         )?;
 
         for name in self.targets.signed.role_names() {
@@ -109,7 +256,7 @@ impl Repository {
                     filename.as_str(),
                     self.limits.max_targets_size,
                     "max_targets_size argument",
-                    &metadata_outdir,
+                    sink,
                 )?;
             }
         }
@@ -118,17 +265,14 @@ impl Repository {
     }
 
     /// Cache all versions of root.json less than or equal to the current version.
-    fn cache_root_chain<P>(&self, outdir: P) -> Result<()>
-    where
-        P: AsRef<Path>,
-    {
+    fn cache_root_chain(&self, sink: &mut dyn CacheSink) -> Result<()> {
         for ver in (1..=self.root.signed.version.get()).rev() {
             let root_json_filename = format!("{}.root.json", ver);
             self.cache_file_from_transport(
                 root_json_filename.as_str(),
                 self.limits.max_root_size,
                 "max_root_size argument",
-                &outdir,
+                sink,
             )?;
         }
         Ok(())
@@ -159,8 +303,7 @@ impl Repository {
                 "{}.{}.json",
                 self.snapshot
                     .signed
-                    .meta
-                    .get(&format!("{}.json", name))?
+                    .meta_entry(&format!("{}.json", name))?
                     .version,
                 name
             ))
@@ -169,16 +312,22 @@ impl Repository {
         }
     }
 
-    /// Copies a file using `Transport` to `outdir`.
-    fn cache_file_from_transport<P: AsRef<Path>>(
+    /// Copies a metadata file using `Transport` to `sink`.
+    fn cache_file_from_transport(
         &self,
         filename: &str,
         max_size: u64,
         max_size_specifier: &'static str,
-        outdir: P,
+        sink: &mut dyn CacheSink,
     ) -> Result<()> {
+        let gzip_transport = GzipTransport::new(self.transport.as_ref());
+        let transport = if self.metadata_fetch.compressed {
+            &gzip_transport as &dyn Transport
+        } else {
+            self.transport.as_ref()
+        };
         let mut read = fetch_max_size(
-            self.transport.as_ref(),
+            transport,
             self.metadata_base_url
                 .join(filename)
                 .context(error::JoinUrl {
@@ -188,22 +337,19 @@ impl Repository {
             max_size,
             max_size_specifier,
         )?;
-        let outpath = outdir.as_ref().join(&filename);
-        let mut file = std::fs::File::create(&outpath).context(error::CacheFileWrite {
-            path: outpath.clone(),
-        })?;
         let mut root_file_data = Vec::new();
         read.read_to_end(&mut root_file_data)
             .context(error::CacheFileRead {
                 url: self.metadata_base_url.clone(),
             })?;
-        file.write_all(&root_file_data)
-            .context(error::CacheFileWrite { path: outpath })
+        sink.write_metadata(filename, &root_file_data)
     }
 
-    /// Saves a signed target to the specified `outdir`. Retains the digest-prepended filename if
-    /// consistent snapshots are used.
-    fn cache_target<P: AsRef<Path>>(&self, outdir: P, name: &str) -> Result<()> {
+    /// Saves a signed target to `sink`. Retains the digest-prepended filename if consistent
+    /// snapshots are used. Fails if the target name is not a safe relative path.
+    fn cache_target(&self, sink: &mut dyn CacheSink, name: &str) -> Result<()> {
+        // Refuse names that would be written outside of the targets directory.
+        safe_target_path(name)?;
         let t = self
             .targets
             .signed
@@ -211,66 +357,87 @@ impl Repository {
             .context(error::CacheTargetMissing {
                 target_name: name.to_owned(),
             })?;
-        let (sha, filename) = self.target_digest_and_filename(t, name);
-        let mut reader = self.fetch_target(t, &sha, filename.as_str())?;
-        let path = outdir.as_ref().join(filename);
-        let mut f = OpenOptions::new()
-            .write(true)
-            .create(true)
-            .open(&path)
-            .context(error::CacheTargetWrite { path: path.clone() })?;
-        let _ = std::io::copy(&mut reader, &mut f).context(error::CacheTargetWrite { path })?;
-        Ok(())
+        self.check_required_hashes(t, name)?;
+        let filename = self.target_filename(t, name);
+        let mut reader = self.fetch_target(t, filename.as_str())?;
+        sink.write_target(&filename, t.length, &mut reader)
     }
 
-    /// Gets the max size of the snapshot.json file as specified by the timestamp file.
+    /// Gets the max size of the snapshot.json file as specified by the timestamp file, or by
+    /// `Limits` if the timestamp file doesn't specify it.
     fn max_snapshot_size(&self) -> Result<u64> {
-        let snapshot_meta =
-            self.timestamp()
-                .signed
-                .meta
-                .get("snapshot.json")
-                .context(error::MetaMissing {
-                    file: "snapshot.json",
-                    role: RoleType::Timestamp,
-                })?;
-        Ok(snapshot_meta.length)
+        let snapshot_meta = self
+            .timestamp()
+            .signed
+            .meta_entry("snapshot.json")
+            .context(error::MetaMissing {
+                file: "snapshot.json",
+                role: RoleType::Timestamp,
+            })?;
+        Ok(snapshot_meta
+            .length
+            .unwrap_or(self.limits.max_snapshot_size))
     }
 
-    /// Prepends the target digest to the name if using consistent snapshots. Returns both the
-    /// digest and the filename.
-    pub(crate) fn target_digest_and_filename(
-        &self,
-        target: &Target,
-        name: &str,
-    ) -> (Vec<u8>, String) {
-        let sha256 = &target.hashes.sha256.clone().into_vec();
-        if self.consistent_snapshot {
-            (sha256.clone(), format!("{}.{}", hex::encode(sha256), name))
-        } else {
-            (sha256.clone(), name.to_owned())
+    /// Prepends a target digest to the name if using consistent snapshots. The SHA-256 digest is
+    /// used if there is one, then SHA-512, then whichever digest is listed first.
+    pub(crate) fn target_filename(&self, target: &Target, name: &str) -> String {
+        match preferred_digest(&target.hashes) {
+            Some(digest) if self.consistent_snapshot => format!("{}.{}", hex::encode(digest), name),
+            _ => name.to_owned(),
+        }
+    }
+
+    /// Returns the URL of a target's file, named by `target_filename`. The name is percent-encoded
+    /// for the URL, e.g. `hello world.txt` is fetched from `hello%20world.txt`, while files are
+    /// written under the name itself.
+    pub(crate) fn target_url(&self, filename: &str) -> Result<Url> {
+        let encoded = filename
+            .split('/')
+            .map(|segment| utf8_percent_encode(segment, TARGET_PATH_SEGMENT).to_string())
+            .collect::<Vec<_>>()
+            .join("/");
+        self.targets_base_url
+            .join(&encoded)
+            .context(error::JoinUrl {
+                path: filename,
+                url: self.targets_base_url.clone(),
+            })
+    }
+
+    /// Fails if the target doesn't list a hash from each of the algorithms set with
+    /// [`RepositoryLoader::require_hash_algorithms`](crate::RepositoryLoader::require_hash_algorithms).
+    pub(crate) fn check_required_hashes(&self, target: &Target, name: &str) -> Result<()> {
+        for algorithm in &self.hash_requirements.algorithms {
+            ensure!(
+                target.hashes.digests.contains_key(algorithm),
+                error::MissingRequiredHash {
+                    target: name,
+                    algorithm,
+                }
+            );
         }
+        Ok(())
     }
 
     /// Fetches the signed target using `Transport`. Aborts with error if the fetched target is
     /// larger than its signed size.
-    pub(crate) fn fetch_target(
-        &self,
-        target: &Target,
-        digest: &[u8],
-        filename: &str,
-    ) -> Result<impl Read> {
-        fetch_sha256(
+    pub(crate) fn fetch_target(&self, target: &Target, filename: &str) -> Result<impl Read> {
+        fetch_hashes(
             self.transport.as_ref(),
-            self.targets_base_url
-                .join(filename)
-                .context(error::JoinUrl {
-                    path: filename,
-                    url: self.targets_base_url.clone(),
-                })?,
+            self.target_url(filename)?,
             target.length,
             "targets.json",
-            digest,
+            &target.hashes,
         )
     }
 }
+
+/// Returns the digest that names a target's file: its SHA-256 digest, if it has one, then its
+/// SHA-512 digest, then any other.
+pub(crate) fn preferred_digest(hashes: &Hashes) -> Option<&Decoded<Hex>> {
+    hashes
+        .sha256()
+        .or_else(|| hashes.sha512())
+        .or_else(|| hashes.digests.values().next())
+}
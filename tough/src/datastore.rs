@@ -2,36 +2,117 @@
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
 use crate::error::{self, Result};
+use crate::{
+    CacheValidators, DatastoreLocking, TimeSource, Transport, TransportError, TransportErrorKind,
+};
+use chrono::{DateTime, Utc};
+use fs2::FileExt;
 use log::debug;
 use serde::Serialize;
 use snafu::ResultExt;
-use std::fs::{self, File};
-use std::io::{ErrorKind, Read};
+use std::collections::BTreeMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{ErrorKind, Read, Write};
 use std::path::{Path, PathBuf};
-use std::sync::{Arc, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
-use tempfile::TempDir;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use tempfile::{NamedTempFile, TempDir};
+use url::Url;
+
+/// The name of the lock file that a [`Datastore`] holds in a user-managed directory.
+const LOCK_FILE: &str = ".lock";
+
+/// The name of the file in which a [`Datastore`] keeps [`CacheValidators`], keyed by URL.
+const CACHE_VALIDATORS_FILE: &str = "cache_validators.json";
 
 #[derive(Debug, Clone)]
-pub(crate) struct Datastore(Arc<RwLock<DatastorePath>>);
+pub(crate) struct Datastore {
+    path: Arc<RwLock<DatastorePath>>,
+    /// The directory of `path`, which can be borrowed without taking the lock.
+    dir: PathBuf,
+    /// The path of [`LOCK_FILE`], which is locked while metadata is written; see
+    /// [`Datastore::lock`]. Temporary directories are never shared, so they aren't locked.
+    lock_path: Option<PathBuf>,
+    locking: DatastoreLocking,
+    /// Serializes updates of the latest known system time; see [`Datastore::lock_time`].
+    time: Arc<Mutex<()>>,
+    /// Where the current time is read from; see [`Datastore::now`].
+    time_source: TimeSource,
+}
 
 impl Datastore {
-    pub(crate) fn new(path: Option<PathBuf>) -> Result<Self> {
+    /// Uses the directory at `path`, or a temporary directory if there's none. The temporary
+    /// directory is removed when the last clone of the datastore is dropped, unless `keep_temp` is
+    /// `true`. The current time is read from `time_source`.
+    pub(crate) fn new(
+        path: Option<PathBuf>,
+        locking: DatastoreLocking,
+        keep_temp: bool,
+        time_source: TimeSource,
+    ) -> Result<Self> {
         // using pattern matching instead of mapping because TempDir::new() can error
-        Ok(Self(Arc::new(RwLock::new(match path {
-            None => DatastorePath::TempDir(TempDir::new().context(error::DatastoreInit)?),
-            Some(p) => DatastorePath::Path(p),
-        }))))
+        let (path, lock_path) = match path {
+            None => {
+                let dir = TempDir::new().context(error::DatastoreInit)?;
+                if keep_temp {
+                    let dir = dir.into_path();
+                    debug!("keeping temporary datastore at '{}'", dir.display());
+                    (DatastorePath::Path(dir), None)
+                } else {
+                    (DatastorePath::TempDir(dir), None)
+                }
+            }
+            Some(p) => {
+                let lock_path = p.join(LOCK_FILE);
+                (DatastorePath::Path(p), Some(lock_path))
+            }
+        };
+        Ok(Self {
+            dir: path.path().to_owned(),
+            path: Arc::new(RwLock::new(path)),
+            lock_path,
+            locking,
+            time: Arc::new(Mutex::new(())),
+            time_source,
+        })
+    }
+
+    /// Takes an exclusive advisory lock on [`LOCK_FILE`], which is held until the returned file is
+    /// dropped. This is held while a set of metadata files is checked against the trusted metadata
+    /// and written, so that two repositories can't interleave their updates. Returns `None` for a
+    /// temporary directory.
+    pub(crate) fn lock(&self) -> Result<Option<File>> {
+        self.lock_path
+            .as_deref()
+            .map(|path| lock(path, self.locking))
+            .transpose()
+    }
+
+    /// The directory that the datastore keeps its files in.
+    pub(crate) fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Locks the latest known system time. Checking the current time against the stored time and
+    /// then storing the current time must happen while this lock is held, so that concurrent calls
+    /// can't store their times out of order and make a later call think that time went backward.
+    pub(crate) fn lock_time(&self) -> MutexGuard<'_, ()> {
+        self.time.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// The current time, as told by the datastore's time source.
+    pub(crate) fn now(&self) -> DateTime<Utc> {
+        self.time_source.now()
     }
 
     // Because we are not actually changing the underlying data in the lock, we can ignore when a
     // lock is poisoned.
 
     fn read(&self) -> RwLockReadGuard<'_, DatastorePath> {
-        self.0.read().unwrap_or_else(PoisonError::into_inner)
+        self.path.read().unwrap_or_else(PoisonError::into_inner)
     }
 
     fn write(&self) -> RwLockWriteGuard<'_, DatastorePath> {
-        self.0.write().unwrap_or_else(PoisonError::into_inner)
+        self.path.write().unwrap_or_else(PoisonError::into_inner)
     }
 
     pub(crate) fn reader(&self, file: &str) -> Result<Option<impl Read>> {
@@ -47,14 +128,63 @@ impl Datastore {
 
     pub(crate) fn create<T: Serialize>(&self, file: &str, value: &T) -> Result<()> {
         let path = self.write().path().join(file);
-        serde_json::to_writer_pretty(
-            File::create(&path).context(error::DatastoreCreate { path: &path })?,
-            value,
-        )
-        .context(error::DatastoreSerialize {
-            what: format!("{} in datastore", file),
-            path,
-        })
+        let mut tmp = temp_file_for(&path)?;
+        serde_json::to_writer_pretty(tmp.as_file_mut(), value).context(
+            error::DatastoreSerialize {
+                what: format!("{} in datastore", file),
+                path: &path,
+            },
+        )?;
+        persist(tmp, &path)
+    }
+
+    /// Writes `data` to `file` as-is. Metadata files are stored this way so that they can be
+    /// checked against the lengths and hashes listed in other metadata files when they are read
+    /// back.
+    pub(crate) fn create_raw(&self, file: &str, data: &[u8]) -> Result<()> {
+        let path = self.write().path().join(file);
+        let mut tmp = temp_file_for(&path)?;
+        tmp.as_file_mut()
+            .write_all(data)
+            .context(error::DatastoreCreate { path: &path })?;
+        persist(tmp, &path)
+    }
+
+    /// Returns the validators stored for `url`, or empty validators if there are none.
+    pub(crate) fn cache_validators(&self, url: &Url) -> Result<CacheValidators> {
+        Ok(self
+            .all_cache_validators()?
+            .remove(url.as_str())
+            .unwrap_or_default())
+    }
+
+    /// Stores `validators` for `url`, replacing any validators that were stored for it before.
+    /// Storing empty validators removes them.
+    pub(crate) fn set_cache_validators(
+        &self,
+        url: &Url,
+        validators: CacheValidators,
+    ) -> Result<()> {
+        let mut all = self.all_cache_validators()?;
+        let changed = if validators.is_empty() {
+            all.remove(url.as_str()).is_some()
+        } else {
+            all.insert(url.to_string(), validators.clone()) != Some(validators)
+        };
+        if changed {
+            self.create(CACHE_VALIDATORS_FILE, &all)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Reads [`CACHE_VALIDATORS_FILE`]. A missing or unreadable file means that there are no
+    /// validators, so the next fetches won't be conditional.
+    fn all_cache_validators(&self) -> Result<BTreeMap<String, CacheValidators>> {
+        Ok(self
+            .reader(CACHE_VALIDATORS_FILE)?
+            .and_then(|reader| serde_json::from_reader(reader).ok())
+            .unwrap_or_default())
     }
 
     pub(crate) fn remove(&self, file: &str) -> Result<()> {
@@ -70,6 +200,45 @@ impl Datastore {
     }
 }
 
+/// Opens (creating if needed) the lock file at `path` and takes an exclusive lock on it.
+fn lock(path: &Path, locking: DatastoreLocking) -> Result<File> {
+    let file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .open(path)
+        .context(error::DatastoreLock { path })?;
+    match locking {
+        DatastoreLocking::Block => file
+            .lock_exclusive()
+            .context(error::DatastoreLock { path })?,
+        DatastoreLocking::Fail => match file.try_lock_exclusive() {
+            Ok(()) => {}
+            Err(err) if err.raw_os_error() == fs2::lock_contended_error().raw_os_error() => {
+                return error::DatastoreLocked { path }.fail();
+            }
+            Err(err) => return Err(err).context(error::DatastoreLock { path }),
+        },
+    }
+    Ok(file)
+}
+
+/// Creates a temporary file in the same directory as `path`, so that it can be renamed to `path`
+/// once it is complete. Writing files this way means that a process that is killed part way through
+/// a write never leaves a truncated file in the datastore; the next load would otherwise be unable
+/// to parse it, and would lose the rollback protection that the stored metadata provides.
+fn temp_file_for(path: &Path) -> Result<NamedTempFile> {
+    let dir = path.parent().unwrap_or_else(|| Path::new("."));
+    NamedTempFile::new_in(dir).context(error::DatastoreCreate { path })
+}
+
+/// Atomically replaces `path` with the completed temporary file `tmp`.
+fn persist(tmp: NamedTempFile, path: &Path) -> Result<()> {
+    tmp.persist(path)
+        .map(|_| ())
+        .map_err(|e| e.error)
+        .context(error::DatastoreCreate { path })
+}
+
 /// Because `TempDir` is an RAII object, we need to hold on to it. This private enum allows us to
 /// hold either a `TempDir` or a `PathBuf` depending on whether or not the user wants to manage the
 /// directory.
@@ -90,3 +259,59 @@ impl DatastorePath {
         }
     }
 }
+
+// =^..^=   =^..^=   =^..^=   =^..^=   =^..^=   =^..^=   =^..^=   =^..^=   =^..^=   =^..^=   =^..^=
+
+/// A [`Transport`] that serves metadata files that a previous load stored in the datastore, and
+/// nothing else. Used when a [`RepositoryLoader`](crate::RepositoryLoader) is set to `offline`.
+///
+/// Only the filename of each requested URL is used. The datastore holds the snapshot and targets
+/// metadata as `snapshot.json` and `targets.json`, so when consistent snapshots are used, a request
+/// for e.g. `42.snapshot.json` is served from `snapshot.json`. The version that was served is then
+/// checked by the loader like any other.
+#[derive(Debug, Clone)]
+pub(crate) struct DatastoreTransport {
+    datastore: Datastore,
+}
+
+impl DatastoreTransport {
+    pub(crate) fn new(datastore: Datastore) -> Self {
+        Self { datastore }
+    }
+}
+
+impl Transport for DatastoreTransport {
+    fn fetch(&self, url: Url) -> std::result::Result<Box<dyn Read + Send>, TransportError> {
+        let filename = url
+            .path_segments()
+            .and_then(Iterator::last)
+            .unwrap_or_default()
+            .to_owned();
+        let unversioned = filename
+            .split_once('.')
+            .filter(|(prefix, _)| !prefix.is_empty() && prefix.bytes().all(|b| b.is_ascii_digit()))
+            .map(|(_, rest)| rest);
+        let candidates = std::iter::once(filename.as_str()).chain(unversioned);
+        for candidate in candidates {
+            match self.datastore.reader(candidate) {
+                Ok(Some(reader)) => return Ok(Box::new(reader)),
+                Ok(None) => {}
+                Err(e) => {
+                    return Err(TransportError::new_with_cause(
+                        TransportErrorKind::Other,
+                        url,
+                        e,
+                    ))
+                }
+            }
+        }
+        Err(TransportError::new_with_cause(
+            TransportErrorKind::FileNotFound,
+            url,
+            format!(
+                "'{}' is not in the datastore, and nothing is fetched in offline mode",
+                filename
+            ),
+        ))
+    }
+}
@@ -4,6 +4,7 @@
 use crate::error::{self, Result};
 use crate::key_source::KeySource;
 use crate::schema::decoded::{Decoded, Hex};
+use crate::schema::key::Key;
 use crate::schema::{Delegations, KeyHolder, RoleId, RoleKeys, Root, Signed, Targets};
 use crate::sign::Sign;
 use snafu::{ensure, OptionExt, ResultExt};
@@ -21,6 +22,22 @@ impl KeyHolder {
         }
     }
 
+    /// Returns the key with the given ID, as it's listed in the metadata
+    pub(crate) fn key(&self, key_id: &Decoded<Hex>) -> Option<&Key> {
+        match self {
+            Self::Delegations(delegations) => delegations.keys.get(key_id),
+            Self::Root(root) => root.keys.get(key_id),
+        }
+    }
+
+    /// Returns the ID of the key listed in the metadata that has the same public key as `key_pair`
+    pub(crate) fn key_id(&self, key_pair: &dyn Sign) -> Option<Decoded<Hex>> {
+        match self {
+            Self::Delegations(delegations) => delegations.key_id(key_pair),
+            Self::Root(root) => root.key_id(key_pair),
+        }
+    }
+
     /// Returns role keys for the provided role id
     pub(crate) fn role_keys(&self, name: RoleId) -> Result<RoleKeys> {
         match self {
@@ -45,6 +45,7 @@ const SPEC_VERSION: &str = "1.0.0";
 /// `from_repo()` method. When a repo is loaded in this way, versions and
 /// expirations are discarded. It is good practice to update these whenever
 /// a repo is changed.
+/// To keep them, use the `from_repository()` method instead.
 ///
 /// Targets, versions, and expirations may be added to their respective roles
 /// via the provided "setter" methods. The final step in the process is the
@@ -93,9 +94,14 @@ impl RepositoryEditor {
         // make sense to continue
         let root_path = root_path.as_ref();
         let root_buf = std::fs::read(root_path).context(error::FileRead { path: root_path })?;
-        let root_buf_len = root_buf.len() as u64;
         let root = serde_json::from_slice::<Signed<Root>>(&root_buf)
             .context(error::FileParseJson { path: root_path })?;
+        Self::from_signed_root(root, root_buf)
+    }
+
+    /// Create a `RepositoryEditor` from a parsed root and the buffer it was parsed from
+    fn from_signed_root(root: Signed<Root>, root_buf: Vec<u8>) -> Result<Self> {
+        let root_buf_len = root_buf.len() as u64;
 
         // Quick check that root is signed by enough key IDs
         for (roletype, rolekeys) in &root.signed.roles {
@@ -153,6 +159,34 @@ impl RepositoryEditor {
         Ok(editor)
     }
 
+    /// Create a `RepositoryEditor` from an already loaded `tough::Repository`, starting from its
+    /// current root. Unlike `from_repo()`, the versions and expirations of the targets, snapshot,
+    /// and timestamp roles are kept, so that only the ones that should change need to be set
+    /// before signing; e.g. bump each version with `targets_version()` and friends.
+    ///
+    /// The editor includes all of the targets, including delegated targets, and the `_extra` and
+    /// `custom` data that `tough` doesn't otherwise understand, so that it isn't dropped when the
+    /// repository is signed again. The repository's `Transport` and `Limits` are kept too.
+    pub fn from_repository(repo: &Repository) -> Result<RepositoryEditor> {
+        let mut editor =
+            RepositoryEditor::from_signed_root(repo.root.clone(), repo.raw_metadata.root.clone())?;
+        editor
+            .targets(repo.targets.clone())?
+            .targets_version(repo.targets.signed.version)?
+            .targets_expires(repo.targets.signed.expires)?;
+        editor
+            .snapshot(repo.snapshot.signed.clone())?
+            .snapshot_version(repo.snapshot.signed.version)
+            .snapshot_expires(repo.snapshot.signed.expires);
+        editor
+            .timestamp(repo.timestamp.signed.clone())?
+            .timestamp_version(repo.timestamp.signed.version)
+            .timestamp_expires(repo.timestamp.signed.expires);
+        editor.transport = Some(repo.transport.clone());
+        editor.limits = Some(repo.limits);
+        Ok(editor)
+    }
+
     /// Builds and signs each required role and returns a complete signed set
     /// of TUF repository metadata.
     ///
@@ -663,10 +697,7 @@ impl RepositoryEditor {
         R: Role,
     {
         SnapshotMeta {
-            hashes: Some(Hashes {
-                sha256: role.sha256.to_vec().into(),
-                _extra: HashMap::new(),
-            }),
+            hashes: Some(Hashes::from_sha256(role.sha256.to_vec().into())),
             length: Some(role.length),
             version: role.signed.signed.version(),
             _extra: HashMap::new(),
@@ -701,11 +732,8 @@ impl RepositoryEditor {
         R: Role,
     {
         TimestampMeta {
-            hashes: Hashes {
-                sha256: role.sha256.to_vec().into(),
-                _extra: HashMap::new(),
-            },
-            length: role.length,
+            hashes: Hashes::from_sha256(role.sha256.to_vec().into()),
+            length: Some(role.length),
             version: role.signed.signed.version(),
             _extra: HashMap::new(),
         }
@@ -10,10 +10,9 @@ use crate::error::{self, Result};
 use crate::io::DigestAdapter;
 use crate::key_source::KeySource;
 use crate::schema::{
-    DelegatedTargets, KeyHolder, Role, RoleType, Root, Signature, Signed, Snapshot, Target,
-    Targets, Timestamp,
+    to_canonical_json, DelegatedTargets, KeyHolder, Role, RoleType, Root, Signature, Signed,
+    Snapshot, Target, Targets, Timestamp,
 };
-use olpc_cjson::CanonicalFormatter;
 use ring::digest::{digest, SHA256, SHA256_OUTPUT_LEN};
 use ring::rand::SecureRandom;
 use serde::{Deserialize, Serialize};
@@ -21,6 +20,7 @@ use serde_plain::derive_fromstr_from_deserialize;
 use snafu::{ensure, OptionExt, ResultExt};
 use std::collections::HashMap;
 use std::fs;
+use std::io::Write;
 
 #[cfg(not(target_os = "windows"))]
 use std::os::unix::fs::symlink;
@@ -69,20 +69,18 @@ where
 
         // Create the `Signed` struct for this role. This struct will be
         // mutated later to contain the signatures.
-        let mut role = Signed {
-            signed: role,
-            signatures: Vec::new(),
-        };
+        let mut role = Signed::new(role, Vec::new());
 
-        let mut data = Vec::new();
-        let mut ser = serde_json::Serializer::with_formatter(&mut data, CanonicalFormatter::new());
-        role.signed
-            .serialize(&mut ser)
-            .context(error::SerializeRole {
-                role: T::TYPE.to_string(),
-            })?;
+        let data = to_canonical_json(&role.signed).context(error::SerializeRole {
+            role: T::TYPE.to_string(),
+        })?;
         for (signing_key_id, signing_key) in valid_keys {
-            let sig = signing_key.sign(&data, rng).context(error::SignMessage)?;
+            // Sign with the scheme that the key is listed with, which verifiers will use
+            let sig = match key_holder.key(signing_key_id) {
+                Some(key) => signing_key.sign_with_scheme(&data, key, rng),
+                None => signing_key.sign(&data, rng),
+            }
+            .context(error::SignMessage)?;
 
             // Add the signatures to the `Signed` struct for this role
             role.signatures.push(Signature {
@@ -147,6 +145,11 @@ where
         &self.length
     }
 
+    /// Provides the file name that `write` gives the current role.
+    pub fn filename(&self, consistent_snapshot: bool) -> String {
+        self.signed.signed.filename(consistent_snapshot)
+    }
+
     /// Write the current role's buffer to the given directory with the
     /// appropriate file name.
     pub fn write<P>(&self, outdir: P, consistent_snapshot: bool) -> Result<()>
@@ -156,12 +159,26 @@ where
         let outdir = outdir.as_ref();
         std::fs::create_dir_all(outdir).context(error::DirCreate { path: outdir })?;
 
-        let filename = self.signed.signed.filename(consistent_snapshot);
+        let filename = self.filename(consistent_snapshot);
 
         let path = outdir.join(filename);
         std::fs::write(&path, &self.buffer).context(error::FileWrite { path })
     }
 
+    /// Write the current role's buffer to `writer` instead of a file, e.g.
+    /// to upload it to object storage. The buffer is written unchanged, so
+    /// the length and hashes listed for this role in other roles stay valid.
+    pub fn write_to<W>(&self, mut writer: W) -> Result<()>
+    where
+        W: Write,
+    {
+        writer
+            .write_all(&self.buffer)
+            .context(error::MetadataWrite {
+                filename: self.filename(false),
+            })
+    }
+
     /// Append the old signatures for root role
     pub fn add_old_signatures(mut self, old_signatures: Vec<Signature>) -> Result<Self> {
         for old_signature in old_signatures {
@@ -247,6 +264,55 @@ impl SignedRepository {
         Ok(())
     }
 
+    /// Writes the metadata of a single role to `writer`, byte for byte what
+    /// `write` would write to file, so that it can be published without
+    /// touching disk. `role` is "root", "timestamp", "snapshot", "targets",
+    /// or the name of a delegated role.
+    pub fn write_metadata_to<W>(&self, role: &str, mut writer: W) -> Result<()>
+    where
+        W: Write,
+    {
+        let (buffer, filename) = self.metadata(role)?;
+        writer
+            .write_all(buffer)
+            .context(error::MetadataWrite { filename })
+    }
+
+    /// Returns the file name that `write` gives the metadata of `role`,
+    /// e.g. to use as the key of an uploaded object. `role` is as for
+    /// `write_metadata_to`.
+    pub fn metadata_filename(&self, role: &str) -> Result<String> {
+        self.metadata(role).map(|(_, filename)| filename)
+    }
+
+    /// Returns the buffer and file name of the metadata of `role`.
+    fn metadata(&self, role: &str) -> Result<(&[u8], String)> {
+        let consistent_snapshot = self.root.signed.signed.consistent_snapshot;
+        Ok(match role {
+            "root" => (&self.root.buffer, self.root.filename(consistent_snapshot)),
+            "timestamp" => (
+                &self.timestamp.buffer,
+                self.timestamp.filename(consistent_snapshot),
+            ),
+            "snapshot" => (
+                &self.snapshot.buffer,
+                self.snapshot.filename(consistent_snapshot),
+            ),
+            "targets" => (
+                &self.targets.buffer,
+                self.targets.filename(consistent_snapshot),
+            ),
+            name => {
+                let delegated = self
+                    .delegated_targets
+                    .as_ref()
+                    .and_then(|delegated_targets| delegated_targets.role(name))
+                    .context(error::DelegateNotFound { name })?;
+                (&delegated.buffer, delegated.filename(consistent_snapshot))
+            }
+        })
+    }
+
     /// Crawls a given directory and symlinks any targets found to the given
     /// "out" directory. If consistent snapshots are used, the target files
     /// are prefixed with their `sha256`.
@@ -388,7 +454,12 @@ impl TargetsWalker for SignedRepository {
     fn targets(&self) -> HashMap<String, &Target> {
         // Since there is access to `targets.json` metadata, all targets
         // can be found using `targets_map()`
-        self.targets.signed.signed.targets_map()
+        self.targets
+            .signed
+            .signed
+            .targets_map()
+            .into_iter()
+            .collect()
     }
 
     fn consistent_snapshot(&self) -> bool {
@@ -416,6 +487,24 @@ impl SignedDelegatedTargets {
         Ok(())
     }
 
+    /// Writes the metadata of the delegated role `name` to `writer`, byte
+    /// for byte what `write` would write to file.
+    pub fn write_metadata_to<W>(&self, name: &str, writer: W) -> Result<()>
+    where
+        W: Write,
+    {
+        self.role(name)
+            .context(error::DelegateNotFound { name })?
+            .write_to(writer)
+    }
+
+    /// Returns the signed role named `name`, if any
+    fn role(&self, name: &str) -> Option<&SignedRole<DelegatedTargets>> {
+        self.roles
+            .iter()
+            .find(|role| role.signed.signed.name == name)
+    }
+
     /// Returns all `SignedRole<DelegatedTargets>>` contained by this `SignedDelegatedTargets`
     pub fn roles(self) -> Vec<SignedRole<DelegatedTargets>> {
         self.roles
@@ -666,18 +755,30 @@ trait TargetsWalker {
         // should match, or we alert the caller; if target replacement is intended, it should
         // happen earlier, in RepositoryEditor.
         ensure!(
-            target_from_path.hashes.sha256 == repo_target.hashes.sha256,
+            target_from_path.hashes.sha256() == repo_target.hashes.sha256(),
             error::HashMismatch {
                 context: "target",
-                calculated: hex::encode(target_from_path.hashes.sha256),
-                expected: hex::encode(&repo_target.hashes.sha256),
+                calculated: target_from_path
+                    .hashes
+                    .sha256()
+                    .map(hex::encode)
+                    .unwrap_or_default(),
+                expected: repo_target
+                    .hashes
+                    .sha256()
+                    .map(hex::encode)
+                    .unwrap_or_default(),
             }
         );
 
         let dest = if self.consistent_snapshot() {
             outdir.join(format!(
                 "{}.{}",
-                hex::encode(&target_from_path.hashes.sha256),
+                target_from_path
+                    .hashes
+                    .sha256()
+                    .map(hex::encode)
+                    .unwrap_or_default(),
                 file_name
             ))
         } else {
@@ -697,13 +798,13 @@ trait TargetsWalker {
             // Use DigestAdapter to get a streaming checksum of the file without needing to hold
             // its contents.
             let f = fs::File::open(&dest).context(error::FileOpen { path: &dest })?;
-            let mut reader = DigestAdapter::sha256(
+            let mut reader = DigestAdapter::hashes(
                 Box::new(f),
-                &repo_target.hashes.sha256,
+                &repo_target.hashes,
                 Url::from_file_path(&dest)
                     .ok() // dump unhelpful `()` error
                     .context(error::FileUrl { path: &dest })?,
-            );
+            )?;
             let mut dev_null = std::io::sink();
             // The act of reading with the DigestAdapter verifies the checksum, assuming the read
             // succeeds.
@@ -20,7 +20,7 @@ use crate::Repository;
 use chrono::{DateTime, Utc};
 use ring::rand::SystemRandom;
 use serde_json::Value;
-use snafu::{OptionExt, ResultExt};
+use snafu::{ensure, OptionExt, ResultExt};
 use std::borrow::Cow;
 use std::collections::HashMap;
 use std::num::NonZeroU64;
@@ -312,6 +312,11 @@ impl TargetsEditor {
     /// Adds a `DelegatedRole` to `new_roles`
     /// To use `delegate_role()` a new `Targets` should be created using `TargetsEditor::new()`
     /// followed by `create_signed()` to provide a `Signed<DelegatedTargets>` for the new role.
+    ///
+    /// `paths` are checked as clients would check them: if this is a delegated role, they must be
+    /// within the paths delegated to it, and they must not already be delegated to a terminating
+    /// role, which clients would consult first. Overlapping with a non-terminating role is allowed,
+    /// since clients consult each matching role in order.
     pub fn delegate_role(
         &mut self,
         targets: Signed<DelegatedTargets>,
@@ -320,6 +325,7 @@ impl TargetsEditor {
         keyids: Vec<Decoded<Hex>>,
         threshold: NonZeroU64,
     ) -> Result<&mut Self> {
+        self.verify_delegated_paths(&targets.signed.name, &paths)?;
         self.add_key(key_pairs, None)?;
         self.new_roles
             .get_or_insert(Vec::new())
@@ -329,14 +335,58 @@ impl TargetsEditor {
                 keyids,
                 threshold,
                 terminating: false,
-                targets: Some(Signed {
-                    signed: targets.signed.targets,
-                    signatures: targets.signatures,
-                }),
+                targets: Some(Signed::new(targets.signed.targets, targets.signatures)),
             });
         Ok(self)
     }
 
+    /// Checks that `paths` can be delegated to the role `name`, as described in `delegate_role()`.
+    /// Only path patterns are checked; path hash prefixes can't be compared with patterns.
+    fn verify_delegated_paths(&self, name: &str, paths: &PathSet) -> Result<()> {
+        let paths = match paths {
+            PathSet::Paths(paths) => paths,
+            PathSet::PathHashPrefixes(_) => return Ok(()),
+        };
+
+        // If this is a delegated role, find the paths that were delegated to it
+        if let Some(KeyHolder::Delegations(parent)) = &self.key_holder {
+            if let Some(role) = parent.roles.iter().find(|role| role.name == self.name) {
+                if let PathSet::Paths(_) = role.paths {
+                    for path in paths {
+                        ensure!(
+                            role.paths.matched_target(path),
+                            error::DelegatedPathNotAllowed {
+                                role: &self.name,
+                                path,
+                            }
+                        );
+                    }
+                }
+            }
+        }
+
+        let siblings = self
+            .delegations
+            .iter()
+            .flat_map(|delegations| delegations.roles.iter())
+            .chain(self.new_roles.iter().flatten());
+        for sibling in siblings.filter(|sibling| sibling.terminating && sibling.name != name) {
+            if let PathSet::Paths(_) = sibling.paths {
+                for path in paths {
+                    ensure!(
+                        !sibling.paths.matched_target(path),
+                        error::DelegatedPathShadowed {
+                            name,
+                            path,
+                            sibling: &sibling.name,
+                        }
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+
     /// Removes a role from delegations
     /// If `recursive` is `false`, `role` is only removed if it is directly delegated by this role
     /// If `true` removes whichever role eventually delegates 'role'
@@ -397,13 +447,13 @@ impl TargetsEditor {
             })?;
 
         // Create `Signed<DelegatedTargets>` for the role
-        let delegated_targets = Signed {
-            signed: DelegatedTargets {
+        let delegated_targets = Signed::new(
+            DelegatedTargets {
                 name: name.to_string(),
                 targets: role.signed.clone(),
             },
-            signatures: role.signatures.clone(),
-        };
+            role.signatures.clone(),
+        );
         let (keyids, key_pairs) = if let Some(keys) = keys {
             (keys.keys().cloned().collect(), keys)
         } else {
@@ -6,7 +6,7 @@ mod tests {
     use crate::editor::RepositoryEditor;
     use crate::key_source::LocalKeySource;
     use crate::schema::{Signed, Snapshot, Target, Targets, Timestamp};
-    use chrono::{Duration, Utc};
+    use chrono::{Duration, TimeZone, Utc};
     use std::num::NonZeroU64;
     use std::path::PathBuf;
 
@@ -104,11 +104,12 @@ mod tests {
         let root = root_path();
         let root_key = key_path();
         let key_source = LocalKeySource { path: root_key };
-        let timestamp_expiration = Utc::now().checked_add_signed(Duration::days(3)).unwrap();
+        let now = Utc.ymd(2021, 1, 1).and_hms(0, 0, 0);
+        let timestamp_expiration = now.checked_add_signed(Duration::days(3)).unwrap();
         let timestamp_version = NonZeroU64::new(1234).unwrap();
-        let snapshot_expiration = Utc::now().checked_add_signed(Duration::days(21)).unwrap();
+        let snapshot_expiration = now.checked_add_signed(Duration::days(21)).unwrap();
         let snapshot_version = NonZeroU64::new(5432).unwrap();
-        let targets_expiration = Utc::now().checked_add_signed(Duration::days(13)).unwrap();
+        let targets_expiration = now.checked_add_signed(Duration::days(13)).unwrap();
         let targets_version = NonZeroU64::new(789).unwrap();
         let target1 = targets_path().join("file1.txt");
         let target2 = targets_path().join("file2.txt");
@@ -38,6 +38,21 @@ pub enum Error {
         backtrace: Backtrace,
     },
 
+    /// The datastore is locked by another `Repository`, possibly in another process.
+    #[snafu(display(
+        "Datastore lock file {} is held by another repository",
+        path.display()
+    ))]
+    DatastoreLocked { path: PathBuf, backtrace: Backtrace },
+
+    /// The library failed to lock the datastore.
+    #[snafu(display("Failed to lock datastore with lock file {}: {}", path.display(), source))]
+    DatastoreLock {
+        path: PathBuf,
+        source: std::io::Error,
+        backtrace: Backtrace,
+    },
+
     /// The library failed to create a file in the datastore.
     #[snafu(display("Failed to create file at datastore path {}: {}", path.display(), source))]
     DatastoreCreate {
@@ -71,6 +86,23 @@ pub enum Error {
         backtrace: Backtrace,
     },
 
+    /// A JSON file in the datastore could not be parsed.
+    #[snafu(display("Failed to parse {} at datastore path {}: {}", what, path.display(), source))]
+    DatastoreDeserialize {
+        what: String,
+        path: PathBuf,
+        source: serde_json::Error,
+        backtrace: Backtrace,
+    },
+
+    /// The library failed to save a root metadata file fetched during loading.
+    #[snafu(display("Failed to save root metadata to '{}': {}", path.display(), source))]
+    SaveRootChain {
+        path: PathBuf,
+        source: std::io::Error,
+        backtrace: Backtrace,
+    },
+
     #[snafu(display("Failed to create directory '{}': {}", path.display(), source))]
     DirCreate {
         path: PathBuf,
@@ -123,6 +155,27 @@ pub enum Error {
         backtrace: Backtrace,
     },
 
+    /// A target couldn't be read to the end by
+    /// [`VerifyingReader::finish`](crate::VerifyingReader::finish) or
+    /// [`Repository::verify_target_bytes`](crate::Repository::verify_target_bytes).
+    #[snafu(display("Failed to read target: {}", source))]
+    TargetRead {
+        source: std::io::Error,
+        backtrace: Backtrace,
+    },
+
+    /// [`VerifyingReader::finish`](crate::VerifyingReader::finish) was called after a read of the
+    /// target returned an error, so the target wasn't verified.
+    #[snafu(display("An earlier read of the target failed, so it was not verified"))]
+    TargetReadFailed { backtrace: Backtrace },
+
+    #[snafu(display("Failed to write metadata {}: {}", filename, source))]
+    MetadataWrite {
+        filename: String,
+        source: std::io::Error,
+        backtrace: Backtrace,
+    },
+
     /// A downloaded target's checksum does not match the checksum listed in the repository
     /// metadata.
     #[snafu(display(
@@ -138,6 +191,69 @@ pub enum Error {
         backtrace: Backtrace,
     },
 
+    /// None of the hashes listed for a file in the repository metadata were calculated by a hash
+    /// function that this library recognizes, so the file can't be verified.
+    #[snafu(display("No recognized hash function among the hashes listed for {}", context))]
+    NoRecognizedHashes {
+        context: String,
+        backtrace: Backtrace,
+    },
+
+    /// A target doesn't list a hash that was required with
+    /// [`RepositoryLoader::require_hash_algorithms`](crate::RepositoryLoader::require_hash_algorithms).
+    #[snafu(display("Target '{}' has no {} hash, which is required", target, algorithm))]
+    MissingRequiredHash {
+        target: String,
+        algorithm: String,
+        backtrace: Backtrace,
+    },
+
+    /// [`Repository::read_target_range`](crate::Repository::read_target_range) was called without
+    /// [`RepositoryLoader::allow_unverified_partial_reads`](crate::RepositoryLoader::allow_unverified_partial_reads).
+    #[snafu(display("Partial target reads, which aren't verified, are not allowed"))]
+    PartialReadsNotAllowed { backtrace: Backtrace },
+
+    /// A range passed to
+    /// [`Repository::read_target_range`](crate::Repository::read_target_range) ends past the end
+    /// of the target.
+    #[snafu(display(
+        "Range of {} bytes from byte {} is outside of target '{}', which is {} bytes long",
+        len,
+        start,
+        target,
+        length
+    ))]
+    TargetRangeOutOfBounds {
+        target: String,
+        start: u64,
+        len: u64,
+        length: u64,
+        backtrace: Backtrace,
+    },
+
+    /// [`Repository::verify_target_bytes`](crate::Repository::verify_target_bytes) was asked about
+    /// a target that isn't listed in the repository metadata.
+    #[snafu(display("Target '{}' is not listed in the repository metadata", name))]
+    UnknownTarget { name: String, backtrace: Backtrace },
+
+    /// Targets have no hash that can be verified, which
+    /// [`RepositoryLoader::require_target_hashes`](crate::RepositoryLoader::require_target_hashes)
+    /// rejects.
+    #[snafu(display("Targets have no recognized hash: {}", targets.join(", ")))]
+    TargetHashesMissing {
+        targets: Vec<String>,
+        backtrace: Backtrace,
+    },
+
+    /// A hash algorithm passed to
+    /// [`RepositoryLoader::require_hash_algorithms`](crate::RepositoryLoader::require_hash_algorithms)
+    /// isn't one that can be verified.
+    #[snafu(display("Unrecognized hash algorithm '{}'", algorithm))]
+    UnrecognizedHashAlgorithm {
+        algorithm: String,
+        backtrace: Backtrace,
+    },
+
     #[snafu(display("Source path for target must be file or symlink - '{}'", path.display()))]
     InvalidFileType { path: PathBuf, backtrace: Backtrace },
 
@@ -156,6 +272,11 @@ pub enum Error {
         backtrace: Backtrace,
     },
 
+    /// The key given to [`sign_bytes`](crate::sign::sign_bytes) isn't listed in the metadata
+    /// that's meant to trust it.
+    #[snafu(display("The signing key is not listed in the given root or delegations"))]
+    SigningKeyNotListed { backtrace: Backtrace },
+
     #[snafu(display("Private key rejected: {}", source))]
     KeyRejected {
         source: ring::error::KeyRejected,
@@ -183,6 +304,24 @@ pub enum Error {
         backtrace: Backtrace,
     },
 
+    /// The repository delegates more roles than the maximum delegated roles setting allows.
+    #[snafu(display("Maximum delegated roles {} exceeded", max_delegated_roles))]
+    MaxDelegatedRolesExceeded {
+        max_delegated_roles: u64,
+        backtrace: Backtrace,
+    },
+
+    /// The metadata downloaded while loading a repository exceeded a limit set by the consumer of
+    /// this library.
+    #[snafu(display(
+        "Maximum total download {} (specified by max_total_download) exceeded",
+        max_total_download
+    ))]
+    MaxTotalDownloadExceeded {
+        max_total_download: u64,
+        backtrace: Backtrace,
+    },
+
     /// The maximum root updates setting was exceeded.
     #[snafu(display("Maximum root updates {} exceeded", max_root_updates))]
     MaxUpdatesExceeded {
@@ -190,6 +329,16 @@ pub enum Error {
         backtrace: Backtrace,
     },
 
+    /// The hashes of a metadata file are missing from the metadata file that lists it, and they
+    /// are required, e.g. by
+    /// [`RepositoryLoader::require_targets_hash`](crate::RepositoryLoader::require_targets_hash).
+    #[snafu(display("Hashes for {:?} missing from {} metadata", file, role))]
+    MetaHashesMissing {
+        file: &'static str,
+        role: RoleType,
+        backtrace: Backtrace,
+    },
+
     /// A required reference to a metadata file is missing from a metadata file.
     #[snafu(display("Meta for {:?} missing from {} metadata", file, role))]
     MetaMissing {
@@ -316,6 +465,21 @@ pub enum Error {
         backtrace: Backtrace,
     },
 
+    /// A role's metadata declares a spec version that doesn't meet the requirement set with
+    /// [`RepositoryLoader::require_spec_version`](crate::RepositoryLoader::require_spec_version).
+    #[snafu(display(
+        "Metadata for role '{}' declares spec version '{}', which does not match '{}'",
+        role,
+        given,
+        required
+    ))]
+    IncompatibleSpecVersion {
+        role: String,
+        given: String,
+        required: semver::VersionReq,
+        backtrace: Backtrace,
+    },
+
     /// System time is behaving irrationally, went back in time
     #[snafu(display(
         "System time stepped backward: system time '{}', last known time '{}'",
@@ -327,6 +491,13 @@ pub enum Error {
         latest_known_time: DateTime<Utc>,
     },
 
+    /// A repository was loaded without a time source, which is needed when the `clock` feature is
+    /// disabled.
+    #[snafu(display(
+        "No time source: set one with RepositoryLoader::time_source or enable the 'clock' feature"
+    ))]
+    NoTimeSource { backtrace: Backtrace },
+
     #[snafu(display("Refusing to replace {} with requested {} for target {}", found, expected, path.display()))]
     TargetFileTypeMismatch {
         expected: String,
@@ -342,6 +513,22 @@ pub enum Error {
         backtrace: Backtrace,
     },
 
+    /// A URL can't be fetched by the default transport because its scheme is `http` or `https`,
+    /// and this library was built without the `http` feature.
+    #[snafu(display(
+        "Can't fetch {}: the http feature of tough must be enabled to fetch http and https URLs",
+        url
+    ))]
+    HttpFeatureDisabled { url: url::Url, backtrace: Backtrace },
+
+    /// A URL can't be fetched by the default transport because of its scheme.
+    #[snafu(display(
+        "Can't fetch {}: the {} URL scheme isn't supported by the default transport",
+        url,
+        url.scheme()
+    ))]
+    UnsupportedUrlScheme { url: url::Url, backtrace: Backtrace },
+
     /// A transport error occurred while fetching a URL.
     #[snafu(display("Failed to fetch {}: {}", url, source))]
     Transport {
@@ -372,6 +559,20 @@ pub enum Error {
         backtrace: Backtrace,
     },
 
+    /// The new root metadata file passed to
+    /// [`RepositoryLoader::accept_new_root`][crate::RepositoryLoader::accept_new_root] didn't
+    /// match the fingerprint it was expected to have.
+    #[snafu(display(
+        "New root metadata has SHA-256 fingerprint {}, expected {}",
+        calculated,
+        expected
+    ))]
+    RootFingerprintMismatch {
+        expected: String,
+        calculated: String,
+        backtrace: Backtrace,
+    },
+
     /// A fetched metadata file did not have the version we expected it to have.
     #[snafu(display(
         "{} metadata version mismatch: fetched {}, expected {}",
@@ -414,6 +615,38 @@ pub enum Error {
         backtrace: Backtrace,
     },
 
+    #[snafu(display("Error writing '{}' to tar archive: {}", path.display(), source))]
+    CacheTarWrite {
+        path: PathBuf,
+        source: std::io::Error,
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display("Error finishing tar archive: {}", source))]
+    CacheTarFinish {
+        source: std::io::Error,
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display("Failed to open tar archive '{}': {}", path.display(), source))]
+    TarTransportOpen {
+        path: PathBuf,
+        source: std::io::Error,
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display("Failed to read tar archive: {}", source))]
+    TarTransportRead {
+        source: std::io::Error,
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display(
+        "Tar archive '{}' is compressed with zstd, which needs tough's 'zstd' feature",
+        path.display()
+    ))]
+    TarTransportZstd { path: PathBuf, backtrace: Backtrace },
+
     #[snafu(display("The target '{}' was not found", target_name))]
     CacheTargetMissing {
         target_name: String,
@@ -421,6 +654,12 @@ pub enum Error {
         backtrace: Backtrace,
     },
 
+    #[snafu(display(
+        "Target name '{}' is not a safe relative path: it must not be absolute or contain '..'",
+        name
+    ))]
+    UnsafeTargetName { name: String, backtrace: Backtrace },
+
     #[snafu(display("Failed to walk directory tree '{}': {}", directory.display(), source))]
     WalkDir {
         directory: PathBuf,
@@ -462,6 +701,19 @@ pub enum Error {
     #[snafu(display("Role missing from snapshot meta: {}", name))]
     RoleNotInMeta { name: String },
 
+    /// The snapshot metadata and the delegation tree disagree, as found by
+    /// [`Repository::validate_snapshot_completeness`](crate::Repository::validate_snapshot_completeness).
+    #[snafu(display(
+        "Snapshot metadata is inconsistent with the delegations: missing {:?}, not delegated {:?}",
+        missing,
+        orphaned
+    ))]
+    SnapshotInconsistent {
+        missing: Vec<String>,
+        orphaned: Vec<String>,
+        backtrace: Backtrace,
+    },
+
     #[snafu(display("The key for {} was not included", role))]
     KeyNotFound {
         role: String,
@@ -496,10 +748,59 @@ pub enum Error {
         source: schema::Error,
     },
 
+    /// A role tried to delegate a path pattern that it wasn't delegated itself
+    #[snafu(display(
+        "Role '{}' can't delegate path pattern '{}', which is outside its own paths",
+        role,
+        path
+    ))]
+    DelegatedPathNotAllowed {
+        role: String,
+        path: String,
+        backtrace: Backtrace,
+    },
+
+    /// A target is listed by more than one role along its search path, which
+    /// [`RepositoryLoader::strict_delegation_uniqueness`](crate::RepositoryLoader::strict_delegation_uniqueness)
+    /// rejects.
+    #[snafu(display(
+        "Target '{}' is listed by more than one role: {}",
+        name,
+        roles.join(", ")
+    ))]
+    DuplicateTarget {
+        name: String,
+        roles: Vec<String>,
+        backtrace: Backtrace,
+    },
+
+    /// A path pattern was delegated that an earlier, terminating delegation already covers
+    #[snafu(display(
+        "Path pattern '{}' is already delegated to terminating role '{}', which shadows '{}'",
+        path,
+        sibling,
+        name
+    ))]
+    DelegatedPathShadowed {
+        name: String,
+        path: String,
+        sibling: String,
+        backtrace: Backtrace,
+    },
+
     /// SignedDelegatedTargets has more than 1 signed targets
     #[snafu(display("Exactly 1 role was required, but {} were created", count))]
     InvalidRoleCount { count: usize },
 
+    /// A target's custom metadata field could not be deserialized into the requested type.
+    #[snafu(display("Invalid custom field '{}' for target '{}': {}", key, target, source))]
+    TargetCustomField {
+        target: String,
+        key: String,
+        source: schema::Error,
+        backtrace: Backtrace,
+    },
+
     /// Could not create a targets map
     #[snafu(display("Could not create a targets map: {}", source))]
     TargetsMap { source: schema::Error },
@@ -532,6 +833,14 @@ pub enum Error {
 }
 
 // used in `std::io::Read` implementations
+/// Lets [`TargetName`](crate::TargetName) itself be passed where a name that converts to one is
+/// expected; that conversion can't fail.
+impl From<std::convert::Infallible> for Error {
+    fn from(err: std::convert::Infallible) -> Self {
+        match err {}
+    }
+}
+
 impl From<Error> for std::io::Error {
     fn from(err: Error) -> Self {
         Self::new(std::io::ErrorKind::Other, err)
@@ -2,12 +2,109 @@
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
 use crate::error::{self, Result};
-use crate::io::{DigestAdapter, MaxSizeAdapter};
-use crate::transport::Transport;
+use crate::instrument::debug_event;
+#[cfg(feature = "tracing")]
+use crate::instrument::redact;
+use crate::io::{DigestAdapter, DownloadBudget, MaxSizeAdapter};
+use crate::schema::Hashes;
+use crate::transport::{CacheValidators, ConditionalFetch, Transport, TransportError};
+use flate2::read::GzDecoder;
 use snafu::ResultExt;
 use std::io::Read;
 use url::Url;
 
+/// A `Transport` that counts everything read from the files that `inner` fetches against a
+/// [`DownloadBudget`], so that the fetch helpers below are bounded in total as well as per file.
+#[derive(Debug, Clone)]
+pub(crate) struct BudgetTransport<'a> {
+    inner: &'a dyn Transport,
+    budget: DownloadBudget,
+}
+
+impl<'a> BudgetTransport<'a> {
+    pub(crate) fn new(inner: &'a dyn Transport, max_total_download: u64) -> Self {
+        Self {
+            inner,
+            budget: DownloadBudget::new(max_total_download),
+        }
+    }
+}
+
+impl Transport for BudgetTransport<'_> {
+    fn fetch(&self, url: Url) -> std::result::Result<Box<dyn Read + Send>, TransportError> {
+        Ok(self.budget.reader(self.inner.fetch(url)?))
+    }
+
+    fn fetch_if_modified(
+        &self,
+        url: Url,
+        validators: &CacheValidators,
+    ) -> std::result::Result<ConditionalFetch, TransportError> {
+        Ok(match self.inner.fetch_if_modified(url, validators)? {
+            ConditionalFetch::Modified { reader, validators } => ConditionalFetch::Modified {
+                reader: self.budget.reader(reader),
+                validators,
+            },
+            not_modified => not_modified,
+        })
+    }
+
+    fn head(&self, url: Url) -> std::result::Result<Option<u64>, TransportError> {
+        self.inner.head(url)
+    }
+}
+
+/// A `Transport` for mirrors that store metadata compressed with gzip, as set with
+/// [`RepositoryLoader::compressed_metadata`](crate::RepositoryLoader::compressed_metadata). Each
+/// file is fetched from its URL with `.gz` appended to the path, and decompressed as it is read,
+/// so the fetch helpers below bound and hash the decompressed JSON.
+#[derive(Debug, Clone)]
+pub(crate) struct GzipTransport<'a> {
+    inner: &'a dyn Transport,
+}
+
+impl<'a> GzipTransport<'a> {
+    pub(crate) fn new(inner: &'a dyn Transport) -> Self {
+        Self { inner }
+    }
+}
+
+fn gz_url(mut url: Url) -> Url {
+    let path = format!("{}.gz", url.path());
+    url.set_path(&path);
+    url
+}
+
+impl Transport for GzipTransport<'_> {
+    fn fetch(&self, url: Url) -> std::result::Result<Box<dyn Read + Send>, TransportError> {
+        Ok(Box::new(GzDecoder::new(self.inner.fetch(gz_url(url))?)))
+    }
+
+    fn fetch_if_modified(
+        &self,
+        url: Url,
+        validators: &CacheValidators,
+    ) -> std::result::Result<ConditionalFetch, TransportError> {
+        Ok(
+            match self.inner.fetch_if_modified(gz_url(url), validators)? {
+                ConditionalFetch::Modified { reader, validators } => ConditionalFetch::Modified {
+                    reader: Box::new(GzDecoder::new(reader)),
+                    validators,
+                },
+                not_modified => not_modified,
+            },
+        )
+    }
+}
+
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(
+        level = "debug",
+        skip_all,
+        fields(url = %redact(&url), max_size = max_size)
+    )
+)]
 pub(crate) fn fetch_max_size(
     transport: &dyn Transport,
     url: Url,
@@ -23,14 +120,54 @@ pub(crate) fn fetch_max_size(
     ))
 }
 
-pub(crate) fn fetch_sha256(
+/// Like [`fetch_max_size`], but returns `None` if `validators` show that the file hasn't changed.
+/// Otherwise, the reader is returned with the validators for the next fetch.
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(
+        level = "debug",
+        skip_all,
+        fields(url = %redact(&url), max_size = max_size)
+    )
+)]
+pub(crate) fn fetch_max_size_if_modified(
+    transport: &dyn Transport,
+    url: Url,
+    validators: &CacheValidators,
+    max_size: u64,
+    specifier: &'static str,
+) -> Result<Option<(impl Read + Send, CacheValidators)>> {
+    match transport
+        .fetch_if_modified(url.clone(), validators)
+        .context(error::Transport { url })?
+    {
+        ConditionalFetch::NotModified => {
+            debug_event!("not modified");
+            Ok(None)
+        }
+        ConditionalFetch::Modified { reader, validators } => Ok(Some((
+            MaxSizeAdapter::new(reader, specifier, max_size),
+            validators,
+        ))),
+    }
+}
+
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(
+        level = "debug",
+        skip_all,
+        fields(url = %redact(&url), size = size)
+    )
+)]
+pub(crate) fn fetch_hashes(
     transport: &dyn Transport,
     url: Url,
     size: u64,
     specifier: &'static str,
-    sha256: &[u8],
+    hashes: &Hashes,
 ) -> Result<impl Read + Send> {
-    Ok(DigestAdapter::sha256(
+    DigestAdapter::hashes(
         Box::new(MaxSizeAdapter::new(
             transport
                 .fetch(url.clone())
@@ -38,7 +175,7 @@ pub(crate) fn fetch_sha256(
             specifier,
             size,
         )),
-        sha256,
+        hashes,
         url,
-    ))
+    )
 }
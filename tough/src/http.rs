@@ -1,14 +1,20 @@
 //! The `http` module provides `HttpTransport` which enables `Repository` objects to be
 //! loaded over HTTP
-use crate::{Transport, TransportError, TransportErrorKind};
+use crate::{CacheValidators, ConditionalFetch, Transport, TransportError, TransportErrorKind};
 use log::{debug, error, trace};
 use reqwest::blocking::{Client, ClientBuilder, Request, Response};
-use reqwest::header::{self, HeaderValue, ACCEPT_RANGES};
-use reqwest::{Error, Method};
+use reqwest::header::{
+    self, HeaderValue, ACCEPT_ENCODING, ACCEPT_RANGES, CONTENT_ENCODING, CONTENT_LENGTH, ETAG,
+    IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED,
+};
+use reqwest::Method;
+use reqwest::StatusCode;
 use snafu::ResultExt;
 use snafu::Snafu;
 use std::cmp::Ordering;
+use std::fmt::{self, Debug, Formatter};
 use std::io::Read;
+use std::sync::Arc;
 use std::time::Duration;
 use url::Url;
 
@@ -21,12 +27,13 @@ use url::Url;
 /// let http_transport = HttpTransportBuilder::new()
 /// .tries(3)
 /// .backoff_factor(1.5)
-/// .build();
+/// .build()
+/// .unwrap();
 /// ```
 ///
 /// See [`HttpTransport`] for proxy support and other behavior details.
 ///
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Debug)]
 pub struct HttpTransportBuilder {
     timeout: Duration,
     connect_timeout: Duration,
@@ -34,6 +41,15 @@ pub struct HttpTransportBuilder {
     initial_backoff: Duration,
     max_backoff: Duration,
     backoff_factor: f32,
+    accept_gzip: bool,
+    accept_zstd: bool,
+    #[cfg(any(feature = "http-native-tls", feature = "http-rustls"))]
+    root_certificates: Vec<reqwest::Certificate>,
+    danger_accept_invalid_certs: bool,
+    client_identity: Option<ClientIdentity>,
+    retry_on: RetryPredicate,
+    max_redirects: usize,
+    custom_client: Option<Client>,
 }
 
 impl Default for HttpTransportBuilder {
@@ -46,6 +62,15 @@ impl Default for HttpTransportBuilder {
             initial_backoff: std::time::Duration::from_millis(100),
             max_backoff: std::time::Duration::from_secs(1),
             backoff_factor: 1.5,
+            accept_gzip: false,
+            accept_zstd: false,
+            #[cfg(any(feature = "http-native-tls", feature = "http-rustls"))]
+            root_certificates: Vec::new(),
+            danger_accept_invalid_certs: false,
+            client_identity: None,
+            retry_on: RetryPredicate(Arc::new(Self::default_retry_on)),
+            max_redirects: 5,
+            custom_client: None,
         }
     }
 }
@@ -94,10 +119,318 @@ impl HttpTransportBuilder {
         self
     }
 
+    /// Retry a failed fetch only if `predicate` returns `true` for the kind of error, up to the
+    /// number of [`tries`](HttpTransportBuilder::tries). This replaces
+    /// [`HttpTransportBuilder::default_retry_on`], which `predicate` can call for the kinds it
+    /// doesn't want to decide itself. An error while reading a response body is a
+    /// [`TransportErrorKind::Connection`] error.
+    ///
+    /// ```
+    /// # use tough::{HttpTransportBuilder, TransportErrorKind};
+    /// // Also retry when the server rejects a request, e.g. with an expired session.
+    /// let http_transport = HttpTransportBuilder::new()
+    ///     .retry_on(|kind| {
+    ///         matches!(kind, TransportErrorKind::Rejected)
+    ///             || HttpTransportBuilder::default_retry_on(kind)
+    ///     })
+    ///     .build()
+    ///     .unwrap();
+    /// ```
+    pub fn retry_on<F>(mut self, predicate: F) -> Self
+    where
+        F: Fn(&TransportErrorKind) -> bool + Send + Sync + 'static,
+    {
+        self.retry_on = RetryPredicate(Arc::new(predicate));
+        self
+    }
+
+    /// Whether an error is retried if [`HttpTransportBuilder::retry_on`] isn't set. Connection
+    /// failures, timeouts, `429 Too Many Requests` and `5xx` statuses are retried. Other `4xx`
+    /// statuses, including those for files that don't exist, aren't, because trying again would
+    /// get the same answer.
+    pub fn default_retry_on(kind: &TransportErrorKind) -> bool {
+        matches!(
+            kind,
+            TransportErrorKind::Connection
+                | TransportErrorKind::TooManyRequests
+                | TransportErrorKind::ServerError
+        )
+    }
+
+    /// Set the maximum number of redirects to follow for a request. A response that would redirect
+    /// again is a [`TransportErrorKind::TooManyRedirects`] error, so with `0`, any redirect is an
+    /// error rather than being followed. The default is 5.
+    ///
+    /// The size limits that apply to a file also apply to the response it's redirected to.
+    pub fn max_redirects(mut self, value: usize) -> Self {
+        self.max_redirects = value;
+        self
+    }
+
+    /// Accept responses compressed with `encoding`, and transparently decompress them. Call this
+    /// once for each encoding to accept. By default, no `Accept-Encoding` header is sent and
+    /// response bodies are returned exactly as received.
+    ///
+    /// The lengths and hashes in TUF metadata describe the files as they are stored in the
+    /// repository, which is the decompressed body of a response with a `Content-Encoding`.
+    /// Decompressed responses are therefore what TUF verifies. Don't enable this for a server that
+    /// labels files that are *stored* compressed (e.g. a `.gz` target listed with the hash of the
+    /// compressed file) with a `Content-Encoding`, because those files would fail verification.
+    pub fn accept_encoding(mut self, encoding: ContentEncoding) -> Self {
+        match encoding {
+            ContentEncoding::Gzip => self.accept_gzip = true,
+            ContentEncoding::Zstd => self.accept_zstd = true,
+        }
+        self
+    }
+
+    /// The value of the `Accept-Encoding` header, if any encodings are accepted.
+    fn accept_encoding_header(&self) -> Option<&'static str> {
+        match (self.accept_gzip, self.accept_zstd) {
+            (false, false) => None,
+            (true, false) => Some("gzip"),
+            (false, true) => Some("zstd"),
+            (true, true) => Some("gzip, zstd"),
+        }
+    }
+
+    /// Trust `cert`, a PEM- or DER-encoded X.509 certificate, as a root for TLS connections in
+    /// addition to the system's trusted roots. A PEM file may contain several certificates, all of
+    /// which are trusted. Call this once for each additional certificate or file.
+    ///
+    /// This only affects how the transport authenticates the server it connects to. It has no
+    /// bearing on TUF's verification of metadata and targets, which happens regardless of how they
+    /// were fetched.
+    ///
+    /// The certificates are parsed here, so one that can't be parsed is an error now rather than a
+    /// failure of every fetch. TLS needs the `http-native-tls` or `http-rustls` feature; without
+    /// either, this returns [`HttpError::NoTlsBackend`].
+    #[cfg(any(feature = "http-native-tls", feature = "http-rustls"))]
+    pub fn add_root_certificate(mut self, cert: &[u8]) -> Result<Self, HttpError> {
+        for cert in parse_certificates(cert)? {
+            // rustls only checks a certificate when it's added to a client.
+            let _ = ClientBuilder::new()
+                .add_root_certificate(cert.clone())
+                .build()
+                .context(RootCertificate)?;
+            self.root_certificates.push(cert);
+        }
+        Ok(self)
+    }
+
+    /// Trust `cert`, a PEM- or DER-encoded X.509 certificate, as a root for TLS connections. TLS
+    /// needs the `http-native-tls` or `http-rustls` feature, so this returns
+    /// [`HttpError::NoTlsBackend`].
+    #[cfg(not(any(feature = "http-native-tls", feature = "http-rustls")))]
+    pub fn add_root_certificate(self, _cert: &[u8]) -> Result<Self, HttpError> {
+        NoTlsBackend.fail()
+    }
+
+    /// **Dangerous:** If `value` is `true`, TLS certificates are not verified at all, so the
+    /// transport will talk to any server that claims to be the one in the URL. Prefer
+    /// [`HttpTransportBuilder::add_root_certificate`] for servers with private certificates.
+    ///
+    /// TUF's verification of metadata and targets still applies, but anything else that relies on
+    /// the connection being authentic (e.g. the server's availability) does not. This has no
+    /// effect without the `http-native-tls` or `http-rustls` feature, since there's no TLS.
+    pub fn danger_accept_invalid_certs(mut self, value: bool) -> Self {
+        self.danger_accept_invalid_certs = value;
+        self
+    }
+
+    /// Present `identity` as a client certificate when a server asks for one, as servers that
+    /// require mutual TLS do. The identity is used for every request made by the transport,
+    /// including the requests made when retrying a failed read.
+    ///
+    /// `identity` is either a PKCS #12 archive, which is decrypted with `password`, or PEM containing
+    /// a private key and at least one certificate. `password` is ignored for PEM, whose private key
+    /// must not be encrypted.
+    ///
+    /// PKCS #12 needs the `http-native-tls` feature and PEM needs the `http-rustls` feature; the
+    /// client uses the TLS backend that matches the identity. Without the needed feature, this
+    /// returns [`HttpError::TlsBackendDisabled`].
+    ///
+    /// The identity is checked here, so an identity that can't be loaded (e.g. because of a wrong
+    /// password) is an error now rather than a failed handshake when fetching.
+    pub fn client_identity(mut self, identity: &[u8], password: &str) -> Result<Self, HttpError> {
+        let identity = ClientIdentity {
+            pem: !pem::parse_many(identity).is_empty(),
+            data: identity.to_vec(),
+            password: password.to_owned(),
+        };
+        let _ = identity.configure(ClientBuilder::new())?;
+        self.client_identity = Some(identity);
+        Ok(self)
+    }
+
+    /// Use `client` for every request, including retries, instead of a client built from this
+    /// builder's settings, e.g. to share an application's existing client, with its proxy, TLS and
+    /// connection pool configuration. Clones of the transport share `client`.
+    ///
+    /// The settings that configure a client are then ignored: the timeouts,
+    /// [`max_redirects`](HttpTransportBuilder::max_redirects), root certificates,
+    /// [`danger_accept_invalid_certs`](HttpTransportBuilder::danger_accept_invalid_certs) and the
+    /// [`client_identity`](HttpTransportBuilder::client_identity). Retries and
+    /// [`accept_encoding`](HttpTransportBuilder::accept_encoding) still apply, since they're handled
+    /// for each request. Don't enable automatic decompression in `client`, or the lengths and hashes
+    /// in TUF metadata may not match what it returns.
+    pub fn with_client(mut self, client: Client) -> Self {
+        self.custom_client = Some(client);
+        self
+    }
+
+    /// Creates a reqwest client from these settings.
+    fn client(&self) -> Result<Client, HttpError> {
+        let max_redirects = self.max_redirects;
+        // Unlike this policy, `Policy::limited(n)` follows only `n - 1` redirects, and with `0`, all
+        // of them.
+        let redirect = reqwest::redirect::Policy::custom(move |attempt| {
+            // The previous URLs include the original, so there's one for each redirect so far.
+            if attempt.previous().len() > max_redirects {
+                attempt.error("too many redirects")
+            } else {
+                attempt.follow()
+            }
+        });
+        let mut builder = ClientBuilder::new()
+            .timeout(self.timeout)
+            .connect_timeout(self.connect_timeout)
+            .redirect(redirect);
+        // Without a TLS backend, there are no TLS connections for these settings to apply to.
+        #[cfg(any(feature = "http-native-tls", feature = "http-rustls"))]
+        {
+            builder = builder.danger_accept_invalid_certs(self.danger_accept_invalid_certs);
+            for cert in &self.root_certificates {
+                builder = builder.add_root_certificate(cert.clone());
+            }
+        }
+        if let Some(identity) = &self.client_identity {
+            builder = identity.configure(builder)?;
+        }
+        builder.build().context(HttpClient)
+    }
+
     /// Construct an [`HttpTransport`] transport from this builder's settings.
-    pub fn build(self) -> HttpTransport {
-        HttpTransport { settings: self }
+    ///
+    /// The HTTP client is created here, once, so that its connections are kept alive and reused by
+    /// every fetch made with the transport or its clones. An error building it, e.g. because the
+    /// TLS backend can't be initialized, is returned as [`HttpError::HttpClient`].
+    pub fn build(self) -> Result<HttpTransport, HttpError> {
+        let client = match &self.custom_client {
+            Some(client) => client.clone(),
+            None => self.client()?,
+        };
+        Ok(HttpTransport {
+            client,
+            settings: self,
+        })
+    }
+}
+
+/// A client certificate and private key set with [`HttpTransportBuilder::client_identity`].
+#[derive(Clone)]
+struct ClientIdentity {
+    /// Whether `data` is PEM rather than PKCS #12.
+    pem: bool,
+    // Unused when neither TLS backend feature is enabled, since every identity is then an error.
+    #[cfg_attr(
+        not(any(feature = "http-native-tls", feature = "http-rustls")),
+        allow(dead_code)
+    )]
+    data: Vec<u8>,
+    #[cfg_attr(not(feature = "http-native-tls"), allow(dead_code))]
+    password: String,
+}
+
+impl ClientIdentity {
+    /// Has `builder` present the identity, switching it to the TLS backend that can load it; each
+    /// backend only supports one of the identity formats.
+    fn configure(&self, builder: ClientBuilder) -> Result<ClientBuilder, HttpError> {
+        if self.pem {
+            self.configure_pem(builder)
+        } else {
+            self.configure_pkcs12(builder)
+        }
+    }
+
+    #[cfg(feature = "http-rustls")]
+    fn configure_pem(&self, builder: ClientBuilder) -> Result<ClientBuilder, HttpError> {
+        let identity = reqwest::Identity::from_pem(&self.data).context(ClientIdentityLoad)?;
+        Ok(builder.use_rustls_tls().identity(identity))
+    }
+
+    #[cfg(not(feature = "http-rustls"))]
+    #[allow(clippy::unused_self)]
+    fn configure_pem(&self, _builder: ClientBuilder) -> Result<ClientBuilder, HttpError> {
+        TlsBackendDisabled {
+            format: "PEM",
+            feature: "http-rustls",
+        }
+        .fail()
+    }
+
+    #[cfg(feature = "http-native-tls")]
+    fn configure_pkcs12(&self, builder: ClientBuilder) -> Result<ClientBuilder, HttpError> {
+        let identity = reqwest::Identity::from_pkcs12_der(&self.data, &self.password)
+            .context(ClientIdentityLoad)?;
+        Ok(builder.use_native_tls().identity(identity))
     }
+
+    #[cfg(not(feature = "http-native-tls"))]
+    #[allow(clippy::unused_self)]
+    fn configure_pkcs12(&self, _builder: ClientBuilder) -> Result<ClientBuilder, HttpError> {
+        TlsBackendDisabled {
+            format: "PKCS #12",
+            feature: "http-native-tls",
+        }
+        .fail()
+    }
+}
+
+/// Leaves out the identity itself, which includes a private key.
+impl Debug for ClientIdentity {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ClientIdentity")
+            .field("pem", &self.pem)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A predicate set with [`HttpTransportBuilder::retry_on`].
+#[derive(Clone)]
+struct RetryPredicate(Arc<dyn Fn(&TransportErrorKind) -> bool + Send + Sync>);
+
+impl Debug for RetryPredicate {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RetryPredicate").finish_non_exhaustive()
+    }
+}
+
+/// Parses the certificates in `data`, which is either a DER-encoded certificate or PEM containing
+/// one or more certificates.
+#[cfg(any(feature = "http-native-tls", feature = "http-rustls"))]
+fn parse_certificates(data: &[u8]) -> Result<Vec<reqwest::Certificate>, HttpError> {
+    let pems = pem::parse_many(data);
+    if pems.is_empty() {
+        return Ok(vec![
+            reqwest::Certificate::from_der(data).context(RootCertificate)?
+        ]);
+    }
+    pems.iter()
+        .filter(|pem| pem.tag == "CERTIFICATE")
+        .map(|pem| reqwest::Certificate::from_der(&pem.contents).context(RootCertificate))
+        .collect()
+}
+
+/// A compression scheme that [`HttpTransport`] can decompress. See
+/// [`HttpTransportBuilder::accept_encoding`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ContentEncoding {
+    /// `Content-Encoding: gzip`
+    Gzip,
+    /// `Content-Encoding: zstd`
+    Zstd,
 }
 
 /// A [`Transport`] over HTTP with retry logic. Use the [`HttpTransportBuilder`] to construct a
@@ -108,14 +441,38 @@ impl HttpTransportBuilder {
 /// - 404: Not Found.
 /// - 410: Gone.
 ///
+/// Other `4xx` statuses are [`TransportErrorKind::Rejected`] errors, except for
+/// `429 Too Many Requests`, which is [`TransportErrorKind::TooManyRequests`]. `5xx` statuses are
+/// [`TransportErrorKind::ServerError`] errors. See [`HttpTransportBuilder::retry_on`] for which of
+/// these are retried.
+///
 /// # Proxy Support
 ///
 /// To use the `HttpTransport` with a proxy, specify the `HTTPS_PROXY` environment variable.
 /// The transport will also respect the `NO_PROXY` environment variable.
 ///
-#[derive(Clone, Copy, Debug, Default)]
+/// # Connection Reuse
+///
+/// An `HttpTransport` and its clones share one HTTP client, whose connection pool keeps
+/// connections alive between fetches, including retries. Fetching many files from the same host
+/// only pays for connecting (and the TLS handshake) once per connection.
+///
+#[derive(Clone, Debug)]
 pub struct HttpTransport {
     settings: HttpTransportBuilder,
+    /// The client set with [`HttpTransportBuilder::with_client`], or else the client built from
+    /// `settings`. `Client` is reference counted, so clones share its connection pool.
+    client: Client,
+}
+
+/// Panics if the HTTP client can't be built, like [`Client::new`]. Use
+/// [`HttpTransportBuilder::build`] to handle the error instead.
+impl Default for HttpTransport {
+    fn default() -> Self {
+        HttpTransportBuilder::default()
+            .build()
+            .expect("the default HTTP client could not be built")
+    }
 }
 
 /// Implement the `tough` `Transport` trait for `HttpRetryTransport`
@@ -124,10 +481,144 @@ impl Transport for HttpTransport {
     /// returned `RetryRead` will also retry as necessary per the `ClientSettings`.
     fn fetch(&self, url: Url) -> Result<Box<dyn Read + Send>, TransportError> {
         let mut r = RetryState::new(self.settings.initial_backoff);
-        Ok(Box::new(
-            fetch_with_retries(&mut r, &self.settings, &url)
+        let retry_read = fetch_with_retries(
+            &mut r,
+            &self.settings,
+            self.client.clone(),
+            Method::GET,
+            &url,
+            None,
+        )
+        .map_err(|e| TransportError::from((url.clone(), e)))?;
+        decode(&self.settings, retry_read).map_err(|e| TransportError::from((url, e)))
+    }
+
+    /// Send a GET request to the URL with `If-None-Match` and `If-Modified-Since` headers from
+    /// `validators`. A `304 Not Modified` response is returned as [`ConditionalFetch::NotModified`].
+    /// Otherwise, the response's `ETag` and `Last-Modified` headers are returned as the new
+    /// validators.
+    fn fetch_if_modified(
+        &self,
+        url: Url,
+        validators: &CacheValidators,
+    ) -> Result<ConditionalFetch, TransportError> {
+        let mut r = RetryState::new(self.settings.initial_backoff);
+        let retry_read = fetch_with_retries(
+            &mut r,
+            &self.settings,
+            self.client.clone(),
+            Method::GET,
+            &url,
+            Some(validators),
+        )
+        .map_err(|e| TransportError::from((url.clone(), e)))?;
+        if retry_read.response.status() == StatusCode::NOT_MODIFIED {
+            return Ok(ConditionalFetch::NotModified);
+        }
+        let header = |name| {
+            retry_read
+                .response
+                .headers()
+                .get(name)
+                .and_then(|value: &HeaderValue| value.to_str().ok())
+                .map(str::to_owned)
+        };
+        let validators = CacheValidators::new(header(ETAG), header(LAST_MODIFIED));
+        Ok(ConditionalFetch::Modified {
+            reader: decode(&self.settings, retry_read)
                 .map_err(|e| TransportError::from((url, e)))?,
-        ))
+            validators,
+        })
+    }
+
+    /// Send a GET request to the URL with a `Range` header for the `len` bytes from `start`, with
+    /// retries per the `ClientSettings`. A server that responds with the whole file, rather than
+    /// `206 Partial Content`, doesn't support ranges, which is a
+    /// [`TransportErrorKind::UnsupportedRange`] error. The range isn't decompressed, so
+    /// `Accept-Encoding` isn't sent.
+    fn fetch_range(
+        &self,
+        url: Url,
+        start: u64,
+        len: u64,
+    ) -> Result<Box<dyn Read + Send>, TransportError> {
+        let mut r = RetryState::new(self.settings.initial_backoff);
+        r.next_byte = start;
+        r.range_end = Some(start.saturating_add(len.saturating_sub(1)));
+        let retry_read = fetch_with_retries(
+            &mut r,
+            &self.settings,
+            self.client.clone(),
+            Method::GET,
+            &url,
+            None,
+        )
+        .map_err(|e| TransportError::from((url.clone(), e)))?;
+        let status = retry_read.response.status();
+        if status != StatusCode::PARTIAL_CONTENT {
+            return Err(TransportError::from((
+                url,
+                HttpError::RangeIgnored { status },
+            )));
+        }
+        Ok(Box::new(retry_read.take(len)))
+    }
+
+    /// Send a HEAD request to the URL, with retries per the `ClientSettings`, and return its
+    /// `Content-Length` header. Returns `None` if the server doesn't support `HEAD` requests (`405`
+    /// or `501`) or doesn't send the header.
+    fn head(&self, url: Url) -> Result<Option<u64>, TransportError> {
+        let mut r = RetryState::new(self.settings.initial_backoff);
+        let result = fetch_with_retries(
+            &mut r,
+            &self.settings,
+            self.client.clone(),
+            Method::HEAD,
+            &url,
+            None,
+        );
+        let retry_read = match result {
+            Ok(retry_read) => retry_read,
+            Err(e) if method_unsupported(&e) => return Ok(None),
+            Err(e) => return Err(TransportError::from((url, e))),
+        };
+        // `Response::content_length` is the size of the (empty) body of a `HEAD` response, not
+        // the header.
+        Ok(retry_read
+            .response
+            .headers()
+            .get(CONTENT_LENGTH)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.trim().parse().ok()))
+    }
+}
+
+/// Wraps `retry_read` in a decoder if its response has a `Content-Encoding` that the settings
+/// accept. Retries happen beneath the decoder, on the encoded bytes.
+fn decode(
+    settings: &HttpTransportBuilder,
+    retry_read: RetryRead,
+) -> Result<Box<dyn Read + Send>, HttpError> {
+    if settings.accept_encoding_header().is_none() {
+        return Ok(Box::new(retry_read));
+    }
+    let encoding = match retry_read.response.headers().get(CONTENT_ENCODING) {
+        None => return Ok(Box::new(retry_read)),
+        Some(value) => value
+            .to_str()
+            .unwrap_or_default()
+            .trim()
+            .to_ascii_lowercase(),
+    };
+    match encoding.as_str() {
+        "identity" => Ok(Box::new(retry_read)),
+        "gzip" | "x-gzip" if settings.accept_gzip => {
+            Ok(Box::new(flate2::read::GzDecoder::new(retry_read)))
+        }
+        "zstd" if settings.accept_zstd => Ok(Box::new(
+            zstd::stream::read::Decoder::new(retry_read).context(ZstdDecoder)?,
+        )),
+        _ => UnsupportedContentEncoding { encoding }.fail(),
     }
 }
 
@@ -136,6 +627,7 @@ impl Transport for HttpTransport {
 pub struct RetryRead {
     retry_state: RetryState,
     settings: HttpTransportBuilder,
+    client: Client,
     response: Response,
     url: Url,
 }
@@ -147,7 +639,7 @@ impl Read for RetryRead {
         loop {
             let retry_err = match self.response.read(buf) {
                 Ok(sz) => {
-                    self.retry_state.next_byte += sz;
+                    self.retry_state.next_byte += sz as u64;
                     return Ok(sz);
                 }
                 // store the error in `retry_err` to return later if there are no more retries
@@ -155,6 +647,10 @@ impl Read for RetryRead {
             };
             debug!("error during read of '{}': {:?}", self.url, retry_err);
 
+            // a failed read is a connection error, which the settings might not retry.
+            if !(self.settings.retry_on.0)(&TransportErrorKind::Connection) {
+                return Err(retry_err);
+            }
             // increment the `retry_state` and fetch a new reader if retries are not exhausted
             if self.retry_state.current_try >= self.settings.tries - 1 {
                 // we are out of retries, so return the last known error.
@@ -164,9 +660,15 @@ impl Read for RetryRead {
             self.err_if_no_range_support(retry_err)?;
             // wait, then retry the request (with a range header).
             std::thread::sleep(self.retry_state.wait);
-            let new_retry_read =
-                fetch_with_retries(&mut self.retry_state, &self.settings, &self.url)
-                    .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
+            let new_retry_read = fetch_with_retries(
+                &mut self.retry_state,
+                &self.settings,
+                self.client.clone(),
+                Method::GET,
+                &self.url,
+                None,
+            )
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
             // the new fetch succeeded so we need to replace our read object with the new one.
             self.response = new_retry_read.response;
         }
@@ -212,7 +714,9 @@ struct RetryState {
     /// The amount that the we should sleep before the next retry.
     wait: Duration,
     /// The next byte that we should read. e.g. the last read byte + 1.
-    next_byte: usize,
+    next_byte: u64,
+    /// The last byte of the file to read, if only part of it is requested.
+    range_end: Option<u64>,
 }
 
 impl RetryState {
@@ -221,6 +725,7 @@ impl RetryState {
             current_try: 0,
             wait: initial_wait,
             next_byte: 0,
+            range_end: None,
         }
     }
 }
@@ -244,53 +749,66 @@ impl RetryState {
     }
 }
 
-/// Sends a `GET` request to the `url`. Retries the request as necessary per the `ClientSettings`.
+/// Sends a request (`GET` or `HEAD`) to the `url` with `client`. Retries the request as necessary
+/// per the `ClientSettings`. If `validators` are given, the request is conditional on the file
+/// having changed.
 fn fetch_with_retries(
     r: &mut RetryState,
     cs: &HttpTransportBuilder,
+    client: Client,
+    method: Method,
     url: &Url,
+    validators: Option<&CacheValidators>,
 ) -> Result<RetryRead, HttpError> {
     trace!("beginning fetch for '{}'", url);
-    // create a reqwest client
-    let client = ClientBuilder::new()
-        .timeout(cs.timeout)
-        .connect_timeout(cs.connect_timeout)
-        .build()
-        .context(HttpClient)?;
+    // A `HEAD` request is for the size of the file as it's stored, so it isn't compressed, and
+    // neither is a range of it.
+    let accept_encoding = if method == Method::HEAD || r.range_end.is_some() {
+        None
+    } else {
+        cs.accept_encoding_header()
+    };
 
     // retry loop
     loop {
         // build the request
-        let request = build_request(&client, r.next_byte, url)?;
-
-        // send the GET request, then categories the outcome by converting to an HttpResult.
-        let http_result: HttpResult = client.execute(request).into();
-
-        match http_result {
-            HttpResult::Ok(response) => {
+        let request = build_request(
+            &client,
+            method.clone(),
+            r.next_byte,
+            r.range_end,
+            url,
+            accept_encoding,
+            validators,
+        )?;
+
+        // send the GET request, treating an HTTP error status as an error.
+        let err = match client.execute(request).and_then(Response::error_for_status) {
+            Ok(response) => {
                 trace!("{:?} - returning from successful fetch", r);
                 return Ok(RetryRead {
                     retry_state: *r,
-                    settings: *cs,
+                    settings: cs.clone(),
+                    client,
                     response,
                     url: url.clone(),
                 });
             }
-            HttpResult::Fatal(err) => {
-                trace!("{:?} - returning fatal error from fetch: {}", r, err);
-                return Err(err).context(FetchFatal);
-            }
-            HttpResult::FileNotFound(err) => {
-                trace!("{:?} - returning file not found from fetch: {}", r, err);
-                return Err(err).context(FetchFileNotFound);
-            }
-            HttpResult::Retryable(err) => {
-                trace!("{:?} - retryable error: {}", r, err);
-                if r.current_try >= cs.tries - 1 {
-                    debug!("{:?} - returning failure, no more retries: {}", r, err);
-                    return Err(err).context(FetchNoMoreRetries { tries: cs.tries });
-                }
-            }
+            Err(err) => err,
+        };
+
+        let kind = error_kind(&err);
+        if !(cs.retry_on.0)(&kind) {
+            trace!("{:?} - returning {} error from fetch: {}", r, kind, err);
+            return match kind {
+                TransportErrorKind::FileNotFound => Err(err).context(FetchFileNotFound),
+                _ => Err(err).context(FetchFatal),
+            };
+        }
+        trace!("{:?} - retryable {} error: {}", r, kind, err);
+        if r.current_try >= cs.tries - 1 {
+            debug!("{:?} - returning failure, no more retries: {}", r, err);
+            return Err(err).context(FetchNoMoreRetries { tries: cs.tries });
         }
 
         r.increment(cs);
@@ -298,98 +816,77 @@ fn fetch_with_retries(
     }
 }
 
-/// Much of the complexity in the `fetch_with_retries` function is in deciphering the `Result`
-/// we get from `reqwest::Client::execute`. Using this enum we categorize the states of the
-/// `Result` into the categories that we need to understand.
-enum HttpResult {
-    /// We got a response with an HTTP code that indicates success.
-    Ok(reqwest::blocking::Response),
-    /// We got an `Error` (other than file-not-found) which we will not retry.
-    Fatal(reqwest::Error),
-    /// The file could not be found (HTTP status 403 or 404).
-    FileNotFound(reqwest::Error),
-    /// We received an `Error`, or we received an HTTP response code that we can retry.
-    Retryable(reqwest::Error),
-}
-
-/// Takes the `Result` type from `reqwest::Client::execute`, and categorizes it into an
-/// `HttpResult` variant.
-impl From<Result<reqwest::blocking::Response, reqwest::Error>> for HttpResult {
-    fn from(result: Result<Response, Error>) -> Self {
-        match result {
-            Ok(response) => {
-                trace!("response received");
-                // checks the status code of the response for errors
-                parse_response_code(response)
-            }
-            Err(e) if e.is_timeout() => {
-                // a connection timeout occurred
-                trace!("timeout error during fetch: {}", e);
-                HttpResult::Retryable(e)
-            }
-            Err(e) if e.is_request() => {
-                // an error occurred while sending the request
-                trace!("error sending request during fetch: {}", e);
-                HttpResult::Retryable(e)
-            }
-            Err(e) => {
-                // the error is not from an HTTP status code or a timeout, retries will not succeed.
-                // these appear to be internal, reqwest errors and are expected to be unlikely.
-                trace!("internal reqwest error during fetch: {}", e);
-                HttpResult::Fatal(e)
-            }
+/// Returns `true` if `e` is from a response saying that the server doesn't support the request's
+/// method.
+fn method_unsupported(e: &HttpError) -> bool {
+    match e {
+        HttpError::FetchFatal { source } | HttpError::FetchNoMoreRetries { source, .. } => {
+            matches!(
+                source.status(),
+                Some(StatusCode::METHOD_NOT_ALLOWED | StatusCode::NOT_IMPLEMENTED)
+            )
         }
+        _ => false,
     }
 }
 
-/// Checks the HTTP response code and converts a non-successful response code to an error.
-fn parse_response_code(response: reqwest::blocking::Response) -> HttpResult {
-    match response.error_for_status() {
-        Ok(ok) => {
-            trace!("response is success");
-            // http status code indicates success
-            HttpResult::Ok(ok)
+/// Categorizes an error from `reqwest::Client::execute`, or the error status of a response, into
+/// the kind of [`TransportError`] that it becomes. The kind also decides whether the fetch is
+/// retried.
+fn error_kind(err: &reqwest::Error) -> TransportErrorKind {
+    match err.status() {
+        Some(status) if matches!(status.as_u16(), 403 | 404 | 410) => {
+            TransportErrorKind::FileNotFound
         }
-        // http status is an error
-        Err(err) => match err.status() {
-            None => {
-                // this shouldn't happen, we received this err from the err_for_status function,
-                // so the error should have a status. we cannot consider this a retryable error.
-                trace!("error is fatal (no status): {}", err);
-                HttpResult::Fatal(err)
-            }
-            Some(status) if status.is_server_error() => {
-                trace!("error is retryable: {}", err);
-                HttpResult::Retryable(err)
-            }
-            Some(status) if matches!(status.as_u16(), 403 | 404 | 410) => {
-                trace!("error is file not found: {}", err);
-                HttpResult::FileNotFound(err)
-            }
-            Some(_) => {
-                trace!("error is fatal (status): {}", err);
-                HttpResult::Fatal(err)
-            }
-        },
+        Some(StatusCode::TOO_MANY_REQUESTS) => TransportErrorKind::TooManyRequests,
+        Some(status) if status.is_server_error() => TransportErrorKind::ServerError,
+        Some(status) if status.is_client_error() => TransportErrorKind::Rejected,
+        Some(_) => TransportErrorKind::Other,
+        None if err.is_redirect() => TransportErrorKind::TooManyRedirects,
+        // a timeout, or an error while sending the request
+        None if err.is_timeout() || err.is_request() => TransportErrorKind::Connection,
+        // these appear to be internal, reqwest errors and are expected to be unlikely.
+        None => TransportErrorKind::Other,
     }
 }
 
-/// Builds a GET request. If `next_byte` is greater than zero, adds a byte range header to the request.
-fn build_request(client: &Client, next_byte: usize, url: &Url) -> Result<Request, HttpError> {
-    if next_byte == 0 {
-        let request = client
-            .request(Method::GET, url.as_str())
-            .build()
-            .context(RequestBuild)?;
+/// Builds a request with `method`. If `next_byte` is greater than zero, or there's a `range_end`,
+/// adds a byte range header to the request. If `accept_encoding` is given, adds it as the `Accept-Encoding` header. If
+/// `validators` are given, adds them as `If-None-Match` and `If-Modified-Since` headers.
+fn build_request(
+    client: &Client,
+    method: Method,
+    next_byte: u64,
+    range_end: Option<u64>,
+    url: &Url,
+    accept_encoding: Option<&'static str>,
+    validators: Option<&CacheValidators>,
+) -> Result<Request, HttpError> {
+    let mut request = client.request(method, url.as_str());
+    if let Some(accept_encoding) = accept_encoding {
+        request = request.header(ACCEPT_ENCODING, accept_encoding);
+    }
+    if let Some(validators) = validators {
+        if let Some(etag) = &validators.etag {
+            request = request.header(IF_NONE_MATCH, etag.as_str());
+        }
+        if let Some(last_modified) = &validators.last_modified {
+            request = request.header(IF_MODIFIED_SINCE, last_modified.as_str());
+        }
+    }
+    if next_byte == 0 && range_end.is_none() {
+        let request = request.build().context(RequestBuild)?;
         Ok(request)
     } else {
-        let header_value_string = format!("bytes={}-", next_byte);
+        let header_value_string = match range_end {
+            Some(range_end) => format!("bytes={}-{}", next_byte, range_end),
+            None => format!("bytes={}-", next_byte),
+        };
         let header_value =
             HeaderValue::from_str(header_value_string.as_str()).context(InvalidHeader {
                 header_value: &header_value_string,
             })?;
-        let request = client
-            .request(Method::GET, url.as_str())
+        let request = request
             .header(header::RANGE, header_value)
             .build()
             .context(RequestBuild)?;
@@ -402,6 +899,9 @@ fn build_request(client: &Client, next_byte: usize, url: &Url) -> Result<Request
 #[non_exhaustive]
 #[allow(missing_docs)]
 pub enum HttpError {
+    #[snafu(display("Unable to load client identity: {}", source))]
+    ClientIdentityLoad { source: reqwest::Error },
+
     #[snafu(display("A non-retryable error occurred: {}", source))]
     FetchFatal { source: reqwest::Error },
 
@@ -420,18 +920,50 @@ pub enum HttpError {
         source: reqwest::header::InvalidHeaderValue,
     },
 
+    #[snafu(display(
+        "Server responded to a range request with status {} rather than 206 Partial Content",
+        status
+    ))]
+    RangeIgnored { status: StatusCode },
+
     #[snafu(display("Unable to create HTTP request: {}", source))]
     RequestBuild { source: reqwest::Error },
+
+    #[snafu(display(
+        "TLS needs tough's 'http-native-tls' or 'http-rustls' feature to be enabled"
+    ))]
+    NoTlsBackend,
+
+    #[snafu(display("Invalid root certificate: {}", source))]
+    RootCertificate { source: reqwest::Error },
+
+    #[snafu(display(
+        "Client identity is {}, which needs tough's '{}' feature to be enabled",
+        format,
+        feature
+    ))]
+    TlsBackendDisabled {
+        format: &'static str,
+        feature: &'static str,
+    },
+
+    #[snafu(display("Response has unsupported Content-Encoding '{}'", encoding))]
+    UnsupportedContentEncoding { encoding: String },
+
+    #[snafu(display("Unable to create zstd decoder: {}", source))]
+    ZstdDecoder { source: std::io::Error },
 }
 
 /// Convert a URL `Url` and an `HttpError` into a `TransportError`
 impl From<(Url, HttpError)> for TransportError {
     fn from((url, e): (Url, HttpError)) -> Self {
-        match e {
-            HttpError::FetchFileNotFound { .. } => {
-                TransportError::new_with_cause(TransportErrorKind::FileNotFound, url, e)
-            }
-            _ => TransportError::new_with_cause(TransportErrorKind::Other, url, e),
-        }
+        let kind = match &e {
+            HttpError::FetchFatal { source }
+            | HttpError::FetchFileNotFound { source }
+            | HttpError::FetchNoMoreRetries { source, .. } => error_kind(source),
+            HttpError::RangeIgnored { .. } => TransportErrorKind::UnsupportedRange,
+            _ => TransportErrorKind::Other,
+        };
+        TransportError::new_with_cause(kind, url, e)
     }
 }
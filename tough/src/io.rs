@@ -1,50 +1,129 @@
 // Copyright 2019 Amazon.com, Inc. or its affiliates. All Rights Reserved.
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
-use crate::error;
-use ring::digest::{Context, SHA256};
+use crate::error::{self, Error, Result};
+use crate::schema::Hashes;
+use log::warn;
+use ring::digest::{Algorithm, Context, SHA256, SHA512};
+use snafu::{ensure, ResultExt};
+use std::fmt::{self, Debug, Formatter};
 use std::io::{self, Read};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
 use url::Url;
 
 pub(crate) struct DigestAdapter {
     url: Url,
     reader: Box<dyn Read + Send>,
-    hash: Vec<u8>,
-    digest: Option<Context>,
+    /// The digests being calculated, each with the hash it's expected to match.
+    digests: Option<Vec<(Context, Vec<u8>)>>,
 }
 
 impl DigestAdapter {
-    pub(crate) fn sha256(reader: Box<dyn Read + Send>, hash: &[u8], url: Url) -> Self {
-        Self {
+    /// Checks every hash in `hashes` that was calculated by a recognized hash function. Others are
+    /// skipped with a warning, and it's an error if none are recognized.
+    pub(crate) fn hashes(reader: Box<dyn Read + Send>, hashes: &Hashes, url: Url) -> Result<Self> {
+        let mut digests = Vec::new();
+        for (name, hash) in &hashes.digests {
+            match hash_algorithm(name) {
+                Some(algorithm) => digests.push((Context::new(algorithm), hash.to_vec())),
+                None => warn!(
+                    "not checking the {} hash of {}, which isn't a recognized hash function",
+                    name, url
+                ),
+            }
+        }
+        ensure!(
+            !digests.is_empty(),
+            error::NoRecognizedHashes {
+                context: url.to_string()
+            }
+        );
+        Ok(Self {
             url,
             reader,
-            hash: hash.to_owned(),
-            digest: Some(Context::new(&SHA256)),
+            digests: Some(digests),
+        })
+    }
+}
+
+/// Reads `reader` to the end and returns whether it has exactly `length` bytes and matches every
+/// hash in `hashes` that was calculated by a recognized hash function. At most one byte more than
+/// `length` is read. As for `DigestAdapter`, it's an error if no hash is recognized; `context`
+/// names the file in errors and warnings.
+pub(crate) fn matches_hashes<R: Read>(
+    reader: R,
+    length: u64,
+    hashes: &Hashes,
+    context: &str,
+) -> Result<bool> {
+    let mut digests = Vec::new();
+    for (name, hash) in &hashes.digests {
+        match hash_algorithm(name) {
+            Some(algorithm) => digests.push((Context::new(algorithm), hash.as_ref())),
+            None => warn!(
+                "not checking the {} hash of {}, which isn't a recognized hash function",
+                name, context
+            ),
         }
     }
+    ensure!(!digests.is_empty(), error::NoRecognizedHashes { context });
+
+    let mut reader = reader.take(length.saturating_add(1));
+    let mut buf = [0; 8192];
+    let mut size = 0;
+    loop {
+        let read = match reader.read(&mut buf) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err).context(error::TargetRead),
+        };
+        size += read as u64;
+        for (digest, _) in &mut digests {
+            digest.update(&buf[..read]);
+        }
+    }
+    Ok(size == length
+        && digests
+            .into_iter()
+            .all(|(digest, hash)| digest.finish().as_ref() == hash))
+}
+
+/// Returns the hash function with the given name in TUF metadata, if it's recognized.
+pub(crate) fn hash_algorithm(name: &str) -> Option<&'static Algorithm> {
+    match name {
+        "sha256" => Some(&SHA256),
+        "sha512" => Some(&SHA512),
+        _ => None,
+    }
 }
 
 impl Read for DigestAdapter {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
         assert!(
-            self.digest.is_some(),
+            self.digests.is_some(),
             "DigestAdapter::read called after end of file"
         );
 
         let size = self.reader.read(buf)?;
         if size == 0 {
-            let result = std::mem::replace(&mut self.digest, None).unwrap().finish();
-            if result.as_ref() != self.hash.as_slice() {
-                error::HashMismatch {
-                    context: self.url.to_string(),
-                    calculated: hex::encode(result),
-                    expected: hex::encode(&self.hash),
+            for (digest, hash) in self.digests.take().unwrap() {
+                let result = digest.finish();
+                if result.as_ref() != hash.as_slice() {
+                    error::HashMismatch {
+                        context: self.url.to_string(),
+                        calculated: hex::encode(result),
+                        expected: hex::encode(&hash),
+                    }
+                    .fail()?;
                 }
-                .fail()?;
             }
             Ok(size)
-        } else if let Some(digest) = &mut self.digest {
-            digest.update(&buf[..size]);
+        } else if let Some(digests) = &mut self.digests {
+            for (digest, _) in digests {
+                digest.update(&buf[..size]);
+            }
             Ok(size)
         } else {
             unreachable!();
@@ -92,9 +171,188 @@ impl Read for MaxSizeAdapter {
     }
 }
 
+/// A limit on the total number of bytes read from several readers, e.g. all the metadata fetched
+/// while loading a repository. Clones share the same count.
+#[derive(Debug, Clone)]
+pub(crate) struct DownloadBudget {
+    max_total_download: u64,
+    counter: Arc<AtomicU64>,
+}
+
+impl DownloadBudget {
+    pub(crate) fn new(max_total_download: u64) -> Self {
+        Self {
+            max_total_download,
+            counter: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Wraps `reader` so that the bytes read from it count against this budget.
+    pub(crate) fn reader(&self, reader: Box<dyn Read + Send>) -> Box<dyn Read + Send> {
+        Box::new(DownloadBudgetAdapter {
+            reader,
+            budget: self.clone(),
+        })
+    }
+}
+
+struct DownloadBudgetAdapter {
+    reader: Box<dyn Read + Send>,
+    budget: DownloadBudget,
+}
+
+impl Read for DownloadBudgetAdapter {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let size = self.reader.read(buf)?;
+        let total = self
+            .budget
+            .counter
+            .fetch_add(size as u64, Ordering::Relaxed)
+            + size as u64;
+        if total > self.budget.max_total_download {
+            error::MaxTotalDownloadExceeded {
+                max_total_download: self.budget.max_total_download,
+            }
+            .fail()?;
+        }
+        Ok(size)
+    }
+}
+
+/// A reader of a target's contents, returned by [`Repository::read_target`], which also knows how
+/// long the target should be.
+///
+/// [`Repository::read_target`]: crate::Repository::read_target
+pub struct TargetReader {
+    reader: Box<dyn Read + Send>,
+    expected_len: Option<u64>,
+}
+
+impl TargetReader {
+    pub(crate) fn new(reader: Box<dyn Read + Send>, expected_len: Option<u64>) -> Self {
+        Self {
+            reader,
+            expected_len,
+        }
+    }
+
+    /// The length of the target in its targets metadata, if known. A target that is read without
+    /// an error has exactly this many bytes, so this can be used to preallocate space for it or to
+    /// show progress.
+    pub fn expected_len(&self) -> Option<u64> {
+        self.expected_len
+    }
+
+    /// Wraps this reader in a [`VerifyingReader`], so that the target's contents can be verified
+    /// with [`VerifyingReader::finish`] even if it isn't read to the end.
+    pub fn verifying(self) -> VerifyingReader {
+        VerifyingReader {
+            reader: self,
+            state: VerifyingState::Reading,
+        }
+    }
+}
+
+impl Read for TargetReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.reader.read(buf)
+    }
+}
+
+impl Debug for TargetReader {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TargetReader")
+            .field("expected_len", &self.expected_len)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A [`TargetReader`] whose verification is confirmed explicitly with [`VerifyingReader::finish`],
+/// returned by [`TargetReader::verifying`].
+///
+/// A target's hashes can only be checked once all of its contents have been read, so a caller
+/// that stops reading early never learns whether what it read was valid. `finish` reads whatever
+/// is left and returns whether the whole target matched its targets metadata. Reading only part
+/// of a target is then an explicit decision: **a `VerifyingReader` that is dropped without calling
+/// `finish` has not been verified, unless it was read to the end without an error.**
+pub struct VerifyingReader {
+    reader: TargetReader,
+    state: VerifyingState,
+}
+
+/// How far a [`VerifyingReader`] has been read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum VerifyingState {
+    /// The end of the target hasn't been reached.
+    Reading,
+    /// The end of the target was reached, and its contents were verified.
+    Verified,
+    /// A read returned an error, so the target can't be verified.
+    Failed,
+}
+
+impl VerifyingReader {
+    /// The length of the target in its targets metadata, as for [`TargetReader::expected_len`].
+    pub fn expected_len(&self) -> Option<u64> {
+        self.reader.expected_len()
+    }
+
+    /// Reads and discards the rest of the target, and returns `Ok(())` if all of its contents
+    /// matched its targets metadata. Otherwise, returns the error, e.g. a hash mismatch; if an
+    /// earlier read already returned an error, an error saying so is returned.
+    pub fn finish(mut self) -> Result<()> {
+        match self.state {
+            VerifyingState::Verified => Ok(()),
+            VerifyingState::Failed => error::TargetReadFailed.fail(),
+            VerifyingState::Reading => match io::copy(&mut self, &mut io::sink()) {
+                Ok(_) => Ok(()),
+                // Errors from the adapters are this library's errors wrapped in an `io::Error`.
+                Err(err) if err.get_ref().map_or(false, |inner| inner.is::<Error>()) => {
+                    match err.into_inner().map(|inner| inner.downcast::<Error>()) {
+                        Some(Ok(err)) => Err(*err),
+                        _ => unreachable!("checked that the inner error is an Error"),
+                    }
+                }
+                Err(err) => Err(err).context(error::TargetRead),
+            },
+        }
+    }
+}
+
+impl Read for VerifyingReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self.state {
+            VerifyingState::Reading => {}
+            VerifyingState::Verified => return Ok(0),
+            VerifyingState::Failed => error::TargetReadFailed.fail()?,
+        }
+        match self.reader.read(buf) {
+            Ok(0) if !buf.is_empty() => {
+                self.state = VerifyingState::Verified;
+                Ok(0)
+            }
+            Ok(size) => Ok(size),
+            Err(err) => {
+                self.state = VerifyingState::Failed;
+                Err(err)
+            }
+        }
+    }
+}
+
+impl Debug for VerifyingReader {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("VerifyingReader")
+            .field("expected_len", &self.reader.expected_len)
+            .field("state", &self.state)
+            .finish()
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::io::{DigestAdapter, MaxSizeAdapter};
+    use crate::io::{DigestAdapter, MaxSizeAdapter, TargetReader};
+    use crate::schema::Hashes;
     use hex_literal::hex;
     use std::io::{Cursor, Read};
     use url::Url;
@@ -111,23 +369,107 @@ mod tests {
         assert!(reader.read_to_end(&mut buf).is_err());
     }
 
+    fn hashes(digests: &[(&str, &[u8])]) -> Hashes {
+        Hashes {
+            digests: digests
+                .iter()
+                .map(|(name, digest)| ((*name).to_owned(), digest.to_vec().into()))
+                .collect(),
+        }
+    }
+
+    fn digest_adapter(digests: &[(&str, &[u8])]) -> crate::error::Result<DigestAdapter> {
+        DigestAdapter::hashes(
+            Box::new(Cursor::new(b"hello".to_vec())),
+            &hashes(digests),
+            Url::parse("file:///").unwrap(),
+        )
+    }
+
     #[test]
     fn test_digest_adapter() {
-        let mut reader = DigestAdapter::sha256(
-            Box::new(Cursor::new(b"hello".to_vec())),
+        let mut reader = digest_adapter(&[(
+            "sha256",
             &hex!("2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"),
-            Url::parse("file:///").unwrap(),
-        );
+        )])
+        .unwrap();
         let mut buf = Vec::new();
         assert!(reader.read_to_end(&mut buf).is_ok());
         assert_eq!(buf, b"hello");
 
-        let mut reader = DigestAdapter::sha256(
-            Box::new(Cursor::new(b"hello".to_vec())),
+        let mut reader = digest_adapter(&[(
+            "sha256",
             &hex!("0ebdc3317b75839f643387d783535adc360ca01f33c75f7c1e7373adcd675c0b"),
-            Url::parse("file:///").unwrap(),
-        );
+        )])
+        .unwrap();
+        let mut buf = Vec::new();
+        assert!(reader.read_to_end(&mut buf).is_err());
+    }
+
+    #[test]
+    fn test_digest_adapter_algorithms() {
+        let sha256 = hex!("2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824");
+        let sha512 = hex!("9b71d224bd62f3785d96d46ad3ea3d73319bfbc2890caadae2dff72519673ca72323c3d99ba5c11d7c7acc6e14b8c5da0c4663475c2e5c3adef46f73bcdec043");
+
+        // Every recognized hash is checked, and unrecognized ones are skipped.
+        let mut reader = digest_adapter(&[
+            ("sha256", &sha256),
+            ("sha512", &sha512),
+            ("blake2b", &[0; 64]),
+        ])
+        .unwrap();
+        let mut buf = Vec::new();
+        assert!(reader.read_to_end(&mut buf).is_ok());
+
+        // A mismatched SHA-512 fails even if SHA-256 matches.
+        let mut reader = digest_adapter(&[("sha256", &sha256), ("sha512", &[0; 64])]).unwrap();
         let mut buf = Vec::new();
         assert!(reader.read_to_end(&mut buf).is_err());
+
+        // With no recognized hash, there's nothing to check against.
+        assert!(digest_adapter(&[("blake2b", &[0; 64])]).is_err());
+        assert!(digest_adapter(&[]).is_err());
+    }
+
+    fn verifying_reader(digest: &[u8]) -> super::VerifyingReader {
+        TargetReader::new(
+            Box::new(digest_adapter(&[("sha256", digest)]).unwrap()),
+            Some(5),
+        )
+        .verifying()
+    }
+
+    #[test]
+    fn test_verifying_reader() {
+        let good = hex!("2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824");
+        let bad = hex!("0ebdc3317b75839f643387d783535adc360ca01f33c75f7c1e7373adcd675c0b");
+
+        // Reading only part of the target, then finishing, verifies the rest.
+        let mut reader = verifying_reader(&good);
+        let mut buf = [0; 2];
+        reader.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"he");
+        assert!(reader.finish().is_ok());
+
+        let mut reader = verifying_reader(&bad);
+        reader.read_exact(&mut buf).unwrap();
+        assert!(matches!(
+            reader.finish(),
+            Err(crate::error::Error::HashMismatch { .. })
+        ));
+
+        // Reading to the end verifies the target, and finishing confirms it.
+        let mut reader = verifying_reader(&good);
+        let mut buf = Vec::new();
+        reader.read_to_end(&mut buf).unwrap();
+        assert!(reader.finish().is_ok());
+
+        // A failed read can't be finished successfully.
+        let mut reader = verifying_reader(&bad);
+        assert!(reader.read_to_end(&mut Vec::new()).is_err());
+        assert!(matches!(
+            reader.finish(),
+            Err(crate::error::Error::TargetReadFailed { .. })
+        ));
     }
 }
@@ -17,6 +17,24 @@
 //! Integration tests require docker and are disabled by default behind a feature named `integ`.
 //! To run all tests, including integration tests: `cargo test --all-features` or
 //! `cargo test --features 'http,integ'`.
+//!
+//! # Tracing
+//!
+//! With the `tracing` feature, loading and refreshing a [`Repository`] emits [`tracing`] spans
+//! and events at the debug level. There is a span for each step of the load (updating the root
+//! metadata, loading each top-level role, and walking the delegations) and for each fetch, which
+//! shows where the time goes; events record the versions and sizes of the verified metadata. URLs
+//! are recorded without credentials, query strings and fragments. Without the feature, nothing is
+//! emitted and the instrumentation isn't compiled in.
+//!
+//! [`tracing`]: https://docs.rs/tracing
+//!
+//! # Time
+//!
+//! Metadata expiration is checked against the system clock, which is read through `chrono` with
+//! the default `clock` feature. Builds without a system clock, e.g. for embedded targets, can
+//! disable default features to drop `chrono`'s clock support and its dependencies, and set a
+//! [`RepositoryLoader::time_source`] instead.
 
 #![forbid(missing_debug_implementations, missing_copy_implementations)]
 #![deny(rust_2018_idioms)]
@@ -29,6 +47,7 @@
     clippy::missing_errors_doc
 )]
 
+mod archive;
 mod cache;
 mod datastore;
 pub mod editor;
@@ -36,28 +55,61 @@ pub mod error;
 mod fetch;
 #[cfg(feature = "http")]
 pub mod http;
+mod instrument;
 mod io;
 pub mod key_source;
+mod parse;
+mod refresh;
 pub mod schema;
 pub mod sign;
+mod target_cache;
+mod target_path;
 mod transport;
+mod validate;
 
-use crate::datastore::Datastore;
+pub use crate::archive::TarTransport;
+use crate::datastore::{Datastore, DatastoreTransport};
 use crate::error::Result;
-use crate::fetch::{fetch_max_size, fetch_sha256};
+use crate::fetch::{
+    fetch_hashes, fetch_max_size, fetch_max_size_if_modified, BudgetTransport, GzipTransport,
+};
 /// An HTTP transport that includes retries.
 #[cfg(feature = "http")]
-pub use crate::http::{HttpTransport, HttpTransportBuilder, RetryRead};
+pub use crate::http::{ContentEncoding, HttpTransport, HttpTransportBuilder, RetryRead};
+#[cfg(feature = "tracing")]
+use crate::instrument::redact;
+use crate::instrument::{debug_event, warn_event};
+use crate::io::{hash_algorithm, matches_hashes};
+pub use crate::io::{TargetReader, VerifyingReader};
+use crate::parse::{parse_metadata, parse_metadata_reader, ParsedMetadata};
+pub use crate::refresh::RefreshHandle;
+use crate::schema::decoded::{Decoded, Hex};
+use crate::schema::key::Key;
 use crate::schema::{DelegatedRole, Delegations};
-use crate::schema::{Role, RoleType, Root, Signed, Snapshot, Timestamp};
+use crate::schema::{Role, RoleType, Root, Signed, Snapshot, Target, Timestamp};
+use crate::target_cache::{cached_target_path, read_cached, CachingReader};
+pub use crate::target_path::{safe_target_path, TargetName};
 pub use crate::transport::{
-    DefaultTransport, FilesystemTransport, Transport, TransportError, TransportErrorKind,
+    CacheValidators, ConditionalFetch, ConfinedFilesystemTransport, DefaultTransport,
+    FilesystemTransport, NoNetworkTransport, Transport, TransportError, TransportErrorKind,
 };
+#[cfg(feature = "clock")]
+pub use crate::validate::validate_metadata;
+pub use crate::validate::{validate_metadata_at, MetadataReport, RoleReport};
 use chrono::{DateTime, Utc};
+use log::warn;
+use semver::{Version, VersionReq};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
 use snafu::{ensure, OptionExt, ResultExt};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::convert::TryInto;
+use std::fmt::{self, Debug, Formatter};
 use std::io::Read;
-use std::path::PathBuf;
+use std::num::NonZeroU64;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
 use url::Url;
 
 /// Represents whether a Repository should fail to load when metadata is expired (`Safe`) or whether
@@ -95,6 +147,26 @@ impl From<ExpirationEnforcement> for bool {
     }
 }
 
+/// Represents what a [`Repository`] does when its [`datastore`](RepositoryLoader::datastore) is
+/// locked by another `Repository` that is updating it, in this process or another one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DatastoreLocking {
+    /// Loading fails with [`Error::DatastoreLocked`](crate::error::Error::DatastoreLocked).
+    Fail,
+
+    /// Loading waits until the other `Repository` releases the lock.
+    Block,
+}
+
+/// `DatastoreLocking` defaults to `Block` mode. The lock is only held while metadata is being
+/// updated, so loading waits for another update to finish, not for another `Repository` to be
+/// dropped.
+impl Default for DatastoreLocking {
+    fn default() -> Self {
+        DatastoreLocking::Block
+    }
+}
+
 /// A builder for settings with which to load a [`Repository`]. Required settings are provided in
 /// the [`RepositoryLoader::new`] function. Optional parameters can be added after calling new.
 /// Finally, call [`RepositoryLoader::load`] to load the [`Repository`].
@@ -157,7 +229,21 @@ where
     transport: Option<Box<dyn Transport>>,
     limits: Option<Limits>,
     datastore: Option<PathBuf>,
+    datastore_locking: Option<DatastoreLocking>,
+    keep_datastore: bool,
     expiration_enforcement: Option<ExpirationEnforcement>,
+    metadata_fetch: MetadataFetch,
+    root_chain_dir: Option<PathBuf>,
+    on_key_rotation: Option<KeyRotationCallback>,
+    time_source: Option<TimeSource>,
+    lenient_datetime: bool,
+    delegation_options: DelegationOptions,
+    filenames: MetadataFilenames,
+    required_spec_version: Option<VersionReq>,
+    hash_requirements: HashRequirements,
+    allow_unverified_partial_reads: bool,
+    target_cache_dir: Option<PathBuf>,
+    new_root: Option<Vec<u8>>,
 }
 
 impl<R: Read> RepositoryLoader<R> {
@@ -178,10 +264,60 @@ impl<R: Read> RepositoryLoader<R> {
             transport: None,
             limits: None,
             datastore: None,
+            datastore_locking: None,
+            keep_datastore: false,
             expiration_enforcement: None,
+            metadata_fetch: MetadataFetch::default(),
+            root_chain_dir: None,
+            on_key_rotation: None,
+            time_source: None,
+            lenient_datetime: false,
+            delegation_options: DelegationOptions::default(),
+            filenames: MetadataFilenames::default(),
+            required_spec_version: None,
+            hash_requirements: HashRequirements::default(),
+            allow_unverified_partial_reads: false,
+            target_cache_dir: None,
+            new_root: None,
         }
     }
 
+    /// Create a new `RepositoryLoader` for a repository whose metadata and targets are in the
+    /// `metadata` and `targets` directories under `base_url`. For example, with a `base_url` of
+    /// `https://example.com/repo`, metadata is fetched from `https://example.com/repo/metadata/`
+    /// and targets from `https://example.com/repo/targets/`. `base_url` may or may not end with a
+    /// slash.
+    ///
+    /// If the metadata and targets are in the same directory, pass its URL as both base URLs to
+    /// [`RepositoryLoader::new`] instead.
+    pub fn new_same_base(root: R, base_url: Url) -> Self {
+        Self::new(
+            root,
+            subdirectory_url(&base_url, "metadata"),
+            subdirectory_url(&base_url, "targets"),
+        )
+    }
+
+    /// Sets the transport according to the schemes of the base URLs: [`FilesystemTransport`] if
+    /// both are `file` URLs, and otherwise [`DefaultTransport`], which also supports `http` and
+    /// `https` URLs if the `http` feature is enabled.
+    ///
+    /// Fails if a URL's scheme isn't supported, including `http` and `https` without the `http`
+    /// feature, so that this is reported before anything is fetched. [`RepositoryLoader::load`]
+    /// checks the URLs the same way when no transport is set.
+    pub fn transport_from_url(mut self) -> Result<Self> {
+        check_url_scheme(&self.metadata_base_url)?;
+        check_url_scheme(&self.targets_base_url)?;
+        self.transport = if self.metadata_base_url.scheme() == "file"
+            && self.targets_base_url.scheme() == "file"
+        {
+            Some(Box::new(FilesystemTransport))
+        } else {
+            Some(Box::new(DefaultTransport::new()))
+        };
+        Ok(self)
+    }
+
     /// Load and verify TUF repository metadata.
     pub fn load(self) -> Result<Repository> {
         Repository::load(self)
@@ -206,11 +342,39 @@ impl<R: Read> RepositoryLoader<R> {
     /// You may chose to provide a [`PathBuf`] to a directory on a persistent filesystem, which must
     /// exist prior to calling [`RepositoryLoader::load`]. If no datastore is provided, a temporary
     /// directory will be created and cleaned up for for you.
+    ///
+    /// While a [`Repository`] is loaded, [refreshed](Repository::refresh) or
+    /// [prefetched](Repository::prefetch), it holds an exclusive advisory lock on a `.lock` file in
+    /// the datastore, so that two repositories can't interleave their writes to the datastore and
+    /// corrupt the metadata used to detect rollbacks. This applies to repositories in the same
+    /// process as well as in different processes. The lock is released once the update is done,
+    /// so several repositories can use the same datastore. See
+    /// [`RepositoryLoader::datastore_locking`] for what happens when the datastore is already
+    /// locked.
     pub fn datastore<P: Into<PathBuf>>(mut self, datastore: P) -> Self {
         self.datastore = Some(datastore.into());
         self
     }
 
+    /// Set the [`DatastoreLocking`] mode, which decides whether loading waits (the default) or
+    /// fails when another [`Repository`] is updating the
+    /// [`datastore`](RepositoryLoader::datastore) and holds its lock. The same mode is used when
+    /// the repository is refreshed.
+    pub fn datastore_locking(mut self, locking: DatastoreLocking) -> Self {
+        self.datastore_locking = Some(locking);
+        self
+    }
+
+    /// If `keep` is `true`, the temporary directory that is created when no
+    /// [`datastore`](RepositoryLoader::datastore) is set isn't removed, so that the metadata in it
+    /// can be inspected, e.g. to debug a failed load. Its path is logged at the debug level when
+    /// it's created, and is returned by [`Repository::datastore_path`] once loaded. A datastore set
+    /// with `datastore` is never removed.
+    pub fn keep_datastore(mut self, keep: bool) -> Self {
+        self.keep_datastore = keep;
+        self
+    }
+
     /// Set the [`ExpirationEnforcement`].
     ///
     /// **CAUTION:** TUF metadata expiration dates, particularly `timestamp.json`, are designed to
@@ -220,6 +384,308 @@ impl<R: Read> RepositoryLoader<R> {
         self.expiration_enforcement = Some(exp);
         self
     }
+
+    /// Load the repository without fetching any metadata, using only the metadata files that a
+    /// previous load stored in the [`datastore`](RepositoryLoader::datastore).
+    ///
+    /// The stored metadata is verified exactly as fetched metadata would be: signatures, versions,
+    /// hashes, and (subject to [`ExpirationEnforcement`]) expiration dates are all checked against
+    /// the trusted root. Newer roots are looked for in the datastore, so `root` may be older than
+    /// the latest trusted root if [`Repository::prefetch`] stored the chain of roots leading up to
+    /// it; otherwise, `root` should be the latest trusted root. If a required metadata file is not
+    /// in the datastore, loading fails with a
+    /// [`TransportErrorKind::FileNotFound`] transport error naming the file.
+    ///
+    /// Only metadata is read from the datastore; targets are still fetched with the configured
+    /// [`Transport`] from `targets_base_url`.
+    pub fn offline(mut self) -> Self {
+        self.metadata_fetch.offline = true;
+        self
+    }
+
+    /// Save each root metadata file that is fetched while updating from the trusted `root` to the
+    /// latest root. The files are written to `dir`, which is created if needed, as
+    /// `VERSION.root.json`, exactly as they were received. A file is only written once its
+    /// signatures and version have been verified against the previous root.
+    ///
+    /// The trusted `root` itself is not written, and nothing is written if it is already the latest
+    /// root. Unlike [`Repository::cache_metadata`] with `cache_root_chain`, which fetches every
+    /// root version again, this captures the chain that was verified during the load.
+    pub fn save_root_chain<P: Into<PathBuf>>(mut self, dir: P) -> Self {
+        self.root_chain_dir = Some(dir.into());
+        self
+    }
+
+    /// Call `callback` whenever the keys of a top-level role differ between a root metadata file
+    /// and the next one, while updating from the trusted `root` to the latest root, e.g. to alert
+    /// an operator of a key rotation. It's passed the role and its keys before and after the
+    /// update. It's also called when the repository is [refreshed](Repository::refresh).
+    ///
+    /// The callback is only told about root metadata that has been verified, and it has no effect
+    /// on how the repository is loaded.
+    pub fn on_key_rotation<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(RoleType, &[Key], &[Key]) + Send + Sync + 'static,
+    {
+        self.on_key_rotation = Some(KeyRotationCallback(Arc::new(callback)));
+        self
+    }
+
+    /// Read the current time from `time_source` instead of the system clock. The time is used to
+    /// check metadata expiration and to detect the system time stepping backward, both when the
+    /// repository is loaded and afterward.
+    ///
+    /// This is required when tough is built without the `clock` feature; otherwise
+    /// [`load`](Self::load) fails with [`Error::NoTimeSource`](error::Error::NoTimeSource).
+    pub fn time_source<F>(mut self, time_source: F) -> Self
+    where
+        F: Fn() -> DateTime<Utc> + Send + Sync + 'static,
+    {
+        self.time_source = Some(TimeSource(Arc::new(time_source)));
+        self
+    }
+
+    /// Trust `new_root` instead of the `root` passed to [`new`](Self::new), to move the client to
+    /// a new root of trust that the root metadata chain doesn't lead to, e.g. after a repository
+    /// is rebuilt with new keys or the client is pointed at a different repository.
+    ///
+    /// **This is an out-of-band trust action.** `new_root` is trusted without being signed by any
+    /// root the client already trusts, so its fingerprint, the hex-encoded SHA-256 digest of the
+    /// file, must be obtained through a separate channel that you trust (not from the repository
+    /// itself) and passed as `fingerprint`. The fingerprint is compared case-insensitively, and a
+    /// mismatch is an error. The root metadata chain is then updated from `new_root` as usual.
+    ///
+    /// The timestamp, snapshot and targets metadata in the datastore were trusted under the old
+    /// root, so once the root metadata chain has been verified from `new_root`, they are discarded
+    /// rather than being used to check the new repository for rollbacks. If the chain can't be
+    /// verified, they're kept, and loading fails.
+    pub fn accept_new_root<N: Read>(mut self, mut new_root: N, fingerprint: &str) -> Result<Self> {
+        let mut data = Vec::new();
+        new_root
+            .read_to_end(&mut data)
+            .map_err(serde_json::Error::io)
+            .context(error::ParseTrustedMetadata)?;
+        let calculated = hex::encode(ring::digest::digest(&ring::digest::SHA256, &data));
+        ensure!(
+            fingerprint.eq_ignore_ascii_case(&calculated),
+            error::RootFingerprintMismatch {
+                expected: fingerprint,
+                calculated,
+            }
+        );
+        self.new_root = Some(data);
+        Ok(self)
+    }
+
+    /// Set whether `expires` in the metadata of every role may be a datetime other than RFC 3339,
+    /// which the TUF specification requires. When `true`, an ISO 8601 datetime without an offset,
+    /// such as `2030-01-01T00:00:00`, is accepted and interpreted as UTC, and a space may separate
+    /// the date and time. Signatures are still verified against the metadata as it was received.
+    ///
+    /// The default is `false`, so that metadata that doesn't conform to the specification is
+    /// rejected. Only enable this to interoperate with a repository that you can't fix.
+    pub fn lenient_datetime(mut self, lenient_datetime: bool) -> Self {
+        self.lenient_datetime = lenient_datetime;
+        self
+    }
+
+    /// Set whether a delegated role whose metadata fails to load (e.g. it can't be fetched, or
+    /// fails verification) is skipped rather than failing the load. This includes a role that isn't
+    /// listed in the snapshot metadata, e.g. because it hasn't been published yet, which is skipped
+    /// without fetching anything. A skipped role is logged as a
+    /// warning, and its [`DelegatedRole::targets`] is `None`, so the targets that it, or the roles
+    /// it delegates to, would have listed are not found. This also applies when refreshing.
+    ///
+    /// The default is `false`. Only enable this if you can accept its security cost: the targets
+    /// of a skipped role are looked for in the roles after it instead, so a lower-priority role
+    /// (or an attacker who can make a role fail to load) may provide a target that the skipped role
+    /// should have. The top-level roles are always verified.
+    pub fn best_effort_delegations(mut self, best_effort: bool) -> Self {
+        self.delegation_options.best_effort = best_effort;
+        self
+    }
+
+    /// Set how many delegated roles may be fetched at once. The roles delegated by the same role
+    /// are independent of each other, so up to `concurrency` of them are fetched and verified
+    /// concurrently, each on its own thread, before the roles they delegate to are loaded. The
+    /// result is the same whatever order the fetches finish in. This also applies when refreshing.
+    ///
+    /// The default is 1, which fetches delegated roles one at a time on the calling thread; 0 is
+    /// treated as 1. A higher value helps most with a repository that has many delegated roles
+    /// served over HTTP, where the default transport shares its connection pool between threads.
+    pub fn delegation_concurrency(mut self, concurrency: usize) -> Self {
+        self.delegation_options.concurrency = concurrency;
+        self
+    }
+
+    /// Set whether loading fails if more than one role could provide the same target. Target
+    /// lookups search the delegation tree in preorder and take the first role that lists a target,
+    /// so a later role that also lists it is shadowed. When `true`, a target listed by more than
+    /// one role along its search path, i.e. by roles whose delegated paths all allow it and that
+    /// aren't cut off by a terminating delegation, is an error naming those roles. This also
+    /// applies when refreshing.
+    ///
+    /// The default is `false`, which keeps the precedence that the TUF specification describes.
+    /// Enabling it catches a delegation that was added to shadow another role's targets, at the
+    /// cost of rejecting repositories that list a target twice on purpose.
+    pub fn strict_delegation_uniqueness(mut self, strict: bool) -> Self {
+        self.delegation_options.strict_uniqueness = strict;
+        self
+    }
+
+    /// Set whether the snapshot metadata must list hashes for the top-level targets metadata. When
+    /// `true`, loading (or refreshing) fails if it doesn't, rather than fetching the targets
+    /// metadata up to its length (or [`Limits::max_targets_size`]) and relying on its signatures
+    /// and version alone.
+    ///
+    /// The default is `false`, because the TUF specification makes the hashes optional. Enabling
+    /// it hardens a client against a repository that omits them, e.g. one published by tooling
+    /// that doesn't bind the targets metadata to the snapshot metadata by hash.
+    pub fn require_targets_hash(mut self, require: bool) -> Self {
+        self.hash_requirements.targets_hash = require;
+        self
+    }
+
+    /// Set whether every target must list a hash that can be verified. When `true`, loading (or
+    /// refreshing) fails if a target listed by the targets metadata, or by any delegated role,
+    /// has no hashes, or only hashes by functions that this library doesn't recognize, so that its
+    /// content couldn't be verified when it's read. See [`Repository::targets_missing_hashes`].
+    ///
+    /// This is about the targets themselves, unlike
+    /// [`require_targets_hash`](Self::require_targets_hash), which is about the targets metadata
+    /// file. The default is `false`; such a target fails when it's read instead.
+    pub fn require_target_hashes(mut self, require: bool) -> Self {
+        self.hash_requirements.target_hashes = require;
+        self
+    }
+
+    /// Set whether [`Repository::read_target_range`] may be used to read part of a target.
+    ///
+    /// **Bytes read this way are not verified.** The hashes in TUF metadata cover whole targets,
+    /// so there's nothing to check part of a target against, and whoever serves the target can
+    /// return anything in its place. Only enable this if you can safely handle such bytes, e.g.
+    /// to decide whether to download a whole target, which is then verified as usual.
+    ///
+    /// The default is `false`, so that every byte read from a repository is verified.
+    pub fn allow_unverified_partial_reads(mut self, allow: bool) -> Self {
+        self.allow_unverified_partial_reads = allow;
+        self
+    }
+
+    /// Require the `spec_version` declared by the metadata of every role, including delegated
+    /// roles, to match `requirement`, e.g. `VersionReq::parse("^1").unwrap()`. Loading (or
+    /// refreshing) fails if a role declares a spec version that doesn't match, or that isn't a
+    /// version at all. A spec version with only two components, such as `1.0`, is read as `1.0.0`.
+    ///
+    /// Without a requirement, a role that declares a spec version whose major version differs from
+    /// the one this library implements (1) is logged as a warning with the `tracing` feature, and
+    /// the metadata is used anyway.
+    pub fn require_spec_version(mut self, requirement: VersionReq) -> Self {
+        self.required_spec_version = Some(requirement);
+        self
+    }
+
+    /// Set the filename that the timestamp metadata is fetched as, instead of `timestamp.json`.
+    ///
+    /// This does not conform to the TUF specification, which fixes the filename. It's only meant
+    /// for interoperating with a mirror that you can't fix. The metadata is still verified as
+    /// usual, and it's still stored in the datastore as `timestamp.json`. In
+    /// [`offline`](Self::offline) mode, this has no effect, because the metadata is read from the
+    /// datastore.
+    pub fn timestamp_filename<S: Into<String>>(mut self, filename: S) -> Self {
+        self.filenames.timestamp = filename.into();
+        self
+    }
+
+    /// Set the filename that the snapshot metadata is fetched as, instead of `snapshot.json`.
+    /// With consistent snapshots, the version is still prepended to it, e.g. `42.FILENAME`.
+    ///
+    /// As with [`timestamp_filename`](Self::timestamp_filename), this does not conform to the TUF
+    /// specification, and has no effect in offline mode. The timestamp metadata must still list
+    /// the snapshot metadata as `snapshot.json`.
+    pub fn snapshot_filename<S: Into<String>>(mut self, filename: S) -> Self {
+        self.filenames.snapshot = filename.into();
+        self
+    }
+
+    /// Set the filename that the top-level targets metadata is fetched as, instead of
+    /// `targets.json`. With consistent snapshots, the version is still prepended to it, e.g.
+    /// `42.FILENAME`.
+    ///
+    /// As with [`timestamp_filename`](Self::timestamp_filename), this does not conform to the TUF
+    /// specification, and has no effect in offline mode. The snapshot metadata must still list the
+    /// targets metadata as `targets.json`. Delegated roles' metadata filenames are unaffected.
+    pub fn targets_filename<S: Into<String>>(mut self, filename: S) -> Self {
+        self.filenames.targets = filename.into();
+        self
+    }
+
+    /// Set whether metadata is fetched from a mirror that stores it compressed with gzip. When
+    /// `true`, `.gz` is appended to the name of every metadata file that is fetched, including the
+    /// root metadata files, delegated roles' metadata, and the overrides set with
+    /// [`timestamp_filename`](Self::timestamp_filename) and friends, e.g. `timestamp.json.gz`.
+    /// Each file is decompressed before it is parsed. This also applies when refreshing.
+    ///
+    /// The lengths and hashes that the timestamp and snapshot metadata list for other metadata
+    /// files are checked against the decompressed JSON, i.e. the files as they were signed and
+    /// listed by the repository, not the compressed files served by the mirror. Likewise, the size
+    /// limits in [`Limits`] apply to the decompressed JSON, except for
+    /// [`Limits::max_total_download`], which counts the compressed bytes that are fetched. The
+    /// datastore, and the output of [`Repository::cache`], hold the decompressed files under
+    /// their usual names.
+    ///
+    /// The default is `false`. This does not conform to the TUF specification, and only affects
+    /// how metadata files are named; a transport that decompresses responses with a
+    /// `Content-Encoding` needs no option. Targets are never decompressed, and this has no effect
+    /// in offline mode.
+    pub fn compressed_metadata(mut self, compressed: bool) -> Self {
+        self.metadata_fetch.compressed = compressed;
+        self
+    }
+
+    /// Set a directory that [`Repository::read_target`] keeps a copy of each target in, so that a
+    /// target that hasn't changed isn't downloaded again. The directory is created when the
+    /// repository is loaded, if it doesn't exist. Unlike the datastore, which only holds metadata,
+    /// it can be shared by several repositories and kept across runs.
+    ///
+    /// A target is cached at its path under the directory, with the hex digest of its hash
+    /// prepended to the file name, as with consistent snapshots, e.g. `linux/HASH.foo.bin`. When a
+    /// target is read, a cached copy is used only if its length and hashes match the target's
+    /// metadata; the copy is read in full to check this before it is returned. Otherwise, e.g. if
+    /// the copy is missing, truncated, or corrupt, the target is downloaded as usual. A downloaded
+    /// target is written to the cache once it has been read to the end and verified. Failing to
+    /// read or write the cache never fails `read_target`; it is logged as a warning.
+    ///
+    /// Only `read_target` uses the cache; [`Repository::cache`] always downloads.
+    pub fn target_cache_dir<P: Into<PathBuf>>(mut self, dir: P) -> Self {
+        self.target_cache_dir = Some(dir.into());
+        self
+    }
+
+    /// Require every target to list a hash from each of `algorithms`, e.g. `&["sha512"]`, by
+    /// their names in TUF metadata. Reading (or caching) a target that doesn't list all of them
+    /// fails before anything is downloaded. Loading fails if an algorithm isn't one that this
+    /// library can verify (`sha256` or `sha512`).
+    ///
+    /// Every listed hash that this library can verify is always checked, whether it is required
+    /// or not, so a required hash must also match the target's contents. By default, no algorithm
+    /// is required, and a target only needs to list one hash that can be verified.
+    pub fn require_hash_algorithms(mut self, algorithms: &[&str]) -> Self {
+        self.hash_requirements.algorithms = algorithms
+            .iter()
+            .map(|algorithm| (*algorithm).to_owned())
+            .collect();
+        self
+    }
+}
+
+impl<'a> RepositoryLoader<&'a [u8]> {
+    /// Create a new `RepositoryLoader` from the bytes of the trusted root metadata file, e.g. one
+    /// embedded in your software with `include_bytes!("1.root.json")`, so that no file needs to be
+    /// read to bootstrap trust. See [`RepositoryLoader::new`] for the meaning of the arguments.
+    pub fn from_root_bytes(root: &'a [u8], metadata_base_url: Url, targets_base_url: Url) -> Self {
+        Self::new(root, metadata_base_url, targets_base_url)
+    }
 }
 
 /// Limits used when fetching repository metadata.
@@ -240,6 +706,10 @@ impl<R: Read> RepositoryLoader<R> {
 /// * `max_targets_size`: 10 MiB
 /// * `max_timestamp_size`: 1 MiB
 /// * `max_root_updates`: 1024
+/// * `max_snapshot_size`: 1 MiB
+/// * `max_delegated_roles`: 2048
+/// * `max_total_download`: 1 GiB
+/// * `max_root_total_size`: 64 MiB
 #[derive(Debug, Clone, Copy)]
 pub struct Limits {
     /// The maximum allowable size in bytes for downloaded root.json files.
@@ -255,6 +725,28 @@ pub struct Limits {
 
     /// The maximum number of updates to root.json to download.
     pub max_root_updates: u64,
+
+    /// The maximum allowable size in bytes for the downloaded snapshot.json file **if** the size is
+    /// not listed in timestamp.json. This setting is ignored if the size of snapshot.json is in the
+    /// signed timestamp.json file.
+    pub max_snapshot_size: u64,
+
+    /// The maximum number of delegated roles to load, counted across all levels of delegation.
+    /// Each delegated role is fetched separately, so this bounds the number of requests that a
+    /// repository can cause, however small each of its metadata files is.
+    pub max_delegated_roles: u64,
+
+    /// The maximum number of bytes of metadata to download while loading or refreshing a
+    /// repository, counted across the root chain, timestamp, snapshot, targets, and all delegated
+    /// targets. Each file is also bounded on its own, by its listed length or the limits above.
+    pub max_total_download: u64,
+
+    /// The maximum number of bytes of root.json files to download while updating the root
+    /// metadata, counted across all the updates. Each file is also bounded by `max_root_size`, but
+    /// without this, a chain of `max_root_updates` files could add up to `max_root_updates *
+    /// max_root_size` bytes. When fewer than `max_root_size` bytes are left, the next file is
+    /// bounded by what is left instead.
+    pub max_root_total_size: u64,
 }
 
 impl Default for Limits {
@@ -264,6 +756,10 @@ impl Default for Limits {
             max_targets_size: 1024 * 1024 * 10, // 10 MiB
             max_timestamp_size: 1024 * 1024,    // 1 MiB
             max_root_updates: 1024,
+            max_snapshot_size: 1024 * 1024, // 1 MiB
+            max_delegated_roles: 2048,
+            max_total_download: 1024 * 1024 * 1024, // 1 GiB
+            max_root_total_size: 1024 * 1024 * 64,  // 64 MiB
         }
     }
 }
@@ -271,6 +767,11 @@ impl Default for Limits {
 /// A TUF repository.
 ///
 /// You can create a `Repository` using a [`RepositoryLoader`].
+///
+/// A `Repository` is `Send` and `Sync`, so its methods, including [`Repository::read_target`],
+/// can be called concurrently from several threads, either through a shared reference (e.g. an
+/// `Arc<Repository>`) or on clones. Clones share the same datastore, so they also share its lock
+/// and the latest known system time that is used to detect the clock stepping backward.
 #[derive(Debug, Clone)]
 pub struct Repository {
     transport: Box<dyn Transport>,
@@ -279,95 +780,698 @@ pub struct Repository {
     earliest_expiration: DateTime<Utc>,
     earliest_expiration_role: RoleType,
     root: Signed<Root>,
+    root_update_outcome: RootUpdateOutcome,
     snapshot: Signed<Snapshot>,
     timestamp: Signed<Timestamp>,
     targets: Signed<crate::schema::Targets>,
+    raw_metadata: RawMetadata,
     limits: Limits,
     metadata_base_url: Url,
     targets_base_url: Url,
     expiration_enforcement: ExpirationEnforcement,
+    metadata_fetch: MetadataFetch,
+    root_chain_dir: Option<PathBuf>,
+    on_key_rotation: Option<KeyRotationCallback>,
+    lenient_datetime: bool,
+    delegation_options: DelegationOptions,
+    filenames: MetadataFilenames,
+    required_spec_version: Option<VersionReq>,
+    hash_requirements: HashRequirements,
+    allow_unverified_partial_reads: bool,
+    target_cache_dir: Option<PathBuf>,
+}
+
+/// The filenames that the top-level metadata files other than root are fetched as, which can be
+/// overridden with [`RepositoryLoader::timestamp_filename`] and friends.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct MetadataFilenames {
+    timestamp: String,
+    snapshot: String,
+    targets: String,
+}
+
+impl Default for MetadataFilenames {
+    fn default() -> Self {
+        Self {
+            timestamp: "timestamp.json".to_owned(),
+            snapshot: "snapshot.json".to_owned(),
+            targets: "targets.json".to_owned(),
+        }
+    }
+}
+
+/// Where the top-level metadata is read from, set with [`RepositoryLoader::offline`] and
+/// [`RepositoryLoader::compressed_metadata`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct MetadataFetch {
+    /// Whether metadata is read back from the datastore instead of being fetched.
+    offline: bool,
+    /// Whether fetched metadata is compressed with gzip.
+    compressed: bool,
+}
+
+/// How delegated roles are loaded, set with [`RepositoryLoader::best_effort_delegations`],
+/// [`RepositoryLoader::delegation_concurrency`] and
+/// [`RepositoryLoader::strict_delegation_uniqueness`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct DelegationOptions {
+    best_effort: bool,
+    concurrency: usize,
+    strict_uniqueness: bool,
+}
+
+impl Default for DelegationOptions {
+    fn default() -> Self {
+        Self {
+            best_effort: false,
+            concurrency: 1,
+            strict_uniqueness: false,
+        }
+    }
+}
+
+/// The hashes that metadata must list, set with [`RepositoryLoader::require_targets_hash`],
+/// [`RepositoryLoader::require_target_hashes`] and [`RepositoryLoader::require_hash_algorithms`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct HashRequirements {
+    targets_hash: bool,
+    target_hashes: bool,
+    algorithms: Vec<String>,
+}
+
+/// The signature of a callback set with [`RepositoryLoader::on_key_rotation`]: it's passed a role
+/// and its keys before and after the update.
+type KeyRotationFn = dyn Fn(RoleType, &[Key], &[Key]) + Send + Sync;
+
+/// A callback set with [`RepositoryLoader::on_key_rotation`].
+#[derive(Clone)]
+struct KeyRotationCallback(Arc<KeyRotationFn>);
+
+impl Debug for KeyRotationCallback {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KeyRotationCallback")
+            .finish_non_exhaustive()
+    }
+}
+
+/// The signature of a time source set with [`RepositoryLoader::time_source`].
+type TimeSourceFn = dyn Fn() -> DateTime<Utc> + Send + Sync;
+
+/// Where the current time is read from: a time source set with
+/// [`RepositoryLoader::time_source`], or the system clock.
+#[derive(Clone)]
+pub(crate) struct TimeSource(Arc<TimeSourceFn>);
+
+impl TimeSource {
+    /// Reads the system clock, if tough was built with the `clock` feature.
+    pub(crate) fn system() -> Result<Self> {
+        #[cfg(feature = "clock")]
+        return Ok(Self(Arc::new(Utc::now)));
+        #[cfg(not(feature = "clock"))]
+        return error::NoTimeSource.fail();
+    }
+
+    /// A time source that always returns `now`.
+    pub(crate) fn fixed(now: DateTime<Utc>) -> Self {
+        Self(Arc::new(move || now))
+    }
+
+    fn now(&self) -> DateTime<Utc> {
+        (self.0)()
+    }
+}
+
+impl Debug for TimeSource {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TimeSource").finish_non_exhaustive()
+    }
+}
+
+/// How updating the root metadata went while loading a [`Repository`], returned by
+/// [`Repository::root_update_outcome`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct RootUpdateOutcome {
+    /// The version of the trusted root metadata that updating started from.
+    pub initial_version: NonZeroU64,
+    /// The version of the root metadata that updating ended at, which the repository trusts.
+    pub final_version: NonZeroU64,
+    /// Why updating stopped at `final_version`.
+    pub reason: RootUpdateStopReason,
+}
+
+/// Why updating the root metadata stopped, as part of a [`RootUpdateOutcome`].
+///
+/// Only the reasons that let loading go on are listed. Updating more than
+/// [`Limits::max_root_updates`] times, or fetching a root metadata file that fails verification,
+/// is an error instead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum RootUpdateStopReason {
+    /// There is no root metadata file for the version after the final version, so the final
+    /// version is the latest one. This is how updating normally stops.
+    ReachedLatest,
+    /// The root metadata file for the version after the final version lists the final version
+    /// itself, so it was ignored rather than updating in a loop. This is off-spec: the file's name
+    /// doesn't match its contents, which suggests a problem with the repository.
+    SameVersion,
+}
+
+/// The versions of a [`Repository`]'s metadata, returned by [`Repository::metadata_versions`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct MetadataVersions {
+    /// The version of the root metadata.
+    pub root: NonZeroU64,
+    /// The version of the timestamp metadata.
+    pub timestamp: NonZeroU64,
+    /// The version of the snapshot metadata.
+    pub snapshot: NonZeroU64,
+    /// The version of the top-level targets metadata.
+    pub targets: NonZeroU64,
+    /// The versions of the delegated roles' metadata, keyed by role name.
+    pub delegated: BTreeMap<String, NonZeroU64>,
+}
+
+/// The targets that differ between two versions of a repository's targets metadata, returned by
+/// [`Repository::diff_targets`]. Each list is sorted by name.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct TargetDiff {
+    /// Targets listed now that weren't listed before.
+    pub added: Vec<String>,
+    /// Targets listed before that aren't listed now.
+    pub removed: Vec<String>,
+    /// Targets listed in both whose hashes are different.
+    pub changed: Vec<String>,
+}
+
+/// The metadata files of the top-level roles, exactly as they were received.
+#[derive(Debug, Clone)]
+struct RawMetadata {
+    root: Vec<u8>,
+    timestamp: Vec<u8>,
+    snapshot: Vec<u8>,
+    targets: Vec<u8>,
+    /// The metadata of each delegated role that was loaded, keyed by the role's name.
+    delegated: HashMap<String, Vec<u8>>,
 }
 
+/// The top-level metadata fetched and verified by [`RepositoryLoader::load`] and
+/// [`Repository::refresh`], with the files it was parsed from.
+struct TopLevelMetadata {
+    root: Signed<Root>,
+    timestamp: Signed<Timestamp>,
+    snapshot: Signed<Snapshot>,
+    targets: Signed<crate::schema::Targets>,
+    raw: RawMetadata,
+}
+
+/// The top-level targets metadata returned by [`load_targets`], with the file it was parsed from and
+/// the files of the delegated roles that were loaded, keyed by the role's name.
+type LoadedTargets = (
+    Signed<crate::schema::Targets>,
+    Vec<u8>,
+    HashMap<String, Vec<u8>>,
+);
+
 impl Repository {
     /// Load and verify TUF repository metadata using a [`RepositoryLoader`] for the settings.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(metadata_base_url = %redact(&loader.metadata_base_url))
+        )
+    )]
     fn load<R: Read>(loader: RepositoryLoader<R>) -> Result<Self> {
-        let datastore = Datastore::new(loader.datastore)?;
-        let transport = loader
-            .transport
-            .unwrap_or_else(|| Box::new(DefaultTransport::new()));
+        let time_source = match loader.time_source {
+            Some(time_source) => time_source,
+            None => TimeSource::system()?,
+        };
+        let datastore = Datastore::new(
+            loader.datastore,
+            loader.datastore_locking.unwrap_or_default(),
+            loader.keep_datastore,
+            time_source,
+        )?;
+        let _lock = datastore.lock()?;
+        let transport = match loader.transport {
+            Some(transport) => transport,
+            None => {
+                // Report a URL that the default transport can't fetch now, rather than as a
+                // transport error from the first fetch. Metadata isn't fetched in offline mode.
+                if !loader.metadata_fetch.offline {
+                    check_url_scheme(&loader.metadata_base_url)?;
+                }
+                check_url_scheme(&loader.targets_base_url)?;
+                Box::new(DefaultTransport::new())
+            }
+        };
         let limits = loader.limits.unwrap_or_default();
         let expiration_enforcement = loader.expiration_enforcement.unwrap_or_default();
+        for algorithm in &loader.hash_requirements.algorithms {
+            ensure!(
+                hash_algorithm(algorithm).is_some(),
+                error::UnrecognizedHashAlgorithm { algorithm }
+            );
+        }
+        // Cached targets are checked through `file` URLs, which need an absolute path.
+        let target_cache_dir = match loader.target_cache_dir {
+            Some(dir) => {
+                std::fs::create_dir_all(&dir).context(error::DirCreate { path: &dir })?;
+                Some(
+                    dir.canonicalize()
+                        .context(error::FileMetadata { path: &dir })?,
+                )
+            }
+            None => None,
+        };
         let metadata_base_url = parse_url(loader.metadata_base_url)?;
         let targets_base_url = parse_url(loader.targets_base_url)?;
 
-        // 0. Load the trusted root metadata file + 1. Update the root metadata file
-        let root = load_root(
-            transport.as_ref(),
-            loader.root,
-            &datastore,
-            limits.max_root_size,
-            limits.max_root_updates,
-            &metadata_base_url,
-            expiration_enforcement,
-        )?;
+        // The datastore has the metadata under the spec filenames, whatever they were fetched as.
+        let default_filenames = MetadataFilenames::default();
+        let filenames = if loader.metadata_fetch.offline {
+            &default_filenames
+        } else {
+            &loader.filenames
+        };
 
-        // 2. Download the timestamp metadata file
-        let timestamp = load_timestamp(
-            transport.as_ref(),
-            &root,
-            &datastore,
-            limits.max_timestamp_size,
-            &metadata_base_url,
-            expiration_enforcement,
-        )?;
+        // A new root accepted out of band replaces the trusted root.
+        let mut trusted_root = loader.root;
+        let mut new_root = loader.new_root.as_deref();
+        let re_root = new_root.is_some();
+        let trusted_root: &mut dyn Read = match &mut new_root {
+            Some(new_root) => new_root,
+            None => &mut trusted_root,
+        };
 
-        // 3. Download the snapshot metadata file
-        let snapshot = load_snapshot(
+        // The closure below can't borrow `loader`, which has been partly moved out of.
+        let metadata_fetch = loader.metadata_fetch;
+        let root_chain_dir = loader.root_chain_dir.as_deref();
+        let on_key_rotation = loader.on_key_rotation.as_ref();
+        let lenient_datetime = loader.lenient_datetime;
+        let delegation_options = &loader.delegation_options;
+        let targets_hash = loader.hash_requirements.targets_hash;
+        let (root_update_outcome, metadata) = with_metadata_transport(
             transport.as_ref(),
-            &root,
-            &timestamp,
             &datastore,
-            &metadata_base_url,
-            expiration_enforcement,
-        )?;
+            metadata_fetch,
+            limits.max_total_download,
+            |metadata_transport| {
+                // 0. Load the trusted root metadata file + 1. Update the root metadata file
+                let (root, root_data, root_update_outcome) = load_root(
+                    metadata_transport,
+                    trusted_root,
+                    root_chain_dir,
+                    on_key_rotation,
+                    &datastore,
+                    limits.max_root_size,
+                    limits.max_root_updates,
+                    limits.max_root_total_size,
+                    &metadata_base_url,
+                    expiration_enforcement,
+                    lenient_datetime,
+                    metadata_fetch.offline,
+                )?;
+                // Once the new root's chain has been verified, the metadata trusted under the old
+                // root is discarded, as in step 1.9, so that its versions don't prevent loading the
+                // new repository. Until then, it's kept in case the new root fails to load.
+                if re_root {
+                    let r1 = datastore.remove("timestamp.json");
+                    let r2 = datastore.remove("snapshot.json");
+                    let r3 = datastore.remove("targets.json");
+                    r1.and(r2).and(r3)?;
+                }
 
-        // 4. Download the targets metadata file
-        let targets = load_targets(
-            transport.as_ref(),
+                // 2. Download the timestamp metadata file
+                let (timestamp, timestamp_data) = load_timestamp(
+                    metadata_transport,
+                    &root,
+                    &datastore,
+                    limits.max_timestamp_size,
+                    &metadata_base_url,
+                    &filenames.timestamp,
+                    expiration_enforcement,
+                    lenient_datetime,
+                )?;
+
+                // 3. Download the snapshot metadata file
+                let (snapshot, snapshot_data) = load_snapshot(
+                    metadata_transport,
+                    &root,
+                    &timestamp,
+                    &datastore,
+                    limits.max_snapshot_size,
+                    &metadata_base_url,
+                    &filenames.snapshot,
+                    expiration_enforcement,
+                    lenient_datetime,
+                )?;
+
+                // 4. Download the targets metadata file
+                let (targets, targets_data, delegated_data) = load_targets(
+                    metadata_transport,
+                    &root,
+                    &snapshot,
+                    &datastore,
+                    limits.max_targets_size,
+                    limits.max_delegated_roles,
+                    &metadata_base_url,
+                    &filenames.targets,
+                    expiration_enforcement,
+                    lenient_datetime,
+                    delegation_options,
+                    targets_hash,
+                )?;
+                Ok((
+                    root_update_outcome,
+                    TopLevelMetadata {
+                        root,
+                        timestamp,
+                        snapshot,
+                        targets,
+                        raw: RawMetadata {
+                            root: root_data,
+                            timestamp: timestamp_data,
+                            snapshot: snapshot_data,
+                            targets: targets_data,
+                            delegated: delegated_data,
+                        },
+                    },
+                ))
+            },
+        )?;
+        let TopLevelMetadata {
+            root,
+            timestamp,
+            snapshot,
+            targets,
+            raw: raw_metadata,
+        } = metadata;
+        check_spec_versions(
+            loader.required_spec_version.as_ref(),
             &root,
+            &timestamp,
             &snapshot,
-            &datastore,
-            limits.max_targets_size,
-            &metadata_base_url,
-            expiration_enforcement,
+            &targets,
         )?;
+        if loader.delegation_options.strict_uniqueness {
+            check_delegation_uniqueness(&targets.signed)?;
+        }
+        if loader.hash_requirements.target_hashes {
+            check_target_hashes(&targets.signed)?;
+        }
 
-        let expires_iter = [
-            (root.signed.expires, RoleType::Root),
-            (timestamp.signed.expires, RoleType::Timestamp),
-            (snapshot.signed.expires, RoleType::Snapshot),
-            (targets.signed.expires, RoleType::Targets),
-        ];
         let (earliest_expiration, earliest_expiration_role) =
-            expires_iter.iter().min_by_key(|tup| tup.0).unwrap();
+            earliest_expiration(&root, &timestamp, &snapshot, &targets);
 
         Ok(Self {
             transport,
             consistent_snapshot: root.signed.consistent_snapshot,
             datastore,
-            earliest_expiration: *earliest_expiration,
-            earliest_expiration_role: *earliest_expiration_role,
+            earliest_expiration,
+            earliest_expiration_role,
             root,
+            root_update_outcome,
             snapshot,
             timestamp,
             targets,
+            raw_metadata,
             limits,
             metadata_base_url,
             targets_base_url,
             expiration_enforcement,
+            metadata_fetch: loader.metadata_fetch,
+            root_chain_dir: loader.root_chain_dir,
+            on_key_rotation: loader.on_key_rotation,
+            lenient_datetime: loader.lenient_datetime,
+            delegation_options: loader.delegation_options,
+            filenames: loader.filenames,
+            required_spec_version: loader.required_spec_version,
+            hash_requirements: loader.hash_requirements,
+            allow_unverified_partial_reads: loader.allow_unverified_partial_reads,
+            target_cache_dir,
         })
     }
 
+    /// Fetches the latest metadata and, if it has changed, updates this `Repository` to it. The
+    /// same steps and settings are used as when the `Repository` was loaded, starting from the root
+    /// metadata that it already trusts. Returns `true` if any metadata changed.
+    ///
+    /// If neither the root nor the timestamp metadata has changed, the snapshot and targets
+    /// metadata can't have changed either, so they aren't fetched again. With a [`Transport`] that
+    /// supports [conditional requests](Transport::fetch_if_modified), an unchanged timestamp
+    /// metadata file isn't downloaded again either; the copy in the datastore is verified instead.
+    ///
+    /// If an error occurs, the `Repository` is left as it was.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(metadata_base_url = %redact(&self.metadata_base_url))
+        )
+    )]
+    pub fn refresh(&mut self) -> Result<bool> {
+        let _lock = self.datastore.lock()?;
+        let (root_update_outcome, metadata) = with_metadata_transport(
+            self.transport.as_ref(),
+            &self.datastore,
+            self.metadata_fetch,
+            self.limits.max_total_download,
+            |metadata_transport| self.fetch_changed_metadata(metadata_transport),
+        )?;
+        self.root_update_outcome = root_update_outcome;
+        if let Some(metadata) = metadata {
+            let (earliest_expiration, earliest_expiration_role) = earliest_expiration(
+                &metadata.root,
+                &metadata.timestamp,
+                &metadata.snapshot,
+                &metadata.targets,
+            );
+            self.consistent_snapshot = metadata.root.signed.consistent_snapshot;
+            self.earliest_expiration = earliest_expiration;
+            self.earliest_expiration_role = earliest_expiration_role;
+            self.root = metadata.root;
+            self.timestamp = metadata.timestamp;
+            self.snapshot = metadata.snapshot;
+            self.targets = metadata.targets;
+            self.raw_metadata = metadata.raw;
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+
+    /// Fetches the latest top-level metadata for [`Repository::refresh`] through `transport`. The
+    /// snapshot and targets metadata are only fetched if the root or timestamp metadata has
+    /// changed; otherwise, `None` is returned with the outcome of the root update.
+    fn fetch_changed_metadata(
+        &self,
+        transport: &dyn Transport,
+    ) -> Result<(RootUpdateOutcome, Option<TopLevelMetadata>)> {
+        let default_filenames = MetadataFilenames::default();
+        let filenames = if self.metadata_fetch.offline {
+            &default_filenames
+        } else {
+            &self.filenames
+        };
+
+        let (root, root_data, root_update_outcome) = load_root(
+            transport,
+            self.raw_metadata.root.as_slice(),
+            self.root_chain_dir.as_deref(),
+            self.on_key_rotation.as_ref(),
+            &self.datastore,
+            self.limits.max_root_size,
+            self.limits.max_root_updates,
+            self.limits.max_root_total_size,
+            &self.metadata_base_url,
+            self.expiration_enforcement,
+            self.lenient_datetime,
+            self.metadata_fetch.offline,
+        )?;
+        let (timestamp, timestamp_data) = load_timestamp(
+            transport,
+            &root,
+            &self.datastore,
+            self.limits.max_timestamp_size,
+            &self.metadata_base_url,
+            &filenames.timestamp,
+            self.expiration_enforcement,
+            self.lenient_datetime,
+        )?;
+        if root_data == self.raw_metadata.root && timestamp_data == self.raw_metadata.timestamp {
+            return Ok((root_update_outcome, None));
+        }
+        let (snapshot, snapshot_data) = load_snapshot(
+            transport,
+            &root,
+            &timestamp,
+            &self.datastore,
+            self.limits.max_snapshot_size,
+            &self.metadata_base_url,
+            &filenames.snapshot,
+            self.expiration_enforcement,
+            self.lenient_datetime,
+        )?;
+        let (targets, targets_data, delegated_data) = load_targets(
+            transport,
+            &root,
+            &snapshot,
+            &self.datastore,
+            self.limits.max_targets_size,
+            self.limits.max_delegated_roles,
+            &self.metadata_base_url,
+            &filenames.targets,
+            self.expiration_enforcement,
+            self.lenient_datetime,
+            &self.delegation_options,
+            self.hash_requirements.targets_hash,
+        )?;
+        check_spec_versions(
+            self.required_spec_version.as_ref(),
+            &root,
+            &timestamp,
+            &snapshot,
+            &targets,
+        )?;
+        if self.delegation_options.strict_uniqueness {
+            check_delegation_uniqueness(&targets.signed)?;
+        }
+        if self.hash_requirements.target_hashes {
+            check_target_hashes(&targets.signed)?;
+        }
+        Ok((
+            root_update_outcome,
+            Some(TopLevelMetadata {
+                root,
+                timestamp,
+                snapshot,
+                targets,
+                raw: RawMetadata {
+                    root: root_data,
+                    timestamp: timestamp_data,
+                    snapshot: snapshot_data,
+                    targets: targets_data,
+                    delegated: delegated_data,
+                },
+            }),
+        ))
+    }
+
+    /// Makes sure that the datastore holds every metadata file needed to load the repository again
+    /// [`offline`](RepositoryLoader::offline): the top-level roles, every delegated role, and the
+    /// chain of root metadata files from version 1 up to the current version, so that an offline
+    /// load can start from any trusted root in the chain. Call this before going offline.
+    ///
+    /// Each older root metadata file is fetched and checked to have signed the next one, and each
+    /// delegated role is fetched again and verified as in [`RepositoryLoader::load`]. Roles that
+    /// were skipped by [`RepositoryLoader::best_effort_delegations`] are skipped again. Unlike
+    /// [`Repository::cache`], nothing is written outside of the datastore.
+    pub fn prefetch(&self) -> Result<()> {
+        let _lock = self.datastore.lock()?;
+        with_metadata_transport(
+            self.transport.as_ref(),
+            &self.datastore,
+            self.metadata_fetch,
+            self.limits.max_total_download,
+            |metadata_transport| {
+                self.prefetch_root_chain(metadata_transport)?;
+                self.datastore
+                    .create_raw("timestamp.json", &self.raw_metadata.timestamp)?;
+                self.datastore
+                    .create_raw("snapshot.json", &self.raw_metadata.snapshot)?;
+                self.datastore
+                    .create_raw("targets.json", &self.raw_metadata.targets)?;
+                if let Some(delegations) = &self.targets.signed.delegations {
+                    self.prefetch_delegations(metadata_transport, delegations)?;
+                }
+                Ok(())
+            },
+        )
+    }
+
+    /// Stores the current root metadata file and every older version in the datastore. The chain
+    /// is walked backward from the current root, which is already trusted, so each older version
+    /// is checked by verifying that it signed the version after it.
+    fn prefetch_root_chain(&self, transport: &dyn Transport) -> Result<()> {
+        let current = self.root.signed.version.get();
+        self.datastore
+            .create_raw(&format!("{}.root.json", current), &self.raw_metadata.root)?;
+        let (mut newer, mut newer_canonical) =
+            parse_metadata::<Root>(&self.raw_metadata.root, self.lenient_datetime).context(
+                error::ParseMetadata {
+                    role: RoleType::Root,
+                },
+            )?;
+        for version in (1..current).rev() {
+            let path = format!("{}.root.json", version);
+            let reader = fetch_max_size(
+                transport,
+                self.metadata_base_url.join(&path).context(error::JoinUrl {
+                    path: &path,
+                    url: self.metadata_base_url.clone(),
+                })?,
+                self.limits.max_root_size,
+                "max_root_size argument",
+            )?;
+            let data = read_metadata(reader, RoleType::Root)?;
+            let (older, older_canonical) = parse_metadata::<Root>(&data, self.lenient_datetime)
+                .context(error::ParseMetadata {
+                    role: RoleType::Root,
+                })?;
+            ensure!(
+                older.signed.version.get() == version,
+                error::VersionMismatch {
+                    role: RoleType::Root,
+                    fetched: older.signed.version.get(),
+                    expected: version,
+                }
+            );
+            older
+                .signed
+                .verify_role_canonical(&newer, newer_canonical.as_deref())
+                .context(error::VerifyMetadata {
+                    role: RoleType::Root,
+                })?;
+            self.datastore.create_raw(&path, &data)?;
+            newer = older;
+            newer_canonical = older_canonical;
+        }
+        Ok(())
+    }
+
+    /// Fetches and stores every loaded role in the delegation tree under `delegations`.
+    fn prefetch_delegations(
+        &self,
+        transport: &dyn Transport,
+        delegations: &Delegations,
+    ) -> Result<()> {
+        for role in &delegations.roles {
+            if let Some(targets) = &role.targets {
+                let (path, _, data) = load_delegated_role(
+                    transport,
+                    &self.snapshot,
+                    self.consistent_snapshot,
+                    &self.metadata_base_url,
+                    self.limits.max_targets_size,
+                    delegations,
+                    role,
+                    self.lenient_datetime,
+                )?;
+                self.datastore.create_raw(&path, &data)?;
+                if let Some(delegations) = &targets.signed.delegations {
+                    self.prefetch_delegations(transport, delegations)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
     /// Returns the list of targets present in the repository.
     pub fn targets(&self) -> &Signed<crate::schema::Targets> {
         &self.targets
@@ -388,11 +1492,196 @@ impl Repository {
         &self.timestamp
     }
 
-    ///return a vec of all targets including all target files delegated by targets
-    pub fn all_targets(&self) -> impl Iterator + '_ {
+    /// Returns the metadata file of a top-level role exactly as it was received, before it was
+    /// parsed and verified. Re-serializing the parsed metadata may reorder keys or change
+    /// whitespace; these bytes are the ones that were fetched (or, for `root.json`, read from the
+    /// trusted root if it is the latest), and can be republished as-is without re-signing them.
+    ///
+    /// Returns `None` for [`RoleType::DelegatedTargets`].
+    pub fn raw_metadata(&self, role: RoleType) -> Option<&[u8]> {
+        match role {
+            RoleType::Root => Some(&self.raw_metadata.root),
+            RoleType::Timestamp => Some(&self.raw_metadata.timestamp),
+            RoleType::Snapshot => Some(&self.raw_metadata.snapshot),
+            RoleType::Targets => Some(&self.raw_metadata.targets),
+            RoleType::DelegatedTargets => None,
+        }
+    }
+
+    /// Returns the metadata file of the named delegated role exactly as it was received, as
+    /// [`Repository::raw_metadata`] does for top-level roles, or `None` if no such role was loaded.
+    pub fn delegated_raw_metadata(&self, name: &str) -> Option<&[u8]> {
+        self.raw_metadata.delegated.get(name).map(Vec::as_slice)
+    }
+
+    /// Returns the version of a top-level role's metadata.
+    ///
+    /// Returns `None` for [`RoleType::DelegatedTargets`]; use [`Repository::metadata_versions`] for
+    /// the versions of delegated roles.
+    pub fn metadata_version(&self, role: RoleType) -> Option<NonZeroU64> {
+        match role {
+            RoleType::Root => Some(self.root.signed.version),
+            RoleType::Timestamp => Some(self.timestamp.signed.version),
+            RoleType::Snapshot => Some(self.snapshot.signed.version),
+            RoleType::Targets => Some(self.targets.signed.version),
+            RoleType::DelegatedTargets => None,
+        }
+    }
+
+    /// Returns which version of the root metadata updating started from and ended at, and why it
+    /// stopped, when the repository was loaded or last [refreshed](Repository::refresh). This can
+    /// help to find out why a client appears to be stuck on an old root.
+    pub fn root_update_outcome(&self) -> RootUpdateOutcome {
+        self.root_update_outcome
+    }
+
+    /// Returns the versions of all of the loaded metadata, including delegated roles. Comparing
+    /// these before and after [`Repository::refresh`] shows which roles changed.
+    pub fn metadata_versions(&self) -> MetadataVersions {
+        MetadataVersions {
+            root: self.root.signed.version,
+            timestamp: self.timestamp.signed.version,
+            snapshot: self.snapshot.signed.version,
+            targets: self.targets.signed.version,
+            delegated: self
+                .delegated_roles()
+                .filter_map(|role| {
+                    role.targets
+                        .as_ref()
+                        .map(|targets| (role.name.clone(), targets.signed.version))
+                })
+                .collect(),
+        }
+    }
+
+    /// Returns the IDs of the keys whose signatures on a top-level role's metadata were accepted
+    /// when it was verified, in the order of the signatures. This shows which of the keys trusted
+    /// for the role actually signed it, e.g. to notice an unexpected key during a key rotation.
+    ///
+    /// Returns `None` for [`RoleType::DelegatedTargets`]; use
+    /// [`Delegations::verifying_key_ids`] for delegated roles.
+    pub fn signing_key_ids(&self, role: RoleType) -> Option<Vec<Decoded<Hex>>> {
+        let raw = self.raw_metadata(role)?;
+        let root = &self.root.signed;
+        let key_ids = match role {
+            RoleType::Root => signing_key_ids(root, &self.root, raw, self.lenient_datetime),
+            RoleType::Timestamp => {
+                signing_key_ids(root, &self.timestamp, raw, self.lenient_datetime)
+            }
+            RoleType::Snapshot => signing_key_ids(root, &self.snapshot, raw, self.lenient_datetime),
+            RoleType::Targets => signing_key_ids(root, &self.targets, raw, self.lenient_datetime),
+            RoleType::DelegatedTargets => return None,
+        };
+        Some(key_ids)
+    }
+
+    /// Returns the path of the datastore directory, which is a temporary directory if no
+    /// [`datastore`](RepositoryLoader::datastore) was set. A temporary directory is removed when
+    /// the repository and all of its clones are dropped, unless
+    /// [`keep_datastore`](RepositoryLoader::keep_datastore) was set.
+    pub fn datastore_path(&self) -> &Path {
+        self.datastore.dir()
+    }
+
+    /// Returns `true` if the repository uses consistent snapshots, i.e. if metadata files are named
+    /// `VERSION.ROLE.json` and target files are named `HASH.NAME`.
+    pub fn consistent_snapshot(&self) -> bool {
+        self.consistent_snapshot
+    }
+
+    /// Returns the latest system time that the datastore has recorded, or `None` if it hasn't
+    /// recorded one. The time is recorded whenever metadata is checked for expiration, and loading
+    /// or refreshing fails with [`SystemTimeSteppedBackward`](error::Error::SystemTimeSteppedBackward)
+    /// if the system time is ever earlier than it, so this can help to diagnose that error, e.g. a
+    /// clock that was ahead and has since been corrected.
+    pub fn latest_known_time(&self) -> Result<Option<DateTime<Utc>>> {
+        let _time_guard = self.datastore.lock_time();
+        self.datastore
+            .reader(LATEST_KNOWN_TIME_FILE)?
+            .map(|reader| {
+                serde_json::from_reader(reader).context(error::DatastoreDeserialize {
+                    what: format!("{} in datastore", LATEST_KNOWN_TIME_FILE),
+                    path: self.datastore.dir().join(LATEST_KNOWN_TIME_FILE),
+                })
+            })
+            .transpose()
+    }
+
+    /// Forgets the latest system time that the datastore has recorded, so that the next check is
+    /// against the current system time only. Use this to recover from
+    /// [`SystemTimeSteppedBackward`](error::Error::SystemTimeSteppedBackward) once you've confirmed
+    /// that the recorded time was wrong, e.g. because the clock was set ahead by mistake.
+    ///
+    /// **This reduces rollback protection.** The recorded time is what stops an attacker who can
+    /// set the system clock back from getting expired metadata accepted again, so don't call this
+    /// automatically in response to the error.
+    pub fn reset_known_time(&self) -> Result<()> {
+        let _time_guard = self.datastore.lock_time();
+        self.datastore.remove(LATEST_KNOWN_TIME_FILE)
+    }
+
+    /// Returns every target in the repository, including targets listed by delegated roles, with
+    /// its name, sorted by name. See [`Targets::targets_map`](crate::schema::Targets::targets_map)
+    /// for which target is used when more than one role lists a name.
+    pub fn all_targets(&self) -> impl Iterator<Item = (String, &Target)> + '_ {
         self.targets.signed.targets_iter()
     }
 
+    /// Returns the names of all targets in the repository, including targets listed by delegated
+    /// roles, sorted by name. A name that isn't a valid [`TargetName`] can't be read, and is left
+    /// out.
+    pub fn target_names(&self) -> Vec<TargetName> {
+        self.targets
+            .signed
+            .targets_map()
+            .into_keys()
+            .filter_map(|name| TargetName::new(&name).ok())
+            .collect()
+    }
+
+    /// Compares the targets in the repository to those in `previous`, e.g. targets metadata saved
+    /// before the last [`Repository::refresh`], to report which targets are new, gone, or changed.
+    /// Targets listed by delegated roles are included in both. A target counts as changed if its
+    /// hashes are different, whether or not anything else about it changed; the target found for a
+    /// name is the one [`Repository::read_target`] would use.
+    pub fn diff_targets(&self, previous: &Signed<crate::schema::Targets>) -> TargetDiff {
+        let current = &self.targets.signed;
+        let previous = &previous.signed;
+        let current_names: BTreeSet<String> = current.targets_map().into_keys().collect();
+        let previous_names: BTreeSet<String> = previous.targets_map().into_keys().collect();
+
+        let mut diff = TargetDiff {
+            added: current_names.difference(&previous_names).cloned().collect(),
+            removed: previous_names.difference(&current_names).cloned().collect(),
+            changed: Vec::new(),
+        };
+        for name in current_names.intersection(&previous_names) {
+            if let (Ok(now), Ok(before)) = (current.find_target(name), previous.find_target(name)) {
+                if now.hashes != before.hashes {
+                    diff.changed.push(name.clone());
+                }
+            }
+        }
+        diff
+    }
+
+    /// Returns `true` if the named target is listed by the top-level targets role or by any of its
+    /// delegated roles.
+    pub fn contains_target(&self, name: &str) -> bool {
+        self.targets.signed.find_target(name).is_ok()
+    }
+
+    /// Deserializes the `custom` field `key` of the named target into `T`. The target is looked up
+    /// in the top-level targets role and in any of its delegated roles.
+    ///
+    /// Returns `None` if the target is not listed in the repository or does not have the field.
+    pub fn target_custom<T: DeserializeOwned>(&self, name: &str, key: &str) -> Option<Result<T>> {
+        let target = self.targets.signed.find_target(name).ok()?;
+        target
+            .custom_field(key)
+            .map(|result| result.context(error::TargetCustomField { target: name, key }))
+    }
+
     /// Fetches a target from the repository.
     ///
     /// If the repository metadata is expired or there is an issue making the request, `Err` is
@@ -403,17 +1692,28 @@ impl Repository {
     /// Otherwise, a reader is returned, which provides streaming access to the target contents
     /// before its checksum is validated. If the maximum size is reached or there is a checksum
     /// mismatch, the reader returns a [`std::io::Error`]. **Consumers of this library must not use
-    /// data from the reader if it returns an error.**
-    pub fn read_target(&self, name: &str) -> Result<Option<impl Read + Send>> {
-        // Check for repository metadata expiration.
-        if self.expiration_enforcement == ExpirationEnforcement::Safe {
-            ensure!(
-                system_time(&self.datastore)? < self.earliest_expiration,
-                error::ExpiredMetadata {
-                    role: self.earliest_expiration_role
-                }
-            );
-        }
+    /// data from the reader if it returns an error.** The contents are only verified once the end
+    /// of the target is reached; to verify a target that isn't read to the end, use
+    /// [`TargetReader::verifying`].
+    ///
+    /// The maximum size is the target's `length` in its targets metadata. Every target must have a
+    /// length, so the download is always bounded by it, however large it is; [`Limits`] only apply
+    /// to metadata files. The reader's [`TargetReader::expected_len`] is this length.
+    ///
+    /// With a [`target_cache_dir`](RepositoryLoader::target_cache_dir), a cached copy of the
+    /// target is returned instead if it matches the target's metadata.
+    ///
+    /// `name` is anything that converts to a [`TargetName`], e.g. a `&str`. A name that isn't a
+    /// safe relative path is rejected with
+    /// [`Error::UnsafeTargetName`](error::Error::UnsafeTargetName) before anything is looked up.
+    pub fn read_target<N>(&self, name: N) -> Result<Option<TargetReader>>
+    where
+        N: TryInto<TargetName>,
+        error::Error: From<N::Error>,
+    {
+        let name = name.try_into()?;
+        let name = name.as_str();
+        self.check_expired()?;
 
         // 5. Verify the desired target against its targets metadata.
         //
@@ -433,54 +1733,562 @@ impl Repository {
         //   found earlier in step 4. In either case, the client MUST write the file to
         //   non-volatile storage as FILENAME.EXT.
         Ok(if let Ok(target) = self.targets.signed.find_target(name) {
-            let (sha256, file) = self.target_digest_and_filename(target, name);
-            Some(self.fetch_target(target, &sha256, file.as_str())?)
+            self.check_required_hashes(target, name)?;
+            let cached_path = self
+                .target_cache_dir
+                .as_deref()
+                .and_then(|dir| cached_target_path(dir, target, name));
+            if let Some(reader) = cached_path
+                .as_deref()
+                .and_then(|path| read_cached(path, target))
+            {
+                return Ok(Some(TargetReader::new(reader, Some(target.length))));
+            }
+            let file = self.target_filename(target, name);
+            let reader: Box<dyn Read + Send> = Box::new(self.fetch_target(target, file.as_str())?);
+            let reader = match cached_path {
+                Some(path) => Box::new(CachingReader::new(reader, path)),
+                None => reader,
+            };
+            Some(TargetReader::new(reader, Some(target.length)))
         } else {
             None
         })
     }
 
-    /// Return the named `DelegatedRole` if found.
-    pub fn delegated_role(&self, name: &str) -> Option<&DelegatedRole> {
-        self.targets.signed.delegated_role(name).ok()
+    /// Fetches `len` bytes of the named target starting at byte `start`, e.g. with an HTTP `Range`
+    /// request, to read a header or footer of a large target without downloading all of it.
+    ///
+    /// **The bytes returned are not verified.** TUF hashes cover whole targets, so part of a
+    /// target can't be checked against them; the range is only checked against the target's
+    /// `length`. This fails with
+    /// [`Error::PartialReadsNotAllowed`](error::Error::PartialReadsNotAllowed) unless it was
+    /// enabled with [`RepositoryLoader::allow_unverified_partial_reads`]. Use
+    /// [`Repository::read_target`] for anything that must be trusted.
+    ///
+    /// Returns `Ok(None)` if the target is not listed in the repository metadata. A range that
+    /// ends past the target's `length` is an error, as is a transport that can't fetch part of a
+    /// file (see [`Transport::fetch_range`]). The target cache isn't used. `name` is checked as it
+    /// is by `read_target`.
+    pub fn read_target_range<N>(
+        &self,
+        name: N,
+        start: u64,
+        len: u64,
+    ) -> Result<Option<Box<dyn Read + Send>>>
+    where
+        N: TryInto<TargetName>,
+        error::Error: From<N::Error>,
+    {
+        ensure!(
+            self.allow_unverified_partial_reads,
+            error::PartialReadsNotAllowed
+        );
+        let name = name.try_into()?;
+        let name = name.as_str();
+        self.check_expired()?;
+        let target = match self.targets.signed.find_target(name) {
+            Ok(target) => target,
+            Err(_) => return Ok(None),
+        };
+        ensure!(
+            start
+                .checked_add(len)
+                .map_or(false, |end| end <= target.length),
+            error::TargetRangeOutOfBounds {
+                target: name,
+                start,
+                len,
+                length: target.length,
+            }
+        );
+        if len == 0 {
+            return Ok(Some(Box::new(std::io::empty())));
+        }
+        let file = self.target_filename(target, name);
+        let url = self.target_url(&file)?;
+        self.transport
+            .fetch_range(url.clone(), start, len)
+            .map(Some)
+            .context(error::Transport { url })
+    }
+
+    /// Asks the transport for the size of the named target as served, without downloading it, e.g.
+    /// with an HTTP `HEAD` request. Comparing this to the target's `length` in its targets metadata
+    /// finds a mirror that serves the wrong file before downloading it.
+    ///
+    /// Returns `Ok(None)` if the target is not listed in the repository metadata, or if the
+    /// transport can't tell the size (see [`Transport::head`]). The download is bounded by the
+    /// target's length in either case, so [`Repository::read_target`] can be used as usual. `name`
+    /// is checked as it is by `read_target`.
+    pub fn target_served_size<N>(&self, name: N) -> Result<Option<u64>>
+    where
+        N: TryInto<TargetName>,
+        error::Error: From<N::Error>,
+    {
+        let name = name.try_into()?;
+        let name = name.as_str();
+        let target = match self.targets.signed.find_target(name) {
+            Ok(target) => target,
+            Err(_) => return Ok(None),
+        };
+        let file = self.target_filename(target, name);
+        let url = self.target_url(&file)?;
+        self.transport
+            .head(url.clone())
+            .context(error::Transport { url })
+    }
+
+    /// Checks contents of the named target that were obtained some other way, e.g. from a peer or
+    /// a local cache, against the target's length and hashes in its targets metadata. `reader` is
+    /// read to the end, or until it's longer than the target; nothing is fetched.
+    ///
+    /// Returns `Ok(true)` if the contents match, and `Ok(false)` if they don't. It's an error if
+    /// the target is not listed in the repository metadata, if the metadata has expired, or if
+    /// `reader` returns an error. `name` is checked as it is by [`Repository::read_target`], and
+    /// the hashes required by [`RepositoryLoader::require_hash_algorithms`] must be listed.
+    pub fn verify_target_bytes<N, R>(&self, name: N, reader: R) -> Result<bool>
+    where
+        N: TryInto<TargetName>,
+        error::Error: From<N::Error>,
+        R: Read,
+    {
+        let name = name.try_into()?;
+        let name = name.as_str();
+        self.check_expired()?;
+        let target = self
+            .targets
+            .signed
+            .find_target(name)
+            .ok()
+            .context(error::UnknownTarget { name })?;
+        self.check_required_hashes(target, name)?;
+        matches_hashes(reader, target.length, &target.hashes, name)
+    }
+
+    /// Returns an error if any of the top-level metadata has expired since the repository was
+    /// loaded, unless expiration isn't being enforced. Checked before reading a target.
+    fn check_expired(&self) -> Result<()> {
+        if self.expiration_enforcement == ExpirationEnforcement::Safe {
+            ensure!(
+                system_time(&self.datastore)? < self.earliest_expiration,
+                error::ExpiredMetadata {
+                    role: self.earliest_expiration_role
+                }
+            );
+        }
+        Ok(())
+    }
+
+    /// Fetches several targets from the repository, in the order they are named.
+    ///
+    /// Yields each name along with the result of calling [`Repository::read_target`] for it, so
+    /// that a failure to read one target doesn't prevent reading the others. Targets are fetched
+    /// lazily, as the iterator reaches them. The same rules apply to the returned readers as to
+    /// those returned by `read_target`: **consumers of this library must not use data from a
+    /// reader if it returns an error.**
+    pub fn read_targets<'a>(
+        &'a self,
+        names: &'a [&str],
+    ) -> impl Iterator<Item = (&'a str, Result<Option<TargetReader>>)> + 'a {
+        names
+            .iter()
+            .map(move |name| (*name, self.read_target(*name)))
+    }
+
+    /// Return the named `DelegatedRole` if found.
+    pub fn delegated_role(&self, name: &str) -> Option<&DelegatedRole> {
+        self.targets.signed.delegated_role(name).ok()
+    }
+
+    /// Returns every delegated role in the repository, starting from the top-level targets role and
+    /// walking the delegation tree in preorder, which is the order used to resolve targets. All
+    /// delegated roles are loaded with the repository, so this does not fetch anything.
+    pub fn delegated_roles(&self) -> impl Iterator<Item = &DelegatedRole> + '_ {
+        self.targets.signed.delegated_roles()
+    }
+
+    /// Returns the names of the targets, listed by the targets metadata or by any delegated role,
+    /// that have no hash this library can verify, sorted by name. Such a target can't be read, and
+    /// loading fails if there are any when
+    /// [`RepositoryLoader::require_target_hashes`] is set. A name that isn't a valid
+    /// [`TargetName`] can't be read either way, and is left out.
+    pub fn targets_missing_hashes(&self) -> Vec<TargetName> {
+        targets_missing_hashes(&self.targets.signed)
+            .into_iter()
+            .filter_map(|name| TargetName::new(name).ok())
+            .collect()
+    }
+
+    /// Returns the keys that the trusted root metadata authorizes to sign the metadata of the
+    /// top-level `role`, and the threshold of their signatures that the metadata needs, e.g. to
+    /// show who can sign what. Key IDs that the root lists for the role without including the key
+    /// are skipped, as they are when verifying signatures.
+    ///
+    /// Delegated roles aren't listed in the root metadata, so [`RoleType::DelegatedTargets`] has no
+    /// keys and a threshold of 0; use [`Repository::delegated_role_keys`] instead.
+    pub fn role_keys(&self, role: RoleType) -> (Vec<&Key>, u64) {
+        let threshold = self
+            .root
+            .signed
+            .roles
+            .get(&role)
+            .map_or(0, |role_keys| role_keys.threshold.get());
+        (self.root.signed.keys(role).collect(), threshold)
+    }
+
+    /// Returns the keys that the delegating role authorizes to sign the metadata of the delegated
+    /// role `name`, and the threshold of their signatures that the metadata needs, or `None` if
+    /// there is no such role.
+    pub fn delegated_role_keys(&self, name: &str) -> Option<(Vec<&Key>, u64)> {
+        let delegations = self.targets.signed.parent_of(name).ok()?;
+        let role = delegations.roles.iter().find(|role| role.name == name)?;
+        let keys = role
+            .keyids
+            .iter()
+            .filter_map(|keyid| delegations.keys.get(keyid))
+            .collect();
+        Some((keys, role.threshold.get()))
+    }
+
+    /// Returns the delegated role that provides the named target, i.e. the role whose metadata
+    /// [`Repository::read_target`] uses to verify it.
+    ///
+    /// Returns `None` if the target is listed by the top-level targets role, or is not listed in the
+    /// repository at all; use [`Repository::contains_target`] to tell these apart.
+    pub fn find_target_role(&self, name: &str) -> Option<&DelegatedRole> {
+        self.targets.signed.find_target_role(name)
+    }
+
+    /// Checks that the snapshot metadata and the delegation tree are consistent: the snapshot's
+    /// `meta` lists `targets.json` and a `NAME.json` file for every delegated role, and it lists no
+    /// other files, besides `root.json`, which older versions of the TUF specification listed.
+    /// This helps publishers catch a role that was left out of the snapshot, or one that was
+    /// removed from the delegations but not from the snapshot, before clients do.
+    ///
+    /// Loading already fails if a delegated role is missing from the snapshot, unless it was
+    /// skipped by [`RepositoryLoader::best_effort_delegations`]. The roles delegated by a skipped
+    /// role aren't known, so their files are reported as not belonging to any role.
+    pub fn validate_snapshot_completeness(&self) -> Result<()> {
+        let (missing, orphaned) =
+            snapshot_inconsistencies(&self.snapshot.signed, &self.targets.signed);
+        ensure!(
+            missing.is_empty() && orphaned.is_empty(),
+            error::SnapshotInconsistent { missing, orphaned }
+        );
+        Ok(())
+    }
+}
+
+/// Returns the metadata files that `snapshot` should list for the delegation tree of `targets` but
+/// doesn't, and those that it lists that don't belong to any role, each sorted by name.
+fn snapshot_inconsistencies(
+    snapshot: &Snapshot,
+    targets: &crate::schema::Targets,
+) -> (Vec<String>, Vec<String>) {
+    let expected: BTreeSet<String> = std::iter::once("targets.json".to_owned())
+        .chain(
+            targets
+                .delegated_roles()
+                .map(|role| format!("{}.json", role.name)),
+        )
+        .collect();
+    let missing = expected
+        .iter()
+        .filter(|file| snapshot.meta_entry(file).is_none())
+        .cloned()
+        .collect();
+    let mut orphaned: Vec<String> = snapshot
+        .meta
+        .keys()
+        .filter(|file| {
+            // The suffix is matched case-sensitively, as `meta_entry` matches it: a key ending in
+            // `.JSON` isn't the `NAME.json` form, so it can only be an unsuffixed role name.
+            #[allow(clippy::case_sensitive_file_extension_comparisons)]
+            let file = if file.ends_with(".json") {
+                (*file).clone()
+            } else {
+                format!("{}.json", file)
+            };
+            file != "root.json" && !expected.contains(&file)
+        })
+        .cloned()
+        .collect();
+    orphaned.sort();
+    (missing, orphaned)
+}
+
+/// The datastore file that the latest known system time is stored in.
+const LATEST_KNOWN_TIME_FILE: &str = "latest_known_time.json";
+
+/// Ensures that system time has not stepped backward since it was last sampled
+fn system_time(datastore: &Datastore) -> Result<DateTime<Utc>> {
+    let file = LATEST_KNOWN_TIME_FILE;
+    // Hold the lock until the new time is stored, and sample the time only once it's held, so that
+    // threads sharing the datastore store their times in the order they sampled them.
+    let _time_guard = datastore.lock_time();
+    // Get 'current' system time
+    let sys_time = datastore.now();
+    // Load the latest known system time, if it exists
+    let latest_known_time = match datastore
+        .reader(file)?
+        .map(serde_json::from_reader::<_, DateTime<Utc>>)
+    {
+        Some(Ok(latest_known_time)) => Some(latest_known_time),
+        Some(Err(err)) => {
+            warn_corrupt_file(file, &err);
+            None
+        }
+        None => None,
+    };
+    if let Some(latest_known_time) = latest_known_time {
+        // Make sure the sampled system time did not go back in time
+        ensure!(
+            sys_time >= latest_known_time,
+            error::SystemTimeSteppedBackward {
+                sys_time,
+                latest_known_time
+            }
+        );
+    }
+    // Store the latest known time
+    // Serializes RFC3339 time string and store to datastore
+    datastore.create(file, &sys_time)?;
+    Ok(sys_time)
+}
+
+/// Reads the trusted metadata file `file` from the datastore, for a rollback check. A file that
+/// can't be parsed, e.g. because it was corrupted on disk, can't be checked against, so a warning is
+/// logged and `None` is returned; the file is replaced once the new metadata is verified.
+fn read_trusted<T: DeserializeOwned>(
+    datastore: &Datastore,
+    file: &str,
+    lenient_datetime: bool,
+) -> Result<Option<ParsedMetadata<T>>> {
+    match datastore
+        .reader(file)?
+        .map(|reader| parse_metadata_reader::<_, T>(reader, lenient_datetime))
+    {
+        Some(Ok(metadata)) => Ok(Some(metadata)),
+        Some(Err(err)) => {
+            warn_corrupt_file(file, &err);
+            Ok(None)
+        }
+        None => Ok(None),
+    }
+}
+
+/// Reports that `file` in the datastore couldn't be parsed, so the check that it's used for is
+/// skipped this time.
+fn warn_corrupt_file(file: &str, err: &serde_json::Error) {
+    warn!(
+        "ignoring '{}' in datastore, which can't be parsed, until it is replaced: {}",
+        file, err
+    );
+    warn_event!(file, error = %err, "ignoring corrupt file in datastore");
+}
+
+/// TUF v1.0.16, 5.2.9, 5.3.3, 5.4.5, 5.5.4, The expiration timestamp in the `[metadata]` file MUST
+/// be higher than the fixed update start time.
+fn check_expired<T: Role>(datastore: &Datastore, role: &T) -> Result<()> {
+    ensure!(
+        system_time(datastore)? <= role.expires(),
+        error::ExpiredMetadata { role: T::TYPE }
+    );
+    Ok(())
+}
+
+/// Reads a fetched metadata file into memory, so that it can be both parsed and stored in the
+/// datastore exactly as it was received.
+fn read_metadata<R: Read>(mut reader: R, role: RoleType) -> Result<Vec<u8>> {
+    let mut data = Vec::new();
+    reader
+        .read_to_end(&mut data)
+        .map_err(serde_json::Error::io)
+        .context(error::ParseMetadata { role })?;
+    Ok(data)
+}
+
+/// Returns the earliest expiration of the top-level roles, and the role it belongs to.
+fn earliest_expiration(
+    root: &Signed<Root>,
+    timestamp: &Signed<Timestamp>,
+    snapshot: &Signed<Snapshot>,
+    targets: &Signed<crate::schema::Targets>,
+) -> (DateTime<Utc>, RoleType) {
+    [
+        (root.signed.expires, RoleType::Root),
+        (timestamp.signed.expires, RoleType::Timestamp),
+        (snapshot.signed.expires, RoleType::Snapshot),
+        (targets.signed.expires, RoleType::Targets),
+    ]
+    .iter()
+    .copied()
+    .min_by_key(|tup| tup.0)
+    .unwrap()
+}
+
+/// Checks that no target is listed by more than one role along its search path. See
+/// [`RepositoryLoader::strict_delegation_uniqueness`].
+fn check_delegation_uniqueness(targets: &crate::schema::Targets) -> Result<()> {
+    for name in targets.targets_map().keys() {
+        let mut roles = Vec::new();
+        target_claimants(targets, "targets", name, &mut roles);
+        ensure!(
+            roles.len() <= 1,
+            error::DuplicateTarget {
+                name: name.as_str(),
+                roles: roles
+                    .iter()
+                    .map(|role| (*role).to_owned())
+                    .collect::<Vec<_>>(),
+            }
+        );
+    }
+    Ok(())
+}
+
+/// Returns the names of the targets listed by `targets` or any role it delegates to that have no
+/// recognized hash, sorted and without duplicates.
+fn targets_missing_hashes(targets: &crate::schema::Targets) -> Vec<&str> {
+    let roles = std::iter::once(targets).chain(
+        targets
+            .delegated_roles()
+            .filter_map(|role| role.targets.as_ref().map(|targets| &targets.signed)),
+    );
+    let mut missing = BTreeSet::new();
+    for role in roles {
+        for (name, target) in &role.targets {
+            if !target
+                .hashes
+                .digests
+                .keys()
+                .any(|algorithm| hash_algorithm(algorithm).is_some())
+            {
+                missing.insert(name.as_str());
+            }
+        }
+    }
+    missing.into_iter().collect()
+}
+
+/// Checks that every target has a recognized hash. See
+/// [`RepositoryLoader::require_target_hashes`].
+fn check_target_hashes(targets: &crate::schema::Targets) -> Result<()> {
+    let missing = targets_missing_hashes(targets);
+    ensure!(
+        missing.is_empty(),
+        error::TargetHashesMissing {
+            targets: missing
+                .iter()
+                .map(|name| (*name).to_owned())
+                .collect::<Vec<_>>(),
+        }
+    );
+    Ok(())
+}
+
+/// Adds the name of each role that lists the target `name` to `roles`, searching from the role
+/// `role_name` in preorder, only descending into delegations whose paths allow `name`. Returns
+/// `true` if the search was stopped by a matching terminating delegation.
+fn target_claimants<'a>(
+    targets: &'a crate::schema::Targets,
+    role_name: &'a str,
+    name: &str,
+    roles: &mut Vec<&'a str>,
+) -> bool {
+    if targets.targets.contains_key(name) {
+        roles.push(role_name);
+    }
+    if let Some(delegations) = &targets.delegations {
+        for role in &delegations.roles {
+            if !role.matches_path(name) {
+                continue;
+            }
+            if let Some(delegated) = &role.targets {
+                if target_claimants(&delegated.signed, &role.name, name, roles) {
+                    return true;
+                }
+            }
+            if role.terminating {
+                return true;
+            }
+        }
+    }
+    false
+}
+
+/// The major version of the TUF specification that this library implements.
+const SPEC_MAJOR_VERSION: u64 = 1;
+
+/// Checks the spec version declared by the metadata of each top-level role and each loaded
+/// delegated role. See [`RepositoryLoader::require_spec_version`].
+fn check_spec_versions(
+    required: Option<&VersionReq>,
+    root: &Signed<Root>,
+    timestamp: &Signed<Timestamp>,
+    snapshot: &Signed<Snapshot>,
+    targets: &Signed<crate::schema::Targets>,
+) -> Result<()> {
+    check_spec_version(required, "root", &root.signed.spec_version)?;
+    check_spec_version(required, "timestamp", &timestamp.signed.spec_version)?;
+    check_spec_version(required, "snapshot", &snapshot.signed.spec_version)?;
+    check_spec_version(required, "targets", &targets.signed.spec_version)?;
+    if let Some(delegations) = &targets.signed.delegations {
+        check_delegated_spec_versions(required, delegations)?;
     }
+    Ok(())
 }
 
-/// Ensures that system time has not stepped backward since it was last sampled
-fn system_time(datastore: &Datastore) -> Result<DateTime<Utc>> {
-    let file = "latest_known_time.json";
-    // Get 'current' system time
-    let sys_time = Utc::now();
-    // Load the latest known system time, if it exists
-    if let Some(Ok(latest_known_time)) = datastore
-        .reader(file)?
-        .map(serde_json::from_reader::<_, DateTime<Utc>>)
-    {
-        // Make sure the sampled system time did not go back in time
-        ensure!(
-            sys_time >= latest_known_time,
-            error::SystemTimeSteppedBackward {
-                sys_time,
-                latest_known_time
+fn check_delegated_spec_versions(
+    required: Option<&VersionReq>,
+    delegations: &Delegations,
+) -> Result<()> {
+    for role in &delegations.roles {
+        if let Some(targets) = &role.targets {
+            check_spec_version(required, &role.name, &targets.signed.spec_version)?;
+            if let Some(delegations) = &targets.signed.delegations {
+                check_delegated_spec_versions(required, delegations)?;
             }
-        );
+        }
     }
-    // Store the latest known time
-    // Serializes RFC3339 time string and store to datastore
-    datastore.create(file, &sys_time)?;
-    Ok(sys_time)
+    Ok(())
 }
 
-/// TUF v1.0.16, 5.2.9, 5.3.3, 5.4.5, 5.5.4, The expiration timestamp in the `[metadata]` file MUST
-/// be higher than the fixed update start time.
-fn check_expired<T: Role>(datastore: &Datastore, role: &T) -> Result<()> {
-    ensure!(
-        system_time(datastore)? <= role.expires(),
-        error::ExpiredMetadata { role: T::TYPE }
-    );
+/// Checks the spec version declared by the metadata of the role named `role` against `required`,
+/// or, without a requirement, warns if its major version isn't the one this library implements.
+fn check_spec_version(required: Option<&VersionReq>, role: &str, spec_version: &str) -> Result<()> {
+    let version = parse_spec_version(spec_version);
+    match required {
+        Some(required) => ensure!(
+            version.map_or(false, |version| required.matches(&version)),
+            error::IncompatibleSpecVersion {
+                role,
+                given: spec_version,
+                required: required.clone(),
+            }
+        ),
+        None => {
+            if version.map_or(true, |version| version.major != SPEC_MAJOR_VERSION) {
+                warn_event!(
+                    role,
+                    spec_version,
+                    "metadata declares a spec version this library may not support"
+                );
+            }
+        }
+    }
     Ok(())
 }
 
+/// Parses a spec version as a semantic version, reading `MAJOR.MINOR` as `MAJOR.MINOR.0`, which
+/// some repositories declare.
+fn parse_spec_version(spec_version: &str) -> Option<Version> {
+    Version::parse(spec_version)
+        .or_else(|_| Version::parse(&format!("{}.0", spec_version)))
+        .ok()
+}
+
 /// Checks to see if the `Url` has a trailing slash and adds one if not. Without a trailing slash,
 /// the last component of a `Url` is considered to be a file. `metadata_url` and `targets_url`
 /// must refer to a base (i.e. directory), so we need them to end with a slash.
@@ -494,29 +2302,149 @@ fn parse_url(url: Url) -> Result<Url> {
     }
 }
 
+/// Checks that [`DefaultTransport`] supports the scheme of `url`.
+fn check_url_scheme(url: &Url) -> Result<()> {
+    match url.scheme() {
+        "file" => Ok(()),
+        "http" | "https" => {
+            ensure!(
+                cfg!(feature = "http"),
+                error::HttpFeatureDisabled { url: url.clone() }
+            );
+            Ok(())
+        }
+        _ => error::UnsupportedUrlScheme { url: url.clone() }.fail(),
+    }
+}
+
+/// Returns the URL of the directory `name` under `base_url`, with a trailing slash.
+fn subdirectory_url(base_url: &Url, name: &str) -> Url {
+    let mut url = base_url.clone();
+    let path = format!("{}/{}/", base_url.path().trim_end_matches('/'), name);
+    url.set_path(&path);
+    url
+}
+
+/// Writes a verified root metadata file to `dir` as `VERSION.root.json`.
+fn save_root(dir: &Path, root: &Signed<Root>, data: &[u8]) -> Result<()> {
+    std::fs::create_dir_all(dir).context(error::DirCreate { path: dir })?;
+    let path = dir.join(format!("{}.root.json", root.signed.version));
+    std::fs::write(&path, data).context(error::SaveRootChain { path })
+}
+
+/// Calls `callback` for each top-level role whose keys in `new_root` differ from those in `root`.
+fn report_key_rotations(root: &Root, new_root: &Root, callback: &KeyRotationFn) {
+    for role in &[
+        RoleType::Root,
+        RoleType::Snapshot,
+        RoleType::Targets,
+        RoleType::Timestamp,
+    ] {
+        let old_keys: Vec<Key> = root.keys(*role).cloned().collect();
+        let new_keys: Vec<Key> = new_root.keys(*role).cloned().collect();
+        let changed =
+            old_keys.len() != new_keys.len() || new_keys.iter().any(|key| !old_keys.contains(key));
+        if changed {
+            callback(*role, &old_keys, &new_keys);
+        }
+    }
+}
+
+/// Returns the IDs of the keys in `root` that made valid signatures on `role`, which was parsed
+/// from `raw`. The metadata has already been verified, so the signatures are checked against the
+/// same canonical form as they were then.
+fn signing_key_ids<T: Role + Serialize + DeserializeOwned>(
+    root: &Root,
+    role: &Signed<T>,
+    raw: &[u8],
+    lenient_datetime: bool,
+) -> Vec<Decoded<Hex>> {
+    let canonical = if lenient_datetime {
+        parse_metadata::<T>(raw, lenient_datetime)
+            .ok()
+            .and_then(|(_, canonical)| canonical)
+    } else {
+        None
+    };
+    root.verifying_key_ids_canonical(role, canonical.as_deref())
+        .map(|(key_ids, _)| key_ids)
+        .unwrap_or_default()
+}
+
+/// Calls `f` with the transport that metadata is fetched through. In offline mode, that's the
+/// datastore; otherwise, it's `transport`, decompressing gzip files if `fetch` asks for them.
+/// Either way, everything read through it counts against `max_total_download`.
+fn with_metadata_transport<T>(
+    transport: &dyn Transport,
+    datastore: &Datastore,
+    fetch: MetadataFetch,
+    max_total_download: u64,
+    f: impl FnOnce(&dyn Transport) -> Result<T>,
+) -> Result<T> {
+    let datastore_transport;
+    let transport = if fetch.offline {
+        datastore_transport = DatastoreTransport::new(datastore.clone());
+        &datastore_transport as &dyn Transport
+    } else {
+        transport
+    };
+    let budget_transport = BudgetTransport::new(transport, max_total_download);
+    if fetch.compressed && !fetch.offline {
+        f(&GzipTransport::new(&budget_transport))
+    } else {
+        f(&budget_transport)
+    }
+}
+
 /// Steps 0 and 1 of the client application, which load the current root metadata file based on a
-/// trusted root metadata file.
+/// trusted root metadata file. When `offline`, the metadata is loaded from the datastore, so the
+/// trusted timestamp and snapshot metadata are kept even if their keys were rotated; they are
+/// verified against the new keys when they're loaded.
+#[allow(clippy::too_many_arguments, clippy::too_many_lines)]
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(
+        level = "debug",
+        skip_all,
+        fields(max_root_size = max_root_size, max_root_updates = max_root_updates)
+    )
+)]
 fn load_root<R: Read>(
     transport: &dyn Transport,
-    root: R,
+    mut root: R,
+    root_chain_dir: Option<&Path>,
+    on_key_rotation: Option<&KeyRotationCallback>,
     datastore: &Datastore,
     max_root_size: u64,
     max_root_updates: u64,
+    max_root_total_size: u64,
     metadata_base_url: &Url,
     expiration_enforcement: ExpirationEnforcement,
-) -> Result<Signed<Root>> {
+    lenient_datetime: bool,
+    offline: bool,
+) -> Result<(Signed<Root>, Vec<u8>, RootUpdateOutcome)> {
     // 0. Load the trusted root metadata file. We assume that a good, trusted copy of this file was
     //    shipped with the package manager or software updater using an out-of-band process. Note
     //    that the expiration of the trusted root metadata file does not matter, because we will
     //    attempt to update it in the next step.
-    let mut root: Signed<Root> =
-        serde_json::from_reader(root).context(error::ParseTrustedMetadata)?;
+    let mut root_data = Vec::new();
+    root.read_to_end(&mut root_data)
+        .map_err(serde_json::Error::io)
+        .context(error::ParseTrustedMetadata)?;
+    let (mut root, root_canonical) = parse_metadata::<Root>(&root_data, lenient_datetime)
+        .context(error::ParseTrustedMetadata)?;
     root.signed
-        .verify_role(&root)
+        .verify_role_canonical(&root, root_canonical.as_deref())
         .context(error::VerifyTrustedMetadata)?;
+    debug_event!(
+        version = root.signed.version.get(),
+        "verified trusted root metadata"
+    );
 
     // Used in step 1.2
     let original_root_version = root.signed.version.get();
+    let initial_version = root.signed.version;
+    let mut root_total_size = 0;
 
     // Used in step 1.9
     let original_timestamp_keys = root
@@ -535,7 +2463,7 @@ fn load_root<R: Read>(
     //    set of keys. To do so, the client MUST download intermediate root metadata files, until
     //    the latest available one is reached. Therefore, it MUST temporarily turn on consistent
     //    snapshots in order to download versioned root metadata files as described next.
-    loop {
+    let reason = loop {
         // 1.1. Let N denote the version number of the trusted root metadata file.
         //
         // 1.2. Try downloading version N+1 of the root metadata file, up to some X number of bytes
@@ -550,22 +2478,47 @@ fn load_root<R: Read>(
             root.signed.version.get() < original_root_version + max_root_updates,
             error::MaxUpdatesExceeded { max_root_updates }
         );
+        //
+        // Off-spec: the root metadata files downloaded here are also bounded in total, so that a
+        // long chain of large files can't add up to max_root_updates * max_root_size bytes.
         let path = format!("{}.root.json", root.signed.version.get() + 1);
+        let remaining = max_root_total_size.saturating_sub(root_total_size);
+        let (max_size, specifier) = if remaining < max_root_size {
+            (remaining, "max_root_total_size argument")
+        } else {
+            (max_root_size, "max_root_size argument")
+        };
         match fetch_max_size(
             transport,
             metadata_base_url.join(&path).context(error::JoinUrl {
                 path,
                 url: metadata_base_url.clone(),
             })?,
-            max_root_size,
-            "max_root_size argument",
+            max_size,
+            specifier,
         ) {
-            Err(_) => break, // If this file is not available, then go to step 1.8.
+            // If this file is not available, then go to step 1.8. Any other failure (e.g. a server
+            // error or a broken connection) must not be mistaken for "no newer root exists", so we
+            // abort the update cycle and report it.
+            Err(error::Error::Transport { source, .. })
+                if matches!(source.kind(), TransportErrorKind::FileNotFound) =>
+            {
+                debug_event!(
+                    version = root.signed.version.get(),
+                    "no newer root metadata"
+                );
+                break RootUpdateStopReason::ReachedLatest;
+            }
+            Err(err) => return Err(err),
             Ok(reader) => {
-                let new_root: Signed<Root> =
-                    serde_json::from_reader(reader).context(error::ParseMetadata {
-                        role: RoleType::Root,
-                    })?;
+                let new_root_data = read_metadata(reader, RoleType::Root)?;
+                root_total_size += new_root_data.len() as u64;
+                let (new_root, new_root_canonical) =
+                    parse_metadata::<Root>(&new_root_data, lenient_datetime).context(
+                        error::ParseMetadata {
+                            role: RoleType::Root,
+                        },
+                    )?;
 
                 // 1.3. Check signatures. Version N+1 of the root metadata file MUST have been
                 //   signed by: (1) a threshold of keys specified in the trusted root metadata file
@@ -574,13 +2527,13 @@ fn load_root<R: Read>(
                 //   discard it, abort the update cycle, and report the signature failure. On the
                 //   next update cycle, begin at step 0 and version N of the root metadata file.
                 root.signed
-                    .verify_role(&new_root)
+                    .verify_role_canonical(&new_root, new_root_canonical.as_deref())
                     .context(error::VerifyMetadata {
                         role: RoleType::Root,
                     })?;
                 new_root
                     .signed
-                    .verify_role(&new_root)
+                    .verify_role_canonical(&new_root, new_root_canonical.as_deref())
                     .context(error::VerifyMetadata {
                         role: RoleType::Root,
                     })?;
@@ -609,7 +2562,7 @@ fn load_root<R: Read>(
                 // path we built above, referencing N+1, has a filename that doesn't match its
                 // contents, which would have to list version N.
                 if root.signed.version == new_root.signed.version {
-                    break;
+                    break RootUpdateStopReason::SameVersion;
                 }
 
                 // 1.5. Note that the expiration of the new (intermediate) root metadata file does
@@ -618,13 +2571,25 @@ fn load_root<R: Read>(
                 // 1.6. Set the trusted root metadata file to the new root metadata file.
                 //
                 // (This is where version N+1 becomes version N.)
+                if let Some(dir) = root_chain_dir {
+                    save_root(dir, &new_root, &new_root_data)?;
+                }
+                if let Some(KeyRotationCallback(callback)) = on_key_rotation {
+                    report_key_rotations(&root.signed, &new_root.signed, callback.as_ref());
+                }
+                debug_event!(
+                    version = new_root.signed.version.get(),
+                    size = new_root_data.len(),
+                    "updated root metadata"
+                );
                 root = new_root;
+                root_data = new_root_data;
 
                 // 1.7. Repeat steps 1.1 to 1.7.
                 continue;
             }
         }
-    }
+    };
 
     // TUF v1.0.16, 5.2.9. Check for a freeze attack. The expiration timestamp in the trusted root
     // metadata file MUST be higher than the fixed update start time. If the trusted root metadata
@@ -640,12 +2605,13 @@ fn load_root<R: Read>(
     //   happens when attackers arbitrarily increase the version numbers of: (1) the timestamp
     //   metadata, (2) the snapshot metadata, and / or (3) the targets, or a delegated targets,
     //   metadata file in the snapshot metadata.
-    if original_timestamp_keys
-        .iter()
-        .ne(root.signed.keys(RoleType::Timestamp))
-        || original_snapshot_keys
+    if !offline
+        && (original_timestamp_keys
             .iter()
-            .ne(root.signed.keys(RoleType::Snapshot))
+            .ne(root.signed.keys(RoleType::Timestamp))
+            || original_snapshot_keys
+                .iter()
+                .ne(root.signed.keys(RoleType::Snapshot)))
     {
         let r1 = datastore.remove("timestamp.json");
         let r2 = datastore.remove("snapshot.json");
@@ -658,42 +2624,84 @@ fn load_root<R: Read>(
     // (This is done by checking the value of root.signed.consistent_snapshot throughout this
     // library.)
 
-    Ok(root)
+    let outcome = RootUpdateOutcome {
+        initial_version,
+        final_version: root.signed.version,
+        reason,
+    };
+    Ok((root, root_data, outcome))
 }
 
 /// Step 2 of the client application, which loads the timestamp metadata file.
+#[allow(clippy::too_many_arguments)]
+#[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
 fn load_timestamp(
     transport: &dyn Transport,
     root: &Signed<Root>,
     datastore: &Datastore,
     max_timestamp_size: u64,
     metadata_base_url: &Url,
+    filename: &str,
     expiration_enforcement: ExpirationEnforcement,
-) -> Result<Signed<Timestamp>> {
+    lenient_datetime: bool,
+) -> Result<(Signed<Timestamp>, Vec<u8>)> {
     // 2. Download the timestamp metadata file, up to Y number of bytes (because the size is
     //    unknown.) The value for Y is set by the authors of the application using TUF. For
     //    example, Y may be tens of kilobytes. The filename used to download the timestamp metadata
     //    file is of the fixed form FILENAME.EXT (e.g., timestamp.json).
-    let path = "timestamp.json";
-    let reader = fetch_max_size(
+    //
+    // If the datastore has a timestamp metadata file, the request is conditional on the file having
+    // changed since then. An unchanged file is read back from the datastore and verified again.
+    //
+    // The filename is `timestamp.json` unless it was overridden, but the file is always stored in
+    // the datastore as `timestamp.json`.
+    let url = metadata_base_url.join(filename).context(error::JoinUrl {
+        path: filename,
+        url: metadata_base_url.clone(),
+    })?;
+    let cached = datastore.reader("timestamp.json")?;
+    let validators = if cached.is_some() {
+        datastore.cache_validators(&url)?
+    } else {
+        CacheValidators::default()
+    };
+    let fetched = fetch_max_size_if_modified(
         transport,
-        metadata_base_url.join(path).context(error::JoinUrl {
-            path,
-            url: metadata_base_url.clone(),
-        })?,
+        url.clone(),
+        &validators,
         max_timestamp_size,
         "max_timestamp_size argument",
     )?;
-    let timestamp: Signed<Timestamp> =
-        serde_json::from_reader(reader).context(error::ParseMetadata {
-            role: RoleType::Timestamp,
-        })?;
+    let (timestamp_data, validators) = match (fetched, cached) {
+        (Some((reader, validators)), _) => {
+            (read_metadata(reader, RoleType::Timestamp)?, validators)
+        }
+        (None, Some(cached)) => (read_metadata(cached, RoleType::Timestamp)?, validators),
+        (None, None) => {
+            let reader = fetch_max_size(
+                transport,
+                url.clone(),
+                max_timestamp_size,
+                "max_timestamp_size argument",
+            )?;
+            (
+                read_metadata(reader, RoleType::Timestamp)?,
+                CacheValidators::default(),
+            )
+        }
+    };
+    let (timestamp, timestamp_canonical) =
+        parse_metadata::<Timestamp>(&timestamp_data, lenient_datetime).context(
+            error::ParseMetadata {
+                role: RoleType::Timestamp,
+            },
+        )?;
 
     // 2.1. Check signatures. The new timestamp metadata file must have been signed by a threshold
     //   of keys specified in the trusted root metadata file. If the new timestamp metadata file is
     //   not properly signed, discard it, abort the update cycle, and report the signature failure.
     root.signed
-        .verify_role(&timestamp)
+        .verify_role_canonical(&timestamp, timestamp_canonical.as_deref())
         .context(error::VerifyMetadata {
             role: RoleType::Timestamp,
         })?;
@@ -702,11 +2710,14 @@ fn load_timestamp(
     //   if any, must be less than or equal to the version number of the new timestamp metadata
     //   file. If the new timestamp metadata file is older than the trusted timestamp metadata
     //   file, discard it, abort the update cycle, and report the potential rollback attack.
-    if let Some(Ok(old_timestamp)) = datastore
-        .reader("timestamp.json")?
-        .map(serde_json::from_reader::<_, Signed<Timestamp>>)
+    if let Some((old_timestamp, old_canonical)) =
+        read_trusted::<Timestamp>(datastore, "timestamp.json", lenient_datetime)?
     {
-        if root.signed.verify_role(&old_timestamp).is_ok() {
+        if root
+            .signed
+            .verify_role_canonical(&old_timestamp, old_canonical.as_deref())
+            .is_ok()
+        {
             ensure!(
                 old_timestamp.signed.version <= timestamp.signed.version,
                 error::OlderMetadata {
@@ -725,22 +2736,34 @@ fn load_timestamp(
     if expiration_enforcement == ExpirationEnforcement::Safe {
         check_expired(datastore, &timestamp.signed)?;
     }
+    debug_event!(
+        version = timestamp.signed.version.get(),
+        size = timestamp_data.len(),
+        "verified timestamp metadata"
+    );
 
-    // Now that everything seems okay, write the timestamp file to the datastore.
-    datastore.create("timestamp.json", &timestamp)?;
+    // Now that everything seems okay, write the timestamp file to the datastore, along with the
+    // validators for fetching it conditionally next time.
+    datastore.create_raw("timestamp.json", &timestamp_data)?;
+    datastore.set_cache_validators(&url, validators)?;
 
-    Ok(timestamp)
+    Ok((timestamp, timestamp_data))
 }
 
 /// Step 3 of the client application, which loads the snapshot metadata file.
+#[allow(clippy::too_many_arguments)]
+#[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
 fn load_snapshot(
     transport: &dyn Transport,
     root: &Signed<Root>,
     timestamp: &Signed<Timestamp>,
     datastore: &Datastore,
+    max_snapshot_size: u64,
     metadata_base_url: &Url,
+    filename: &str,
     expiration_enforcement: ExpirationEnforcement,
-) -> Result<Signed<Snapshot>> {
+    lenient_datetime: bool,
+) -> Result<(Signed<Snapshot>, Vec<u8>)> {
     // 3. Download snapshot metadata file, up to the number of bytes specified in the timestamp
     //    metadata file. If consistent snapshots are not used (see Section 7), then the filename
     //    used to download the snapshot metadata file is of the fixed form FILENAME.EXT (e.g.,
@@ -748,40 +2771,47 @@ fn load_snapshot(
     //    42.snapshot.json), where VERSION_NUMBER is the version number of the snapshot metadata
     //    file listed in the timestamp metadata file. In either case, the client MUST write the
     //    file to non-volatile storage as FILENAME.EXT.
-    let snapshot_meta = timestamp
-        .signed
-        .meta
-        .get("snapshot.json")
-        .context(error::MetaMissing {
-            file: "snapshot.json",
-            role: RoleType::Timestamp,
-        })?;
+    let snapshot_meta =
+        timestamp
+            .signed
+            .meta_entry("snapshot.json")
+            .context(error::MetaMissing {
+                file: "snapshot.json",
+                role: RoleType::Timestamp,
+            })?;
     let path = if root.signed.consistent_snapshot {
-        format!("{}.snapshot.json", snapshot_meta.version)
+        format!("{}.{}", snapshot_meta.version, filename)
     } else {
-        "snapshot.json".to_owned()
+        filename.to_owned()
     };
-    let reader = fetch_sha256(
+    let (max_snapshot_size, specifier) = match snapshot_meta.length {
+        Some(length) => (length, "timestamp.json"),
+        None => (max_snapshot_size, "max_snapshot_size parameter"),
+    };
+    let reader = fetch_hashes(
         transport,
         metadata_base_url.join(&path).context(error::JoinUrl {
             path,
             url: metadata_base_url.clone(),
         })?,
-        snapshot_meta.length,
-        "timestamp.json",
-        &snapshot_meta.hashes.sha256,
+        max_snapshot_size,
+        specifier,
+        &snapshot_meta.hashes,
     )?;
-    let snapshot: Signed<Snapshot> =
-        serde_json::from_reader(reader).context(error::ParseMetadata {
-            role: RoleType::Snapshot,
-        })?;
+    let snapshot_data = read_metadata(reader, RoleType::Snapshot)?;
+    let (snapshot, snapshot_canonical) =
+        parse_metadata::<Snapshot>(&snapshot_data, lenient_datetime).context(
+            error::ParseMetadata {
+                role: RoleType::Snapshot,
+            },
+        )?;
 
     // 3.1. Check against timestamp metadata. The hashes and version number of the new snapshot
     //   metadata file MUST match the hashes and version number listed in timestamp metadata. If
     //   hashes and version do not match, discard the new snapshot metadata, abort the update
     //   cycle, and report the failure.
     //
-    // (We already checked the hash in `fetch_sha256` above.)
+    // (We already checked the hash in `fetch_hashes` above.)
     ensure!(
         snapshot.signed.version == snapshot_meta.version,
         error::VersionMismatch {
@@ -796,7 +2826,7 @@ fn load_snapshot(
     //   not signed as required, discard it, abort the update cycle, and report the signature
     //   failure.
     root.signed
-        .verify_role(&snapshot)
+        .verify_role_canonical(&snapshot, snapshot_canonical.as_deref())
         .context(error::VerifyMetadata {
             role: RoleType::Snapshot,
         })?;
@@ -805,15 +2835,18 @@ fn load_snapshot(
     //
     // 3.3.1. Note that the trusted snapshot metadata file may be checked for authenticity, but its
     //   expiration does not matter for the following purposes.
-    if let Some(Ok(old_snapshot)) = datastore
-        .reader("snapshot.json")?
-        .map(serde_json::from_reader::<_, Signed<Snapshot>>)
+    if let Some((old_snapshot, old_canonical)) =
+        read_trusted::<Snapshot>(datastore, "snapshot.json", lenient_datetime)?
     {
         // 3.3.2. The version number of the trusted snapshot metadata file, if any, MUST be less
         //   than or equal to the version number of the new snapshot metadata file. If the new
         //   snapshot metadata file is older than the trusted metadata file, discard it, abort the
         //   update cycle, and report the potential rollback attack.
-        if root.signed.verify_role(&old_snapshot).is_ok() {
+        if root
+            .signed
+            .verify_role_canonical(&old_snapshot, old_canonical.as_deref())
+            .is_ok()
+        {
             ensure!(
                 old_snapshot.signed.version <= snapshot.signed.version,
                 error::OlderMetadata {
@@ -830,12 +2863,11 @@ fn load_snapshot(
             //   metadata file, if any, MUST continue to be listed in the new snapshot metadata
             //   file. If any of these conditions are not met, discard the new snaphot metadadata
             //   file, abort the update cycle, and report the failure.
-            if let Some(old_targets_meta) = old_snapshot.signed.meta.get("targets.json") {
+            if let Some(old_targets_meta) = old_snapshot.signed.meta_entry("targets.json") {
                 let targets_meta =
                     snapshot
                         .signed
-                        .meta
-                        .get("targets.json")
+                        .meta_entry("targets.json")
                         .context(error::MetaMissing {
                             file: "targets.json",
                             role: RoleType::Snapshot,
@@ -859,23 +2891,35 @@ fn load_snapshot(
     if expiration_enforcement == ExpirationEnforcement::Safe {
         check_expired(datastore, &snapshot.signed)?;
     }
+    debug_event!(
+        version = snapshot.signed.version.get(),
+        size = snapshot_data.len(),
+        "verified snapshot metadata"
+    );
 
     // Now that everything seems okay, write the snapshot file to the datastore.
-    datastore.create("snapshot.json", &snapshot)?;
+    datastore.create_raw("snapshot.json", &snapshot_data)?;
 
-    Ok(snapshot)
+    Ok((snapshot, snapshot_data))
 }
 
 /// Step 4 of the client application, which loads the targets metadata file.
+#[allow(clippy::too_many_arguments)]
+#[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
 fn load_targets(
     transport: &dyn Transport,
     root: &Signed<Root>,
     snapshot: &Signed<Snapshot>,
     datastore: &Datastore,
     max_targets_size: u64,
+    max_delegated_roles: u64,
     metadata_base_url: &Url,
+    filename: &str,
     expiration_enforcement: ExpirationEnforcement,
-) -> Result<Signed<crate::schema::Targets>> {
+    lenient_datetime: bool,
+    delegation_options: &DelegationOptions,
+    require_hash: bool,
+) -> Result<LoadedTargets> {
     // 4. Download the top-level targets metadata file, up to either the number of bytes specified
     //    in the snapshot metadata file, or some Z number of bytes. The value for Z is set by the
     //    authors of the application using TUF. For example, Z may be tens of kilobytes. If
@@ -887,16 +2931,22 @@ fn load_targets(
     //    FILENAME.EXT.
     let targets_meta = snapshot
         .signed
-        .meta
-        .get("targets.json")
+        .meta_entry("targets.json")
         .context(error::MetaMissing {
             file: "targets.json",
             role: RoleType::Timestamp,
         })?;
+    ensure!(
+        !require_hash || targets_meta.hashes.is_some(),
+        error::MetaHashesMissing {
+            file: "targets.json",
+            role: RoleType::Snapshot,
+        }
+    );
     let path = if root.signed.consistent_snapshot {
-        format!("{}.targets.json", targets_meta.version)
+        format!("{}.{}", targets_meta.version, filename)
     } else {
-        "targets.json".to_owned()
+        filename.to_owned()
     };
     let targets_url = metadata_base_url.join(&path).context(error::JoinUrl {
         path,
@@ -907,12 +2957,12 @@ fn load_targets(
         None => (max_targets_size, "max_targets_size parameter"),
     };
     let reader = if let Some(hashes) = &targets_meta.hashes {
-        Box::new(fetch_sha256(
+        Box::new(fetch_hashes(
             transport,
             targets_url,
             max_targets_size,
             specifier,
-            &hashes.sha256,
+            hashes,
         )?) as Box<dyn Read>
     } else {
         Box::new(fetch_max_size(
@@ -922,17 +2972,20 @@ fn load_targets(
             specifier,
         )?)
     };
-    let mut targets: Signed<crate::schema::Targets> =
-        serde_json::from_reader(reader).context(error::ParseMetadata {
-            role: RoleType::Targets,
-        })?;
+    let targets_data = read_metadata(reader, RoleType::Targets)?;
+    let (mut targets, targets_canonical) =
+        parse_metadata::<crate::schema::Targets>(&targets_data, lenient_datetime).context(
+            error::ParseMetadata {
+                role: RoleType::Targets,
+            },
+        )?;
 
     // 4.1. Check against snapshot metadata. The hashes (if any), and version number of the new
     //   targets metadata file MUST match the trusted snapshot metadata. This is done, in part, to
     //   prevent a mix-and-match attack by man-in-the-middle attackers. If the new targets metadata
     //   file does not match, discard it, abort the update cycle, and report the failure.
     //
-    // (We already checked the hash in `fetch_sha256` above.)
+    // (We already checked the hash in `fetch_hashes` above.)
     ensure!(
         targets.signed.version == targets_meta.version,
         error::VersionMismatch {
@@ -947,7 +3000,7 @@ fn load_targets(
     //   targets metadata file is not signed as required, discard it, abort the update cycle, and
     //   report the failure.
     root.signed
-        .verify_role(&targets)
+        .verify_role_canonical(&targets, targets_canonical.as_deref())
         .context(error::VerifyMetadata {
             role: RoleType::Targets,
         })?;
@@ -956,11 +3009,14 @@ fn load_targets(
     //   if any, MUST be less than or equal to the version number of the new targets metadata file.
     //   If the new targets metadata file is older than the trusted targets metadata file, discard
     //   it, abort the update cycle, and report the potential rollback attack.
-    if let Some(Ok(old_targets)) = datastore
-        .reader("targets.json")?
-        .map(serde_json::from_reader::<_, Signed<crate::schema::Targets>>)
+    if let Some((old_targets, old_canonical)) =
+        read_trusted::<crate::schema::Targets>(datastore, "targets.json", lenient_datetime)?
     {
-        if root.signed.verify_role(&old_targets).is_ok() {
+        if root
+            .signed
+            .verify_role_canonical(&old_targets, old_canonical.as_deref())
+            .is_ok()
+        {
             ensure!(
                 old_targets.signed.version <= targets.signed.version,
                 error::OlderMetadata {
@@ -979,93 +3035,108 @@ fn load_targets(
     if expiration_enforcement == ExpirationEnforcement::Safe {
         check_expired(datastore, &targets.signed)?;
     }
+    debug_event!(
+        version = targets.signed.version.get(),
+        size = targets_data.len(),
+        "verified targets metadata"
+    );
 
     // Now that everything seems okay, write the targets file to the datastore.
-    datastore.create("targets.json", &targets)?;
+    datastore.create_raw("targets.json", &targets_data)?;
 
     // 4.5. Perform a preorder depth-first search for metadata about the desired target, beginning
     //   with the top-level targets role.
+    let mut delegated_data = HashMap::new();
     if let Some(delegations) = &mut targets.signed.delegations {
+        let mut delegated_role_count = 0;
         load_delegations(
             transport,
             snapshot,
             root.signed.consistent_snapshot,
             metadata_base_url,
             max_targets_size,
+            max_delegated_roles,
+            &mut delegated_role_count,
             delegations,
+            &mut delegated_data,
             datastore,
+            lenient_datetime,
+            delegation_options.best_effort,
+            delegation_options.concurrency,
         )?;
     }
 
-    Ok(targets)
+    Ok((targets, targets_data, delegated_data))
 }
 
-// Follow the paths of delegations starting with the top level targets.json delegation
+// Follow the paths of delegations starting with the top level targets.json delegation.
+// `delegated_role_count` is the number of delegated roles loaded so far, across all levels of
+// delegation, which may not exceed `max_delegated_roles`. Up to `concurrency` of the roles at each
+// level are fetched at once. The metadata file of each role that's loaded is added to
+// `delegated_data`.
+#[allow(clippy::too_many_arguments)]
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(level = "debug", skip_all, fields(roles = delegation.roles.len()))
+)]
 fn load_delegations(
     transport: &dyn Transport,
     snapshot: &Signed<Snapshot>,
     consistent_snapshot: bool,
     metadata_base_url: &Url,
     max_targets_size: u64,
+    max_delegated_roles: u64,
+    delegated_role_count: &mut u64,
     delegation: &mut Delegations,
+    delegated_data: &mut HashMap<String, Vec<u8>>,
     datastore: &Datastore,
+    lenient_datetime: bool,
+    best_effort: bool,
+    concurrency: usize,
 ) -> Result<()> {
-    let mut delegated_roles: HashMap<String, Option<Signed<crate::schema::Targets>>> =
-        HashMap::new();
-    for delegated_role in &delegation.roles {
-        // find the role file metadata
-        let role_meta = snapshot
-            .signed
-            .meta
-            .get(&format!("{}.json", &delegated_role.name))
-            .context(error::RoleNotInMeta {
-                name: delegated_role.name.clone(),
-            })?;
-
-        let path = if consistent_snapshot {
-            format!("{}.{}.json", &role_meta.version, &delegated_role.name)
-        } else {
-            format!("{}.json", &delegated_role.name)
-        };
-        let role_url = metadata_base_url.join(&path).context(error::JoinUrl {
-            path: path.clone(),
-            url: metadata_base_url.clone(),
-        })?;
-        let specifier = "max_targets_size parameter";
-        // load the role json file
-        let reader = Box::new(fetch_max_size(
-            transport,
-            role_url,
-            max_targets_size,
-            specifier,
-        )?);
-        // since each role is a targets, we load them as such
-        let role: Signed<crate::schema::Targets> =
-            serde_json::from_reader(reader).context(error::ParseMetadata {
-                role: RoleType::Targets,
-            })?;
-        // verify each role with the delegation
-        delegation
-            .verify_role(&role, &delegated_role.name)
-            .context(error::VerifyMetadata {
-                role: RoleType::Targets,
-            })?;
+    for _ in &delegation.roles {
+        *delegated_role_count += 1;
         ensure!(
-            role.signed.version == role_meta.version,
-            error::VersionMismatch {
-                role: RoleType::Targets,
-                fetched: role.signed.version,
-                expected: role_meta.version
+            *delegated_role_count <= max_delegated_roles,
+            error::MaxDelegatedRolesExceeded {
+                max_delegated_roles
             }
         );
-        {
-            if let Some(delegations) = role.signed.delegations.as_ref() {
-                delegations.verify_paths().context(error::InvalidPath {})?;
+    }
+    let delegations: &Delegations = delegation;
+    let loaded = map_concurrently(&delegation.roles, concurrency, |delegated_role| {
+        load_delegated_role(
+            transport,
+            snapshot,
+            consistent_snapshot,
+            metadata_base_url,
+            max_targets_size,
+            delegations,
+            delegated_role,
+            lenient_datetime,
+        )
+    });
+    // The results are in the order the roles are listed, so the datastore is written, and the
+    // first error is returned, just as if the roles had been loaded one at a time.
+    let mut delegated_roles: HashMap<String, Option<Signed<crate::schema::Targets>>> =
+        HashMap::new();
+    for (delegated_role, loaded) in delegation.roles.iter().zip(loaded) {
+        let role = match loaded {
+            Ok((path, role, role_data)) => {
+                datastore.create_raw(&path, &role_data)?;
+                delegated_data.insert(delegated_role.name.clone(), role_data);
+                Some(role)
             }
-        }
-
-        datastore.create(&path, &role)?;
-        delegated_roles.insert(delegated_role.name.clone(), Some(role));
+            Err(err) if best_effort => {
+                warn!(
+                    "skipping delegated role '{}', which failed to load: {}",
+                    delegated_role.name, err
+                );
+                None
+            }
+            Err(err) => return Err(err),
+        };
+        delegated_roles.insert(delegated_role.name.clone(), role);
     }
     // load all roles delegated by this role
     for delegated_role in &mut delegation.roles {
@@ -1082,8 +3153,14 @@ fn load_delegations(
                     consistent_snapshot,
                     metadata_base_url,
                     max_targets_size,
+                    max_delegated_roles,
+                    delegated_role_count,
                     delegations,
+                    delegated_data,
                     datastore,
+                    lenient_datetime,
+                    best_effort,
+                    concurrency,
                 )?;
             }
         }
@@ -1091,9 +3168,141 @@ fn load_delegations(
     Ok(())
 }
 
+/// Calls `f` on each of `items` on up to `concurrency` threads, and returns the results in the
+/// order of `items`. With a `concurrency` of 0 or 1, or a single item, `f` is called on the
+/// current thread.
+fn map_concurrently<T, U, F>(items: &[T], concurrency: usize, f: F) -> Vec<U>
+where
+    T: Sync,
+    U: Send,
+    F: Fn(&T) -> U + Sync,
+{
+    let threads = concurrency.min(items.len());
+    if threads <= 1 {
+        return items.iter().map(f).collect();
+    }
+    let next = AtomicUsize::new(0);
+    let mut results: Vec<(usize, U)> = std::thread::scope(|scope| {
+        let workers: Vec<_> = (0..threads)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut results = Vec::new();
+                    loop {
+                        let index = next.fetch_add(1, Ordering::Relaxed);
+                        match items.get(index) {
+                            Some(item) => results.push((index, f(item))),
+                            None => return results,
+                        }
+                    }
+                })
+            })
+            .collect();
+        workers
+            .into_iter()
+            .flat_map(|worker| {
+                worker
+                    .join()
+                    .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+            })
+            .collect()
+    });
+    results.sort_by_key(|(index, _)| *index);
+    results.into_iter().map(|(_, result)| result).collect()
+}
+
+/// Fetches and verifies the metadata of `delegated_role`, which is delegated by `delegation`.
+/// Returns the path to store it at in the datastore, the verified role, and the metadata as it was
+/// received.
+#[allow(clippy::too_many_arguments)]
+fn load_delegated_role(
+    transport: &dyn Transport,
+    snapshot: &Signed<Snapshot>,
+    consistent_snapshot: bool,
+    metadata_base_url: &Url,
+    max_targets_size: u64,
+    delegation: &Delegations,
+    delegated_role: &DelegatedRole,
+    lenient_datetime: bool,
+) -> Result<(String, Signed<crate::schema::Targets>, Vec<u8>)> {
+    // find the role file metadata
+    let role_meta = snapshot
+        .signed
+        .meta_entry(&format!("{}.json", &delegated_role.name))
+        .context(error::RoleNotInMeta {
+            name: delegated_role.name.clone(),
+        })?;
+
+    let path = if consistent_snapshot {
+        format!("{}.{}.json", &role_meta.version, &delegated_role.name)
+    } else {
+        format!("{}.json", &delegated_role.name)
+    };
+    let role_url = metadata_base_url.join(&path).context(error::JoinUrl {
+        path: path.clone(),
+        url: metadata_base_url.clone(),
+    })?;
+    let (max_targets_size, specifier) = match role_meta.length {
+        Some(length) => (length, "snapshot.json"),
+        None => (max_targets_size, "max_targets_size parameter"),
+    };
+    // load the role json file, checking its hash if the snapshot lists one
+    let reader = if let Some(hashes) = &role_meta.hashes {
+        Box::new(fetch_hashes(
+            transport,
+            role_url,
+            max_targets_size,
+            specifier,
+            hashes,
+        )?) as Box<dyn Read>
+    } else {
+        Box::new(fetch_max_size(
+            transport,
+            role_url,
+            max_targets_size,
+            specifier,
+        )?)
+    };
+    // since each role is a targets, we load them as such
+    let role_data = read_metadata(reader, RoleType::Targets)?;
+    let (role, role_canonical) =
+        parse_metadata::<crate::schema::Targets>(&role_data, lenient_datetime).context(
+            error::ParseMetadata {
+                role: RoleType::Targets,
+            },
+        )?;
+    // verify each role with the delegation
+    delegation
+        .verify_role_canonical(&role, &delegated_role.name, role_canonical.as_deref())
+        .context(error::VerifyMetadata {
+            role: RoleType::Targets,
+        })?;
+    ensure!(
+        role.signed.version == role_meta.version,
+        error::VersionMismatch {
+            role: RoleType::Targets,
+            fetched: role.signed.version,
+            expected: role_meta.version
+        }
+    );
+    {
+        if let Some(delegations) = role.signed.delegations.as_ref() {
+            delegations.verify_paths().context(error::InvalidPath {})?;
+        }
+    }
+
+    debug_event!(
+        role = %delegated_role.name,
+        version = role.signed.version.get(),
+        size = role_data.len(),
+        "verified delegated targets metadata"
+    );
+    Ok((path, role, role_data))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use chrono::TimeZone;
 
     // Check if a url with a trailing slash and one without trailing slash can both be parsed
     #[test]
@@ -1108,6 +3317,165 @@ mod tests {
         );
     }
 
+    // Check that the subdirectories of a base URL are the same with and without a trailing slash
+    #[test]
+    fn subdirectory_url_trailing_slash() {
+        for base in &["https://example.org/repo", "https://example.org/repo/"] {
+            let base = Url::parse(base).unwrap();
+            assert_eq!(
+                subdirectory_url(&base, "metadata").as_str(),
+                "https://example.org/repo/metadata/"
+            );
+        }
+        let base = Url::parse("https://example.org").unwrap();
+        assert_eq!(
+            subdirectory_url(&base, "targets").as_str(),
+            "https://example.org/targets/"
+        );
+    }
+
+    // Check that files missing from the snapshot metadata, and files that it lists for roles that
+    // aren't delegated, are both found
+    #[test]
+    fn snapshot_consistency() {
+        let base = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/data/tuf-reference-impl/metadata");
+        let snapshot = || {
+            serde_json::from_slice::<Signed<Snapshot>>(
+                &std::fs::read(base.join("snapshot.json")).unwrap(),
+            )
+            .unwrap()
+            .signed
+        };
+        let targets = serde_json::from_slice::<Signed<crate::schema::Targets>>(
+            &std::fs::read(base.join("targets.json")).unwrap(),
+        )
+        .unwrap()
+        .signed;
+
+        // Only `role1` is known, because `role2` is delegated by `role1`, which isn't loaded here.
+        let (missing, orphaned) = snapshot_inconsistencies(&snapshot(), &targets);
+        assert!(missing.is_empty());
+        assert_eq!(orphaned, vec!["role2.json"]);
+
+        let mut incomplete = snapshot();
+        incomplete.meta.remove("role1.json");
+        incomplete.meta.remove("role2.json");
+        let (missing, orphaned) = snapshot_inconsistencies(&incomplete, &targets);
+        assert_eq!(missing, vec!["role1.json"]);
+        assert!(orphaned.is_empty());
+    }
+
+    // Check that meta entries keyed without the `.json` suffix are found, and that the suffixed
+    // key is preferred when both are present
+    #[test]
+    fn unsuffixed_meta_keys() {
+        let base = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/data/tuf-reference-impl/metadata");
+        let mut snapshot = serde_json::from_slice::<Signed<Snapshot>>(
+            &std::fs::read(base.join("snapshot.json")).unwrap(),
+        )
+        .unwrap()
+        .signed;
+        let targets = serde_json::from_slice::<Signed<crate::schema::Targets>>(
+            &std::fs::read(base.join("targets.json")).unwrap(),
+        )
+        .unwrap()
+        .signed;
+        for name in &["targets", "role1", "role2"] {
+            let meta = snapshot.meta.remove(&format!("{}.json", name)).unwrap();
+            snapshot.meta.insert((*name).to_owned(), meta);
+        }
+
+        assert!(snapshot.meta_entry("targets.json").is_some());
+        assert!(snapshot.meta_entry("role1.json").is_some());
+        assert!(snapshot.meta_entry("role3.json").is_none());
+        let (missing, orphaned) = snapshot_inconsistencies(&snapshot, &targets);
+        assert!(missing.is_empty());
+        assert_eq!(orphaned, vec!["role2"]);
+
+        let mut newer = snapshot.meta["targets"].clone();
+        newer.version = NonZeroU64::new(newer.version.get() + 1).unwrap();
+        snapshot
+            .meta
+            .insert("targets.json".to_owned(), newer.clone());
+        assert_eq!(snapshot.meta_entry("targets.json"), Some(&newer));
+    }
+
+    // Check that a delegated role that's missing from the snapshot metadata fails the load, unless
+    // delegations are loaded on a best-effort basis, which skips it
+    #[test]
+    fn delegated_role_not_in_snapshot() {
+        let now = Utc.ymd(2021, 1, 1).and_hms(0, 0, 0);
+        let snapshot = Signed::new(
+            Snapshot::new("1.0.0".to_owned(), NonZeroU64::new(1).unwrap(), now),
+            Vec::new(),
+        );
+        let delegations = || {
+            let mut delegations = Delegations::new();
+            delegations.roles.push(DelegatedRole {
+                name: "role1".to_owned(),
+                keyids: Vec::new(),
+                threshold: NonZeroU64::new(1).unwrap(),
+                paths: crate::schema::PathSet::Paths(vec!["*".to_owned()]),
+                terminating: false,
+                targets: None,
+            });
+            delegations
+        };
+        let datastore = Datastore::new(
+            None,
+            DatastoreLocking::default(),
+            false,
+            TimeSource::fixed(now),
+        )
+        .unwrap();
+        let metadata_base_url = Url::parse("file:///nonexistent/metadata/").unwrap();
+        let load = |delegations: &mut Delegations, best_effort: bool| {
+            load_delegations(
+                &FilesystemTransport,
+                &snapshot,
+                false,
+                &metadata_base_url,
+                1024,
+                2048,
+                &mut 0,
+                delegations,
+                &mut HashMap::new(),
+                &datastore,
+                false,
+                best_effort,
+                1,
+            )
+        };
+
+        let mut strict = delegations();
+        assert!(matches!(
+            load(&mut strict, false),
+            Err(error::Error::RoleNotInMeta { .. })
+        ));
+        let mut best_effort = delegations();
+        load(&mut best_effort, true).unwrap();
+        assert!(best_effort.roles[0].targets.is_none());
+    }
+
+    // Check that a URL join error keeps the URL parse error as its source
+    #[test]
+    fn join_url_error_source() {
+        use std::error::Error as _;
+
+        let base = Url::parse("https://example.org/metadata/").unwrap();
+        let path = "//[::1";
+        let err = base
+            .join(path)
+            .context(error::JoinUrl {
+                path,
+                url: base.clone(),
+            })
+            .unwrap_err();
+        assert!(err.source().unwrap().is::<url::ParseError>());
+    }
+
     // Ensure that the `ExpirationEnforcement` traits are not changed by mistake.
     #[test]
     fn expiration_enforcement_traits() {
@@ -1124,4 +3492,17 @@ mod tests {
         let default = ExpirationEnforcement::default();
         assert_eq!(default, ExpirationEnforcement::Safe);
     }
+
+    // Spec versions with two components are read as if they had a third
+    #[test]
+    fn spec_version_parsing() {
+        assert_eq!(parse_spec_version("1.0.0"), Some(Version::new(1, 0, 0)));
+        assert_eq!(parse_spec_version("1.0"), Some(Version::new(1, 0, 0)));
+        assert_eq!(parse_spec_version("1"), None);
+        let required = VersionReq::parse("^1").unwrap();
+        assert!(check_spec_version(Some(&required), "root", "1.0").is_ok());
+        assert!(check_spec_version(Some(&required), "root", "2.0.0").is_err());
+        assert!(check_spec_version(Some(&required), "root", "one").is_err());
+        assert!(check_spec_version(None, "root", "2.0.0").is_ok());
+    }
 }
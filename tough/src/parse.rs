@@ -0,0 +1,99 @@
+//! Parsing of metadata files, including the lenient parsing of `expires` enabled by
+//! [`RepositoryLoader::lenient_datetime`](crate::RepositoryLoader::lenient_datetime).
+
+use crate::schema::{to_canonical_json, Signed};
+use chrono::{DateTime, NaiveDateTime, TimeZone, Utc};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::io::Read;
+
+/// The formats, other than RFC 3339, that `expires` is parsed with when datetimes are lenient.
+/// Datetimes without an offset are in UTC.
+const LENIENT_FORMATS: &[&str] = &["%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%d %H:%M:%S%.f"];
+
+/// A parsed metadata file, and the canonical form of its `signed` object as it was received if the
+/// parsed role doesn't serialize to that (see [`parse_metadata`]).
+pub(crate) type ParsedMetadata<T> = (Signed<T>, Option<Vec<u8>>);
+
+/// Parses a metadata file. If `lenient_datetime` is set, an `expires` value that isn't RFC 3339
+/// but is in one of the [`LENIENT_FORMATS`] is accepted.
+///
+/// Signatures are made over the canonical form of the `signed` object as it was received. When
+/// `expires` is written differently than the parsed role would serialize it, the role no longer
+/// serializes to that, so the original canonical form is returned along with the role to verify
+/// its signatures against.
+pub(crate) fn parse_metadata<T: DeserializeOwned>(
+    data: &[u8],
+    lenient_datetime: bool,
+) -> serde_json::Result<ParsedMetadata<T>> {
+    if !lenient_datetime {
+        return Ok((serde_json::from_slice(data)?, None));
+    }
+    let mut value: Value = serde_json::from_slice(data)?;
+    let original = match value.pointer("/signed/expires") {
+        Some(Value::String(expires)) => expires.clone(),
+        _ => return Ok((serde_json::from_value(value)?, None)),
+    };
+    let parsed = match original
+        .parse::<DateTime<Utc>>()
+        .ok()
+        .or_else(|| parse_lenient_datetime(&original))
+    {
+        Some(parsed) => parsed,
+        None => return Ok((serde_json::from_value(value)?, None)),
+    };
+    // An RFC 3339 value may still be written differently than it would be serialized, e.g. with
+    // fractional seconds of zero.
+    let normalized = serde_json::to_value(parsed)?;
+    if normalized == Value::String(original) {
+        return Ok((serde_json::from_value(value)?, None));
+    }
+    let canonical = to_canonical_json(&value["signed"])?;
+    value["signed"]["expires"] = normalized;
+    Ok((serde_json::from_value(value)?, Some(canonical)))
+}
+
+/// Reads and parses a metadata file with [`parse_metadata`].
+pub(crate) fn parse_metadata_reader<R: Read, T: DeserializeOwned>(
+    mut reader: R,
+    lenient_datetime: bool,
+) -> serde_json::Result<ParsedMetadata<T>> {
+    let mut data = Vec::new();
+    reader
+        .read_to_end(&mut data)
+        .map_err(serde_json::Error::io)?;
+    parse_metadata(&data, lenient_datetime)
+}
+
+/// Parses a datetime in one of the [`LENIENT_FORMATS`], in UTC.
+fn parse_lenient_datetime(value: &str) -> Option<DateTime<Utc>> {
+    let value = value.trim();
+    LENIENT_FORMATS
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+        .map(|naive| Utc.from_utc_datetime(&naive))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_lenient_datetime;
+    use chrono::{TimeZone, Utc};
+
+    #[test]
+    fn lenient_formats() {
+        let expected = Utc.ymd(2030, 1, 2).and_hms(3, 4, 5);
+        for value in &[
+            "2030-01-02T03:04:05",
+            "2030-01-02 03:04:05",
+            " 2030-01-02T03:04:05 ",
+        ] {
+            assert_eq!(parse_lenient_datetime(value), Some(expected), "{}", value);
+        }
+        assert_eq!(
+            parse_lenient_datetime("2030-01-02T03:04:05.250"),
+            Some(Utc.ymd(2030, 1, 2).and_hms_milli(3, 4, 5, 250))
+        );
+        assert_eq!(parse_lenient_datetime("2030-01-02"), None);
+        assert_eq!(parse_lenient_datetime("next tuesday"), None);
+    }
+}
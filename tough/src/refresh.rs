@@ -0,0 +1,102 @@
+//! Provides [`Repository::auto_refresh`], which keeps a [`Repository`] up to date in the
+//! background.
+
+use crate::error::Error;
+use crate::Repository;
+use log::debug;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, SyncSender, TrySendError};
+use std::sync::{Arc, PoisonError, RwLock};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// The number of refresh errors that are kept until they are received from
+/// [`RefreshHandle::errors`]. Later errors are dropped until there is room.
+const ERROR_CAPACITY: usize = 16;
+
+/// A handle to a thread that refreshes a [`Repository`], returned by
+/// [`Repository::auto_refresh`]. Dropping the handle stops the thread.
+#[derive(Debug)]
+pub struct RefreshHandle {
+    repository: Arc<RwLock<Arc<Repository>>>,
+    errors: Receiver<Error>,
+    stop: Option<SyncSender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Repository {
+    /// Spawns a thread that calls [`Repository::refresh`] every `interval`, so that long-running
+    /// clients always have the latest metadata without polling for it themselves. Use
+    /// [`RefreshHandle::repository`] to get the latest `Repository`.
+    ///
+    /// A refresh that fails leaves the latest `Repository` as it was, and the error is sent to
+    /// [`RefreshHandle::errors`]. Refreshing continues after errors, so a transient failure
+    /// doesn't stop updates. Check the receiver regularly: a client that keeps using the last good
+    /// `Repository` while refreshes fail is open to a freeze attack until its metadata expires.
+    pub fn auto_refresh(self, interval: Duration) -> RefreshHandle {
+        let repository = Arc::new(RwLock::new(Arc::new(self.clone())));
+        let (error_tx, errors) = mpsc::sync_channel(ERROR_CAPACITY);
+        let (stop, stop_rx) = mpsc::sync_channel(0);
+        let shared = Arc::clone(&repository);
+        let thread =
+            thread::spawn(move || refresh_loop(self, &shared, interval, &stop_rx, &error_tx));
+        RefreshHandle {
+            repository,
+            errors,
+            stop: Some(stop),
+            thread: Some(thread),
+        }
+    }
+}
+
+/// Refreshes `repo` every `interval` until `stop` is disconnected, publishing changes to `shared`.
+fn refresh_loop(
+    mut repo: Repository,
+    shared: &RwLock<Arc<Repository>>,
+    interval: Duration,
+    stop: &Receiver<()>,
+    errors: &SyncSender<Error>,
+) {
+    while let Err(RecvTimeoutError::Timeout) = stop.recv_timeout(interval) {
+        match repo.refresh() {
+            Ok(false) => {}
+            Ok(true) => {
+                *shared.write().unwrap_or_else(PoisonError::into_inner) = Arc::new(repo.clone());
+            }
+            Err(err) => match errors.try_send(err) {
+                Ok(()) | Err(TrySendError::Disconnected(_)) => {}
+                Err(TrySendError::Full(err)) => debug!("dropping refresh error: {}", err),
+            },
+        }
+    }
+}
+
+impl RefreshHandle {
+    /// Returns the latest `Repository`. This is cheap, and the returned `Repository` doesn't
+    /// change; call this again to see later refreshes.
+    pub fn repository(&self) -> Arc<Repository> {
+        Arc::clone(
+            &self
+                .repository
+                .read()
+                .unwrap_or_else(PoisonError::into_inner),
+        )
+    }
+
+    /// Returns a receiver for the errors of refreshes that failed. Up to 16 errors are kept until
+    /// they are received; later errors are dropped.
+    pub fn errors(&self) -> &Receiver<Error> {
+        &self.errors
+    }
+}
+
+impl Drop for RefreshHandle {
+    /// Stops the refresh thread, waiting for a refresh in progress to finish.
+    fn drop(&mut self) {
+        // Disconnecting the channel wakes the thread up.
+        drop(self.stop.take());
+        if let Some(thread) = self.thread.take() {
+            // A panic in the thread has already been reported, and there is no way to return it.
+            let _ = thread.join();
+        }
+    }
+}
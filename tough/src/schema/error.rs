@@ -20,10 +20,22 @@ pub enum Error {
     #[snafu(display("Duplicate key ID: {}", keyid))]
     DuplicateKeyId { keyid: String },
 
+    /// A signature was added for a key that has already signed the role.
+    #[snafu(display("Role already has a signature by key ID {}", keyid))]
+    DuplicateSignature { keyid: String },
+
     /// A duplicate role was present in the delegations metadata.
     #[snafu(display("Duplicate role name: {}", name))]
     DuplicateRoleName { name: String },
 
+    /// A target's custom metadata field could not be deserialized into the requested type.
+    #[snafu(display("Failed to parse custom field '{}': {}", key, source))]
+    CustomField {
+        key: String,
+        source: serde_json::Error,
+        backtrace: Backtrace,
+    },
+
     /// Unable to open a file
     #[snafu(display("Failed to open '{}': {}", path.display(), source))]
     FileOpen {
@@ -56,6 +68,14 @@ pub enum Error {
         backtrace: Backtrace,
     },
 
+    /// The library failed to deserialize an object from JSON.
+    #[snafu(display("Failed to deserialize {} from JSON: {}", what, source))]
+    JsonDeserialization {
+        what: String,
+        source: serde_json::Error,
+        backtrace: Backtrace,
+    },
+
     /// The library failed to serialize an object to JSON.
     #[snafu(display("Failed to serialize {} to JSON: {}", what, source))]
     JsonSerialization {
@@ -116,6 +136,14 @@ pub enum Error {
 
     #[snafu(display("Role not found: {}", name))]
     RoleNotFound { name: String },
+
+    /// A signature was added for a key ID that isn't among the given keys.
+    #[snafu(display("Key ID {} not found", keyid))]
+    KeyNotFound { keyid: String },
+
+    /// A signature that was added doesn't verify against the key that supposedly made it.
+    #[snafu(display("Signature by key ID {} does not verify", keyid))]
+    InvalidSignature { keyid: String },
 }
 
 /// Wrapper for error types that don't impl [`std::error::Error`].
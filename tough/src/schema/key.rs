@@ -4,7 +4,7 @@
 
 use crate::schema::decoded::{Decoded, EcdsaPem, Hex, RsaPem};
 use crate::schema::error::{self, Result};
-use olpc_cjson::CanonicalFormatter;
+use crate::schema::to_canonical_json;
 use ring::digest::{digest, SHA256};
 use ring::signature::VerificationAlgorithm;
 use serde::{Deserialize, Serialize};
@@ -75,6 +75,8 @@ pub enum Key {
 pub enum RsaScheme {
     /// `rsassa-pss-sha256`: RSA Probabilistic signature scheme with appendix.
     RsassaPssSha256,
+    /// `rsa-pkcs1v15-sha256`: RSA signature scheme with PKCS#1 v1.5 padding.
+    RsaPkcs1v15Sha256,
 }
 
 /// Represents a deserialized (decoded) RSA public key.
@@ -130,15 +132,26 @@ pub struct EcdsaKey {
 impl Key {
     /// Calculate the key ID for this key.
     pub fn key_id(&self) -> Result<Decoded<Hex>> {
-        let mut buf = Vec::new();
-        let mut ser = serde_json::Serializer::with_formatter(&mut buf, CanonicalFormatter::new());
-        self.serialize(&mut ser).context(error::JsonSerialization {
+        let buf = to_canonical_json(self).context(error::JsonSerialization {
             what: "key".to_owned(),
         })?;
         Ok(digest(&SHA256, &buf).as_ref().to_vec().into())
     }
 
-    /// Verify a signature of an object made with this key.
+    /// Returns whether `other` has the same public key as this key, whatever signature scheme
+    /// each of them declares.
+    pub(crate) fn same_public_key(&self, other: &Key) -> bool {
+        match (self, other) {
+            (Key::Rsa { keyval: a, .. }, Key::Rsa { keyval: b, .. }) => a.public == b.public,
+            (Key::Ed25519 { keyval: a, .. }, Key::Ed25519 { keyval: b, .. }) => {
+                a.public == b.public
+            }
+            (Key::Ecdsa { keyval: a, .. }, Key::Ecdsa { keyval: b, .. }) => a.public == b.public,
+            _ => false,
+        }
+    }
+
+    /// Verify a signature of an object made with this key, using the key's signature scheme.
     pub(super) fn verify(&self, msg: &[u8], signature: &[u8]) -> bool {
         let (alg, public_key): (&dyn VerificationAlgorithm, untrusted::Input<'_>) = match self {
             Key::Ecdsa {
@@ -165,6 +178,14 @@ impl Key {
                 &ring::signature::RSA_PSS_2048_8192_SHA256,
                 untrusted::Input::from(&keyval.public),
             ),
+            Key::Rsa {
+                scheme: RsaScheme::RsaPkcs1v15Sha256,
+                keyval,
+                ..
+            } => (
+                &ring::signature::RSA_PKCS1_2048_8192_SHA256,
+                untrusted::Input::from(&keyval.public),
+            ),
         };
 
         alg.verify(
@@ -20,11 +20,12 @@ use chrono::{DateTime, Utc};
 use globset::Glob;
 use olpc_cjson::CanonicalFormatter;
 use ring::digest::{digest, Context, SHA256};
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use serde_plain::{derive_display_from_serialize, derive_fromstr_from_deserialize};
 use snafu::ResultExt;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fs::File;
 use std::io::Read;
 use std::num::NonZeroU64;
@@ -86,14 +87,29 @@ pub trait Role: Serialize {
     /// A deterministic JSON serialization used when calculating the digest of a metadata object.
     /// [More info on canonical JSON](http://wiki.laptop.org/go/Canonical_JSON)
     fn canonical_form(&self) -> Result<Vec<u8>> {
-        let mut data = Vec::new();
-        let mut ser = serde_json::Serializer::with_formatter(&mut data, CanonicalFormatter::new());
-        self.serialize(&mut ser)
-            .context(error::JsonSerialization { what: "role" })?;
-        Ok(data)
+        to_canonical_json(self).context(error::JsonSerialization { what: "role" })
     }
 }
 
+/// Serializes `value` to [canonical JSON](http://wiki.laptop.org/go/Canonical_JSON), the form over
+/// which TUF signatures and key IDs are calculated.
+///
+/// Object keys are sorted, and values that have no canonical form (floating point numbers and
+/// object keys that are not strings) are rejected. The output is byte-for-byte what this library
+/// signs and verifies, so it can be used to prepare a signable payload outside of the library.
+pub fn canonical_json<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>> {
+    to_canonical_json(value).context(error::JsonSerialization { what: "value" })
+}
+
+/// Serializes `value` to canonical JSON, returning the `serde_json` error so that callers can add
+/// their own context.
+pub(crate) fn to_canonical_json<T: Serialize + ?Sized>(value: &T) -> serde_json::Result<Vec<u8>> {
+    let mut data = Vec::new();
+    let mut ser = serde_json::Serializer::with_formatter(&mut data, CanonicalFormatter::new());
+    value.serialize(&mut ser)?;
+    Ok(data)
+}
+
 /// A signed metadata object.
 #[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
 pub struct Signed<T> {
@@ -103,6 +119,60 @@ pub struct Signed<T> {
     pub signatures: Vec<Signature>,
 }
 
+impl<T> Signed<T> {
+    /// Create a new `Signed` object from a role and its signatures.
+    pub fn new(signed: T, signatures: Vec<Signature>) -> Self {
+        Self { signed, signatures }
+    }
+}
+
+/// A [`Signed`] object along with the metadata file that it was parsed from.
+///
+/// Serializing a parsed `Signed` object with `serde` may not reproduce the file, e.g. if the
+/// `signed` object was written with values that parse to the same thing, so republish metadata
+/// from [`RawSigned::to_signed_bytes`] to keep its signatures valid. The files of metadata loaded
+/// by a [`Repository`](crate::Repository) are returned by
+/// [`Repository::raw_metadata`](crate::Repository::raw_metadata) and
+/// [`Repository::delegated_raw_metadata`](crate::Repository::delegated_raw_metadata).
+#[derive(Debug, Clone, PartialEq)]
+pub struct RawSigned<T> {
+    signed: Signed<T>,
+    raw: Vec<u8>,
+}
+
+impl<T> RawSigned<T> {
+    /// Parses a metadata file, keeping its bytes so that they can be returned by
+    /// [`RawSigned::to_signed_bytes`]. Signatures aren't verified.
+    pub fn from_bytes(data: &[u8]) -> Result<Self>
+    where
+        T: DeserializeOwned,
+    {
+        let signed = serde_json::from_slice(data).context(error::JsonDeserialization {
+            what: "signed metadata",
+        })?;
+        Ok(Self {
+            signed,
+            raw: data.to_vec(),
+        })
+    }
+
+    /// Returns the parsed metadata.
+    pub fn signed(&self) -> &Signed<T> {
+        &self.signed
+    }
+
+    /// Returns the parsed metadata, discarding the file it was parsed from.
+    pub fn into_signed(self) -> Signed<T> {
+        self.signed
+    }
+
+    /// Returns the bytes of the metadata file that this was parsed from, which its signatures are
+    /// verified against.
+    pub fn to_signed_bytes(&self) -> &[u8] {
+        &self.raw
+    }
+}
+
 /// A signature and the key ID that made it.
 #[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
 pub struct Signature {
@@ -205,10 +275,10 @@ impl Root {
     }
 
     /// Given an object/key that impls Sign, return the corresponding
-    /// key ID from Root
+    /// key ID from Root. The key may be listed with any signature scheme.
     pub fn key_id(&self, key_pair: &dyn Sign) -> Option<Decoded<Hex>> {
         for (key_id, key) in &self.keys {
-            if key_pair.tuf_key() == *key {
+            if key_pair.tuf_key().same_public_key(key) {
                 return Some(key_id.clone());
             }
         }
@@ -316,19 +386,40 @@ pub struct SnapshotMeta {
     pub _extra: HashMap<String, Value>,
 }
 
-/// Represents the hash dictionary in a `snapshot.json` file.
-#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+/// Represents a hash dictionary, which lists the digests of a file keyed by the name of the
+/// cryptographic hash function that calculated them, e.g. `{ "sha256": HASH, ... }`.
+///
+/// Digests by hash functions that this library doesn't recognize are kept, so that metadata is
+/// unchanged when it's serialized again, but they're not checked when the file is verified.
+#[derive(Debug, Clone, Default, Deserialize, Serialize, PartialEq)]
+#[serde(transparent)]
 pub struct Hashes {
-    /// The SHA 256 digest of a metadata file.
-    pub sha256: Decoded<Hex>,
+    /// The digests of the file, keyed by the name of the hash function.
+    pub digests: BTreeMap<String, Decoded<Hex>>,
+}
 
-    /// Extra arguments found during deserialization.
-    ///
-    /// We must store these to correctly verify signatures for this object.
-    ///
-    /// If you're instantiating this struct, you should make this `HashMap::empty()`.
-    #[serde(flatten)]
-    pub _extra: HashMap<String, Value>,
+impl Hashes {
+    /// Creates a hash dictionary with only a SHA-256 digest.
+    pub fn from_sha256(sha256: Decoded<Hex>) -> Self {
+        let mut digests = BTreeMap::new();
+        digests.insert("sha256".to_owned(), sha256);
+        Hashes { digests }
+    }
+
+    /// Returns `true` if there are no digests.
+    pub fn is_empty(&self) -> bool {
+        self.digests.is_empty()
+    }
+
+    /// Returns the SHA-256 digest, if there is one.
+    pub fn sha256(&self) -> Option<&Decoded<Hex>> {
+        self.digests.get("sha256")
+    }
+
+    /// Returns the SHA-512 digest, if there is one.
+    pub fn sha512(&self) -> Option<&Decoded<Hex>> {
+        self.digests.get("sha512")
+    }
 }
 
 impl Snapshot {
@@ -342,6 +433,13 @@ impl Snapshot {
             _extra: HashMap::new(),
         }
     }
+
+    /// Returns the meta entry for the metadata file `file` (e.g. `targets.json`). Entries are
+    /// expected to be keyed by filename, but some producers leave off the `.json` suffix, so the
+    /// unsuffixed key is tried if the suffixed one isn't present.
+    pub fn meta_entry(&self, file: &str) -> Option<&SnapshotMeta> {
+        meta_entry(&self.meta, file)
+    }
 }
 impl Role for Snapshot {
     const TYPE: RoleType = RoleType::Snapshot;
@@ -429,6 +527,12 @@ pub struct Target {
     /// HASHES is a dictionary that specifies one or more hashes, including the cryptographic hash
     /// function. For example: `{ "sha256": HASH, ... }`. HASH is the hexdigest of the cryptographic
     /// function computed on the target file.
+    ///
+    /// The specification requires at least one hash, but a target with empty `hashes` is accepted,
+    /// so that the rest of the repository can be used; see
+    /// [`RepositoryLoader::require_target_hashes`](crate::RepositoryLoader::require_target_hashes).
+    /// A target without `hashes` is parsed with none, but it's always serialized with `hashes`.
+    #[serde(default)]
     pub hashes: Hashes,
 
     /// If defined, the elements and values of "custom" will be made available to the client
@@ -478,14 +582,21 @@ impl Target {
 
         Ok(Target {
             length,
-            hashes: Hashes {
-                sha256: Decoded::from(digest.finish().as_ref().to_vec()),
-                _extra: HashMap::new(),
-            },
+            hashes: Hashes::from_sha256(Decoded::from(digest.finish().as_ref().to_vec())),
             custom: HashMap::new(),
             _extra: HashMap::new(),
         })
     }
+
+    /// Deserializes the named field of `custom` into `T`.
+    ///
+    /// Returns `None` if the field is not present, and `Some(Err(..))` if the field is present but
+    /// cannot be deserialized into `T`.
+    pub fn custom_field<T: DeserializeOwned>(&self, key: &str) -> Option<Result<T>> {
+        self.custom
+            .get(key)
+            .map(|value| serde_json::from_value(value.clone()).context(error::CustomField { key }))
+    }
 }
 
 impl Targets {
@@ -520,25 +631,61 @@ impl Targets {
         })
     }
 
-    /// Returns a hashmap of all targets and all delegated targets recursively
-    pub fn targets_map(&self) -> HashMap<String, &Target> {
-        let mut targets_map = HashMap::new();
-        for target in &self.targets {
-            targets_map.insert(target.0.clone(), target.1);
+    /// Returns the delegated role that lists `target_name`, searching the same way as
+    /// [`Targets::find_target`]. Returns `None` if `target_name` is listed directly by this role,
+    /// or is not listed at all.
+    pub fn find_target_role(&self, target_name: &str) -> Option<&DelegatedRole> {
+        if self.targets.contains_key(target_name) {
+            return None;
+        }
+        self.find_delegated_target_role(target_name)
+    }
+
+    /// Searches only the roles delegated by this role for the one that lists `target_name`.
+    fn find_delegated_target_role(&self, target_name: &str) -> Option<&DelegatedRole> {
+        for role in &self.delegations.as_ref()?.roles {
+            if let Some(targets) = &role.targets {
+                if targets.signed.targets.contains_key(target_name) {
+                    return Some(role);
+                }
+                if let Some(role) = targets.signed.find_delegated_target_role(target_name) {
+                    return Some(role);
+                }
+            }
+        }
+        None
+    }
+
+    /// Returns every target listed by this role and, recursively, by the roles it delegates to
+    /// that have been loaded, sorted by name. This is the merged view that target lookups use: if
+    /// more than one role lists a name, the target is the one [`Targets::find_target`] returns,
+    /// i.e. this role's own target, or else the first delegated role's in preorder.
+    ///
+    /// The `targets` field, by contrast, only holds the targets listed by this role itself.
+    pub fn targets_map(&self) -> BTreeMap<String, &Target> {
+        let mut targets_map = BTreeMap::new();
+        self.collect_targets(&mut targets_map);
+        targets_map
+    }
+
+    /// Adds this role's targets, then those of its delegated roles in preorder, to `targets_map`,
+    /// keeping the first target found for each name.
+    fn collect_targets<'a>(&'a self, targets_map: &mut BTreeMap<String, &'a Target>) {
+        for (name, target) in &self.targets {
+            targets_map.entry(name.clone()).or_insert(target);
         }
         if let Some(delegations) = &self.delegations {
             for role in &delegations.roles {
                 if let Some(targets) = &role.targets {
-                    targets_map.extend(targets.signed.targets_map());
+                    targets.signed.collect_targets(targets_map);
                 }
             }
         }
-
-        targets_map
     }
 
-    /// Returns an iterator of all targets delegated
-    pub fn targets_iter(&self) -> impl Iterator + '_ {
+    /// Returns an iterator over the same names and targets as [`Targets::targets_map`], sorted by
+    /// name.
+    pub fn targets_iter(&self) -> impl Iterator<Item = (String, &Target)> + '_ {
         self.targets_map().into_iter()
     }
 
@@ -645,6 +792,25 @@ impl Targets {
         roles
     }
 
+    /// Returns every role delegated by this role, directly or indirectly, in preorder: each role is
+    /// followed by the roles it delegates to, before its next sibling.
+    pub fn delegated_roles(&self) -> impl Iterator<Item = &DelegatedRole> + '_ {
+        let mut roles = Vec::new();
+        self.collect_delegated_roles(&mut roles);
+        roles.into_iter()
+    }
+
+    fn collect_delegated_roles<'a>(&'a self, roles: &mut Vec<&'a DelegatedRole>) {
+        if let Some(delegations) = &self.delegations {
+            for role in &delegations.roles {
+                roles.push(role);
+                if let Some(targets) = &role.targets {
+                    targets.signed.collect_delegated_roles(roles);
+                }
+            }
+        }
+    }
+
     /// Returns a reference to the parent delegation of `name`
     pub fn parent_of(&self, name: &str) -> Result<&Delegations> {
         if let Some(delegations) = &self.delegations {
@@ -778,10 +944,7 @@ impl Signed<DelegatedTargets> {
     pub fn targets(self) -> (String, Signed<Targets>) {
         (
             self.signed.name,
-            Signed {
-                signed: self.signed.targets,
-                signatures: self.signatures,
-            },
+            Signed::new(self.signed.targets, self.signatures),
         )
     }
 }
@@ -789,13 +952,13 @@ impl Signed<DelegatedTargets> {
 impl Signed<Targets> {
     /// Use a string and a `Signed<Targets>` to create a `Signed<DelegatedTargets>`
     pub fn delegated_targets(self, name: &str) -> Signed<DelegatedTargets> {
-        Signed {
-            signed: DelegatedTargets {
+        Signed::new(
+            DelegatedTargets {
                 name: name.to_string(),
                 targets: self.signed,
             },
-            signatures: self.signatures,
-        }
+            self.signatures,
+        )
     }
 }
 
@@ -881,7 +1044,7 @@ pub enum PathSet {
 
 impl PathSet {
     /// Given a target string determines if paths match
-    fn matched_target(&self, target: &str) -> bool {
+    pub(crate) fn matched_target(&self, target: &str) -> bool {
         match self {
             Self::Paths(paths) => {
                 for path in paths {
@@ -902,11 +1065,11 @@ impl PathSet {
         false
     }
 
-    /// Given a path hash prefix and a target path determines if target is delegated by prefix
+    /// Given a path hash prefix and a target path determines if target is delegated by prefix,
+    /// i.e. if the hex SHA-256 digest of the target path starts with the prefix
     fn matched_prefix(prefix: &str, target: &str) -> bool {
-        let temp_target = target.to_string();
-        let hash = digest(&SHA256, temp_target.as_bytes());
-        hash.as_ref().starts_with(prefix.as_bytes())
+        let hash = digest(&SHA256, target.as_bytes());
+        hex::encode(hash).starts_with(&prefix.to_ascii_lowercase())
     }
 
     /// Given a shell style wildcard path determines if target matches the path
@@ -936,6 +1099,22 @@ impl Delegations {
         }
     }
 
+    /// Returns the roles that may provide `target`, in the order they're consulted. A role is
+    /// included if its paths match the target (see [`DelegatedRole::matches_path`]); the roles
+    /// after a matching terminating role are never consulted, so they're not included.
+    pub fn roles_for_target(&self, target: &str) -> Vec<&DelegatedRole> {
+        let mut roles = Vec::new();
+        for role in &self.roles {
+            if role.matches_path(target) {
+                roles.push(role);
+                if role.terminating {
+                    break;
+                }
+            }
+        }
+        roles
+    }
+
     /// Determines if target passes pathset specific matching
     pub fn target_is_delegated(&self, target: &str) -> bool {
         for role in &self.roles {
@@ -964,10 +1143,10 @@ impl Delegations {
     }
 
     /// Given an object/key that impls Sign, return the corresponding
-    /// key ID from Delegation
+    /// key ID from Delegation. The key may be listed with any signature scheme.
     pub fn key_id(&self, key_pair: &dyn Sign) -> Option<Decoded<Hex>> {
         for (key_id, key) in &self.keys {
-            if key_pair.tuf_key() == *key {
+            if key_pair.tuf_key().same_public_key(key) {
                 return Some(key_id.clone());
             }
         }
@@ -976,6 +1155,13 @@ impl Delegations {
 }
 
 impl DelegatedRole {
+    /// Returns `true` if this role's paths match `target`. For [`PathSet::Paths`], a pattern may
+    /// use `*` to match any sequence of characters and `?` to match any single character; for
+    /// [`PathSet::PathHashPrefixes`], the hex SHA-256 digest of `target` must start with a prefix.
+    pub fn matches_path(&self, target: &str) -> bool {
+        self.paths.matched_target(target)
+    }
+
     /// Returns a `RoleKeys` representation of the role
     pub fn keys(&self) -> RoleKeys {
         RoleKeys {
@@ -1040,8 +1226,11 @@ pub struct Timestamp {
 /// file, this MUST only include a description of the snapshot.json file.
 #[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
 pub struct TimestampMeta {
-    /// The integer length in bytes of the snapshot.json file.
-    pub length: u64,
+    /// The integer length in bytes of the snapshot.json file. It is OPTIONAL and can be omitted
+    /// to reduce the timestamp metadata file size. In that case the client MUST use a custom
+    /// download limit for the snapshot metadata.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub length: Option<u64>,
 
     /// The hashes of the snapshot.json file.
     pub hashes: Hashes,
@@ -1070,6 +1259,20 @@ impl Timestamp {
             _extra: HashMap::new(),
         }
     }
+
+    /// Returns the meta entry for the metadata file `file` (e.g. `snapshot.json`), falling back to
+    /// the key without the `.json` suffix if the suffixed key isn't present.
+    pub fn meta_entry(&self, file: &str) -> Option<&TimestampMeta> {
+        meta_entry(&self.meta, file)
+    }
+}
+
+/// Looks up `file` in a METAFILES map, trying the spec's `NAME.json` form first and then `NAME`.
+fn meta_entry<'a, T>(meta: &'a HashMap<String, T>, file: &str) -> Option<&'a T> {
+    meta.get(file).or_else(|| {
+        file.strip_suffix(".json")
+            .and_then(|unsuffixed| meta.get(unsuffixed))
+    })
 }
 
 impl Role for Timestamp {
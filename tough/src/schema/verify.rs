@@ -1,57 +1,220 @@
+use super::decoded::{Decoded, Hex};
 use super::error::{self, Result};
-use super::{Delegations, Role, RoleType, Root, Signed, Targets};
-use olpc_cjson::CanonicalFormatter;
+use super::key::Key;
+use super::{to_canonical_json, Delegations, Role, RoleType, Root, Signature, Signed, Targets};
 use serde::Serialize;
 use snafu::{ensure, OptionExt, ResultExt};
-use std::collections::HashSet;
+use std::collections::HashMap;
+use std::num::NonZeroU64;
+
+impl<T: Serialize> Signed<T> {
+    /// Adds a signature of the canonical JSON form of `self.signed` that was made elsewhere, e.g.
+    /// by another holder of one of the role's keys. `keys` is where the key with ID `key_id` is
+    /// looked up: the `keys` of the root metadata for a top-level role, or of the parent's
+    /// delegations for a delegated role.
+    ///
+    /// The signature must verify against that key, and the role must not already have a signature
+    /// by it. This doesn't check that the key is trusted for the role, or whether the role's
+    /// threshold is met; use [`Root::verify_role`] or [`Delegations::verify_role`] for that once
+    /// all signatures have been added.
+    pub fn add_signature(
+        &mut self,
+        keys: &HashMap<Decoded<Hex>, Key>,
+        key_id: Decoded<Hex>,
+        sig: Vec<u8>,
+    ) -> Result<()> {
+        ensure!(
+            !self
+                .signatures
+                .iter()
+                .any(|signature| signature.keyid == key_id),
+            error::DuplicateSignature {
+                keyid: hex::encode(&key_id),
+            }
+        );
+        let key = keys.get(&key_id).context(error::KeyNotFound {
+            keyid: hex::encode(&key_id),
+        })?;
+        let data = to_canonical_json(&self.signed).context(error::JsonSerialization {
+            what: "signed role",
+        })?;
+        ensure!(
+            key.verify(&data, &sig),
+            error::InvalidSignature {
+                keyid: hex::encode(&key_id),
+            }
+        );
+        self.signatures.push(Signature {
+            keyid: key_id,
+            sig: sig.into(),
+        });
+        Ok(())
+    }
+}
 
 impl Root {
     /// Checks that the given metadata role is valid based on a threshold of key signatures.
+    ///
+    /// The role is valid if at least the role's threshold of distinct keys that this root trusts
+    /// for the role (`T::TYPE`) made valid signatures of its canonical JSON form. Signatures by
+    /// other keys, including keys trusted for other roles, don't count toward the threshold.
+    ///
+    /// This is the check that [`RepositoryLoader`](crate::RepositoryLoader) makes for each
+    /// top-level role, and it can be used on its own to verify detached metadata against a trusted
+    /// root. It doesn't check the role's expiration or version; delegated targets are verified
+    /// with [`Delegations::verify_role`] instead.
     pub fn verify_role<T: Role + Serialize>(&self, role: &Signed<T>) -> Result<()> {
+        self.verify_role_canonical(role, None)
+    }
+
+    /// Like [`Root::verify_role`], but checks the signatures against `canonical` if it is given.
+    /// This is the canonical form of the `signed` object that was received, for when it doesn't
+    /// match the canonical form of the parsed role.
+    pub(crate) fn verify_role_canonical<T: Role + Serialize>(
+        &self,
+        role: &Signed<T>,
+        canonical: Option<&[u8]>,
+    ) -> Result<()> {
+        let (valid, threshold) = self.count_valid_signatures_canonical(role, canonical)?;
+        ensure!(
+            valid >= u64::from(threshold),
+            error::SignatureThreshold {
+                role: T::TYPE,
+                threshold,
+                valid,
+            }
+        );
+        Ok(())
+    }
+
+    /// Returns the number of valid signatures on the given metadata role, by distinct keys that are
+    /// trusted for the role, along with the role's threshold.
+    pub(crate) fn count_valid_signatures<T: Role + Serialize>(
+        &self,
+        role: &Signed<T>,
+    ) -> Result<(u64, NonZeroU64)> {
+        self.count_valid_signatures_canonical(role, None)
+    }
+
+    fn count_valid_signatures_canonical<T: Role + Serialize>(
+        &self,
+        role: &Signed<T>,
+        canonical: Option<&[u8]>,
+    ) -> Result<(u64, NonZeroU64)> {
+        let (keyids, threshold) = self.verifying_key_ids_canonical(role, canonical)?;
+        Ok((keyids.len() as u64, threshold))
+    }
+
+    /// Returns the IDs of the keys that are trusted for the given metadata role and made valid
+    /// signatures on it, in the order of the signatures. Each key is listed once, however many
+    /// signatures it made. This doesn't check whether the role's threshold is met; use
+    /// [`Root::verify_role`] for that.
+    pub fn verifying_key_ids<T: Role + Serialize>(
+        &self,
+        role: &Signed<T>,
+    ) -> Result<Vec<Decoded<Hex>>> {
+        Ok(self.verifying_key_ids_canonical(role, None)?.0)
+    }
+
+    /// Like [`Root::verifying_key_ids`], but checks the signatures against `canonical` if it is
+    /// given, as with [`Root::verify_role_canonical`]. Also returns the role's threshold.
+    pub(crate) fn verifying_key_ids_canonical<T: Role + Serialize>(
+        &self,
+        role: &Signed<T>,
+        canonical: Option<&[u8]>,
+    ) -> Result<(Vec<Decoded<Hex>>, NonZeroU64)> {
         let role_keys = self
             .roles
             .get(&T::TYPE)
             .context(error::MissingRole { role: T::TYPE })?;
-        let mut valid = 0;
 
-        let mut data = Vec::new();
-        let mut ser = serde_json::Serializer::with_formatter(&mut data, CanonicalFormatter::new());
-        role.signed
-            .serialize(&mut ser)
-            .context(error::JsonSerialization {
+        let data = match canonical {
+            Some(data) => data.to_vec(),
+            None => to_canonical_json(&role.signed).context(error::JsonSerialization {
                 what: format!("{} role", T::TYPE),
-            })?;
+            })?,
+        };
 
-        let mut valid_keyids = HashSet::new();
+        let mut valid_keyids = Vec::new();
 
         for signature in &role.signatures {
             if role_keys.keyids.contains(&signature.keyid) {
                 if let Some(key) = self.keys.get(&signature.keyid) {
-                    if key.verify(&data, &signature.sig) {
-                        // Ignore duplicate keyids.
-                        if valid_keyids.insert(&signature.keyid) {
-                            valid += 1;
-                        }
+                    // Ignore duplicate keyids.
+                    if key.verify(&data, &signature.sig) && !valid_keyids.contains(&signature.keyid)
+                    {
+                        valid_keyids.push(signature.keyid.clone());
                     }
                 }
             }
         }
 
+        Ok((valid_keyids, role_keys.threshold))
+    }
+}
+
+impl Delegations {
+    /// Verifies that roles matches contain valid keys
+    pub fn verify_role(&self, role: &Signed<Targets>, name: &str) -> Result<()> {
+        self.verify_role_canonical(role, name, None)
+    }
+
+    /// Like [`Delegations::verify_role`], but checks the signatures against `canonical` if it is
+    /// given, as with [`Root::verify_role_canonical`].
+    pub(crate) fn verify_role_canonical(
+        &self,
+        role: &Signed<Targets>,
+        name: &str,
+        canonical: Option<&[u8]>,
+    ) -> Result<()> {
+        let (valid, threshold) = self.count_valid_signatures_canonical(role, name, canonical)?;
         ensure!(
-            valid >= u64::from(role_keys.threshold),
+            valid >= u64::from(threshold),
             error::SignatureThreshold {
-                role: T::TYPE,
-                threshold: role_keys.threshold,
+                role: RoleType::Targets,
+                threshold,
                 valid,
             }
         );
         Ok(())
     }
-}
 
-impl Delegations {
-    /// Verifies that roles matches contain valid keys
-    pub fn verify_role(&self, role: &Signed<Targets>, name: &str) -> Result<()> {
+    /// Returns the number of valid signatures on the named delegated role, by keys that are trusted
+    /// for the role, along with the role's threshold.
+    pub(crate) fn count_valid_signatures(
+        &self,
+        role: &Signed<Targets>,
+        name: &str,
+    ) -> Result<(u64, NonZeroU64)> {
+        self.count_valid_signatures_canonical(role, name, None)
+    }
+
+    fn count_valid_signatures_canonical(
+        &self,
+        role: &Signed<Targets>,
+        name: &str,
+        canonical: Option<&[u8]>,
+    ) -> Result<(u64, NonZeroU64)> {
+        let (keyids, threshold) = self.verifying_key_ids_canonical(role, name, canonical)?;
+        Ok((keyids.len() as u64, threshold))
+    }
+
+    /// Returns the IDs of the keys that are trusted for the named delegated role and made valid
+    /// signatures on it, as with [`Root::verifying_key_ids`].
+    pub fn verifying_key_ids(
+        &self,
+        role: &Signed<Targets>,
+        name: &str,
+    ) -> Result<Vec<Decoded<Hex>>> {
+        Ok(self.verifying_key_ids_canonical(role, name, None)?.0)
+    }
+
+    fn verifying_key_ids_canonical(
+        &self,
+        role: &Signed<Targets>,
+        name: &str,
+        canonical: Option<&[u8]>,
+    ) -> Result<(Vec<Decoded<Hex>>, NonZeroU64)> {
         let role_keys =
             self.roles
                 .iter()
@@ -59,41 +222,35 @@ impl Delegations {
                 .ok_or(error::Error::RoleNotFound {
                     name: name.to_string(),
                 })?;
-        let mut valid = 0;
 
         // serialize the role to verify the key ID by using the JSON representation
-        let mut data = Vec::new();
-        let mut ser = serde_json::Serializer::with_formatter(&mut data, CanonicalFormatter::new());
-        role.signed
-            .serialize(&mut ser)
-            .context(error::JsonSerialization {
+        let data = match canonical {
+            Some(data) => data.to_vec(),
+            None => to_canonical_json(&role.signed).context(error::JsonSerialization {
                 what: format!("{} role", name.to_string()),
-            })?;
+            })?,
+        };
+        let mut valid_keyids = Vec::new();
         for signature in &role.signatures {
             if role_keys.keyids.contains(&signature.keyid) {
                 if let Some(key) = self.keys.get(&signature.keyid) {
-                    if key.verify(&data, &signature.sig) {
-                        valid += 1;
+                    // Ignore duplicate keyids.
+                    if key.verify(&data, &signature.sig) && !valid_keyids.contains(&signature.keyid)
+                    {
+                        valid_keyids.push(signature.keyid.clone());
                     }
                 }
             }
         }
 
-        ensure!(
-            valid >= u64::from(role_keys.threshold),
-            error::SignatureThreshold {
-                role: RoleType::Targets,
-                threshold: role_keys.threshold,
-                valid,
-            }
-        );
-        Ok(())
+        Ok((valid_keyids, role_keys.threshold))
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::{Root, Signed};
+    use std::collections::HashMap;
 
     #[test]
     fn simple_rsa() {
@@ -159,6 +316,43 @@ mod tests {
             .expect_err("expired root signature should not verify");
     }
 
+    #[test]
+    fn add_signature() {
+        let mut root: Signed<Root> =
+            serde_json::from_str(include_str!("../../tests/data/simple-rsa/root.json")).unwrap();
+        let keys = root.signed.keys.clone();
+        let signature = root.signatures.remove(0);
+        root.signed
+            .verify_role(&root)
+            .expect_err("unsigned root should not verify");
+
+        root.add_signature(&keys, signature.keyid.clone(), b"bogus".to_vec())
+            .expect_err("invalid signature should not be added");
+        root.add_signature(
+            &HashMap::new(),
+            signature.keyid.clone(),
+            signature.sig.to_vec(),
+        )
+        .expect_err("signature by unknown key should not be added");
+        root.add_signature(&keys, signature.keyid.clone(), signature.sig.to_vec())
+            .unwrap();
+        root.signed.verify_role(&root).unwrap();
+        root.add_signature(&keys, signature.keyid, signature.sig.to_vec())
+            .expect_err("second signature by the same key should not be added");
+        assert_eq!(root.signatures.len(), 1);
+    }
+
+    #[test]
+    fn verifying_key_ids_are_distinct() {
+        let root: Signed<Root> = serde_json::from_str(include_str!(
+            "../../tests/data/duplicate-sig-keys/root.json"
+        ))
+        .expect("should be parsable root.json");
+        let keyids = root.signed.verifying_key_ids(&root).unwrap();
+        assert_eq!(keyids.len(), 1);
+        assert_eq!(keyids[0], root.signatures[0].keyid);
+    }
+
     #[test]
     fn duplicate_sig_keys_is_err() {
         // This metadata is signed with the non-deterministic rsassa-pss signing scheme to
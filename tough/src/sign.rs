@@ -4,13 +4,15 @@
 //! Provides the `Sign` trait which abstracts over the method of signing with different key types.
 
 use crate::error::{self, Result};
+use crate::key_source::KeySource;
 use crate::schema::key::Key;
+use crate::schema::{KeyHolder, Signature};
 use crate::sign::SignKeyPair::ECDSA;
 use crate::sign::SignKeyPair::ED25519;
 use crate::sign::SignKeyPair::RSA;
-use ring::rand::SecureRandom;
+use ring::rand::{SecureRandom, SystemRandom};
 use ring::signature::{EcdsaKeyPair, Ed25519KeyPair, KeyPair, RsaKeyPair};
-use snafu::ResultExt;
+use snafu::{OptionExt, ResultExt};
 use std::collections::HashMap;
 
 /// This trait must be implemented for each type of key with which you will
@@ -25,6 +27,20 @@ pub trait Sign: Sync + Send {
         msg: &[u8],
         rng: &dyn SecureRandom,
     ) -> std::result::Result<Vec<u8>, Box<dyn std::error::Error + Send + Sync + 'static>>;
+
+    /// Signs the supplied message with the signature scheme declared on `key`, which is this key
+    /// as it's listed in metadata.
+    ///
+    /// The default implementation ignores the declared scheme and calls [`Sign::sign`], which is
+    /// right for keys that only support one scheme.
+    fn sign_with_scheme(
+        &self,
+        msg: &[u8],
+        _key: &Key,
+        rng: &dyn SecureRandom,
+    ) -> std::result::Result<Vec<u8>, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        self.sign(msg, rng)
+    }
 }
 
 /// Implements the Sign trait for ED25519
@@ -77,6 +93,27 @@ impl Sign for RsaKeyPair {
             .context(error::Sign)?;
         Ok(signature)
     }
+
+    fn sign_with_scheme(
+        &self,
+        msg: &[u8],
+        key: &Key,
+        rng: &dyn SecureRandom,
+    ) -> std::result::Result<Vec<u8>, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        use crate::schema::key::RsaScheme;
+
+        let padding: &'static dyn ring::signature::RsaEncoding = match key {
+            Key::Rsa {
+                scheme: RsaScheme::RsaPkcs1v15Sha256,
+                ..
+            } => &ring::signature::RSA_PKCS1_SHA256,
+            _ => &ring::signature::RSA_PSS_SHA256,
+        };
+        let mut signature = vec![0; self.public_modulus_len()];
+        self.sign(padding, rng, msg, &mut signature)
+            .context(error::Sign)?;
+        Ok(signature)
+    }
 }
 
 /// Implements the Sign trait for ECDSA keypairs
@@ -136,6 +173,19 @@ impl Sign for SignKeyPair {
             ECDSA(key) => (key as &dyn Sign).sign(msg, rng),
         }
     }
+
+    fn sign_with_scheme(
+        &self,
+        msg: &[u8],
+        key: &Key,
+        rng: &dyn SecureRandom,
+    ) -> std::result::Result<Vec<u8>, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        match self {
+            RSA(key_pair) => (key_pair as &dyn Sign).sign_with_scheme(msg, key, rng),
+            ED25519(key_pair) => (key_pair as &dyn Sign).sign_with_scheme(msg, key, rng),
+            ECDSA(key_pair) => (key_pair as &dyn Sign).sign_with_scheme(msg, key, rng),
+        }
+    }
 }
 
 /// Parses a supplied keypair and if it is recognized, returns an object that
@@ -166,3 +216,32 @@ pub fn parse_keypair(key: &[u8]) -> Result<impl Sign> {
         error::KeyUnrecognized.fail()
     }
 }
+
+/// Signs `canonical` with the key from `key_source`, returning the signature along with the ID of
+/// the key that made it.
+///
+/// `canonical` should be the canonical JSON form of a role, e.g. from
+/// [`canonical_json`](crate::schema::canonical_json). The key must be listed by `key_holder`, the
+/// root or delegations that will verify the role: the signature has the ID that the key is listed
+/// with, and uses the signature scheme it's listed with. Unlike
+/// [`SignedRole::new`](crate::editor::signed::SignedRole::new), this doesn't check that the key is
+/// trusted for any particular role, so the signature can be added to a `signatures` list assembled
+/// elsewhere, or handed to another system.
+pub fn sign_bytes(
+    key_source: &dyn KeySource,
+    key_holder: &KeyHolder,
+    canonical: &[u8],
+) -> Result<Signature> {
+    let key_pair = key_source.as_sign().context(error::KeyPairFromKeySource)?;
+    let keyid = key_holder
+        .key_id(key_pair.as_ref())
+        .context(error::SigningKeyNotListed)?;
+    let key = key_holder.key(&keyid).context(error::SigningKeyNotListed)?;
+    let sig = key_pair
+        .sign_with_scheme(canonical, key, &SystemRandom::new())
+        .context(error::SignMessage)?;
+    Ok(Signature {
+        keyid,
+        sig: sig.into(),
+    })
+}
@@ -0,0 +1,117 @@
+// Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Provides the client-side target cache that is set with
+//! [`RepositoryLoader::target_cache_dir`](crate::RepositoryLoader::target_cache_dir).
+//!
+//! A target is cached at its path under the cache directory, with the hex digest of its hash
+//! prepended to the file name (as with consistent snapshots), e.g.
+//! `linux/<sha256>.foo.bin`. A cached file is only used if its length and hashes match the
+//! target's metadata; anything else is treated as a miss.
+
+use crate::cache::preferred_digest;
+use crate::error::{self, Result};
+use crate::io::{DigestAdapter, MaxSizeAdapter};
+use crate::schema::Target;
+use crate::target_path::safe_target_path;
+use log::warn;
+use snafu::{OptionExt, ResultExt};
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use tempfile::NamedTempFile;
+use url::Url;
+
+/// Returns the path that the target `name` is cached at in `dir`, or `None` if the target has no
+/// hashes or its name isn't a safe path.
+pub(crate) fn cached_target_path(dir: &Path, target: &Target, name: &str) -> Option<PathBuf> {
+    let digest = preferred_digest(&target.hashes)?;
+    let path = safe_target_path(name).ok()?;
+    let file_name = path.file_name()?.to_string_lossy();
+    let cached_name = format!("{}.{}", hex::encode(digest), file_name);
+    Some(match path.parent() {
+        Some(parent) => dir.join(parent).join(cached_name),
+        None => dir.join(cached_name),
+    })
+}
+
+/// Returns a reader of the target cached at `path`, if it exists and its length and hashes match
+/// `target`. The file is read in full to check it before it is returned, and the returned reader
+/// checks it again, in case the file changes in the meantime.
+pub(crate) fn read_cached(path: &Path, target: &Target) -> Option<Box<dyn Read + Send>> {
+    if !path.is_file() {
+        return None;
+    }
+    let checked = open_cached(path, target).and_then(|mut reader| {
+        io::copy(&mut reader, &mut io::sink()).context(error::TargetRead)?;
+        open_cached(path, target)
+    });
+    match checked {
+        Ok(reader) => Some(Box::new(reader)),
+        Err(err) => {
+            warn!("ignoring cached target {}: {}", path.display(), err);
+            None
+        }
+    }
+}
+
+fn open_cached(path: &Path, target: &Target) -> Result<DigestAdapter> {
+    let file = File::open(path).context(error::FileOpen { path })?;
+    let url = Url::from_file_path(path)
+        .ok()
+        .context(error::FileUrl { path })?;
+    DigestAdapter::hashes(
+        Box::new(MaxSizeAdapter::new(
+            Box::new(file),
+            "targets.json",
+            target.length,
+        )),
+        &target.hashes,
+        url,
+    )
+}
+
+/// Passes through a target as it is downloaded, writing it to a temporary file next to `path`. The
+/// file is moved to `path` once the target has been read to the end, i.e. once the inner reader has
+/// verified it. Failing to write the cache never fails the read; the target just isn't cached.
+pub(crate) struct CachingReader {
+    reader: Box<dyn Read + Send>,
+    file: Option<NamedTempFile>,
+    path: PathBuf,
+}
+
+impl CachingReader {
+    pub(crate) fn new(reader: Box<dyn Read + Send>, path: PathBuf) -> Self {
+        let parent = path.parent().unwrap_or_else(|| Path::new("."));
+        let file = fs::create_dir_all(parent)
+            .and_then(|()| NamedTempFile::new_in(parent))
+            .map_err(|err| warn!("not caching target {}: {}", path.display(), err))
+            .ok();
+        Self { reader, file, path }
+    }
+}
+
+impl Read for CachingReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let size = match self.reader.read(buf) {
+            Ok(size) => size,
+            Err(err) => {
+                self.file = None;
+                return Err(err);
+            }
+        };
+        if size == 0 {
+            if let Some(file) = self.file.take() {
+                if let Err(err) = file.persist(&self.path) {
+                    warn!("not caching target {}: {}", self.path.display(), err);
+                }
+            }
+        } else if let Some(file) = &mut self.file {
+            if let Err(err) = file.write_all(&buf[..size]) {
+                warn!("not caching target {}: {}", self.path.display(), err);
+                self.file = None;
+            }
+        }
+        Ok(size)
+    }
+}
@@ -0,0 +1,197 @@
+//! Provides [`TargetName`], a validated target name, and a way to turn target names into
+//! filesystem paths without escaping the directory that targets are written to.
+
+use crate::error::{self, Error, Result};
+use snafu::ensure;
+use std::convert::TryFrom;
+use std::fmt::{self, Display, Formatter};
+use std::path::{Component, Path, PathBuf};
+
+/// The name of a target, which is known to be a safe relative path, as checked by
+/// [`safe_target_path`]: it isn't empty, absolute, or prefixed by a root or a Windows drive, and
+/// it has no `..` components.
+///
+/// Methods that look up a target by name, such as
+/// [`Repository::read_target`](crate::Repository::read_target), accept anything that converts to
+/// a `TargetName`, e.g. a `&str`, and return
+/// [`Error::UnsafeTargetName`](crate::error::Error::UnsafeTargetName) for a name that doesn't
+/// pass these checks. The name is kept exactly as given, so it matches the name listed in the
+/// targets metadata.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TargetName(String);
+
+impl TargetName {
+    /// Checks `name` and returns it as a `TargetName`.
+    pub fn new(name: &str) -> Result<Self> {
+        safe_target_path(name)?;
+        Ok(Self(name.to_owned()))
+    }
+
+    /// Returns the name as a string slice.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Returns the relative path that the target can be written to, as [`safe_target_path`]
+    /// would.
+    pub fn to_path(&self) -> PathBuf {
+        Path::new(&self.0)
+            .components()
+            .filter_map(|component| match component {
+                Component::Normal(part) => Some(part),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+impl AsRef<str> for TargetName {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Display for TargetName {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl TryFrom<&str> for TargetName {
+    type Error = Error;
+
+    fn try_from(name: &str) -> Result<Self> {
+        Self::new(name)
+    }
+}
+
+impl TryFrom<&String> for TargetName {
+    type Error = Error;
+
+    fn try_from(name: &String) -> Result<Self> {
+        Self::new(name)
+    }
+}
+
+impl TryFrom<String> for TargetName {
+    type Error = Error;
+
+    fn try_from(name: String) -> Result<Self> {
+        safe_target_path(&name)?;
+        Ok(Self(name))
+    }
+}
+
+impl From<&TargetName> for TargetName {
+    fn from(name: &TargetName) -> Self {
+        name.clone()
+    }
+}
+
+impl From<TargetName> for String {
+    fn from(name: TargetName) -> Self {
+        name.0
+    }
+}
+
+/// Converts a target name into a relative path that can be joined onto a directory without
+/// escaping it.
+///
+/// Target names come from repository metadata, so they must not be trusted as paths. A name is
+/// rejected with [`Error::UnsafeTargetName`](crate::error::Error::UnsafeTargetName) if it is
+/// absolute, has a root or a Windows drive prefix, contains a `..` component, or is empty. `.`
+/// components are dropped. Names may contain separators, e.g. `linux/amd64/foo.bin`, in which case
+/// the returned path has several components.
+pub fn safe_target_path(name: &str) -> Result<PathBuf> {
+    let mut path = PathBuf::new();
+    for component in Path::new(name).components() {
+        match component {
+            Component::Normal(part) => path.push(part),
+            Component::CurDir => {}
+            Component::ParentDir | Component::RootDir | Component::Prefix(_) => {
+                return error::UnsafeTargetName { name }.fail();
+            }
+        }
+    }
+    ensure!(
+        path.components().next().is_some(),
+        error::UnsafeTargetName { name }
+    );
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{safe_target_path, TargetName};
+    use std::convert::TryFrom;
+    use std::path::Path;
+
+    #[test]
+    fn safe_names() {
+        assert_eq!(
+            safe_target_path("file1.txt").unwrap(),
+            Path::new("file1.txt")
+        );
+        assert_eq!(
+            safe_target_path("linux/amd64/foo.bin").unwrap(),
+            Path::new("linux").join("amd64").join("foo.bin")
+        );
+        assert_eq!(
+            safe_target_path("./a/./b").unwrap(),
+            Path::new("a").join("b")
+        );
+        assert_eq!(safe_target_path("a..b").unwrap(), Path::new("a..b"));
+    }
+
+    #[test]
+    fn unsafe_names() {
+        for name in &[
+            "",
+            ".",
+            "..",
+            "../../etc/passwd",
+            "a/../../b",
+            "a/..",
+            "/etc/passwd",
+        ] {
+            assert!(safe_target_path(name).is_err(), "{:?} was accepted", name);
+        }
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn unsafe_windows_names() {
+        for name in &[
+            r"C:\Windows\win.ini",
+            r"C:foo",
+            r"\\server\share\foo",
+            r"..\foo",
+        ] {
+            assert!(safe_target_path(name).is_err(), "{:?} was accepted", name);
+        }
+    }
+
+    #[test]
+    fn target_names() {
+        let name = TargetName::new("./linux/amd64/foo.bin").unwrap();
+        assert_eq!(name.as_str(), "./linux/amd64/foo.bin");
+        assert_eq!(
+            name.to_path(),
+            Path::new("linux").join("amd64").join("foo.bin")
+        );
+        assert_eq!(
+            TargetName::try_from("file1.txt".to_owned()).unwrap(),
+            TargetName::try_from("file1.txt").unwrap()
+        );
+        for name in &["", ".", "../etc/passwd", "a/../../b", "/etc/passwd"] {
+            assert!(
+                matches!(
+                    TargetName::new(name),
+                    Err(crate::error::Error::UnsafeTargetName { .. })
+                ),
+                "{:?} was accepted",
+                name
+            );
+        }
+    }
+}
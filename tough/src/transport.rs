@@ -1,9 +1,11 @@
 #[cfg(feature = "http")]
-use crate::{HttpTransport, HttpTransportBuilder};
+use crate::{http::HttpError, HttpTransport, HttpTransportBuilder};
 use dyn_clone::DynClone;
+use serde::{Deserialize, Serialize};
 use std::error::Error;
 use std::fmt::{Debug, Display, Formatter};
-use std::io::{ErrorKind, Read};
+use std::io::{ErrorKind, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
 use url::Url;
 
 /// A trait to abstract over the method/protocol by which files are obtained.
@@ -12,16 +14,116 @@ use url::Url;
 /// `Box<dyn Read + Send>` and by requiring concrete type [`TransportError`] as the error type.
 ///
 /// Inclusion of the `DynClone` trait means that you will need to implement `Clone` when
-/// implementing a `Transport`.
-pub trait Transport: Debug + DynClone {
+/// implementing a `Transport`. A `Transport` must also be `Send + Sync` so that a [`Repository`]
+/// can be shared between threads.
+///
+/// [`Repository`]: crate::Repository
+pub trait Transport: Debug + DynClone + Send + Sync {
     /// Opens a `Read` object for the file specified by `url`.
     fn fetch(&self, url: Url) -> Result<Box<dyn Read + Send>, TransportError>;
+
+    /// Opens a `Read` object for the file specified by `url`, unless `validators` (from an earlier
+    /// call for the same URL) show that the file hasn't changed since then.
+    ///
+    /// The default implementation doesn't support conditional requests: it always calls
+    /// [`Transport::fetch`], and returns empty validators.
+    fn fetch_if_modified(
+        &self,
+        url: Url,
+        validators: &CacheValidators,
+    ) -> Result<ConditionalFetch, TransportError> {
+        let _ = validators;
+        Ok(ConditionalFetch::Modified {
+            reader: self.fetch(url)?,
+            validators: CacheValidators::default(),
+        })
+    }
+
+    /// Returns the size of the file specified by `url` without fetching it, if the transport can
+    /// find it out cheaply, e.g. from the `Content-Length` of an HTTP `HEAD` response.
+    ///
+    /// The default implementation returns `None`, meaning that the size is unknown.
+    fn head(&self, url: Url) -> Result<Option<u64>, TransportError> {
+        let _ = url;
+        Ok(None)
+    }
+
+    /// Opens a `Read` object for `len` bytes of the file specified by `url`, starting at byte
+    /// `start`, e.g. with an HTTP `Range` request. The reader may return fewer bytes if the file
+    /// ends sooner. `len` is greater than zero.
+    ///
+    /// The default implementation doesn't support ranges: it returns a
+    /// [`TransportErrorKind::UnsupportedRange`] error.
+    fn fetch_range(
+        &self,
+        url: Url,
+        start: u64,
+        len: u64,
+    ) -> Result<Box<dyn Read + Send>, TransportError> {
+        let _ = (start, len);
+        Err(TransportError::new(
+            TransportErrorKind::UnsupportedRange,
+            url,
+        ))
+    }
 }
 
 // Implements `Clone` for `Transport` trait objects (i.e. on `Box::<dyn Clone>`). To facilitate
 // this, `Clone` needs to be implemented for any `Transport`s. The compiler will enforce this.
 dyn_clone::clone_trait_object!(Transport);
 
+/// Information from a response that identifies the version of the file it contained, such as an
+/// HTTP `ETag`. See [`Transport::fetch_if_modified`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct CacheValidators {
+    /// An opaque identifier of the file's version, e.g. an HTTP `ETag` header.
+    pub etag: Option<String>,
+    /// When the file was last modified, e.g. an HTTP `Last-Modified` header.
+    pub last_modified: Option<String>,
+}
+
+impl CacheValidators {
+    /// Creates `CacheValidators` from an entity tag and a last modified time.
+    pub fn new(etag: Option<String>, last_modified: Option<String>) -> Self {
+        Self {
+            etag,
+            last_modified,
+        }
+    }
+
+    /// Returns `true` if there are no validators, so a conditional request isn't possible.
+    pub fn is_empty(&self) -> bool {
+        self.etag.is_none() && self.last_modified.is_none()
+    }
+}
+
+/// The result of [`Transport::fetch_if_modified`].
+#[non_exhaustive]
+pub enum ConditionalFetch {
+    /// The file hasn't changed since the response that the validators came from.
+    NotModified,
+    /// The file may have changed, and can be read from `reader`.
+    Modified {
+        /// The contents of the file.
+        reader: Box<dyn Read + Send>,
+        /// The validators to use the next time this file is fetched.
+        validators: CacheValidators,
+    },
+}
+
+impl Debug for ConditionalFetch {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConditionalFetch::NotModified => f.write_str("NotModified"),
+            ConditionalFetch::Modified { validators, .. } => f
+                .debug_struct("Modified")
+                .field("validators", validators)
+                .finish_non_exhaustive(),
+        }
+    }
+}
+
 // =^..^=   =^..^=   =^..^=   =^..^=   =^..^=   =^..^=   =^..^=   =^..^=   =^..^=   =^..^=   =^..^=
 
 /// The kind of error that the transport object experienced during `fetch`.
@@ -46,6 +148,23 @@ pub enum TransportErrorKind {
     /// transports it might be less obvious, but the intent of `FileNotFound` is to indicate that
     /// the file probably doesn't exist.
     FileNotFound,
+    /// The transport couldn't connect to the server, or the connection failed or timed out before
+    /// a response was received.
+    Connection,
+    /// The server is rate limiting requests, e.g. with HTTP status `429 Too Many Requests`.
+    TooManyRequests,
+    /// The server failed to handle the request, e.g. with an HTTP `5xx` status.
+    ServerError,
+    /// The server refused the request, e.g. with an HTTP `4xx` status other than those reported as
+    /// [`TransportErrorKind::FileNotFound`] or [`TransportErrorKind::TooManyRequests`].
+    Rejected,
+    /// The transport was redirected more times than it allows.
+    TooManyRedirects,
+    /// The transport can't fetch part of a file, e.g. because the server ignored an HTTP `Range`
+    /// request. See [`Transport::fetch_range`].
+    UnsupportedRange,
+    /// The transport doesn't fetch anything, e.g. [`NoNetworkTransport`].
+    Offline,
     /// The transport failed for any other reason, e.g. IO error, HTTP broken pipe, etc.
     Other,
 }
@@ -58,6 +177,13 @@ impl Display for TransportErrorKind {
             match self {
                 TransportErrorKind::UnsupportedUrlScheme => "unsupported URL scheme",
                 TransportErrorKind::FileNotFound => "file not found",
+                TransportErrorKind::Connection => "connection",
+                TransportErrorKind::TooManyRequests => "too many requests",
+                TransportErrorKind::ServerError => "server error",
+                TransportErrorKind::Rejected => "rejected",
+                TransportErrorKind::TooManyRedirects => "too many redirects",
+                TransportErrorKind::UnsupportedRange => "unsupported range",
+                TransportErrorKind::Offline => "offline",
                 TransportErrorKind::Other => "other",
             }
         )
@@ -135,37 +261,188 @@ impl Error for TransportError {
 // =^..^=   =^..^=   =^..^=   =^..^=   =^..^=   =^..^=   =^..^=   =^..^=   =^..^=   =^..^=   =^..^=
 
 /// Provides a [`Transport`] for local files.
+///
+/// Only `file://` URLs with an absolute path (e.g. `file:///path/to/repo/metadata`) are supported.
+/// A relative path can't be expressed in a `file://` URL: in `file://./repo/metadata`, `.` is the
+/// host and `/repo/metadata` is the path. Except on Windows, where the host names a network share,
+/// fetching a URL with a host (other than `localhost`) is an error that explains this, rather than
+/// resolving the path against the current directory. To use a relative path, make it absolute
+/// first, e.g. with [`Url::from_directory_path`] and [`std::env::current_dir`].
 #[derive(Debug, Clone, Copy)]
 pub struct FilesystemTransport;
 
 impl Transport for FilesystemTransport {
     fn fetch(&self, url: Url) -> Result<Box<dyn Read + Send>, TransportError> {
-        // If the scheme isn't "file://", reject
-        if url.scheme() != "file" {
-            return Err(TransportError::new(
-                TransportErrorKind::UnsupportedUrlScheme,
+        let file_path = file_path(&url)?;
+        open_file(&file_path, url)
+    }
+
+    fn fetch_range(
+        &self,
+        url: Url,
+        start: u64,
+        len: u64,
+    ) -> Result<Box<dyn Read + Send>, TransportError> {
+        let file_path = file_path(&url)?;
+        open_file_range(&file_path, url, start, len)
+    }
+}
+
+/// Converts a `file://` URL into a file path.
+fn file_path(url: &Url) -> Result<PathBuf, TransportError> {
+    // If the scheme isn't "file://", reject
+    if url.scheme() != "file" {
+        return Err(TransportError::new(
+            TransportErrorKind::UnsupportedUrlScheme,
+            url,
+        ));
+    }
+
+    // Convert the file URL into a file path
+    url.to_file_path().map_err(|_e| {
+        let message = match url.host_str() {
+            // e.g. `file://./repo`, whose host is `.`
+            Some(host) if !host.is_empty() && host != "localhost" => format!(
+                "file URLs must have an absolute path, e.g. 'file:///path/to/repo'; relative paths \
+                 are not supported, and '{}' is the host of this URL",
+                host
+            ),
+            _ => "unable to get filepath from URL".to_string(),
+        };
+        TransportError::new_with_cause(TransportErrorKind::Other, url, message)
+    })
+}
+
+/// Opens the file at `path`, which `url` refers to.
+fn open_file(path: &Path, url: Url) -> Result<Box<dyn Read + Send>, TransportError> {
+    let f = std::fs::File::open(path).map_err(|e| io_error(e, url))?;
+    Ok(Box::new(f))
+}
+
+/// Opens `len` bytes of the file at `path`, which `url` refers to, starting at byte `start`.
+fn open_file_range(
+    path: &Path,
+    url: Url,
+    start: u64,
+    len: u64,
+) -> Result<Box<dyn Read + Send>, TransportError> {
+    let mut f = std::fs::File::open(path).map_err(|e| io_error(e, url.clone()))?;
+    f.seek(SeekFrom::Start(start))
+        .map_err(|e| io_error(e, url))?;
+    Ok(Box::new(f.take(len)))
+}
+
+/// Converts an IO error for the file that `url` refers to into a [`TransportError`].
+fn io_error(e: std::io::Error, url: Url) -> TransportError {
+    let kind = match e.kind() {
+        ErrorKind::NotFound => TransportErrorKind::FileNotFound,
+        _ => TransportErrorKind::Other,
+    };
+    TransportError::new_with_cause(kind, url, e)
+}
+
+// =^..^=   =^..^=   =^..^=   =^..^=   =^..^=   =^..^=   =^..^=   =^..^=   =^..^=   =^..^=   =^..^=
+
+/// Provides a [`Transport`] for local files within a base directory, like [`FilesystemTransport`]
+/// but with a guard against path traversal.
+///
+/// Symbolic links are followed, both in the base directory's path and within it, but a file whose
+/// real path is outside of the base directory's real path is never opened. Fetching it is a
+/// [`TransportErrorKind::FileNotFound`] error, as is fetching any other URL outside of the base
+/// directory.
+#[derive(Debug, Clone)]
+pub struct ConfinedFilesystemTransport {
+    base_dir: PathBuf,
+}
+
+impl ConfinedFilesystemTransport {
+    /// Creates a `ConfinedFilesystemTransport` that only serves files within `base_dir`, e.g. the
+    /// directory that contains a repository's `metadata` and `targets` directories.
+    pub fn new<P: Into<PathBuf>>(base_dir: P) -> Self {
+        Self {
+            base_dir: base_dir.into(),
+        }
+    }
+
+    /// Returns the real path of the file that `url` refers to, if it's within the base directory.
+    fn confined_path(&self, url: &Url) -> Result<PathBuf, TransportError> {
+        let file_path = file_path(url)?;
+        // Resolve every symbolic link, and open the resolved path, so that the file that's
+        // opened is the one that was checked.
+        let base_dir =
+            std::fs::canonicalize(&self.base_dir).map_err(|e| io_error(e, url.clone()))?;
+        let real_path = std::fs::canonicalize(&file_path).map_err(|e| io_error(e, url.clone()))?;
+        if !real_path.starts_with(&base_dir) {
+            return Err(TransportError::new_with_cause(
+                TransportErrorKind::FileNotFound,
                 url,
+                format!(
+                    "'{}' is outside of the base directory '{}'",
+                    real_path.display(),
+                    base_dir.display()
+                ),
             ));
         }
+        Ok(real_path)
+    }
+}
 
-        // Convert the file URL into a file path
-        let file_path = &url.to_file_path().map_err(|_e| {
-            TransportError::new_with_cause(
-                TransportErrorKind::Other,
-                &url,
-                "unable to get filepath from URL".to_string(),
-            )
-        })?;
+impl Transport for ConfinedFilesystemTransport {
+    fn fetch(&self, url: Url) -> Result<Box<dyn Read + Send>, TransportError> {
+        let real_path = self.confined_path(&url)?;
+        open_file(&real_path, url)
+    }
 
-        // And open the file
-        let f = std::fs::File::open(file_path).map_err(|e| {
-            let kind = match e.kind() {
-                ErrorKind::NotFound => TransportErrorKind::FileNotFound,
-                _ => TransportErrorKind::Other,
-            };
-            TransportError::new_with_cause(kind, url, e)
-        })?;
-        Ok(Box::new(f))
+    fn fetch_range(
+        &self,
+        url: Url,
+        start: u64,
+        len: u64,
+    ) -> Result<Box<dyn Read + Send>, TransportError> {
+        let real_path = self.confined_path(&url)?;
+        open_file_range(&real_path, url, start, len)
+    }
+}
+
+// =^..^=   =^..^=   =^..^=   =^..^=   =^..^=   =^..^=   =^..^=   =^..^=   =^..^=   =^..^=   =^..^=
+
+/// A [`Transport`] that fails to fetch any URL with a [`TransportErrorKind::Offline`] error, to
+/// make sure that nothing is fetched, e.g. when verifying metadata that was already downloaded.
+///
+/// Combine it with [`RepositoryLoader::offline`](crate::RepositoryLoader::offline) and a datastore
+/// that a previous load filled, so that metadata is read from the datastore and any attempt to
+/// fetch a file, whether metadata or a target, is an error rather than going to the network.
+/// Unlike other transports, asking for the size of a file with [`Transport::head`] is an error too.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoNetworkTransport;
+
+impl NoNetworkTransport {
+    fn error(url: Url) -> TransportError {
+        TransportError::new_with_cause(
+            TransportErrorKind::Offline,
+            url,
+            "fetching is disabled by NoNetworkTransport",
+        )
+    }
+}
+
+impl Transport for NoNetworkTransport {
+    fn fetch(&self, url: Url) -> Result<Box<dyn Read + Send>, TransportError> {
+        Err(Self::error(url))
+    }
+
+    fn head(&self, url: Url) -> Result<Option<u64>, TransportError> {
+        Err(Self::error(url))
+    }
+
+    fn fetch_range(
+        &self,
+        url: Url,
+        start: u64,
+        len: u64,
+    ) -> Result<Box<dyn Read + Send>, TransportError> {
+        let _ = (start, len);
+        Err(Self::error(url))
     }
 }
 
@@ -173,7 +450,8 @@ impl Transport for FilesystemTransport {
 
 /// A Transport that provides support for both local files and, if the `http` feature is enabled,
 /// HTTP-transported files.
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone)]
+#[cfg_attr(not(feature = "http"), derive(Copy))]
 pub struct DefaultTransport {
     file: FilesystemTransport,
     #[cfg(feature = "http")]
@@ -199,12 +477,13 @@ impl DefaultTransport {
 
 #[cfg(feature = "http")]
 impl DefaultTransport {
-    /// Create a new `DefaultTransport` with potentially customized settings.
-    pub fn new_with_http_settings(builder: HttpTransportBuilder) -> Self {
-        Self {
+    /// Create a new `DefaultTransport` with potentially customized settings. Returns an error if
+    /// the HTTP client can't be built; see [`HttpTransportBuilder::build`].
+    pub fn new_with_http_settings(builder: HttpTransportBuilder) -> Result<Self, HttpError> {
+        Ok(Self {
             file: FilesystemTransport,
-            http: builder.build(),
-        }
+            http: builder.build()?,
+        })
     }
 }
 
@@ -219,6 +498,46 @@ impl Transport for DefaultTransport {
             )),
         }
     }
+
+    #[cfg(feature = "http")]
+    fn fetch_if_modified(
+        &self,
+        url: Url,
+        validators: &CacheValidators,
+    ) -> Result<ConditionalFetch, TransportError> {
+        match url.scheme() {
+            "http" | "https" => self.http.fetch_if_modified(url, validators),
+            _ => Ok(ConditionalFetch::Modified {
+                reader: self.fetch(url)?,
+                validators: CacheValidators::default(),
+            }),
+        }
+    }
+
+    #[cfg(feature = "http")]
+    fn head(&self, url: Url) -> Result<Option<u64>, TransportError> {
+        match url.scheme() {
+            "http" | "https" => self.http.head(url),
+            _ => Ok(None),
+        }
+    }
+
+    fn fetch_range(
+        &self,
+        url: Url,
+        start: u64,
+        len: u64,
+    ) -> Result<Box<dyn Read + Send>, TransportError> {
+        match url.scheme() {
+            "file" => self.file.fetch_range(url, start, len),
+            #[cfg(feature = "http")]
+            "http" | "https" => self.http.fetch_range(url, start, len),
+            _ => Err(TransportError::new(
+                TransportErrorKind::UnsupportedUrlScheme,
+                url,
+            )),
+        }
+    }
 }
 
 impl DefaultTransport {
@@ -0,0 +1,207 @@
+//! Provides [`validate_metadata`] and [`validate_metadata_at`], which check a repository's metadata without loading a
+//! [`Repository`](crate::Repository).
+
+use crate::datastore::Datastore;
+use crate::error::{self, Result};
+use crate::fetch::BudgetTransport;
+use crate::schema::{Delegations, Role, RoleType, Root, Signed};
+use crate::{
+    load_root, load_snapshot, load_targets, load_timestamp, parse_url, DatastoreLocking,
+    DelegationOptions, ExpirationEnforcement, Limits, TimeSource, Transport,
+};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use snafu::ResultExt;
+use std::io::Read;
+use std::num::NonZeroU64;
+use url::Url;
+
+/// A summary of a repository's metadata, returned by [`validate_metadata`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct MetadataReport {
+    /// Reports for the root, timestamp, snapshot, and targets roles, in that order, followed by
+    /// each delegated role in preorder.
+    pub roles: Vec<RoleReport>,
+}
+
+impl MetadataReport {
+    /// Returns `true` if any role's metadata was expired when it was validated.
+    pub fn is_expired(&self) -> bool {
+        self.roles.iter().any(|role| role.expired)
+    }
+}
+
+/// A summary of one role's metadata, as part of a [`MetadataReport`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct RoleReport {
+    /// The type of the role.
+    pub role_type: RoleType,
+    /// The name of the role: `root`, `timestamp`, `snapshot`, or `targets` for the top-level roles,
+    /// or the name of a delegated role.
+    pub name: String,
+    /// The version of the role's metadata.
+    pub version: NonZeroU64,
+    /// When the role's metadata expires.
+    pub expires: DateTime<Utc>,
+    /// Whether the role's metadata was expired when it was validated.
+    pub expired: bool,
+    /// The number of valid signatures on the role's metadata, by keys that are trusted for the
+    /// role.
+    pub valid_signatures: u64,
+    /// The number of valid signatures that the role requires.
+    pub threshold: NonZeroU64,
+}
+
+/// Fetches and verifies a repository's metadata, following the same steps as
+/// [`RepositoryLoader::load`](crate::RepositoryLoader::load), and returns a report of each role's
+/// version, expiration, and signatures.
+///
+/// This is a lighter alternative to loading a [`Repository`](crate::Repository) when there is no
+/// need to read targets, e.g. to check a repository in CI. No datastore is kept, so rollback
+/// checks are only made between the metadata files fetched during validation, and the default
+/// [`Limits`] are used.
+///
+/// Any metadata that fails verification (signatures, versions, lengths, or hashes) results in an
+/// error. Expired metadata does not; it is reported with [`RoleReport::expired`] instead, so that
+/// callers can decide whether it should fail their checks.
+#[cfg(feature = "clock")]
+pub fn validate_metadata<R: Read>(
+    root: R,
+    metadata_base_url: Url,
+    transport: &dyn Transport,
+) -> Result<MetadataReport> {
+    validate_metadata_at(root, metadata_base_url, transport, Utc::now())
+}
+
+/// Like [`validate_metadata`], but checks expiration against `now` instead of the system clock,
+/// e.g. when tough is built without the `clock` feature.
+pub fn validate_metadata_at<R: Read>(
+    root: R,
+    metadata_base_url: Url,
+    transport: &dyn Transport,
+    now: DateTime<Utc>,
+) -> Result<MetadataReport> {
+    let datastore = Datastore::new(
+        None,
+        DatastoreLocking::default(),
+        false,
+        TimeSource::fixed(now),
+    )?;
+    let limits = Limits::default();
+    let metadata_base_url = parse_url(metadata_base_url)?;
+    let expiration_enforcement = ExpirationEnforcement::Unsafe;
+    let budget_transport = BudgetTransport::new(transport, limits.max_total_download);
+    let transport = &budget_transport as &dyn Transport;
+
+    let (root, _, _) = load_root(
+        transport,
+        root,
+        None,
+        None,
+        &datastore,
+        limits.max_root_size,
+        limits.max_root_updates,
+        limits.max_root_total_size,
+        &metadata_base_url,
+        expiration_enforcement,
+        false,
+        false,
+    )?;
+    let (timestamp, _) = load_timestamp(
+        transport,
+        &root,
+        &datastore,
+        limits.max_timestamp_size,
+        &metadata_base_url,
+        "timestamp.json",
+        expiration_enforcement,
+        false,
+    )?;
+    let (snapshot, _) = load_snapshot(
+        transport,
+        &root,
+        &timestamp,
+        &datastore,
+        limits.max_snapshot_size,
+        &metadata_base_url,
+        "snapshot.json",
+        expiration_enforcement,
+        false,
+    )?;
+    let (targets, _, _) = load_targets(
+        transport,
+        &root,
+        &snapshot,
+        &datastore,
+        limits.max_targets_size,
+        limits.max_delegated_roles,
+        &metadata_base_url,
+        "targets.json",
+        expiration_enforcement,
+        false,
+        &DelegationOptions::default(),
+        false,
+    )?;
+
+    let mut roles = vec![
+        top_level_report(&root.signed, &root, now)?,
+        top_level_report(&root.signed, &timestamp, now)?,
+        top_level_report(&root.signed, &snapshot, now)?,
+        top_level_report(&root.signed, &targets, now)?,
+    ];
+    if let Some(delegations) = &targets.signed.delegations {
+        delegated_reports(delegations, now, &mut roles)?;
+    }
+    Ok(MetadataReport { roles })
+}
+
+fn top_level_report<T: Role + Serialize>(
+    root: &Root,
+    role: &Signed<T>,
+    now: DateTime<Utc>,
+) -> Result<RoleReport> {
+    let (valid_signatures, threshold) = root
+        .count_valid_signatures(role)
+        .context(error::VerifyMetadata { role: T::TYPE })?;
+    Ok(RoleReport {
+        role_type: T::TYPE,
+        name: T::TYPE.to_string(),
+        version: role.signed.version(),
+        expires: role.signed.expires(),
+        expired: role.signed.expires() <= now,
+        valid_signatures,
+        threshold,
+    })
+}
+
+/// Adds a report for each role delegated by `delegations`, in preorder, to `reports`.
+fn delegated_reports(
+    delegations: &Delegations,
+    now: DateTime<Utc>,
+    reports: &mut Vec<RoleReport>,
+) -> Result<()> {
+    for delegated_role in &delegations.roles {
+        if let Some(targets) = &delegated_role.targets {
+            let (valid_signatures, threshold) = delegations
+                .count_valid_signatures(targets, &delegated_role.name)
+                .context(error::VerifyMetadata {
+                    role: RoleType::DelegatedTargets,
+                })?;
+            reports.push(RoleReport {
+                role_type: RoleType::DelegatedTargets,
+                name: delegated_role.name.clone(),
+                version: targets.signed.version,
+                expires: targets.signed.expires,
+                expired: targets.signed.expires <= now,
+                valid_signatures,
+                threshold,
+            });
+            if let Some(delegations) = &targets.signed.delegations {
+                delegated_reports(delegations, now, reports)?;
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,69 @@
+// Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+// These tests read the system clock, which needs the `clock` feature.
+#![cfg(feature = "clock")]
+
+use std::fs::{self, File};
+use std::path::Path;
+use tempfile::TempDir;
+use test_utils::{read_to_end, reference_impl_loader, test_data};
+use tough::Repository;
+
+mod test_utils;
+
+/// Copies the metadata of `tuf-reference-impl` to `dir`, with the targets of `role1` changed after
+/// it was signed, so that it fails verification.
+fn write_metadata_with_bad_role1(dir: &Path) {
+    let metadata = test_data().join("tuf-reference-impl").join("metadata");
+    for entry in fs::read_dir(&metadata).unwrap() {
+        let entry = entry.unwrap();
+        fs::copy(entry.path(), dir.join(entry.file_name())).unwrap();
+    }
+    let path = dir.join("role1.json");
+    let mut role1: serde_json::Value = serde_json::from_reader(File::open(&path).unwrap()).unwrap();
+    role1["signed"]["targets"]["file3.txt"]["length"] = serde_json::json!(1);
+    fs::write(&path, serde_json::to_vec(&role1).unwrap()).unwrap();
+}
+
+fn load(metadata_dir: &Path, best_effort: bool) -> tough::error::Result<Repository> {
+    reference_impl_loader(metadata_dir)
+        .best_effort_delegations(best_effort)
+        .load()
+}
+
+/// A delegated role that fails verification fails the load by default.
+#[test]
+fn bad_delegated_role_fails_load() {
+    let metadata = TempDir::new().unwrap();
+    write_metadata_with_bad_role1(metadata.path());
+    assert!(load(metadata.path(), false).is_err());
+}
+
+/// With `best_effort_delegations`, a delegated role that fails verification is skipped, along with
+/// the roles it delegates to, and the rest of the repository can be used.
+#[test]
+fn bad_delegated_role_skipped() {
+    let metadata = TempDir::new().unwrap();
+    write_metadata_with_bad_role1(metadata.path());
+    let repo = load(metadata.path(), true).unwrap();
+
+    assert_eq!(
+        read_to_end(repo.read_target("file1.txt").unwrap().unwrap()),
+        &b"This is an example target file."[..]
+    );
+    assert!(repo.delegated_role("role1").unwrap().targets.is_none());
+    assert!(repo.delegated_role("role2").is_none());
+    assert!(repo.read_target("file3.txt").unwrap().is_none());
+}
+
+/// With `best_effort_delegations`, a repository whose delegated roles are fine loads as usual.
+#[test]
+fn best_effort_good_repository() {
+    let metadata = test_data().join("tuf-reference-impl").join("metadata");
+    let repo = load(&metadata, true).unwrap();
+    assert_eq!(
+        read_to_end(repo.read_target("file3.txt").unwrap().unwrap()),
+        &b"This is role1's target file."[..]
+    );
+}
@@ -0,0 +1,80 @@
+// Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use ring::digest::{digest, SHA256};
+use serde_json::json;
+use std::collections::BTreeMap;
+use test_utils::test_data;
+use tough::schema::{canonical_json, Role, Root, Signed};
+
+mod test_utils;
+
+/// These vectors come from securesystemslib, the library used by the TUF reference implementation
+/// to produce the canonical JSON that the spec requires signatures to be calculated over.
+///
+/// https://github.com/secure-systems-lab/securesystemslib/blob/f466266014aff529510216b8c2f8c8f39de279ec/tests/test_formats.py#L354-L389
+#[test]
+fn securesystemslib_vectors() {
+    assert_eq!(canonical_json(&json!([1, 2, 3])).unwrap(), b"[1,2,3]");
+    assert_eq!(canonical_json(&json!([])).unwrap(), b"[]");
+    assert_eq!(canonical_json(&json!({})).unwrap(), b"{}");
+    assert_eq!(
+        canonical_json(&json!({"A": [99]})).unwrap(),
+        br#"{"A":[99]}"#
+    );
+    assert_eq!(
+        canonical_json(&json!({"A": true})).unwrap(),
+        br#"{"A":true}"#
+    );
+    assert_eq!(
+        canonical_json(&json!({"B": false})).unwrap(),
+        br#"{"B":false}"#
+    );
+    assert_eq!(
+        canonical_json(&json!({"x": 3, "y": 2})).unwrap(),
+        br#"{"x":3,"y":2}"#
+    );
+    assert_eq!(
+        canonical_json(&json!({"x": 3, "y": null})).unwrap(),
+        br#"{"x":3,"y":null}"#
+    );
+
+    assert!(canonical_json(&json!(8.0)).is_err());
+    assert!(canonical_json(&json!({"x": 8.0})).is_err());
+}
+
+/// Object keys are sorted at every level of nesting.
+#[test]
+fn sorts_nested_keys() {
+    assert_eq!(
+        canonical_json(&json!({"b": 2, "a": {"z": null, "m": [3, {"d": 1, "c": 0}]}})).unwrap(),
+        br#"{"a":{"m":[3,{"c":0,"d":1}],"z":null},"b":2}"#
+    );
+}
+
+/// Object keys that cannot be represented as strings are rejected.
+#[test]
+fn rejects_non_string_keys() {
+    let mut map = BTreeMap::new();
+    map.insert((1, 2), "value");
+    assert!(canonical_json(&map).is_err());
+}
+
+/// The helper produces the same bytes that the library signs, verifies, and derives key IDs from.
+#[test]
+fn matches_internal_serialization() {
+    let root: Signed<Root> = serde_json::from_slice(
+        &std::fs::read(test_data().join("simple-rsa").join("root.json")).unwrap(),
+    )
+    .unwrap();
+    assert_eq!(
+        canonical_json(&root.signed).unwrap(),
+        root.signed.canonical_form().unwrap()
+    );
+
+    for (key_id, key) in &root.signed.keys {
+        let calculated = digest(&SHA256, &canonical_json(key).unwrap());
+        assert_eq!(calculated.as_ref(), key_id.as_ref());
+        assert_eq!(key.key_id().unwrap(), *key_id);
+    }
+}
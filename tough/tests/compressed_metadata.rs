@@ -0,0 +1,67 @@
+// Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+// These tests read the system clock, which needs the `clock` feature.
+#![cfg(feature = "clock")]
+
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::fs::{self, File};
+use std::io::Write;
+use tempfile::TempDir;
+use test_utils::{read_to_end, reference_impl_loader, test_data};
+
+mod test_utils;
+
+/// Copies the `tuf-reference-impl` metadata to a new directory, storing each file compressed with
+/// gzip under its name with `.gz` appended, the way some mirrors do.
+fn compressed_metadata() -> TempDir {
+    let source = test_data().join("tuf-reference-impl").join("metadata");
+    let dir = TempDir::new().unwrap();
+    for entry in fs::read_dir(&source).unwrap() {
+        let name = entry.unwrap().file_name().into_string().unwrap();
+        let file = File::create(dir.path().join(format!("{}.gz", name))).unwrap();
+        let mut encoder = GzEncoder::new(file, Compression::default());
+        encoder
+            .write_all(&fs::read(source.join(&name)).unwrap())
+            .unwrap();
+        encoder.finish().unwrap();
+    }
+    dir
+}
+
+/// With compressed metadata, every metadata file, including delegated roles', is fetched with
+/// `.gz` appended and decompressed, and the datastore holds the decompressed files.
+#[test]
+fn compressed() {
+    let metadata = compressed_metadata();
+    let datastore = TempDir::new().unwrap();
+    let mut repo = reference_impl_loader(metadata.path())
+        .compressed_metadata(true)
+        .datastore(datastore.path())
+        .load()
+        .unwrap();
+    assert_eq!(
+        read_to_end(repo.read_target("file3.txt").unwrap().unwrap()),
+        &b"This is role1's target file."[..]
+    );
+    assert_eq!(
+        fs::read(datastore.path().join("timestamp.json")).unwrap(),
+        fs::read(
+            test_data()
+                .join("tuf-reference-impl")
+                .join("metadata")
+                .join("timestamp.json")
+        )
+        .unwrap()
+    );
+    assert!(!repo.refresh().unwrap());
+}
+
+/// Metadata is fetched uncompressed by default.
+#[test]
+fn uncompressed_by_default() {
+    let metadata = compressed_metadata();
+    let error = reference_impl_loader(metadata.path()).load().unwrap_err();
+    assert!(error.to_string().contains("timestamp.json"));
+}
@@ -0,0 +1,64 @@
+// Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+// These tests read the system clock, which needs the `clock` feature.
+#![cfg(feature = "clock")]
+
+use std::sync::Arc;
+use std::thread;
+use tempfile::TempDir;
+use test_utils::{read_to_end, reference_impl_loader, test_data};
+use tough::Repository;
+
+mod test_utils;
+
+const TARGETS: &[(&str, &[u8])] = &[
+    ("file1.txt", b"This is an example target file."),
+    ("file2.txt", b"This is an another example target file."),
+    ("file3.txt", b"This is role1's target file."),
+];
+
+fn assert_send_sync<T: Send + Sync>() {}
+
+/// Reads every target, several times, from `threads` threads that share `repo`.
+fn read_targets_concurrently(repo: &Arc<Repository>, threads: usize) {
+    let handles: Vec<_> = (0..threads)
+        .map(|i| {
+            // Every other thread uses a clone rather than the shared repository.
+            let repo = if i % 2 == 0 {
+                Arc::clone(repo)
+            } else {
+                Arc::new(Repository::clone(repo))
+            };
+            thread::spawn(move || {
+                for _ in 0..25 {
+                    for (name, contents) in TARGETS {
+                        let reader = repo.read_target(*name).unwrap().unwrap();
+                        assert_eq!(read_to_end(reader), *contents);
+                    }
+                }
+            })
+        })
+        .collect();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+}
+
+/// Test that targets can be read from many threads at once, with a temporary or persistent
+/// datastore, without any read failing.
+#[test]
+fn concurrent_read_target() {
+    assert_send_sync::<Repository>();
+
+    let base = test_data().join("tuf-reference-impl");
+    let datastore = TempDir::new().unwrap();
+    for datastore in &[None, Some(datastore.path())] {
+        let mut loader = reference_impl_loader(base.join("metadata"));
+        if let Some(datastore) = datastore {
+            loader = loader.datastore(datastore);
+        }
+        let repo = Arc::new(loader.load().unwrap());
+        read_targets_concurrently(&repo, 8);
+    }
+}
@@ -0,0 +1,209 @@
+// Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+// These tests read the system clock, which needs the `clock` feature.
+#![cfg(feature = "clock")]
+
+use chrono::Utc;
+use fs2::FileExt;
+use std::fs::{self, File};
+use std::path::Path;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+use tempfile::TempDir;
+use test_utils::{reference_impl_loader, test_data};
+use tough::schema::{Signed, Timestamp};
+use tough::{DatastoreLocking, Repository};
+
+mod test_utils;
+
+fn try_load(datastore: &Path, locking: DatastoreLocking) -> tough::error::Result<Repository> {
+    let base = test_data().join("tuf-reference-impl");
+    reference_impl_loader(base.join("metadata"))
+        .datastore(datastore)
+        .datastore_locking(locking)
+        .load()
+}
+
+fn load(datastore: &Path) {
+    try_load(datastore, DatastoreLocking::Fail).unwrap();
+}
+
+/// Returns the names of the files in `dir`, sorted.
+fn file_names(dir: &Path) -> Vec<String> {
+    let mut names: Vec<String> = fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+        .collect();
+    names.sort();
+    names
+}
+
+/// Files are written to the datastore without leaving temporary files behind.
+#[test]
+fn datastore_has_no_temp_files() {
+    let datastore = TempDir::new().unwrap();
+    load(datastore.path());
+    load(datastore.path());
+    assert_eq!(
+        file_names(datastore.path()),
+        vec![
+            ".lock",
+            "latest_known_time.json",
+            "role1.json",
+            "role2.json",
+            "snapshot.json",
+            "targets.json",
+            "timestamp.json"
+        ]
+    );
+}
+
+/// A truncated file in the datastore, e.g. from a write that was interrupted before writes were
+/// atomic, is replaced by a complete file on the next load.
+#[test]
+fn datastore_replaces_truncated_file() {
+    let datastore = TempDir::new().unwrap();
+    load(datastore.path());
+
+    let timestamp_path = datastore.path().join("timestamp.json");
+    let timestamp = fs::read(&timestamp_path).unwrap();
+    fs::write(&timestamp_path, &timestamp[..timestamp.len() / 2]).unwrap();
+    assert!(
+        serde_json::from_slice::<Signed<Timestamp>>(&fs::read(&timestamp_path).unwrap()).is_err()
+    );
+
+    load(datastore.path());
+    assert_eq!(fs::read(&timestamp_path).unwrap(), timestamp);
+}
+
+/// Takes the lock on the datastore, as another repository does while it's updating it.
+fn lock_datastore(datastore: &Path) -> File {
+    let file = File::create(datastore.join(".lock")).unwrap();
+    file.lock_exclusive().unwrap();
+    file
+}
+
+/// Several repositories can use the same datastore, since it's only locked while metadata is
+/// updated.
+#[test]
+fn datastore_shared() {
+    let datastore = TempDir::new().unwrap();
+    let mut first = try_load(datastore.path(), DatastoreLocking::Fail).unwrap();
+    let second = try_load(datastore.path(), DatastoreLocking::Fail).unwrap();
+    first.refresh().unwrap();
+    drop(second);
+    drop(first);
+}
+
+/// With `DatastoreLocking::Fail`, loading fails while another repository is updating the
+/// datastore.
+#[test]
+fn datastore_locked_fail() {
+    let datastore = TempDir::new().unwrap();
+    let lock = lock_datastore(datastore.path());
+    assert!(matches!(
+        try_load(datastore.path(), DatastoreLocking::Fail),
+        Err(tough::error::Error::DatastoreLocked { .. })
+    ));
+    drop(lock);
+    try_load(datastore.path(), DatastoreLocking::Fail).unwrap();
+}
+
+/// By default, loading waits for another repository to finish updating the datastore.
+#[test]
+fn datastore_locked_block() {
+    let datastore = TempDir::new().unwrap();
+    let lock = lock_datastore(datastore.path());
+
+    let (tx, rx) = mpsc::channel();
+    let path = datastore.path().to_owned();
+    let waiter = thread::spawn(move || {
+        let base = test_data().join("tuf-reference-impl");
+        let result = reference_impl_loader(base.join("metadata"))
+            .datastore(&path)
+            .load()
+            .map(drop);
+        tx.send(()).unwrap();
+        result
+    });
+    assert!(rx.recv_timeout(Duration::from_millis(500)).is_err());
+
+    drop(lock);
+    rx.recv_timeout(Duration::from_secs(30)).unwrap();
+    waiter.join().unwrap().unwrap();
+}
+
+/// Loads `tuf-reference-impl` with a temporary datastore.
+fn load_temp(keep: bool) -> Repository {
+    let base = test_data().join("tuf-reference-impl");
+    reference_impl_loader(base.join("metadata"))
+        .keep_datastore(keep)
+        .load()
+        .unwrap()
+}
+
+/// A temporary datastore is removed when the repository is dropped.
+#[test]
+fn temp_datastore_removed() {
+    let repo = load_temp(false);
+    let path = repo.datastore_path().to_owned();
+    assert!(path.join("timestamp.json").is_file());
+    drop(repo);
+    assert!(!path.exists());
+}
+
+/// A temporary datastore is kept after the repository is dropped if `keep_datastore` is set.
+#[test]
+fn temp_datastore_kept() {
+    let repo = load_temp(true);
+    let path = repo.datastore_path().to_owned();
+    drop(repo);
+    assert!(path.join("timestamp.json").is_file());
+    fs::remove_dir_all(path).unwrap();
+}
+
+/// The latest known time is recorded when a repository is loaded, and can be reset to recover from
+/// a clock that was set ahead.
+#[test]
+fn latest_known_time_reset() {
+    let datastore = TempDir::new().unwrap();
+    let before = Utc::now();
+    let mut repo = try_load(datastore.path(), DatastoreLocking::Fail).unwrap();
+    let recorded = repo.latest_known_time().unwrap().unwrap();
+    assert!(recorded >= before && recorded <= Utc::now());
+
+    let future = Utc::now() + chrono::Duration::days(1);
+    fs::write(
+        datastore.path().join("latest_known_time.json"),
+        serde_json::to_vec(&future).unwrap(),
+    )
+    .unwrap();
+    assert_eq!(repo.latest_known_time().unwrap(), Some(future));
+    assert!(matches!(
+        repo.refresh(),
+        Err(tough::error::Error::SystemTimeSteppedBackward { .. })
+    ));
+
+    repo.reset_known_time().unwrap();
+    assert_eq!(repo.latest_known_time().unwrap(), None);
+    repo.refresh().unwrap();
+    assert!(repo.latest_known_time().unwrap().unwrap() < future);
+}
+
+/// A corrupt latest known time is reported as an error when it's read directly, and replaced the
+/// next time the time is checked.
+#[test]
+fn latest_known_time_corrupt() {
+    let datastore = TempDir::new().unwrap();
+    let mut repo = try_load(datastore.path(), DatastoreLocking::Fail).unwrap();
+    let path = datastore.path().join("latest_known_time.json");
+    fs::write(&path, b"\"not a time").unwrap();
+    assert!(matches!(
+        repo.latest_known_time(),
+        Err(tough::error::Error::DatastoreDeserialize { .. })
+    ));
+    repo.refresh().unwrap();
+    assert!(repo.latest_known_time().unwrap().is_some());
+}
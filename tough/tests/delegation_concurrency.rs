@@ -0,0 +1,134 @@
+// Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+// These tests read the system clock, which needs the `clock` feature.
+#![cfg(feature = "clock")]
+
+use chrono::{Duration, Utc};
+use std::fs;
+use std::num::NonZeroU64;
+use std::path::Path;
+use tempfile::TempDir;
+use test_utils::{
+    key, read_to_end, reference_impl_loader, simple_rsa_editor, simple_rsa_loader, test_data,
+};
+use tough::schema::PathSet;
+
+mod test_utils;
+
+const ROLES: usize = 8;
+
+/// Writes a repository in which `targets` delegates to `role0` through `role7`, each of which
+/// lists `file1.txt`.
+fn write_wide_repo(outdir: &Path) {
+    let targets_key = key("snakeoil.pem");
+    let role_key = key("targetskey");
+    let target = test_data()
+        .join("tuf-reference-impl")
+        .join("targets")
+        .join("file1.txt");
+    let expires = Utc::now().checked_add_signed(Duration::days(7)).unwrap();
+    let one = NonZeroU64::new(1).unwrap();
+
+    let mut editor = simple_rsa_editor(1);
+    for i in 0..ROLES {
+        editor
+            .delegate_role(
+                &format!("role{}", i),
+                &role_key,
+                PathSet::Paths(vec!["*.txt".to_string()]),
+                one,
+                expires,
+                one,
+            )
+            .unwrap();
+    }
+    for i in 0..ROLES {
+        editor
+            .sign_targets_editor(&targets_key)
+            .unwrap()
+            .change_delegated_targets(&format!("role{}", i))
+            .unwrap()
+            .add_target_paths(vec![&target])
+            .unwrap()
+            .targets_version(one)
+            .unwrap()
+            .targets_expires(expires)
+            .unwrap()
+            .sign_targets_editor(&role_key)
+            .unwrap()
+            .change_delegated_targets("targets")
+            .unwrap()
+            .targets_version(one)
+            .unwrap()
+            .targets_expires(expires)
+            .unwrap();
+    }
+    editor.sign(&targets_key).unwrap().write(outdir).unwrap();
+}
+
+/// Fetching sibling roles concurrently gives the same delegation tree as fetching them one at a
+/// time, in the order the roles are listed.
+#[test]
+fn concurrent_matches_sequential() {
+    let outdir = TempDir::new().unwrap();
+    write_wide_repo(outdir.path());
+    let sequential = simple_rsa_loader(outdir.path(), outdir.path())
+        .load()
+        .unwrap();
+    let concurrent = simple_rsa_loader(outdir.path(), outdir.path())
+        .delegation_concurrency(3)
+        .load()
+        .unwrap();
+
+    assert_eq!(concurrent.targets(), sequential.targets());
+    let names = concurrent.targets().signed.role_names();
+    let expected: Vec<_> = (0..ROLES).map(|i| format!("role{}", i)).collect();
+    assert_eq!(names, expected.iter().collect::<Vec<_>>());
+    assert_eq!(
+        concurrent
+            .targets()
+            .signed
+            .find_target_role("file1.txt")
+            .unwrap()
+            .name,
+        "role0"
+    );
+}
+
+/// When several roles fail to load, the error is for the first of them, whatever order the
+/// fetches finish in.
+#[test]
+fn concurrent_first_error() {
+    let outdir = TempDir::new().unwrap();
+    write_wide_repo(outdir.path());
+    for entry in fs::read_dir(outdir.path()).unwrap() {
+        let path = entry.unwrap().path();
+        let name = path.file_name().unwrap().to_str().unwrap();
+        if name.ends_with("role3.json") || name.ends_with("role6.json") {
+            fs::remove_file(&path).unwrap();
+        }
+    }
+    for _ in 0..4 {
+        let error = simple_rsa_loader(outdir.path(), outdir.path())
+            .delegation_concurrency(ROLES)
+            .load()
+            .unwrap_err();
+        assert!(error.to_string().contains("role3.json"), "{}", error);
+    }
+}
+
+/// Nested delegations are loaded concurrently too.
+#[test]
+fn concurrent_nested_delegations() {
+    let metadata = test_data().join("tuf-reference-impl").join("metadata");
+    let repo = reference_impl_loader(metadata)
+        .delegation_concurrency(4)
+        .load()
+        .unwrap();
+    assert_eq!(
+        read_to_end(repo.read_target("file3.txt").unwrap().unwrap()),
+        &b"This is role1's target file."[..]
+    );
+    assert!(repo.delegated_role("role2").is_some());
+}
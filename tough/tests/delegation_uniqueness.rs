@@ -0,0 +1,116 @@
+// Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+// These tests read the system clock, which needs the `clock` feature.
+#![cfg(feature = "clock")]
+
+use chrono::{Duration, Utc};
+use std::num::NonZeroU64;
+use std::path::Path;
+use tempfile::TempDir;
+use test_utils::{key, reference_impl_loader, simple_rsa_editor, simple_rsa_loader, test_data};
+use tough::error::Error;
+use tough::schema::PathSet;
+
+mod test_utils;
+
+/// Writes a repository in which the delegated roles `a` and then `b` both list `file1.txt`, and
+/// both are allowed to.
+fn write_shadowed_repo(outdir: &Path) {
+    let targets_key = key("snakeoil.pem");
+    let a_key = key("targetskey");
+    let b_key = key("targetskey-1");
+    let target = test_data()
+        .join("tuf-reference-impl")
+        .join("targets")
+        .join("file1.txt");
+    let expires = Utc::now().checked_add_signed(Duration::days(7)).unwrap();
+    let one = NonZeroU64::new(1).unwrap();
+
+    let mut editor = simple_rsa_editor(1);
+    editor
+        .delegate_role(
+            "a",
+            &a_key,
+            PathSet::Paths(vec!["file?.txt".to_string()]),
+            one,
+            expires,
+            one,
+        )
+        .unwrap()
+        .delegate_role(
+            "b",
+            &b_key,
+            PathSet::Paths(vec!["*.txt".to_string()]),
+            one,
+            expires,
+            one,
+        )
+        .unwrap();
+    for (role, role_key) in &[("a", &a_key), ("b", &b_key)] {
+        editor
+            .sign_targets_editor(&targets_key)
+            .unwrap()
+            .change_delegated_targets(role)
+            .unwrap()
+            .add_target_paths(vec![&target])
+            .unwrap()
+            .targets_version(one)
+            .unwrap()
+            .targets_expires(expires)
+            .unwrap()
+            .sign_targets_editor(role_key)
+            .unwrap()
+            .change_delegated_targets("targets")
+            .unwrap()
+            .targets_version(one)
+            .unwrap()
+            .targets_expires(expires)
+            .unwrap();
+    }
+    editor.sign(&targets_key).unwrap().write(outdir).unwrap();
+}
+
+/// By default, the first role in preorder provides a shadowed target.
+#[test]
+fn shadowed_target_allowed_by_default() {
+    let outdir = TempDir::new().unwrap();
+    write_shadowed_repo(outdir.path());
+    let repo = simple_rsa_loader(outdir.path(), outdir.path())
+        .load()
+        .unwrap();
+    assert_eq!(
+        repo.targets()
+            .signed
+            .find_target_role("file1.txt")
+            .unwrap()
+            .name,
+        "a"
+    );
+}
+
+#[test]
+fn shadowed_target_rejected_when_strict() {
+    let outdir = TempDir::new().unwrap();
+    write_shadowed_repo(outdir.path());
+    match simple_rsa_loader(outdir.path(), outdir.path())
+        .strict_delegation_uniqueness(true)
+        .load()
+    {
+        Err(Error::DuplicateTarget { name, roles, .. }) => {
+            assert_eq!(name, "file1.txt");
+            assert_eq!(roles, vec!["a", "b"]);
+        }
+        Err(e) => panic!("expected a duplicate target error, got: {}", e),
+        Ok(_) => panic!("loading should fail with a shadowed target"),
+    }
+}
+
+/// A repository in which each target is listed once loads in strict mode.
+#[test]
+fn unique_targets_when_strict() {
+    reference_impl_loader(test_data().join("tuf-reference-impl").join("metadata"))
+        .strict_delegation_uniqueness(true)
+        .load()
+        .unwrap();
+}
@@ -0,0 +1,39 @@
+// Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+// These tests read the system clock, which needs the `clock` feature.
+#![cfg(feature = "clock")]
+
+use test_utils::{reference_impl_loader, test_data};
+use tough::schema::Hashes;
+use tough::TargetDiff;
+
+mod test_utils;
+
+fn other_hashes() -> Hashes {
+    Hashes::from_sha256(vec![0; 32].into())
+}
+
+/// Targets are compared across the top-level targets role and its delegated roles, and a target is
+/// changed if its hashes are.
+#[test]
+fn diff_targets() {
+    let base = test_data().join("tuf-reference-impl");
+    let repo = reference_impl_loader(base.join("metadata")).load().unwrap();
+
+    assert_eq!(repo.diff_targets(repo.targets()), TargetDiff::default());
+
+    let mut previous = repo.targets().clone();
+    let targets = &mut previous.signed;
+    targets.remove_target("file1.txt").unwrap();
+    let file9 = targets.targets["file2.txt"].clone();
+    targets.add_target("file9.txt", file9);
+    targets.targets.get_mut("file2.txt").unwrap().hashes = other_hashes();
+    let role1 = targets.delegated_targets_mut("role1").unwrap();
+    role1.signed.targets.get_mut("file3.txt").unwrap().hashes = other_hashes();
+
+    let diff = repo.diff_targets(&previous);
+    assert_eq!(diff.added, vec!["file1.txt"]);
+    assert_eq!(diff.removed, vec!["file9.txt"]);
+    assert_eq!(diff.changed, vec!["file2.txt", "file3.txt"]);
+}
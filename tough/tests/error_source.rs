@@ -0,0 +1,82 @@
+// Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+// These tests read the system clock, which needs the `clock` feature.
+#![cfg(feature = "clock")]
+
+use std::error::Error as _;
+use std::fs;
+use std::path::Path;
+use tempfile::TempDir;
+use test_utils::{reference_impl_loader, test_data};
+use tough::error::Error;
+use tough::{TransportError, TransportErrorKind};
+
+mod test_utils;
+
+/// Copies the reference implementation's metadata to a temporary directory so that it can be
+/// broken.
+fn copy_metadata() -> TempDir {
+    let source = test_data().join("tuf-reference-impl").join("metadata");
+    let metadata = TempDir::new().unwrap();
+    for entry in fs::read_dir(&source).unwrap() {
+        let entry = entry.unwrap();
+        fs::copy(entry.path(), metadata.path().join(entry.file_name())).unwrap();
+    }
+    metadata
+}
+
+fn load(metadata_dir: &Path) -> Error {
+    reference_impl_loader(metadata_dir).load().unwrap_err()
+}
+
+/// Returns the errors in the source chain of `err`, starting with `err`.
+fn chain<'a>(
+    err: &'a (dyn std::error::Error + 'static),
+) -> Vec<&'a (dyn std::error::Error + 'static)> {
+    std::iter::successors(Some(err), |&err| err.source()).collect()
+}
+
+/// The source chain of a metadata parse error reaches the underlying JSON error.
+#[test]
+fn parse_metadata_source() {
+    let metadata = copy_metadata();
+    fs::write(metadata.path().join("timestamp.json"), "{ not json").unwrap();
+
+    let err = load(metadata.path());
+    assert!(matches!(err, Error::ParseMetadata { .. }), "{}", err);
+    let chain = chain(&err);
+    assert_eq!(chain.len(), 2);
+    assert!(chain[1].is::<serde_json::Error>());
+}
+
+/// The source chain of a verification error reaches the underlying schema error.
+#[test]
+fn verify_metadata_source() {
+    let metadata = copy_metadata();
+    let targets_path = metadata.path().join("targets.json");
+    let targets = fs::read_to_string(&targets_path).unwrap();
+    fs::write(
+        &targets_path,
+        targets.replace("\"length\": 31", "\"length\": 32"),
+    )
+    .unwrap();
+
+    let err = load(metadata.path());
+    assert!(matches!(err, Error::VerifyMetadata { .. }), "{}", err);
+    assert!(err.source().unwrap().is::<tough::schema::Error>());
+}
+
+/// The source chain of a transport error reaches the `TransportError` and the I/O error under it.
+#[test]
+fn transport_source() {
+    let metadata = copy_metadata();
+    fs::remove_file(metadata.path().join("timestamp.json")).unwrap();
+
+    let err = load(metadata.path());
+    assert!(matches!(err, Error::Transport { .. }), "{}", err);
+    let chain = chain(&err);
+    let transport = chain[1].downcast_ref::<TransportError>().unwrap();
+    assert!(matches!(transport.kind(), TransportErrorKind::FileNotFound));
+    assert!(chain[2].is::<std::io::Error>());
+}
@@ -1,16 +1,22 @@
 // Copyright 2019 Amazon.com, Inc. or its affiliates. All Rights Reserved.
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
+use chrono::{TimeZone, Utc};
 use std::fs::File;
 use test_utils::{dir_url, test_data};
+#[cfg(feature = "clock")]
 use tough::error::Error::ExpiredMetadata;
+#[cfg(feature = "clock")]
 use tough::schema::RoleType;
-use tough::{ExpirationEnforcement, RepositoryLoader};
+#[cfg(feature = "clock")]
+use tough::ExpirationEnforcement;
+use tough::RepositoryLoader;
 
 mod test_utils;
 
 /// Test that `tough` fails to load an expired repository when `expiration_enforcement` is `Safe`.
 ///
+#[cfg(feature = "clock")]
 #[test]
 fn test_expiration_enforcement_safe() {
     let base = test_data().join("expired-repository");
@@ -39,6 +45,7 @@ fn test_expiration_enforcement_safe() {
 
 /// Test that `tough` loads an expired repository when `expiration_enforcement` is `Unsafe`.
 ///
+#[cfg(feature = "clock")]
 #[test]
 fn test_expiration_enforcement_unsafe() {
     let base = test_data().join("expired-repository");
@@ -51,3 +58,19 @@ fn test_expiration_enforcement_unsafe() {
     .load();
     assert!(result.is_ok())
 }
+
+/// Test that expiration is checked against the time source set on the loader, both when loading
+/// and when reading targets afterward.
+#[test]
+fn test_expiration_time_source() {
+    let base = test_data().join("expired-repository");
+    let repo = RepositoryLoader::new(
+        File::open(base.join("metadata").join("1.root.json")).unwrap(),
+        dir_url(base.join("metadata")),
+        dir_url(base.join("targets")),
+    )
+    .time_source(|| Utc.ymd(1998, 1, 1).and_hms(0, 0, 0))
+    .load()
+    .unwrap();
+    assert!(repo.read_target("no-such-target").unwrap().is_none());
+}
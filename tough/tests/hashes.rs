@@ -0,0 +1,162 @@
+// Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+// These tests read the system clock, which needs the `clock` feature.
+#![cfg(feature = "clock")]
+
+use serde_json::json;
+use std::fs::File;
+use tempfile::TempDir;
+use test_utils::{key, read_to_end, simple_rsa_editor, simple_rsa_loader, test_data};
+use tough::error::Error;
+use tough::schema::{Hashes, Target};
+use tough::RepositoryLoader;
+
+mod test_utils;
+
+/// Digests from hash functions `tough` doesn't recognize are kept, so that metadata listing them
+/// can be re-serialized without losing them.
+#[test]
+fn unknown_algorithm_round_trip() {
+    let value = json!({
+        "blake2b-256": "0ebdc3317b75839f643387d783535adc360ca01f33c75f7c1e7373adcd675c0b",
+        "sha256": "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824",
+    });
+    let hashes: Hashes = serde_json::from_value(value.clone()).unwrap();
+
+    assert_eq!(
+        hex::encode(hashes.sha256().unwrap()),
+        "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"
+    );
+    assert!(hashes.sha512().is_none());
+    assert!(hashes.digests.contains_key("blake2b-256"));
+    assert_eq!(serde_json::to_value(&hashes).unwrap(), value);
+}
+
+/// Builds a repository signed with the `simple-rsa` keys, with `file1.txt` as its only target,
+/// listed with only a SHA-256 hash.
+fn sha256_only_repo() -> TempDir {
+    sha256_only_repo_with(Vec::new())
+}
+
+/// Builds the repository of [`sha256_only_repo`] with the `extra` targets listed too. Only
+/// `file1.txt` is written to the targets directory.
+fn sha256_only_repo_with(extra: Vec<(&str, Target)>) -> TempDir {
+    let repo_dir = TempDir::new().unwrap();
+    let source = test_data().join("tuf-reference-impl/targets/file1.txt");
+    let target = Target::from_path(&source).unwrap();
+    assert!(target.hashes.sha512().is_none());
+    let sha256 = hex::encode(target.hashes.sha256().unwrap());
+
+    let mut editor = simple_rsa_editor(1);
+    editor.add_target("file1.txt", target).unwrap();
+    for (name, target) in extra {
+        editor.add_target(name, target).unwrap();
+    }
+    editor
+        .sign(&key("snakeoil.pem"))
+        .unwrap()
+        .write(repo_dir.path().join("metadata"))
+        .unwrap();
+
+    let targets_dir = repo_dir.path().join("targets");
+    std::fs::create_dir_all(&targets_dir).unwrap();
+    std::fs::copy(&source, targets_dir.join(format!("{}.file1.txt", sha256))).unwrap();
+    repo_dir
+}
+
+fn loader(repo_dir: &TempDir) -> RepositoryLoader<File> {
+    simple_rsa_loader(
+        repo_dir.path().join("metadata"),
+        repo_dir.path().join("targets"),
+    )
+}
+
+/// A target that doesn't list a required hash can't be read, while one that lists every required
+/// hash is read and verified as usual.
+#[test]
+fn required_hash_algorithms() {
+    let repo_dir = sha256_only_repo();
+
+    let repo = loader(&repo_dir)
+        .require_hash_algorithms(&["sha512"])
+        .load()
+        .unwrap();
+    assert!(matches!(
+        repo.read_target("file1.txt"),
+        Err(Error::MissingRequiredHash { .. })
+    ));
+
+    let repo = loader(&repo_dir)
+        .require_hash_algorithms(&["sha256"])
+        .load()
+        .unwrap();
+    assert_eq!(
+        read_to_end(repo.read_target("file1.txt").unwrap().unwrap()),
+        &b"This is an example target file."[..]
+    );
+}
+
+/// Requiring a hash algorithm that can't be verified fails the load.
+#[test]
+fn required_hash_algorithm_unrecognized() {
+    let repo_dir = sha256_only_repo();
+    assert!(matches!(
+        loader(&repo_dir).require_hash_algorithms(&["md5"]).load(),
+        Err(Error::UnrecognizedHashAlgorithm { .. })
+    ));
+}
+
+/// Builds a repository whose `file2.txt` is listed with empty `hashes`.
+fn unhashed_target_repo() -> TempDir {
+    let mut unhashed =
+        Target::from_path(test_data().join("tuf-reference-impl/targets/file2.txt")).unwrap();
+    unhashed.hashes.digests.clear();
+    let repo_dir = sha256_only_repo_with(vec![("file2.txt", unhashed)]);
+    let targets: serde_json::Value = serde_json::from_reader(
+        File::open(repo_dir.path().join("metadata").join("1.targets.json")).unwrap(),
+    )
+    .unwrap();
+    assert_eq!(
+        targets["signed"]["targets"]["file2.txt"]["hashes"],
+        serde_json::json!({})
+    );
+    repo_dir
+}
+
+/// A target listed with empty `hashes` is reported, and can't be read, but the rest of the repository
+/// can be used.
+#[test]
+fn targets_missing_hashes() {
+    let repo_dir = unhashed_target_repo();
+    let repo = loader(&repo_dir).load().unwrap();
+    let missing: Vec<String> = repo
+        .targets_missing_hashes()
+        .iter()
+        .map(ToString::to_string)
+        .collect();
+    assert_eq!(missing, vec!["file2.txt"]);
+    assert!(repo.read_target("file2.txt").is_err());
+    assert_eq!(
+        read_to_end(repo.read_target("file1.txt").unwrap().unwrap()),
+        &b"This is an example target file."[..]
+    );
+}
+
+/// A target listed with empty `hashes` fails the load when target hashes are required.
+#[test]
+fn require_target_hashes() {
+    let repo_dir = unhashed_target_repo();
+    match loader(&repo_dir).require_target_hashes(true).load() {
+        Err(Error::TargetHashesMissing { targets, .. }) => assert_eq!(targets, vec!["file2.txt"]),
+        Err(e) => panic!("expected missing target hashes, got: {}", e),
+        Ok(_) => panic!("loading should fail with a target without hashes"),
+    }
+
+    let repo_dir = sha256_only_repo();
+    let repo = loader(&repo_dir)
+        .require_target_hashes(true)
+        .load()
+        .unwrap();
+    assert!(repo.targets_missing_hashes().is_empty());
+}
@@ -6,8 +6,16 @@ mod http_happy {
     use crate::test_utils::{read_to_end, test_data};
     use httptest::{matchers::*, responders::*, Expectation, Server};
     use std::fs::File;
+    use std::io::{Read, Write};
     use std::str::FromStr;
-    use tough::{DefaultTransport, HttpTransport, RepositoryLoader, Transport};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tough::http::HttpError;
+    use tough::{
+        ContentEncoding, DefaultTransport, HttpTransport, HttpTransportBuilder, Limits,
+        RepositoryLoader, Transport, TransportErrorKind,
+    };
     use url::Url;
 
     /// Set an expectation in a test HTTP server which serves a file from `tuf-reference-impl`.
@@ -33,6 +41,34 @@ mod http_happy {
             .respond_with(status_code(403))
     }
 
+    /// Set an expectation in a test HTTP server which serves a file from `tuf-reference-impl`,
+    /// compressed with `encoding`, only if the request accepts that encoding.
+    fn create_encoded_get(relative_path: &str, encoding: ContentEncoding) -> httptest::Expectation {
+        let repo_dir = test_data().join("tuf-reference-impl");
+        let file_bytes = std::fs::read(&repo_dir.join(relative_path)).unwrap();
+        let (name, body) = match encoding {
+            ContentEncoding::Gzip => {
+                let mut encoder =
+                    flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder.write_all(&file_bytes).unwrap();
+                ("gzip", encoder.finish().unwrap())
+            }
+            ContentEncoding::Zstd => ("zstd", zstd::encode_all(&file_bytes[..], 0).unwrap()),
+            _ => unreachable!(),
+        };
+        Expectation::matching(all_of![
+            request::method_path("GET", format!("/{}", relative_path)),
+            request::headers(contains(("accept-encoding", matches(name)))),
+        ])
+        .times(1)
+        .respond_with(
+            status_code(200)
+                .append_header("content-type", "application/octet-stream")
+                .append_header("content-encoding", name)
+                .body(body),
+        )
+    }
+
     /// Test that `tough` works with a healthy HTTP server.
     #[test]
     fn test_http_transport_happy_case() {
@@ -87,6 +123,523 @@ mod http_happy {
             "0644"
         );
     }
+
+    /// Test that additional TLS roots in PEM and DER form are accepted.
+    #[cfg(any(feature = "http-native-tls", feature = "http-rustls"))]
+    #[test]
+    fn test_http_transport_root_certificates() {
+        let tls_dir = test_data().join("tls");
+        run_http_test(
+            HttpTransportBuilder::new()
+                .add_root_certificate(&std::fs::read(tls_dir.join("ca.pem")).unwrap())
+                .unwrap()
+                .add_root_certificate(&std::fs::read(tls_dir.join("ca.der")).unwrap())
+                .unwrap()
+                .build()
+                .unwrap(),
+        );
+    }
+
+    /// Test that a root certificate that can't be parsed is an error when it is added.
+    #[cfg(any(feature = "http-native-tls", feature = "http-rustls"))]
+    #[test]
+    fn test_http_transport_invalid_root_certificate() {
+        assert!(matches!(
+            HttpTransportBuilder::new().add_root_certificate(b"not a certificate"),
+            Err(HttpError::RootCertificate { .. })
+        ));
+    }
+
+    /// Test that a root certificate is an error without a TLS backend.
+    #[cfg(not(any(feature = "http-native-tls", feature = "http-rustls")))]
+    #[test]
+    fn test_http_transport_root_certificate_no_tls_backend() {
+        let cert = std::fs::read(test_data().join("tls").join("ca.pem")).unwrap();
+        assert!(matches!(
+            HttpTransportBuilder::new().add_root_certificate(&cert),
+            Err(HttpError::NoTlsBackend)
+        ));
+    }
+
+    /// Test that a transport with a client identity in PKCS #12 form can fetch.
+    #[cfg(feature = "http-native-tls")]
+    #[test]
+    fn test_http_transport_client_identity_pkcs12() {
+        let tls_dir = test_data().join("tls");
+        let pkcs12 = std::fs::read(tls_dir.join("client.p12")).unwrap();
+        run_http_test(
+            HttpTransportBuilder::new()
+                .client_identity(&pkcs12, "tough")
+                .unwrap()
+                .build()
+                .unwrap(),
+        );
+    }
+
+    /// Test that a transport with a client identity in PEM form can fetch.
+    #[cfg(feature = "http-rustls")]
+    #[test]
+    fn test_http_transport_client_identity_pem() {
+        let tls_dir = test_data().join("tls");
+        let pem = std::fs::read(tls_dir.join("client.pem")).unwrap();
+        run_http_test(
+            HttpTransportBuilder::new()
+                .client_identity(&pem, "")
+                .unwrap()
+                .build()
+                .unwrap(),
+        );
+    }
+
+    /// Test that a client identity that can't be loaded is an error when it is set.
+    #[cfg(feature = "http-native-tls")]
+    #[test]
+    fn test_http_transport_invalid_client_identity() {
+        let tls_dir = test_data().join("tls");
+        let pkcs12 = std::fs::read(tls_dir.join("client.p12")).unwrap();
+        assert!(matches!(
+            HttpTransportBuilder::new().client_identity(&pkcs12, "wrong password"),
+            Err(HttpError::ClientIdentityLoad { .. })
+        ));
+        assert!(matches!(
+            HttpTransportBuilder::new().client_identity(b"not an identity", "tough"),
+            Err(HttpError::ClientIdentityLoad { .. })
+        ));
+    }
+
+    /// Test that a client identity whose TLS backend isn't enabled is an error when it is set.
+    #[test]
+    fn test_http_transport_client_identity_backend_disabled() {
+        let tls_dir = test_data().join("tls");
+        if cfg!(not(feature = "http-native-tls")) {
+            let pkcs12 = std::fs::read(tls_dir.join("client.p12")).unwrap();
+            assert!(matches!(
+                HttpTransportBuilder::new().client_identity(&pkcs12, "tough"),
+                Err(HttpError::TlsBackendDisabled { .. })
+            ));
+        }
+        if cfg!(not(feature = "http-rustls")) {
+            let pem = std::fs::read(tls_dir.join("client.pem")).unwrap();
+            assert!(matches!(
+                HttpTransportBuilder::new().client_identity(&pem, ""),
+                Err(HttpError::TlsBackendDisabled { .. })
+            ));
+        }
+    }
+
+    /// Test that an unchanged `timestamp.json` isn't downloaded again when refreshing, and that the
+    /// rest of the metadata isn't fetched again.
+    #[test]
+    fn test_http_transport_conditional_refresh() {
+        let server = Server::run();
+        let repo_dir = test_data().join("tuf-reference-impl");
+        let timestamp = std::fs::read(repo_dir.join("metadata/timestamp.json")).unwrap();
+        server.expect(
+            Expectation::matching(all_of![
+                request::method_path("GET", "/metadata/timestamp.json"),
+                not(request::headers(contains(key("if-none-match")))),
+            ])
+            .times(1)
+            .respond_with(
+                status_code(200)
+                    .append_header("etag", "\"v1\"")
+                    .body(timestamp),
+            ),
+        );
+        server.expect(
+            Expectation::matching(all_of![
+                request::method_path("GET", "/metadata/timestamp.json"),
+                request::headers(contains(("if-none-match", "\"v1\""))),
+            ])
+            .times(1)
+            .respond_with(status_code(304)),
+        );
+        server.expect(create_successful_get("metadata/snapshot.json"));
+        server.expect(create_successful_get("metadata/targets.json"));
+        server.expect(create_successful_get("metadata/role1.json"));
+        server.expect(create_successful_get("metadata/role2.json"));
+        server.expect(
+            Expectation::matching(request::method_path("GET", "/metadata/2.root.json"))
+                .times(2)
+                .respond_with(status_code(404)),
+        );
+        let metadata_base_url = Url::from_str(server.url_str("/metadata").as_str()).unwrap();
+        let targets_base_url = Url::from_str(server.url_str("/targets").as_str()).unwrap();
+        let mut repo = RepositoryLoader::new(
+            File::open(repo_dir.join("metadata").join("1.root.json")).unwrap(),
+            metadata_base_url,
+            targets_base_url,
+        )
+        .transport(HttpTransport::default())
+        .load()
+        .unwrap();
+
+        assert!(!repo.refresh().unwrap());
+    }
+
+    /// Test that compressed responses are decompressed before they are verified.
+    fn run_encoded_test(encoding: ContentEncoding) {
+        let server = Server::run();
+        let repo_dir = test_data().join("tuf-reference-impl");
+        server.expect(create_encoded_get("metadata/timestamp.json", encoding));
+        server.expect(create_encoded_get("metadata/snapshot.json", encoding));
+        server.expect(create_encoded_get("metadata/targets.json", encoding));
+        server.expect(create_encoded_get("metadata/role1.json", encoding));
+        server.expect(create_encoded_get("metadata/role2.json", encoding));
+        server.expect(create_encoded_get("targets/file1.txt", encoding));
+        server.expect(create_unsuccessful_get("metadata/2.root.json"));
+        let metadata_base_url = Url::from_str(server.url_str("/metadata").as_str()).unwrap();
+        let targets_base_url = Url::from_str(server.url_str("/targets").as_str()).unwrap();
+        let repo = RepositoryLoader::new(
+            File::open(repo_dir.join("metadata").join("1.root.json")).unwrap(),
+            metadata_base_url,
+            targets_base_url,
+        )
+        .transport(
+            HttpTransportBuilder::new()
+                .accept_encoding(encoding)
+                .build()
+                .unwrap(),
+        )
+        .load()
+        .unwrap();
+
+        assert_eq!(
+            read_to_end(repo.read_target("file1.txt").unwrap().unwrap()),
+            &b"This is an example target file."[..]
+        );
+    }
+
+    #[test]
+    fn test_http_transport_gzip() {
+        run_encoded_test(ContentEncoding::Gzip);
+    }
+
+    #[test]
+    fn test_http_transport_zstd() {
+        run_encoded_test(ContentEncoding::Zstd);
+    }
+
+    /// Test that an encoding which was not accepted is reported as an error.
+    #[test]
+    fn test_http_transport_unsupported_encoding() {
+        let server = Server::run();
+        server.expect(
+            Expectation::matching(request::method_path("GET", "/file.txt")).respond_with(
+                status_code(200)
+                    .append_header("content-encoding", "br")
+                    .body("not really brotli"),
+            ),
+        );
+        let transport = HttpTransportBuilder::new()
+            .accept_encoding(ContentEncoding::Gzip)
+            .build()
+            .unwrap();
+        let url = Url::from_str(server.url_str("/file.txt").as_str()).unwrap();
+        let error = transport.fetch(url).err().unwrap();
+        assert!(error.to_string().contains("Content-Encoding 'br'"));
+    }
+
+    /// Starts an HTTP/1.1 server that responds to every request with `hello` and keeps connections
+    /// alive. Returns its address and the number of connections it has accepted.
+    fn run_counting_server() -> (std::net::SocketAddr, Arc<AtomicUsize>) {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let connections = Arc::new(AtomicUsize::new(0));
+        let accepted = Arc::clone(&connections);
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                accepted.fetch_add(1, Ordering::SeqCst);
+                let mut stream = stream.unwrap();
+                std::thread::spawn(move || {
+                    let mut request = Vec::new();
+                    let mut buf = [0; 1024];
+                    loop {
+                        match stream.read(&mut buf) {
+                            Ok(0) | Err(_) => return,
+                            Ok(n) => request.extend_from_slice(&buf[..n]),
+                        }
+                        // Requests are GETs without bodies, so each ends with a blank line.
+                        while let Some(end) = request.windows(4).position(|w| w == b"\r\n\r\n") {
+                            request.drain(..end + 4);
+                            stream
+                                .write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 5\r\n\r\nhello")
+                                .unwrap();
+                        }
+                    }
+                });
+            }
+        });
+        (addr, connections)
+    }
+
+    /// Test that fetches with a transport and its clones reuse a kept-alive connection.
+    #[test]
+    fn test_http_transport_reuses_connections() {
+        let (addr, connections) = run_counting_server();
+        let transport = HttpTransport::default();
+        let clone = transport.clone();
+        for (i, transport) in [&transport, &clone, &transport].iter().enumerate() {
+            let url = Url::parse(&format!("http://{}/file{}.txt", addr, i)).unwrap();
+            assert_eq!(read_to_end(transport.fetch(url).unwrap()), b"hello");
+        }
+        assert_eq!(connections.load(Ordering::SeqCst), 1);
+    }
+
+    /// Set an expectation in a test HTTP server to redirect `from` to `to` once.
+    fn create_redirect(from: &str, to: &str) -> httptest::Expectation {
+        Expectation::matching(request::method_path("GET", from.to_owned()))
+            .times(1)
+            .respond_with(status_code(302).append_header("location", to.to_owned()))
+    }
+
+    /// Test that redirects are followed.
+    #[test]
+    fn test_http_transport_follows_redirects() {
+        let server = Server::run();
+        server.expect(create_redirect("/a", "/b"));
+        server.expect(create_redirect("/b", "/file.txt"));
+        server.expect(
+            Expectation::matching(request::method_path("GET", "/file.txt"))
+                .times(1)
+                .respond_with(status_code(200).body("hello")),
+        );
+        let url = Url::from_str(server.url_str("/a").as_str()).unwrap();
+        let transport = HttpTransport::default();
+        assert_eq!(read_to_end(transport.fetch(url).unwrap()), b"hello");
+    }
+
+    /// Test that a redirect beyond `max_redirects` is an error, and isn't followed.
+    #[test]
+    fn test_http_transport_max_redirects() {
+        let server = Server::run();
+        server.expect(create_redirect("/a", "/b"));
+        server.expect(create_redirect("/b", "/file.txt"));
+        let url = Url::from_str(server.url_str("/a").as_str()).unwrap();
+        let transport = HttpTransportBuilder::new()
+            .max_redirects(1)
+            .build()
+            .unwrap();
+        let error = transport.fetch(url).err().unwrap();
+        assert!(matches!(error.kind(), TransportErrorKind::TooManyRedirects));
+
+        let server = Server::run();
+        server.expect(create_redirect("/a", "/file.txt"));
+        let url = Url::from_str(server.url_str("/a").as_str()).unwrap();
+        let transport = HttpTransportBuilder::new()
+            .max_redirects(0)
+            .build()
+            .unwrap();
+        let error = transport.fetch(url).err().unwrap();
+        assert!(matches!(error.kind(), TransportErrorKind::TooManyRedirects));
+    }
+
+    /// Test that the size limit of a file applies to the response it's redirected to.
+    #[test]
+    fn test_http_transport_redirect_size_limit() {
+        let server = Server::run();
+        let repo_dir = test_data().join("tuf-reference-impl");
+        server.expect(create_unsuccessful_get("metadata/2.root.json"));
+        server.expect(create_redirect("/metadata/timestamp.json", "/big.json"));
+        server.expect(
+            Expectation::matching(request::method_path("GET", "/big.json"))
+                .times(1)
+                .respond_with(status_code(200).body(vec![b' '; 2048])),
+        );
+        let metadata_base_url = Url::from_str(server.url_str("/metadata").as_str()).unwrap();
+        let targets_base_url = Url::from_str(server.url_str("/targets").as_str()).unwrap();
+        let error = RepositoryLoader::new(
+            File::open(repo_dir.join("metadata").join("1.root.json")).unwrap(),
+            metadata_base_url,
+            targets_base_url,
+        )
+        .transport(HttpTransport::default())
+        .limits(Limits {
+            max_timestamp_size: 1024,
+            ..Limits::default()
+        })
+        .load()
+        .err()
+        .unwrap();
+        assert!(error.to_string().contains("max_timestamp_size"));
+    }
+
+    /// Test that the served size of a target is probed with a `HEAD` request, and that servers
+    /// without support for `HEAD` don't cause an error.
+    #[test]
+    fn test_http_transport_target_served_size() {
+        let server = Server::run();
+        let repo_dir = test_data().join("tuf-reference-impl");
+        server.expect(create_successful_get("metadata/timestamp.json"));
+        server.expect(create_successful_get("metadata/snapshot.json"));
+        server.expect(create_successful_get("metadata/targets.json"));
+        server.expect(create_successful_get("metadata/role1.json"));
+        server.expect(create_successful_get("metadata/role2.json"));
+        server.expect(create_unsuccessful_get("metadata/2.root.json"));
+        server.expect(
+            Expectation::matching(request::method_path("HEAD", "/targets/file1.txt"))
+                .times(1)
+                .respond_with(
+                    status_code(200)
+                        .append_header("content-length", "31")
+                        .body("This is an example target file."),
+                ),
+        );
+        server.expect(
+            Expectation::matching(request::method_path("HEAD", "/targets/file2.txt"))
+                .times(1)
+                .respond_with(status_code(405)),
+        );
+        let metadata_base_url = Url::from_str(server.url_str("/metadata").as_str()).unwrap();
+        let targets_base_url = Url::from_str(server.url_str("/targets").as_str()).unwrap();
+        let repo = RepositoryLoader::new(
+            File::open(repo_dir.join("metadata").join("1.root.json")).unwrap(),
+            metadata_base_url,
+            targets_base_url,
+        )
+        .transport(HttpTransport::default())
+        .load()
+        .unwrap();
+
+        assert_eq!(repo.target_served_size("file1.txt").unwrap(), Some(31));
+        assert_eq!(repo.target_served_size("file2.txt").unwrap(), None);
+        assert_eq!(repo.target_served_size("no-such-target.txt").unwrap(), None);
+    }
+
+    /// Test that `fetch_range` sends a `Range` header and returns the partial content, and that a
+    /// server that ignores the header and sends the whole file is an `UnsupportedRange` error.
+    #[test]
+    fn test_http_transport_fetch_range() {
+        let server = Server::run();
+        server.expect(
+            Expectation::matching(all_of![
+                request::method_path("GET", "/file1.txt"),
+                request::headers(contains(("range", "bytes=8-17"))),
+            ])
+            .times(1)
+            .respond_with(status_code(206).body("an example")),
+        );
+        server.expect(
+            Expectation::matching(request::method_path("GET", "/file2.txt"))
+                .times(1)
+                .respond_with(status_code(200).body("This is an another example target file.")),
+        );
+        let transport = HttpTransport::default();
+        let url = Url::from_str(server.url_str("/file1.txt").as_str()).unwrap();
+        assert_eq!(
+            read_to_end(transport.fetch_range(url, 8, 10).unwrap()),
+            &b"an example"[..]
+        );
+        let url = Url::from_str(server.url_str("/file2.txt").as_str()).unwrap();
+        let error = transport.fetch_range(url, 8, 10).err().unwrap();
+        assert!(matches!(error.kind(), TransportErrorKind::UnsupportedRange));
+    }
+
+    /// A builder that retries quickly, so that tests of retries don't take long.
+    fn quick_retries() -> HttpTransportBuilder {
+        HttpTransportBuilder::new()
+            .tries(3)
+            .initial_backoff(Duration::from_millis(1))
+    }
+
+    /// Fetches a file from a server that always responds with `status`, expecting `times` requests,
+    /// and returns the kind of the error.
+    fn fetch_status(transport: HttpTransport, status: u16, times: usize) -> TransportErrorKind {
+        let server = Server::run();
+        server.expect(
+            Expectation::matching(request::method_path("GET", "/file.txt"))
+                .times(times)
+                .respond_with(status_code(status)),
+        );
+        let url = Url::from_str(server.url_str("/file.txt").as_str()).unwrap();
+        transport.fetch(url).err().unwrap().kind()
+    }
+
+    /// Test that server errors are retried by default.
+    #[test]
+    fn test_http_transport_retries_server_error() {
+        let kind = fetch_status(quick_retries().build().unwrap(), 503, 3);
+        assert!(matches!(kind, TransportErrorKind::ServerError));
+    }
+
+    /// Test that `429 Too Many Requests` is retried by default.
+    #[test]
+    fn test_http_transport_retries_too_many_requests() {
+        let kind = fetch_status(quick_retries().build().unwrap(), 429, 3);
+        assert!(matches!(kind, TransportErrorKind::TooManyRequests));
+    }
+
+    /// Test that other client errors aren't retried by default.
+    #[test]
+    fn test_http_transport_does_not_retry_rejected() {
+        let kind = fetch_status(quick_retries().build().unwrap(), 400, 1);
+        assert!(matches!(kind, TransportErrorKind::Rejected));
+    }
+
+    /// Test that a file that doesn't exist isn't retried by default.
+    #[test]
+    fn test_http_transport_does_not_retry_file_not_found() {
+        let kind = fetch_status(quick_retries().build().unwrap(), 404, 1);
+        assert!(matches!(kind, TransportErrorKind::FileNotFound));
+    }
+
+    /// Test that a client set with `with_client` is used for every try, and is still retried.
+    #[test]
+    fn test_http_transport_with_client() {
+        let mut headers = reqwest::header::HeaderMap::new();
+        headers.insert("x-app", reqwest::header::HeaderValue::from_static("custom"));
+        let client = reqwest::blocking::Client::builder()
+            .default_headers(headers)
+            .build()
+            .unwrap();
+        let server = Server::run();
+        server.expect(
+            Expectation::matching(all_of![
+                request::method_path("GET", "/file.txt"),
+                request::headers(contains(("x-app", "custom"))),
+            ])
+            .times(3)
+            .respond_with(status_code(503)),
+        );
+        let transport = quick_retries().with_client(client).build().unwrap();
+        let url = Url::from_str(server.url_str("/file.txt").as_str()).unwrap();
+        let kind = transport.fetch(url).err().unwrap().kind();
+        assert!(matches!(kind, TransportErrorKind::ServerError));
+    }
+
+    /// Test that a connection failure is retried by default, and that `retry_on` decides each
+    /// retry.
+    #[test]
+    fn test_http_transport_retries_connection() {
+        // Find a port that nothing is listening on.
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let calls = Arc::new(AtomicUsize::new(0));
+        let predicate_calls = Arc::clone(&calls);
+        let transport = quick_retries()
+            .retry_on(move |kind| {
+                predicate_calls.fetch_add(1, Ordering::SeqCst);
+                HttpTransportBuilder::default_retry_on(kind)
+            })
+            .build()
+            .unwrap();
+        let url = Url::parse(&format!("http://127.0.0.1:{}/file.txt", port)).unwrap();
+        let error = transport.fetch(url).err().unwrap();
+        assert!(matches!(error.kind(), TransportErrorKind::Connection));
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+
+    /// Test that `retry_on` overrides the default.
+    #[test]
+    fn test_http_transport_retry_on() {
+        let kind = fetch_status(quick_retries().retry_on(|_| false).build().unwrap(), 503, 1);
+        assert!(matches!(kind, TransportErrorKind::ServerError));
+        let kind = fetch_status(quick_retries().retry_on(|_| true).build().unwrap(), 400, 3);
+        assert!(matches!(kind, TransportErrorKind::Rejected));
+    }
 }
 
 #[cfg(feature = "http")]
@@ -173,7 +726,8 @@ mod http_integ {
                 // we don't want the test to take forever so we use small pauses
                 .initial_backoff(std::time::Duration::from_nanos(100))
                 .max_backoff(std::time::Duration::from_millis(1))
-                .build();
+                .build()
+                .unwrap();
             let root_path = tuf_reference_impl_root_json();
 
             RepositoryLoader::new(
@@ -1,10 +1,14 @@
 // Copyright 2019 Amazon.com, Inc. or its affiliates. All Rights Reserved.
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
+// These tests read the system clock, which needs the `clock` feature.
+#![cfg(feature = "clock")]
+
 use std::fs::File;
 use tempfile::TempDir;
 use test_utils::{dir_url, read_to_end, test_data};
-use tough::{FilesystemTransport, Limits, Repository, RepositoryLoader};
+use tough::schema::{RawSigned, RoleType, Targets};
+use tough::{FilesystemTransport, Limits, Repository, RepositoryLoader, TargetName};
 
 mod test_utils;
 
@@ -24,13 +28,43 @@ fn test_tuf_reference_impl() {
     .load()
     .unwrap();
     assert_tuf_reference_impl(&repo);
+
+    let repo = RepositoryLoader::new_same_base(
+        File::open(base.join("metadata").join("1.root.json")).unwrap(),
+        dir_url(&base),
+    )
+    .load()
+    .unwrap();
+    assert_tuf_reference_impl(&repo);
+
+    let root = std::fs::read(base.join("metadata").join("1.root.json")).unwrap();
+    let repo = RepositoryLoader::from_root_bytes(
+        &root,
+        dir_url(base.join("metadata")),
+        dir_url(base.join("targets")),
+    )
+    .load()
+    .unwrap();
+    assert_tuf_reference_impl(&repo);
+
+    for (role, file) in &[
+        (RoleType::Root, "1.root.json"),
+        (RoleType::Timestamp, "timestamp.json"),
+        (RoleType::Snapshot, "snapshot.json"),
+        (RoleType::Targets, "targets.json"),
+    ] {
+        assert_eq!(
+            repo.raw_metadata(*role).unwrap(),
+            std::fs::read(base.join("metadata").join(file)).unwrap()
+        );
+    }
+    assert!(repo.raw_metadata(RoleType::DelegatedTargets).is_none());
 }
 
 fn assert_tuf_reference_impl(repo: &Repository) {
-    assert_eq!(
-        read_to_end(repo.read_target("file1.txt").unwrap().unwrap()),
-        &b"This is an example target file."[..]
-    );
+    let reader = repo.read_target("file1.txt").unwrap().unwrap();
+    assert_eq!(reader.expected_len(), Some(31));
+    assert_eq!(read_to_end(reader), &b"This is an example target file."[..]);
     assert_eq!(
         read_to_end(repo.read_target("file2.txt").unwrap().unwrap()),
         &b"This is an another example target file."[..]
@@ -54,6 +88,230 @@ fn assert_tuf_reference_impl(repo: &Repository) {
         .as_ref()
         .unwrap()
         .target_is_delegated(&"file3.txt".to_string()));
+
+    assert!(!repo.consistent_snapshot());
+    repo.validate_snapshot_completeness().unwrap();
+
+    assert_eq!(
+        repo.target_names()
+            .iter()
+            .map(TargetName::as_str)
+            .collect::<Vec<_>>(),
+        vec!["file1.txt", "file2.txt", "file3.txt"]
+    );
+    assert_eq!(
+        repo.all_targets().map(|(name, _)| name).collect::<Vec<_>>(),
+        vec!["file1.txt", "file2.txt", "file3.txt"]
+    );
+    assert!(repo.contains_target("file3.txt"));
+    assert!(!repo.contains_target("file4.txt"));
+
+    assert_eq!(repo.find_target_role("file3.txt").unwrap().name, "role1");
+    assert!(repo.find_target_role("file1.txt").is_none());
+    assert!(repo.find_target_role("file4.txt").is_none());
+
+    let names = ["file2.txt", "file4.txt", "file3.txt"];
+    let results: Vec<_> = repo
+        .read_targets(&names)
+        .map(|(name, result)| (name, result.unwrap().map(read_to_end)))
+        .collect();
+    assert_eq!(
+        results,
+        vec![
+            (
+                "file2.txt",
+                Some(b"This is an another example target file.".to_vec())
+            ),
+            ("file4.txt", None),
+            ("file3.txt", Some(b"This is role1's target file.".to_vec())),
+        ]
+    );
+
+    assert_eq!(
+        repo.delegated_roles()
+            .map(|role| role.name.as_str())
+            .collect::<Vec<_>>(),
+        vec!["role1", "role2"]
+    );
+
+    let versions = repo.metadata_versions();
+    assert_eq!(versions.root.get(), 1);
+    assert_eq!(versions.timestamp.get(), 1);
+    assert_eq!(versions.snapshot.get(), 1);
+    assert_eq!(versions.targets.get(), 1);
+    assert_eq!(
+        versions
+            .delegated
+            .iter()
+            .map(|(name, version)| (name.as_str(), version.get()))
+            .collect::<Vec<_>>(),
+        vec![("role1", 1), ("role2", 1)]
+    );
+    assert_eq!(
+        versions.timestamp,
+        repo.metadata_version(RoleType::Timestamp).unwrap()
+    );
+    assert!(repo.metadata_version(RoleType::DelegatedTargets).is_none());
+
+    assert_eq!(
+        repo.signing_key_ids(RoleType::Timestamp).unwrap(),
+        repo.root()
+            .signed
+            .keys(RoleType::Timestamp)
+            .map(|key| key.key_id().unwrap())
+            .collect::<Vec<_>>()
+    );
+    assert!(repo.signing_key_ids(RoleType::DelegatedTargets).is_none());
+
+    assert_eq!(
+        repo.target_custom::<String>("file1.txt", "file_permissions")
+            .unwrap()
+            .unwrap(),
+        "0644"
+    );
+    assert!(repo
+        .target_custom::<u32>("file1.txt", "file_permissions")
+        .unwrap()
+        .is_err());
+    assert!(repo
+        .target_custom::<String>("file3.txt", "file_permissions")
+        .is_none());
+    assert!(repo
+        .target_custom::<String>("file4.txt", "file_permissions")
+        .is_none());
+}
+
+/// When a delegated role lists a target that's also listed by the top-level targets role, the
+/// merged view of the targets uses the top-level one, as target lookups do.
+#[test]
+fn test_targets_map_precedence() {
+    let base = test_data().join("tuf-reference-impl");
+    let repo = RepositoryLoader::new(
+        File::open(base.join("metadata").join("1.root.json")).unwrap(),
+        dir_url(base.join("metadata")),
+        dir_url(base.join("targets")),
+    )
+    .load()
+    .unwrap();
+
+    let mut targets = repo.targets().clone();
+    let mut shadowed = targets.signed.targets["file1.txt"].clone();
+    shadowed.length = 1;
+    targets
+        .signed
+        .delegated_targets_mut("role1")
+        .unwrap()
+        .signed
+        .add_target("file1.txt", shadowed);
+
+    let targets_map = targets.signed.targets_map();
+    assert_eq!(targets_map["file1.txt"].length, 31);
+    assert_eq!(
+        targets_map["file1.txt"],
+        targets.signed.find_target("file1.txt").unwrap()
+    );
+}
+
+/// Each role's keys and threshold can be read from the loaded repository.
+#[test]
+fn test_role_keys() {
+    let base = test_data().join("tuf-reference-impl");
+    let repo = RepositoryLoader::new(
+        File::open(base.join("metadata").join("1.root.json")).unwrap(),
+        dir_url(base.join("metadata")),
+        dir_url(base.join("targets")),
+    )
+    .load()
+    .unwrap();
+    let key_ids = |keys: Vec<&tough::schema::key::Key>| {
+        keys.iter()
+            .map(|key| hex::encode(key.key_id().unwrap()))
+            .collect::<Vec<_>>()
+    };
+
+    let (keys, threshold) = repo.role_keys(RoleType::Timestamp);
+    assert_eq!(
+        key_ids(keys),
+        vec!["8a1c4a3ac2d515dec982ba9910c5fd79b91ae57f625b9cff25d06bf0a61c1758"]
+    );
+    assert_eq!(threshold, 1);
+    let (keys, threshold) = repo.role_keys(RoleType::DelegatedTargets);
+    assert!(keys.is_empty());
+    assert_eq!(threshold, 0);
+
+    let (keys, threshold) = repo.delegated_role_keys("role2").unwrap();
+    assert_eq!(
+        key_ids(keys),
+        vec!["c8022fa1e9b9cb239a6b362bbdffa9649e61ad2cb699d2e4bc4fdf7930a0e64a"]
+    );
+    assert_eq!(threshold, 1);
+    assert!(repo.delegated_role_keys("missing").is_none());
+}
+
+/// The bytes that loaded metadata was parsed from can be parsed again and still verify.
+#[test]
+fn test_signed_bytes_round_trip() {
+    let base = test_data().join("tuf-reference-impl");
+    let repo = RepositoryLoader::new(
+        File::open(base.join("metadata").join("1.root.json")).unwrap(),
+        dir_url(base.join("metadata")),
+        dir_url(base.join("targets")),
+    )
+    .load()
+    .unwrap();
+
+    let bytes = repo.raw_metadata(RoleType::Targets).unwrap();
+    let targets = RawSigned::<Targets>::from_bytes(bytes).unwrap();
+    assert_eq!(targets.to_signed_bytes(), bytes);
+    assert_eq!(targets.signed().signatures, repo.targets().signatures);
+    repo.root().signed.verify_role(targets.signed()).unwrap();
+
+    let bytes = repo.delegated_raw_metadata("role1").unwrap();
+    assert_eq!(
+        bytes,
+        &std::fs::read(base.join("metadata").join("role1.json")).unwrap()[..]
+    );
+    let role1_targets = RawSigned::<Targets>::from_bytes(bytes).unwrap();
+    repo.targets()
+        .signed
+        .delegations
+        .as_ref()
+        .unwrap()
+        .verify_role(role1_targets.signed(), "role1")
+        .unwrap();
+    assert!(repo.delegated_raw_metadata("missing").is_none());
+}
+
+/// Targets can be looked up by a `TargetName`, and unsafe names are rejected rather than treated
+/// as missing targets.
+#[test]
+fn test_target_names() {
+    let base = test_data().join("tuf-reference-impl");
+    let repo = RepositoryLoader::new(
+        File::open(base.join("metadata").join("1.root.json")).unwrap(),
+        dir_url(base.join("metadata")),
+        dir_url(base.join("targets")),
+    )
+    .load()
+    .unwrap();
+
+    let name = TargetName::new("file1.txt").unwrap();
+    assert_eq!(
+        read_to_end(repo.read_target(&name).unwrap().unwrap()),
+        read_to_end(repo.read_target(name.as_str()).unwrap().unwrap())
+    );
+    assert!(repo.read_target(name).unwrap().is_some());
+    assert!(repo.read_target("file4.txt").unwrap().is_none());
+    for name in &["", "../file1.txt", "metadata/../file1.txt", "/file1.txt"] {
+        assert!(
+            matches!(
+                repo.read_target(*name),
+                Err(tough::error::Error::UnsafeTargetName { .. })
+            ),
+            "{:?} was accepted",
+            name
+        );
+    }
 }
 
 /// Test that `tough` can process repositories generated by [`tuf`], the reference Python
@@ -74,6 +332,10 @@ fn test_tuf_reference_impl_default_transport() {
         max_targets_size: 2000,
         max_timestamp_size: 3000,
         max_root_updates: 1,
+        max_snapshot_size: 4000,
+        max_delegated_roles: 2,
+        max_total_download: 5000,
+        max_root_total_size: 6000,
     })
     .datastore(datastore.path())
     .load()
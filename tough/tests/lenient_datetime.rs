@@ -0,0 +1,129 @@
+// Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+// These tests read the system clock, which needs the `clock` feature.
+#![cfg(feature = "clock")]
+
+use chrono::{TimeZone, Utc};
+use olpc_cjson::CanonicalFormatter;
+use ring::rand::SystemRandom;
+use serde::Serialize;
+use serde_json::Value;
+use std::fs::{self, File};
+use std::path::Path;
+use tempfile::TempDir;
+use test_utils::{key, simple_rsa_editor, simple_rsa_loader};
+use tough::error::Error;
+use tough::schema::{RawSigned, RoleType, Signature, Signed, Timestamp};
+
+mod test_utils;
+
+/// Writes a repository to `metadata_dir` whose timestamp metadata expires at `expires`, written
+/// exactly as given.
+fn write_repo_with_timestamp_expires(metadata_dir: &Path, expires: &str) {
+    simple_rsa_editor(1)
+        .sign(&key("snakeoil.pem"))
+        .unwrap()
+        .write(metadata_dir)
+        .unwrap();
+
+    // Re-sign the timestamp metadata with `expires` replaced.
+    let timestamp_path = metadata_dir.join("timestamp.json");
+    let mut timestamp: Signed<Value> =
+        serde_json::from_reader(File::open(&timestamp_path).unwrap()).unwrap();
+    timestamp.signed["expires"] = Value::String(expires.to_owned());
+    let mut canonical = Vec::new();
+    let mut ser = serde_json::Serializer::with_formatter(&mut canonical, CanonicalFormatter::new());
+    timestamp.signed.serialize(&mut ser).unwrap();
+    let sig = key("snakeoil.pem")[0]
+        .as_sign()
+        .unwrap()
+        .sign(&canonical, &SystemRandom::new())
+        .unwrap();
+    let keyid = timestamp.signatures[0].keyid.clone();
+    timestamp.signatures = vec![Signature {
+        keyid,
+        sig: sig.into(),
+    }];
+    fs::write(&timestamp_path, serde_json::to_vec(&timestamp).unwrap()).unwrap();
+}
+
+fn load(metadata_dir: &Path, lenient_datetime: bool) -> tough::error::Result<tough::Repository> {
+    simple_rsa_loader(metadata_dir, metadata_dir)
+        .lenient_datetime(lenient_datetime)
+        .load()
+}
+
+/// An `expires` without an offset is rejected by default, and accepted as UTC when datetimes are
+/// lenient.
+#[test]
+fn expires_without_offset() {
+    let metadata = TempDir::new().unwrap();
+    write_repo_with_timestamp_expires(metadata.path(), "2999-01-02T03:04:05");
+
+    let err = load(metadata.path(), false).unwrap_err();
+    assert!(
+        matches!(err, Error::ParseMetadata { .. }),
+        "unexpected error: {}",
+        err
+    );
+
+    let repo = load(metadata.path(), true).unwrap();
+    assert_eq!(
+        repo.timestamp().signed.expires,
+        Utc.ymd(2999, 1, 2).and_hms(3, 4, 5)
+    );
+}
+
+/// A lenient `expires` doesn't get around signature verification.
+#[test]
+fn lenient_expires_is_signed() {
+    let metadata = TempDir::new().unwrap();
+    write_repo_with_timestamp_expires(metadata.path(), "2999-01-02 03:04:05");
+    let timestamp_path = metadata.path().join("timestamp.json");
+    let timestamp = fs::read_to_string(&timestamp_path).unwrap();
+    fs::write(
+        &timestamp_path,
+        timestamp.replace("2999-01-02 03:04:05", "2999-01-02 03:04:06"),
+    )
+    .unwrap();
+
+    let err = load(metadata.path(), true).unwrap_err();
+    assert!(
+        matches!(err, Error::VerifyMetadata { .. }),
+        "unexpected error: {}",
+        err
+    );
+}
+
+/// Metadata that serde doesn't reproduce, here a lenient `expires` written with an offset rather
+/// than `Z`, can only be republished from the bytes that were verified: re-serializing the parsed
+/// metadata changes what was signed.
+#[test]
+fn republish_signed_bytes() {
+    let metadata = TempDir::new().unwrap();
+    write_repo_with_timestamp_expires(metadata.path(), "2999-01-02T03:04:05+00:00");
+    let repo = load(metadata.path(), true).unwrap();
+    let timestamp_path = metadata.path().join("timestamp.json");
+
+    fs::write(
+        &timestamp_path,
+        serde_json::to_vec(repo.timestamp()).unwrap(),
+    )
+    .unwrap();
+    let err = load(metadata.path(), true).unwrap_err();
+    assert!(
+        matches!(err, Error::VerifyMetadata { .. }),
+        "unexpected error: {}",
+        err
+    );
+
+    let bytes = repo.raw_metadata(RoleType::Timestamp).unwrap();
+    let timestamp = RawSigned::<Timestamp>::from_bytes(bytes).unwrap();
+    fs::write(&timestamp_path, timestamp.to_signed_bytes()).unwrap();
+    let republished = load(metadata.path(), true).unwrap();
+    assert_eq!(
+        republished.timestamp().signed.expires,
+        Utc.ymd(2999, 1, 2).and_hms(3, 4, 5)
+    );
+}
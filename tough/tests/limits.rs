@@ -0,0 +1,208 @@
+// Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+// These tests read the system clock, which needs the `clock` feature.
+#![cfg(feature = "clock")]
+
+use ring::rand::SystemRandom;
+use std::fs::{self, File};
+use std::num::NonZeroU64;
+use std::path::Path;
+use tempfile::TempDir;
+use test_utils::{key, reference_impl_loader, simple_rsa_editor, simple_rsa_loader, test_data};
+use tough::editor::signed::SignedRole;
+use tough::schema::{KeyHolder, Root, Signed, Timestamp};
+use tough::Limits;
+
+mod test_utils;
+
+/// Writes a repository to `metadata_dir` whose timestamp metadata doesn't list the length of the
+/// snapshot metadata.
+fn write_repo_without_snapshot_length(metadata_dir: &Path) {
+    let root_path = test_data().join("simple-rsa").join("root.json");
+    simple_rsa_editor(1)
+        .sign(&key("snakeoil.pem"))
+        .unwrap()
+        .write(metadata_dir)
+        .unwrap();
+
+    // Re-sign the timestamp metadata without the snapshot length.
+    let timestamp_path = metadata_dir.join("timestamp.json");
+    let mut timestamp: Signed<Timestamp> =
+        serde_json::from_reader(File::open(&timestamp_path).unwrap()).unwrap();
+    timestamp
+        .signed
+        .meta
+        .get_mut("snapshot.json")
+        .unwrap()
+        .length = None;
+    let root: Signed<Root> = serde_json::from_reader(File::open(&root_path).unwrap()).unwrap();
+    SignedRole::new(
+        timestamp.signed,
+        &KeyHolder::Root(root.signed),
+        &key("snakeoil.pem"),
+        &SystemRandom::new(),
+    )
+    .unwrap()
+    .write(metadata_dir, false)
+    .unwrap();
+}
+
+fn load(metadata_dir: &Path, max_snapshot_size: u64) -> tough::error::Result<()> {
+    simple_rsa_loader(metadata_dir, metadata_dir)
+        .limits(Limits {
+            max_snapshot_size,
+            ..Limits::default()
+        })
+        .load()
+        .map(drop)
+}
+
+/// When timestamp metadata doesn't list the length of snapshot metadata, the snapshot metadata is
+/// bounded by `max_snapshot_size` instead.
+#[test]
+fn max_snapshot_size() {
+    let metadata = TempDir::new().unwrap();
+    write_repo_without_snapshot_length(metadata.path());
+
+    load(metadata.path(), Limits::default().max_snapshot_size).unwrap();
+    let err = load(metadata.path(), 10).unwrap_err();
+    assert!(
+        err.to_string().contains("max_snapshot_size"),
+        "unexpected error: {}",
+        err
+    );
+}
+
+/// Snapshot metadata that isn't bounded by a length in timestamp metadata is still checked against
+/// the hashes listed there.
+#[test]
+fn snapshot_without_length_checks_hashes() {
+    let metadata = TempDir::new().unwrap();
+    write_repo_without_snapshot_length(metadata.path());
+
+    // Add whitespace to the snapshot metadata, which changes its hash but not its contents.
+    let snapshot_path = metadata.path().join("1.snapshot.json");
+    let mut snapshot = fs::read(&snapshot_path).unwrap();
+    snapshot.push(b'\n');
+    fs::write(&snapshot_path, snapshot).unwrap();
+
+    let err = load(metadata.path(), Limits::default().max_snapshot_size).unwrap_err();
+    assert!(
+        err.to_string().contains("Hash mismatch"),
+        "unexpected error: {}",
+        err
+    );
+}
+
+/// The delegated roles of `tuf-reference-impl`, `role1` and `role2`, which `role1` delegates to,
+/// count toward `max_delegated_roles` together.
+#[test]
+fn max_delegated_roles() {
+    let base = test_data().join("tuf-reference-impl");
+    let load = |max_delegated_roles| {
+        reference_impl_loader(base.join("metadata"))
+            .limits(Limits {
+                max_delegated_roles,
+                ..Limits::default()
+            })
+            .load()
+    };
+
+    load(2).unwrap();
+    let err = load(1).unwrap_err();
+    assert!(
+        err.to_string().contains("delegated roles"),
+        "unexpected error: {}",
+        err
+    );
+}
+
+/// All the metadata fetched while loading `tuf-reference-impl` counts toward `max_total_download`.
+#[test]
+fn max_total_download() {
+    let base = test_data().join("tuf-reference-impl");
+    let metadata_size: u64 = [
+        "timestamp.json",
+        "snapshot.json",
+        "targets.json",
+        "role1.json",
+        "role2.json",
+    ]
+    .iter()
+    .map(|file| {
+        fs::metadata(base.join("metadata").join(file))
+            .unwrap()
+            .len()
+    })
+    .sum();
+    let load = |max_total_download| {
+        reference_impl_loader(base.join("metadata"))
+            .limits(Limits {
+                max_total_download,
+                ..Limits::default()
+            })
+            .load()
+    };
+
+    load(metadata_size).unwrap();
+    let err = load(metadata_size - 1).unwrap_err();
+    assert!(
+        err.to_string().contains("max_total_download"),
+        "unexpected error: {}",
+        err
+    );
+}
+
+/// Writes a repository to `metadata_dir` whose root metadata was updated to version 5, each
+/// version signed with the same keys, and returns the total size of versions 2 through 5.
+fn write_repo_with_root_chain(metadata_dir: &Path) -> u64 {
+    let root_path = test_data().join("simple-rsa").join("root.json");
+    simple_rsa_editor(1)
+        .sign(&key("snakeoil.pem"))
+        .unwrap()
+        .write(metadata_dir)
+        .unwrap();
+
+    let root: Signed<Root> = serde_json::from_reader(File::open(&root_path).unwrap()).unwrap();
+    let mut total = 0;
+    for version in 2..=5 {
+        let mut new_root = root.signed.clone();
+        new_root.version = NonZeroU64::new(version).unwrap();
+        let signed = SignedRole::new(
+            new_root,
+            &KeyHolder::Root(root.signed.clone()),
+            &key("snakeoil.pem"),
+            &SystemRandom::new(),
+        )
+        .unwrap();
+        signed.write(metadata_dir, true).unwrap();
+        total += signed.length();
+    }
+    total
+}
+
+/// The root metadata files downloaded while updating the root count toward `max_root_total_size`,
+/// even though each of them is well within `max_root_size`.
+#[test]
+fn max_root_total_size() {
+    let metadata = TempDir::new().unwrap();
+    let root_chain_size = write_repo_with_root_chain(metadata.path());
+    let load = |max_root_total_size| {
+        simple_rsa_loader(metadata.path(), metadata.path())
+            .limits(Limits {
+                max_root_total_size,
+                ..Limits::default()
+            })
+            .load()
+    };
+
+    let repo = load(root_chain_size).unwrap();
+    assert_eq!(repo.root().signed.version.get(), 5);
+    let err = load(root_chain_size - 1).unwrap_err();
+    assert!(
+        err.to_string().contains("max_root_total_size"),
+        "unexpected error: {}",
+        err
+    );
+}
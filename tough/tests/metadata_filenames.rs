@@ -0,0 +1,59 @@
+// Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+// These tests read the system clock, which needs the `clock` feature.
+#![cfg(feature = "clock")]
+
+use std::fs;
+use tempfile::TempDir;
+use test_utils::{read_to_end, reference_impl_loader, test_data};
+
+mod test_utils;
+
+/// Copies the `tuf-reference-impl` metadata to a new directory, renaming the timestamp, snapshot,
+/// and targets metadata files the way a non-conforming mirror might.
+fn renamed_metadata() -> TempDir {
+    let source = test_data().join("tuf-reference-impl").join("metadata");
+    let dir = TempDir::new().unwrap();
+    for entry in fs::read_dir(&source).unwrap() {
+        let name = entry.unwrap().file_name().into_string().unwrap();
+        let renamed = match name.as_str() {
+            "timestamp.json" => "timestamp.txt",
+            "snapshot.json" => "snapshot.meta.json",
+            "targets.json" => "top-targets.json",
+            other => other,
+        };
+        fs::copy(source.join(&name), dir.path().join(renamed)).unwrap();
+    }
+    dir
+}
+
+/// With the filenames overridden, metadata is fetched from the renamed files, but still stored in
+/// the datastore under the spec filenames.
+#[test]
+fn overridden_filenames() {
+    let metadata = renamed_metadata();
+    let datastore = TempDir::new().unwrap();
+    let repo = reference_impl_loader(metadata.path())
+        .timestamp_filename("timestamp.txt")
+        .snapshot_filename("snapshot.meta.json")
+        .targets_filename("top-targets.json")
+        .datastore(datastore.path())
+        .load()
+        .unwrap();
+    assert_eq!(
+        read_to_end(repo.read_target("file3.txt").unwrap().unwrap()),
+        &b"This is role1's target file."[..]
+    );
+    for name in &["timestamp.json", "snapshot.json", "targets.json"] {
+        assert!(datastore.path().join(name).exists());
+    }
+}
+
+/// The spec filenames are used by default.
+#[test]
+fn default_filenames() {
+    let metadata = renamed_metadata();
+    let error = reference_impl_loader(metadata.path()).load().unwrap_err();
+    assert!(error.to_string().contains("timestamp.json"));
+}
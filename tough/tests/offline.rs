@@ -0,0 +1,140 @@
+// Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+// These tests read the system clock, which needs the `clock` feature.
+#![cfg(feature = "clock")]
+
+use std::fs::{self, File};
+use std::path::Path;
+use tempfile::TempDir;
+use test_utils::{dir_url, read_to_end, reference_impl_loader, test_data};
+use tough::{NoNetworkTransport, RepositoryLoader, TransportErrorKind};
+
+mod test_utils;
+
+/// Loads `tuf-reference-impl`, fetching metadata from `metadata_dir`.
+fn load(metadata_dir: &Path, datastore: &Path, offline: bool) -> tough::error::Result<()> {
+    let mut loader = reference_impl_loader(metadata_dir).datastore(datastore);
+    if offline {
+        loader = loader.offline();
+    }
+    let repo = loader.load()?;
+    assert_eq!(
+        read_to_end(repo.read_target("file3.txt").unwrap().unwrap()),
+        &b"This is role1's target file."[..]
+    );
+    Ok(())
+}
+
+/// Once a repository has been loaded, it can be loaded again from the datastore alone.
+#[test]
+fn offline_load_from_datastore() {
+    let datastore = TempDir::new().unwrap();
+    let metadata = test_data().join("tuf-reference-impl").join("metadata");
+    load(&metadata, datastore.path(), false).unwrap();
+
+    // The metadata URL now points at an empty directory, so nothing can be fetched from it.
+    let empty = TempDir::new().unwrap();
+    load(empty.path(), datastore.path(), true).unwrap();
+}
+
+/// An offline load fails if the datastore does not have the metadata it needs.
+#[test]
+fn offline_load_empty_datastore() {
+    let datastore = TempDir::new().unwrap();
+    let metadata = test_data().join("tuf-reference-impl").join("metadata");
+    match load(&metadata, datastore.path(), true) {
+        Err(tough::error::Error::Transport { source, .. }) => {
+            assert!(matches!(source.kind(), TransportErrorKind::FileNotFound));
+            assert!(source.to_string().contains("timestamp.json"));
+        }
+        Err(e) => panic!("expected a transport error, got: {}", e),
+        Ok(()) => panic!("offline load should fail without cached metadata"),
+    }
+}
+
+/// Metadata read from the datastore is verified just like fetched metadata.
+#[test]
+fn offline_load_verifies_datastore() {
+    let datastore = TempDir::new().unwrap();
+    let metadata = test_data().join("tuf-reference-impl").join("metadata");
+    load(&metadata, datastore.path(), false).unwrap();
+
+    // Change a target's length in the stored targets metadata. Its hash no longer matches the one
+    // listed in snapshot metadata, and its signature is no longer valid.
+    let targets_path = datastore.path().join("targets.json");
+    let targets = fs::read_to_string(&targets_path).unwrap();
+    assert!(targets.contains("\"length\": 31"));
+    fs::write(
+        &targets_path,
+        targets.replace("\"length\": 31", "\"length\": 32"),
+    )
+    .unwrap();
+
+    assert!(load(&metadata, datastore.path(), true).is_err());
+}
+
+/// After `prefetch`, an offline load can start from an older trusted root than the one the
+/// repository was loaded with, because the whole root chain is in the datastore.
+#[test]
+fn offline_load_after_prefetch() {
+    let base = test_data().join("rotated-root");
+    let datastore = TempDir::new().unwrap();
+    let repo = RepositoryLoader::new(
+        File::open(base.join("2.root.json")).unwrap(),
+        dir_url(&base),
+        dir_url(base.join("targets")),
+    )
+    .datastore(datastore.path())
+    .load()
+    .unwrap();
+    repo.prefetch().unwrap();
+    assert!(datastore.path().join("1.root.json").exists());
+    assert!(datastore.path().join("2.root.json").exists());
+    drop(repo);
+
+    let empty = TempDir::new().unwrap();
+    let repo = RepositoryLoader::new(
+        File::open(base.join("1.root.json")).unwrap(),
+        dir_url(empty.path()),
+        dir_url(base.join("targets")),
+    )
+    .datastore(datastore.path())
+    .offline()
+    .load()
+    .unwrap();
+    assert_eq!(repo.root().signed.version.get(), 2);
+}
+
+/// With `NoNetworkTransport`, an offline load reads metadata from the datastore, and any attempt to
+/// fetch a file fails with an `Offline` error.
+#[test]
+fn offline_load_no_network() {
+    let datastore = TempDir::new().unwrap();
+    let base = test_data().join("tuf-reference-impl");
+    load(&base.join("metadata"), datastore.path(), false).unwrap();
+
+    let loader = || {
+        reference_impl_loader(base.join("metadata"))
+            .transport(NoNetworkTransport)
+            .datastore(datastore.path())
+    };
+    let repo = loader().offline().load().unwrap();
+    assert!(repo.targets().signed.targets.contains_key("file1.txt"));
+    match repo.read_target("file1.txt") {
+        Err(tough::error::Error::Transport { source, .. }) => {
+            assert!(matches!(source.kind(), TransportErrorKind::Offline));
+        }
+        Err(e) => panic!("expected an offline transport error, got: {}", e),
+        Ok(_) => panic!("NoNetworkTransport should not fetch targets"),
+    }
+    drop(repo);
+
+    match loader().load() {
+        Err(tough::error::Error::Transport { source, .. }) => {
+            assert!(matches!(source.kind(), TransportErrorKind::Offline));
+        }
+        Err(e) => panic!("expected an offline transport error, got: {}", e),
+        Ok(_) => panic!("NoNetworkTransport should not fetch metadata"),
+    }
+}
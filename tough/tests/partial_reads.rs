@@ -0,0 +1,107 @@
+// Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+// These tests read the system clock, which needs the `clock` feature.
+#![cfg(feature = "clock")]
+
+use std::fs::File;
+use std::io::Read;
+use test_utils::{read_to_end, reference_impl_loader, test_data};
+use tough::error::Error;
+use tough::{
+    FilesystemTransport, Repository, RepositoryLoader, Transport, TransportError,
+    TransportErrorKind,
+};
+use url::Url;
+
+mod test_utils;
+
+/// A `Transport` that serves local files, but can't fetch part of a file.
+#[derive(Debug, Clone, Copy)]
+struct NoRangeTransport;
+
+impl Transport for NoRangeTransport {
+    fn fetch(&self, url: Url) -> Result<Box<dyn Read + Send>, TransportError> {
+        FilesystemTransport.fetch(url)
+    }
+}
+
+fn loader() -> RepositoryLoader<File> {
+    let base = test_data().join("tuf-reference-impl");
+    reference_impl_loader(base.join("metadata"))
+}
+
+fn load_allowed() -> Repository {
+    loader()
+        .allow_unverified_partial_reads(true)
+        .load()
+        .unwrap()
+}
+
+/// Partial reads aren't allowed unless they're enabled.
+#[test]
+fn partial_reads_not_allowed_by_default() {
+    let repo = loader().load().unwrap();
+    assert!(matches!(
+        repo.read_target_range("file1.txt", 0, 4),
+        Err(Error::PartialReadsNotAllowed { .. })
+    ));
+}
+
+/// A range of a target is read from the filesystem, including one that ends at the end of the
+/// target.
+#[test]
+fn read_target_range() {
+    let repo = load_allowed();
+    assert_eq!(
+        read_to_end(repo.read_target_range("file1.txt", 8, 10).unwrap().unwrap()),
+        &b"an example"[..]
+    );
+    assert_eq!(
+        read_to_end(
+            repo.read_target_range("file1.txt", 19, 12)
+                .unwrap()
+                .unwrap()
+        ),
+        &b"target file."[..]
+    );
+    assert!(read_to_end(repo.read_target_range("file1.txt", 31, 0).unwrap().unwrap()).is_empty());
+    assert!(repo
+        .read_target_range("no-such-file.txt", 0, 1)
+        .unwrap()
+        .is_none());
+}
+
+/// A range that ends past the target's length is an error.
+#[test]
+fn read_target_range_out_of_bounds() {
+    let repo = load_allowed();
+    assert!(matches!(
+        repo.read_target_range("file1.txt", 20, 12),
+        Err(Error::TargetRangeOutOfBounds { length: 31, .. })
+    ));
+    assert!(matches!(
+        repo.read_target_range("file1.txt", u64::MAX, 2),
+        Err(Error::TargetRangeOutOfBounds { .. })
+    ));
+}
+
+/// A transport that can't fetch part of a file fails with an error saying so.
+#[test]
+fn read_target_range_unsupported() {
+    let repo = loader()
+        .transport(NoRangeTransport)
+        .allow_unverified_partial_reads(true)
+        .load()
+        .unwrap();
+    match repo.read_target_range("file1.txt", 0, 4) {
+        Err(Error::Transport { source, .. }) => {
+            assert!(matches!(
+                source.kind(),
+                TransportErrorKind::UnsupportedRange
+            ));
+        }
+        Err(e) => panic!("expected a transport error, got: {}", e),
+        Ok(_) => panic!("a transport without ranges can't read part of a target"),
+    }
+}
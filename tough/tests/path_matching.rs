@@ -0,0 +1,85 @@
+// Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use std::num::NonZeroU64;
+use tough::schema::{DelegatedRole, Delegations, PathSet};
+
+fn role(name: &str, paths: PathSet, terminating: bool) -> DelegatedRole {
+    DelegatedRole {
+        name: name.to_owned(),
+        keyids: Vec::new(),
+        threshold: NonZeroU64::new(1).unwrap(),
+        paths,
+        terminating,
+        targets: None,
+    }
+}
+
+fn paths(patterns: &[&str]) -> PathSet {
+    PathSet::Paths(patterns.iter().map(|p| (*p).to_owned()).collect())
+}
+
+#[test]
+fn star() {
+    let role = role("star", paths(&["targets/*.tgz"]), false);
+    assert!(role.matches_path("targets/foo.tgz"));
+    assert!(role.matches_path("targets/.tgz"));
+    assert!(!role.matches_path("targets/foo.txt"));
+    assert!(!role.matches_path("other/foo.tgz"));
+}
+
+#[test]
+fn question_mark() {
+    let role = role("question", paths(&["foo-version-?.tgz"]), false);
+    assert!(role.matches_path("foo-version-2.tgz"));
+    assert!(role.matches_path("foo-version-a.tgz"));
+    assert!(!role.matches_path("foo-version-alpha.tgz"));
+    assert!(!role.matches_path("foo-version-.tgz"));
+}
+
+#[test]
+fn directory_prefix() {
+    let role = role("dir", paths(&["images/*", "docs/readme.md"]), false);
+    assert!(role.matches_path("images/a.img"));
+    assert!(role.matches_path("docs/readme.md"));
+    assert!(!role.matches_path("images"));
+    assert!(!role.matches_path("imagesfoo/a.img"));
+    assert!(!role.matches_path("docs/other.md"));
+}
+
+#[test]
+fn path_hash_prefixes() {
+    // The SHA-256 digest of "file3.txt" starts with 4532257a.
+    let role = role(
+        "bins",
+        PathSet::PathHashPrefixes(vec!["00".to_owned(), "4532".to_owned()]),
+        false,
+    );
+    assert!(role.matches_path("file3.txt"));
+    assert!(!role.matches_path("file1.txt"));
+}
+
+/// Matching roles are listed in order, up to and including the first matching terminating role.
+#[test]
+fn roles_for_target() {
+    let delegations = Delegations {
+        roles: vec![
+            role("a", paths(&["*.txt"]), false),
+            role("b", paths(&["*.tgz"]), true),
+            role("c", paths(&["file?.txt"]), true),
+            role("d", paths(&["*"]), false),
+        ],
+        ..Delegations::new()
+    };
+    let names = |target| {
+        delegations
+            .roles_for_target(target)
+            .iter()
+            .map(|role| role.name.as_str())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(names("file1.txt"), vec!["a", "c"]);
+    assert_eq!(names("notes.txt"), vec!["a", "d"]);
+    assert_eq!(names("foo.tgz"), vec!["b"]);
+    assert_eq!(names("foo.img"), vec!["d"]);
+}
@@ -0,0 +1,119 @@
+// Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+// These tests read the system clock, which needs the `clock` feature.
+#![cfg(feature = "clock")]
+
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+use test_utils::{dir_url, read_to_end, test_data};
+use tough::RepositoryLoader;
+
+mod test_utils;
+
+/// Returns a loader that trusts the `rotated-root` root, which is unrelated to
+/// `tuf-reference-impl`, but fetches `tuf-reference-impl`.
+fn reference_impl_loader(datastore: &Path) -> RepositoryLoader<File> {
+    let base = test_data().join("tuf-reference-impl");
+    RepositoryLoader::new(
+        File::open(test_data().join("rotated-root").join("1.root.json")).unwrap(),
+        dir_url(base.join("metadata")),
+        dir_url(base.join("targets")),
+    )
+    .datastore(datastore)
+}
+
+fn new_root_path() -> PathBuf {
+    test_data()
+        .join("tuf-reference-impl")
+        .join("metadata")
+        .join("1.root.json")
+}
+
+fn fingerprint(path: &Path) -> String {
+    hex::encode(ring::digest::digest(
+        &ring::digest::SHA256,
+        &fs::read(path).unwrap(),
+    ))
+}
+
+/// A client that trusts one repository's root can move to an unrelated repository once it's given
+/// the new root and its fingerprint.
+#[test]
+fn re_root_to_unrelated_root() {
+    let datastore = TempDir::new().unwrap();
+    let base = test_data().join("rotated-root");
+    RepositoryLoader::new(
+        File::open(base.join("1.root.json")).unwrap(),
+        dir_url(&base),
+        dir_url(base.join("targets")),
+    )
+    .datastore(datastore.path())
+    .load()
+    .unwrap();
+
+    // The old root doesn't lead to the new repository's metadata.
+    assert!(reference_impl_loader(datastore.path()).load().is_err());
+
+    let new_root = new_root_path();
+    let repo = reference_impl_loader(datastore.path())
+        .accept_new_root(
+            File::open(&new_root).unwrap(),
+            &fingerprint(&new_root).to_uppercase(),
+        )
+        .unwrap()
+        .load()
+        .unwrap();
+    assert_eq!(
+        read_to_end(repo.read_target("file1.txt").unwrap().unwrap()),
+        &b"This is an example target file."[..]
+    );
+}
+
+/// The new root is rejected if it doesn't have the expected fingerprint.
+#[test]
+fn re_root_fingerprint_mismatch() {
+    let datastore = TempDir::new().unwrap();
+    let mut fingerprint = fingerprint(&new_root_path());
+    fingerprint.replace_range(
+        ..1,
+        if fingerprint.starts_with('0') {
+            "1"
+        } else {
+            "0"
+        },
+    );
+    let result = reference_impl_loader(datastore.path())
+        .accept_new_root(File::open(new_root_path()).unwrap(), &fingerprint);
+    assert!(matches!(
+        result,
+        Err(tough::error::Error::RootFingerprintMismatch { .. })
+    ));
+}
+
+/// A new root that doesn't load leaves the metadata trusted under the old root in the datastore.
+#[test]
+fn re_root_failure_keeps_trusted_metadata() {
+    let datastore = TempDir::new().unwrap();
+    let base = test_data().join("rotated-root");
+    RepositoryLoader::new(
+        File::open(base.join("1.root.json")).unwrap(),
+        dir_url(&base),
+        dir_url(base.join("targets")),
+    )
+    .datastore(datastore.path())
+    .load()
+    .unwrap();
+
+    let new_root = b"{}";
+    let fingerprint = hex::encode(ring::digest::digest(&ring::digest::SHA256, new_root));
+    assert!(reference_impl_loader(datastore.path())
+        .accept_new_root(&new_root[..], &fingerprint)
+        .unwrap()
+        .load()
+        .is_err());
+    for file in &["timestamp.json", "snapshot.json", "targets.json"] {
+        assert!(datastore.path().join(file).exists(), "{} was removed", file);
+    }
+}
@@ -0,0 +1,114 @@
+// Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+// These tests read the system clock, which needs the `clock` feature.
+#![cfg(feature = "clock")]
+
+use std::path::Path;
+use std::thread;
+use std::time::{Duration as StdDuration, Instant};
+use tempfile::TempDir;
+use test_utils::{key, simple_rsa_editor, simple_rsa_loader, test_data};
+use tough::schema::Target;
+use tough::Repository;
+
+mod test_utils;
+
+/// Writes version `version` of a repository signed with the snakeoil key to `metadata_dir`, with
+/// a target for each of `names`.
+fn write_repo(metadata_dir: &Path, version: u64, names: &[&str]) {
+    let target =
+        Target::from_path(test_data().join("tuf-reference-impl/targets/file1.txt")).unwrap();
+    let mut editor = simple_rsa_editor(version);
+    for name in names {
+        editor.add_target(name, target.clone()).unwrap();
+    }
+    editor
+        .sign(&key("snakeoil.pem"))
+        .unwrap()
+        .write(metadata_dir)
+        .unwrap();
+}
+
+fn load(repo_dir: &Path) -> Repository {
+    simple_rsa_loader(repo_dir.join("metadata"), repo_dir.join("targets"))
+        .load()
+        .unwrap()
+}
+
+fn target_names(repo: &Repository) -> Vec<String> {
+    repo.target_names().into_iter().map(String::from).collect()
+}
+
+/// Refreshing a repository that hasn't changed leaves it as it was.
+#[test]
+fn refresh_unchanged() {
+    let repo_dir = TempDir::new().unwrap();
+    write_repo(&repo_dir.path().join("metadata"), 1, &["file1.txt"]);
+    let mut repo = load(repo_dir.path());
+
+    assert!(!repo.refresh().unwrap());
+    assert_eq!(repo.timestamp().signed.version.get(), 1);
+    assert_eq!(target_names(&repo), vec!["file1.txt"]);
+}
+
+/// Refreshing a repository that has been updated picks up the new metadata.
+#[test]
+fn refresh_updated() {
+    let repo_dir = TempDir::new().unwrap();
+    let metadata_dir = repo_dir.path().join("metadata");
+    write_repo(&metadata_dir, 1, &["file1.txt"]);
+    let mut repo = load(repo_dir.path());
+    let before = repo.metadata_versions();
+
+    write_repo(&metadata_dir, 2, &["file1.txt", "file2.txt"]);
+    assert!(repo.refresh().unwrap());
+    let after = repo.metadata_versions();
+    assert_eq!(after.root, before.root);
+    assert_eq!(after.timestamp.get(), 2);
+    assert_eq!(after.snapshot.get(), 2);
+    assert_eq!(after.targets.get(), 2);
+    assert_eq!(target_names(&repo), vec!["file1.txt", "file2.txt"]);
+
+    assert!(!repo.refresh().unwrap());
+}
+
+/// A refresh that fails verification leaves the repository as it was.
+#[test]
+fn refresh_error_keeps_repository() {
+    let repo_dir = TempDir::new().unwrap();
+    let metadata_dir = repo_dir.path().join("metadata");
+    write_repo(&metadata_dir, 2, &["file1.txt"]);
+    let mut repo = load(repo_dir.path());
+
+    // Version 1 of the timestamp metadata is a rollback.
+    write_repo(&metadata_dir, 1, &["file1.txt", "file2.txt"]);
+    assert!(repo.refresh().is_err());
+    assert_eq!(repo.timestamp().signed.version.get(), 2);
+    assert_eq!(target_names(&repo), vec!["file1.txt"]);
+}
+
+/// A repository refreshed in the background picks up new metadata, and reports errors.
+#[test]
+fn auto_refresh() {
+    let repo_dir = TempDir::new().unwrap();
+    let metadata_dir = repo_dir.path().join("metadata");
+    write_repo(&metadata_dir, 2, &["file1.txt"]);
+    let handle = load(repo_dir.path()).auto_refresh(StdDuration::from_millis(10));
+    assert_eq!(handle.repository().timestamp().signed.version.get(), 2);
+
+    // Version 1 of the timestamp metadata is a rollback.
+    write_repo(&metadata_dir, 1, &["file1.txt"]);
+    assert!(handle
+        .errors()
+        .recv_timeout(StdDuration::from_secs(30))
+        .is_ok());
+
+    write_repo(&metadata_dir, 3, &["file1.txt", "file2.txt"]);
+    let deadline = Instant::now() + StdDuration::from_secs(30);
+    while handle.repository().timestamp().signed.version.get() != 3 {
+        assert!(Instant::now() < deadline, "repository was not refreshed");
+        thread::sleep(StdDuration::from_millis(10));
+    }
+    assert!(handle.repository().contains_target("file2.txt"));
+}
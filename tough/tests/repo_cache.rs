@@ -1,12 +1,21 @@
 // Copyright 2019 Amazon.com, Inc. or its affiliates. All Rights Reserved.
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
+// These tests read the system clock, which needs the `clock` feature.
+#![cfg(feature = "clock")]
+
+use chrono::{Duration, Utc};
 use std::fs::File;
 use std::io::Read;
-use std::path::PathBuf;
+use std::num::NonZeroU64;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
 use tempfile::TempDir;
 use test_utils::{dir_url, test_data};
-use tough::{Repository, RepositoryLoader};
+use tough::editor::RepositoryEditor;
+use tough::key_source::{KeySource, LocalKeySource};
+use tough::schema::Target;
+use tough::{FilesystemTransport, Repository, RepositoryLoader, Transport, TransportError};
 use url::Url;
 
 mod test_utils;
@@ -42,6 +51,47 @@ fn load_tuf_reference_impl(paths: &RepoPaths) -> Repository {
     .unwrap()
 }
 
+/// Creates a repository signed with the `simple-rsa` keys, writes its metadata to
+/// `repo_dir/metadata`, and loads it with targets served from `repo_dir/targets`. The repository
+/// lists `tuf-reference-impl`'s file1.txt under each of the given target names; the target files
+/// themselves are not written.
+fn create_repo_with_targets(repo_dir: &Path, names: &[&str]) -> Repository {
+    let root = test_data().join("simple-rsa").join("root.json");
+    let target =
+        Target::from_path(test_data().join("tuf-reference-impl/targets/file1.txt")).unwrap();
+    let expiration = Utc::now().checked_add_signed(Duration::days(7)).unwrap();
+    let version = NonZeroU64::new(1).unwrap();
+
+    let mut editor = RepositoryEditor::new(&root).unwrap();
+    editor
+        .targets_expires(expiration)
+        .unwrap()
+        .targets_version(version)
+        .unwrap()
+        .snapshot_expires(expiration)
+        .snapshot_version(version)
+        .timestamp_expires(expiration)
+        .timestamp_version(version);
+    for name in names {
+        editor.add_target(name, target.clone()).unwrap();
+    }
+    let keys: &[Box<dyn KeySource>] = &[Box::new(LocalKeySource {
+        path: test_data().join("snakeoil.pem"),
+    })];
+    let metadata_dir = repo_dir.join("metadata");
+    editor.sign(keys).unwrap().write(&metadata_dir).unwrap();
+
+    let targets_dir = repo_dir.join("targets");
+    std::fs::create_dir_all(&targets_dir).unwrap();
+    RepositoryLoader::new(
+        File::open(&root).unwrap(),
+        dir_url(&metadata_dir),
+        dir_url(&targets_dir),
+    )
+    .load()
+    .unwrap()
+}
+
 /// Test that the repo.cache() function works when given a list of multiple targets.
 #[test]
 fn test_repo_cache_all_targets() {
@@ -231,3 +281,225 @@ fn test_repo_cache_metadata_no_root_chain() {
     // Verify we did not cache the root.json
     assert!(!metadata_destination.join("1.root.json").exists());
 }
+
+/// Test that the repo.cache_to_tar() function writes an archive that can be extracted and loaded.
+#[test]
+fn test_repo_cache_to_tar() {
+    // load the reference_impl repo
+    let repo_paths = RepoPaths::new();
+    let repo = load_tuf_reference_impl(&repo_paths);
+
+    // cache the repo to an in-memory archive and extract it
+    let archive = repo
+        .cache_to_tar(Vec::new(), None::<&[&str]>, true)
+        .unwrap();
+    let destination = TempDir::new().unwrap();
+    tar::Archive::new(archive.as_slice())
+        .unpack(destination.path())
+        .unwrap();
+    let metadata_destination = destination.as_ref().join("metadata");
+    let targets_destination = destination.as_ref().join("targets");
+    assert!(metadata_destination.join("1.root.json").exists());
+
+    // check that we can load the extracted repo.
+    let copied_repo = RepositoryLoader::new(
+        repo_paths.root(),
+        dir_url(&metadata_destination),
+        dir_url(&targets_destination),
+    )
+    .load()
+    .unwrap();
+
+    // the extracted repo should have all targets, including the delegated file3.
+    for (name, size) in &[("file1.txt", 31), ("file2.txt", 39), ("file3.txt", 28)] {
+        let mut file_data = Vec::new();
+        let file_size = copied_repo
+            .read_target(*name)
+            .unwrap()
+            .unwrap()
+            .read_to_end(&mut file_data)
+            .unwrap();
+        assert_eq!(*size, file_size);
+    }
+}
+
+/// Test that repo.cache_to_tar() fails rather than archiving a target that doesn't match its
+/// metadata.
+#[test]
+fn test_repo_cache_to_tar_bad_target() {
+    let base = test_data().join("tuf-reference-impl");
+    let targets_dir = TempDir::new().unwrap();
+    std::fs::write(
+        targets_dir.path().join("file1.txt"),
+        b"This is an example target file!",
+    )
+    .unwrap();
+    let repo = RepositoryLoader::new(
+        File::open(base.join("metadata").join("1.root.json")).unwrap(),
+        dir_url(base.join("metadata")),
+        dir_url(targets_dir.path()),
+    )
+    .load()
+    .unwrap();
+
+    assert!(repo
+        .cache_to_tar(Vec::new(), Some(&["file1.txt"]), false)
+        .is_err());
+}
+
+/// Test that targets whose names would escape the targets directory are never written.
+#[test]
+fn test_repo_cache_unsafe_target_names() {
+    for name in &["../../etc/passwd", "../escaped.txt", "/tmp/absolute.txt"] {
+        let repo_dir = TempDir::new().unwrap();
+        let repo = create_repo_with_targets(repo_dir.path(), &[name]);
+
+        let destination = TempDir::new().unwrap();
+        let metadata_destination = destination.path().join("metadata");
+        let targets_destination = destination.path().join("nested").join("targets");
+        let result = repo.cache(
+            &metadata_destination,
+            &targets_destination,
+            None::<&[&str]>,
+            false,
+        );
+        assert!(
+            matches!(result, Err(tough::error::Error::UnsafeTargetName { .. })),
+            "caching {:?} did not fail as expected: {:?}",
+            name,
+            result
+        );
+        assert!(!destination
+            .path()
+            .join("nested")
+            .join("escaped.txt")
+            .exists());
+        assert_eq!(
+            std::fs::read_dir(&targets_destination).unwrap().count(),
+            0,
+            "a file was written for {:?}",
+            name
+        );
+
+        assert!(matches!(
+            repo.cache_to_tar(Vec::new(), Some(&[name]), false),
+            Err(tough::error::Error::UnsafeTargetName { .. })
+        ));
+    }
+}
+
+/// Test that targets whose names contain path separators are cached in subdirectories.
+#[test]
+fn test_repo_cache_target_in_subdirectory() {
+    let name = "linux/amd64/foo.bin";
+    let repo_dir = TempDir::new().unwrap();
+    let repo = create_repo_with_targets(repo_dir.path(), &[name]);
+    assert!(repo.consistent_snapshot());
+    let sha256 = hex::encode(&repo.targets().signed.targets[name].hashes.sha256().unwrap());
+    let filename = format!("{}.{}", sha256, name);
+    let target_path = repo_dir.path().join("targets").join(&filename);
+    std::fs::create_dir_all(target_path.parent().unwrap()).unwrap();
+    std::fs::copy(
+        test_data().join("tuf-reference-impl/targets/file1.txt"),
+        &target_path,
+    )
+    .unwrap();
+
+    let destination = TempDir::new().unwrap();
+    let metadata_destination = destination.path().join("metadata");
+    let targets_destination = destination.path().join("targets");
+    repo.cache(
+        &metadata_destination,
+        &targets_destination,
+        Some(&[name]),
+        true,
+    )
+    .unwrap();
+    assert!(targets_destination.join(&filename).is_file());
+
+    let copied_repo = RepositoryLoader::new(
+        File::open(test_data().join("simple-rsa").join("root.json")).unwrap(),
+        dir_url(&metadata_destination),
+        dir_url(&targets_destination),
+    )
+    .load()
+    .unwrap();
+    let mut file_data = Vec::new();
+    copied_repo
+        .read_target(name)
+        .unwrap()
+        .unwrap()
+        .read_to_end(&mut file_data)
+        .unwrap();
+    assert_eq!(file_data, b"This is an example target file.");
+}
+
+/// A `Transport` that serves files from the local filesystem and records the URLs it fetches.
+#[derive(Debug, Clone, Default)]
+struct RecordingTransport {
+    urls: Arc<Mutex<Vec<Url>>>,
+}
+
+impl Transport for RecordingTransport {
+    fn fetch(&self, url: Url) -> Result<Box<dyn Read + Send>, TransportError> {
+        self.urls.lock().unwrap().push(url.clone());
+        FilesystemTransport.fetch(url)
+    }
+}
+
+/// Test that target names are percent-encoded in the URLs they're fetched from, but not in the
+/// names of the files they're cached as.
+#[test]
+fn test_repo_cache_percent_encoded_target_names() {
+    let root = test_data().join("simple-rsa").join("root.json");
+    for (name, encoded) in &[
+        ("hello world.txt", "hello%20world.txt"),
+        ("100% #1?.txt", "100%25%20%231%3F.txt"),
+    ] {
+        let repo_dir = TempDir::new().unwrap();
+        create_repo_with_targets(repo_dir.path(), &[name]);
+        let transport = RecordingTransport::default();
+        let repo = RepositoryLoader::new(
+            File::open(&root).unwrap(),
+            dir_url(repo_dir.path().join("metadata")),
+            dir_url(repo_dir.path().join("targets")),
+        )
+        .transport(transport.clone())
+        .load()
+        .unwrap();
+        let sha256 = hex::encode(
+            &repo.targets().signed.targets[*name]
+                .hashes
+                .sha256()
+                .unwrap(),
+        );
+        let filename = format!("{}.{}", sha256, name);
+        std::fs::copy(
+            test_data().join("tuf-reference-impl/targets/file1.txt"),
+            repo_dir.path().join("targets").join(&filename),
+        )
+        .unwrap();
+
+        let destination = TempDir::new().unwrap();
+        let targets_destination = destination.path().join("targets");
+        repo.cache(
+            &destination.path().join("metadata"),
+            &targets_destination,
+            Some(&[name]),
+            false,
+        )
+        .unwrap();
+        assert!(targets_destination.join(&filename).is_file());
+
+        let encoded_filename = format!("/{}.{}", sha256, encoded);
+        let urls = transport.urls.lock().unwrap();
+        assert!(
+            urls.iter()
+                .any(|url| url.path().ends_with(&encoded_filename)),
+            "{:?} was not fetched from a URL ending with {}: {:?}",
+            name,
+            encoded_filename,
+            urls
+        );
+    }
+}
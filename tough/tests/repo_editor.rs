@@ -1,6 +1,9 @@
 // Copyright 2019 Amazon.com, Inc. or its affiliates. All Rights Reserved.
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
+// These tests read the system clock, which needs the `clock` feature.
+#![cfg(feature = "clock")]
+
 use crate::test_utils::{dir_url, read_to_end, test_data};
 use chrono::{Duration, Utc};
 use std::collections::HashMap;
@@ -16,7 +19,7 @@ use tough::key_source::LocalKeySource;
 use tough::schema::decoded::Decoded;
 use tough::schema::decoded::Hex;
 use tough::schema::key::Key;
-use tough::schema::PathSet;
+use tough::schema::{DelegatedRole, Delegations, KeyHolder, PathSet, Root, Signed, Targets};
 use tough::{Repository, RepositoryLoader};
 use url::Url;
 
@@ -230,13 +233,178 @@ fn create_sign_write_reload_repo() {
         .link_targets(&targets_path(), &targets_destination, PathExists::Skip)
         .is_ok());
     // Load the repo we just created
-    let _new_repo = RepositoryLoader::new(
+    let new_repo = RepositoryLoader::new(
         File::open(&root).unwrap(),
         dir_url(&metadata_destination),
         dir_url(&targets_destination),
     )
     .load()
     .unwrap();
+    assert!(new_repo.consistent_snapshot());
+}
+
+// Ensure that delegated role metadata is rejected if it doesn't match the hash listed for it in
+// snapshot metadata, even if its signatures and version are valid
+#[test]
+fn delegated_role_hash_mismatch() {
+    let mut editor = test_repo_editor();
+    let targets_key: &[Box<dyn KeySource>] = &[Box::new(LocalKeySource { path: key_path() })];
+    let role1_key: &[Box<dyn KeySource>] = &[Box::new(LocalKeySource {
+        path: targets_key_path(),
+    })];
+    editor
+        .delegate_role(
+            "role1",
+            role1_key,
+            PathSet::Paths(vec!["file?.txt".to_string()]),
+            NonZeroU64::new(1).unwrap(),
+            Utc::now().checked_add_signed(Duration::days(21)).unwrap(),
+            NonZeroU64::new(1).unwrap(),
+        )
+        .unwrap();
+
+    let repodir = TempDir::new().unwrap();
+    let metadata_destination = repodir.as_ref().join("metadata");
+    let targets_destination = repodir.as_ref().join("targets");
+    editor
+        .sign(targets_key)
+        .unwrap()
+        .write(&metadata_destination)
+        .unwrap();
+    let load = || {
+        RepositoryLoader::new(
+            File::open(root_path()).unwrap(),
+            dir_url(&metadata_destination),
+            dir_url(&targets_destination),
+        )
+        .load()
+    };
+    assert!(load().is_ok());
+
+    // Move a newline so that the file has the same length and the same signed content, but a
+    // different hash.
+    let role_path = metadata_destination.join("1.role1.json");
+    let role = std::fs::read_to_string(&role_path).unwrap();
+    assert!(role.starts_with("{\n"));
+    std::fs::write(&role_path, role.replacen("{\n", "\n{", 1)).unwrap();
+    let err = load().err().unwrap();
+    assert!(err.to_string().contains("Hash mismatch"), "{}", err);
+}
+
+// Edit a loaded repository, sign it again, and load the result. The reference implementation's
+// signing keys aren't available, so the repository is first created with the snakeoil keys.
+#[test]
+fn edit_loaded_repository() {
+    let targets_key: &[Box<dyn KeySource>] = &[Box::new(LocalKeySource { path: key_path() })];
+    let role1_key: &[Box<dyn KeySource>] = &[Box::new(LocalKeySource {
+        path: targets_key_path(),
+    })];
+    let load = |metadata_dir: &std::path::Path| {
+        RepositoryLoader::new(
+            File::open(root_path()).unwrap(),
+            dir_url(metadata_dir),
+            dir_url(targets_path()),
+        )
+        .load()
+        .unwrap()
+    };
+
+    let mut editor = test_repo_editor();
+    let (name, mut target) =
+        RepositoryEditor::build_target(targets_path().join("file1.txt")).unwrap();
+    target
+        .custom
+        .insert("owner".to_string(), serde_json::json!("ops"));
+    editor
+        .add_target(&name, target)
+        .unwrap()
+        .delegate_role(
+            "role1",
+            role1_key,
+            PathSet::Paths(vec!["role1/*".to_string()]),
+            NonZeroU64::new(1).unwrap(),
+            Utc::now().checked_add_signed(Duration::days(21)).unwrap(),
+            NonZeroU64::new(1).unwrap(),
+        )
+        .unwrap();
+    let repodir = TempDir::new().unwrap();
+    let metadata_destination = repodir.path().join("metadata");
+    editor
+        .sign(targets_key)
+        .unwrap()
+        .write(&metadata_destination)
+        .unwrap();
+    let repo = load(&metadata_destination);
+
+    // Only the versions are bumped; the expirations are kept from the loaded repository
+    let mut editor = RepositoryEditor::from_repository(&repo).unwrap();
+    editor
+        .add_target_paths(vec![targets_path().join("file2.txt")])
+        .unwrap()
+        .targets_version(NonZeroU64::new(790).unwrap())
+        .unwrap()
+        .snapshot_version(NonZeroU64::new(5433).unwrap())
+        .timestamp_version(NonZeroU64::new(1235).unwrap());
+    let edited_destination = repodir.path().join("edited");
+    editor
+        .sign(targets_key)
+        .unwrap()
+        .write(&edited_destination)
+        .unwrap();
+    let edited = load(&edited_destination);
+
+    assert_eq!(edited.targets().signed.version.get(), 790);
+    assert_eq!(edited.snapshot().signed.version.get(), 5433);
+    assert_eq!(edited.timestamp().signed.version.get(), 1235);
+    assert_eq!(
+        edited.timestamp().signed.expires,
+        repo.timestamp().signed.expires
+    );
+    let targets = &edited.targets().signed.targets;
+    assert!(targets.contains_key("file2.txt"));
+    assert!(targets.contains_key("file3.txt"));
+    assert_eq!(
+        targets["file1.txt"].custom["owner"],
+        serde_json::json!("ops")
+    );
+    assert!(edited.delegated_role("role1").is_some());
+}
+
+// Metadata written to a writer is exactly what is written to file
+#[test]
+fn write_metadata_to_writer() {
+    let mut editor = test_repo_editor();
+    let targets_key: &[Box<dyn KeySource>] = &[Box::new(LocalKeySource { path: key_path() })];
+    let role1_key: &[Box<dyn KeySource>] = &[Box::new(LocalKeySource {
+        path: targets_key_path(),
+    })];
+    editor
+        .delegate_role(
+            "role1",
+            role1_key,
+            PathSet::Paths(vec!["file?.txt".to_string()]),
+            NonZeroU64::new(1).unwrap(),
+            Utc::now().checked_add_signed(Duration::days(21)).unwrap(),
+            NonZeroU64::new(1).unwrap(),
+        )
+        .unwrap();
+    let signed_repo = editor.sign(targets_key).unwrap();
+    let outdir = TempDir::new().unwrap();
+    signed_repo.write(outdir.path()).unwrap();
+
+    for role in &["root", "timestamp", "snapshot", "targets", "role1"] {
+        let mut buffer = Vec::new();
+        signed_repo.write_metadata_to(role, &mut buffer).unwrap();
+        let filename = signed_repo.metadata_filename(role).unwrap();
+        assert_eq!(buffer, std::fs::read(outdir.path().join(filename)).unwrap());
+    }
+    assert_eq!(
+        signed_repo.metadata_filename("role1").unwrap(),
+        "1.role1.json"
+    );
+    assert!(signed_repo
+        .write_metadata_to("missing", &mut Vec::new())
+        .is_err());
 }
 
 #[test]
@@ -804,3 +972,105 @@ fn update_targets_flow() {
         &b"Updated file1.txt"[..]
     );
 }
+
+// Ensure that a delegated role can't delegate paths outside of the paths delegated to it
+#[test]
+fn delegate_paths_outside_own_paths() {
+    let mut editor = test_repo_editor();
+    let targets_key: &[Box<dyn KeySource>] = &[Box::new(LocalKeySource { path: key_path() })];
+    let role1_key: &[Box<dyn KeySource>] = &[Box::new(LocalKeySource {
+        path: targets_key_path(),
+    })];
+    let role2_key: &[Box<dyn KeySource>] = &[Box::new(LocalKeySource {
+        path: targets_key_path1(),
+    })];
+    editor
+        .delegate_role(
+            "role1",
+            role1_key,
+            PathSet::Paths(vec!["file?.txt".to_string()]),
+            NonZeroU64::new(1).unwrap(),
+            Utc::now().checked_add_signed(Duration::days(21)).unwrap(),
+            NonZeroU64::new(1).unwrap(),
+        )
+        .unwrap()
+        .sign_targets_editor(targets_key)
+        .unwrap()
+        .change_delegated_targets("role1")
+        .unwrap();
+
+    let err = editor
+        .delegate_role(
+            "role2",
+            role2_key,
+            PathSet::Paths(vec!["file1.txt".to_string(), "other/*".to_string()]),
+            NonZeroU64::new(1).unwrap(),
+            Utc::now().checked_add_signed(Duration::days(21)).unwrap(),
+            NonZeroU64::new(1).unwrap(),
+        )
+        .unwrap_err();
+    assert!(
+        err.to_string().contains("'other/*'"),
+        "unexpected error: {}",
+        err
+    );
+}
+
+// Ensure that paths already delegated to a terminating role can't be delegated to a later role,
+// while overlapping paths of a non-terminating role can be
+#[test]
+fn delegate_paths_shadowed_by_terminating_role() {
+    let role_key: &[Box<dyn KeySource>] = &[Box::new(LocalKeySource {
+        path: targets_key_path(),
+    })];
+    let delegated_role = |name: &str, path: &str, terminating| DelegatedRole {
+        name: name.to_string(),
+        keyids: Vec::new(),
+        threshold: NonZeroU64::new(1).unwrap(),
+        paths: PathSet::Paths(vec![path.to_string()]),
+        terminating,
+        targets: None,
+    };
+    let mut delegations = Delegations::new();
+    delegations
+        .roles
+        .push(delegated_role("role1", "file?.txt", true));
+    delegations
+        .roles
+        .push(delegated_role("role2", "other/*", false));
+    let mut targets = Targets::new(
+        "1.0.0".to_string(),
+        NonZeroU64::new(1).unwrap(),
+        Utc::now().checked_add_signed(Duration::days(21)).unwrap(),
+    );
+    targets.delegations = Some(delegations);
+    let root: Signed<Root> = serde_json::from_reader(File::open(root_path()).unwrap()).unwrap();
+    let mut editor = TargetsEditor::from_targets("targets", targets, KeyHolder::Root(root.signed));
+
+    let mut delegate = |path: &str| {
+        let new_role = TargetsEditor::new("role3")
+            .version(NonZeroU64::new(1).unwrap())
+            .expires(Utc::now().checked_add_signed(Duration::days(21)).unwrap())
+            .create_signed(role_key)
+            .unwrap();
+        let keys = key_hash_map(role_key);
+        let keyids = keys.keys().cloned().collect();
+        editor
+            .delegate_role(
+                new_role,
+                PathSet::Paths(vec![path.to_string()]),
+                keys,
+                keyids,
+                NonZeroU64::new(1).unwrap(),
+            )
+            .map(drop)
+    };
+
+    let err = delegate("file1.txt").unwrap_err();
+    assert!(
+        err.to_string().contains("'file1.txt'") && err.to_string().contains("'role1'"),
+        "unexpected error: {}",
+        err
+    );
+    delegate("other/file1.txt").unwrap();
+}
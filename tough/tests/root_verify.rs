@@ -0,0 +1,78 @@
+// Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+// These tests read the system clock, which needs the `clock` feature.
+#![cfg(feature = "clock")]
+
+use chrono::{Duration, Utc};
+use ring::rand::SystemRandom;
+use std::fs::File;
+use std::num::NonZeroU64;
+use test_utils::test_data;
+use tough::key_source::{KeySource, LocalKeySource};
+use tough::schema::{Role, RoleType, Root, Signature, Signed, Targets};
+
+mod test_utils;
+
+/// Returns the `simple-rsa` root, changed so that the targets role is signed by `snakeoil_2.pem`
+/// rather than `snakeoil.pem`, which still signs the other roles.
+fn root() -> Root {
+    let root: Signed<Root> = serde_json::from_reader(
+        File::open(test_data().join("simple-rsa").join("root.json")).unwrap(),
+    )
+    .unwrap();
+    let mut root = root.signed;
+    let targets_key = key("snakeoil_2.pem").as_sign().unwrap().tuf_key();
+    let targets_key_id = targets_key.key_id().unwrap();
+    root.keys.insert(targets_key_id.clone(), targets_key);
+    root.roles.get_mut(&RoleType::Targets).unwrap().keyids = vec![targets_key_id];
+    root
+}
+
+fn key(name: &str) -> LocalKeySource {
+    LocalKeySource {
+        path: test_data().join(name),
+    }
+}
+
+/// Signs new targets metadata with the given key.
+fn targets(root: &Root, key: &LocalKeySource) -> Signed<Targets> {
+    let targets = Targets::new(
+        "1.0.0".to_owned(),
+        NonZeroU64::new(1).unwrap(),
+        Utc::now() + Duration::days(7),
+    );
+    let signer = key.as_sign().unwrap();
+    let sig = signer
+        .sign(&targets.canonical_form().unwrap(), &SystemRandom::new())
+        .unwrap();
+    Signed::new(
+        targets,
+        vec![Signature {
+            keyid: root.key_id(signer.as_ref()).unwrap(),
+            sig: sig.into(),
+        }],
+    )
+}
+
+/// Targets metadata signed by the targets key verifies against the root.
+#[test]
+fn targets_signed_by_targets_key() {
+    let root = root();
+    root.verify_role(&targets(&root, &key("snakeoil_2.pem")))
+        .unwrap();
+}
+
+/// Targets metadata signed by a key that the root trusts, but only for other roles, doesn't verify.
+#[test]
+fn targets_signed_by_snapshot_key() {
+    let root = root();
+    let err = root
+        .verify_role(&targets(&root, &key("snakeoil.pem")))
+        .unwrap_err();
+    assert!(
+        err.to_string().contains("threshold"),
+        "unexpected error: {}",
+        err
+    );
+}
@@ -1,11 +1,17 @@
 // Copyright 2020 Amazon.com, Inc. or its affiliates. All Rights Reserved.
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
+// These tests read the system clock, which needs the `clock` feature.
+#![cfg(feature = "clock")]
+
 mod test_utils;
 
 use std::fs::File;
+use std::sync::{Arc, Mutex};
+use tempfile::TempDir;
 use test_utils::{dir_url, test_data};
-use tough::RepositoryLoader;
+use tough::schema::RoleType;
+use tough::{RepositoryLoader, RootUpdateStopReason};
 
 #[test]
 fn rotated_root() {
@@ -20,4 +26,115 @@ fn rotated_root() {
     .unwrap();
 
     assert_eq!(u64::from(repo.root().signed.version), 2);
+    assert_eq!(
+        repo.raw_metadata(RoleType::Root).unwrap(),
+        std::fs::read(base.join("2.root.json")).unwrap()
+    );
+}
+
+#[test]
+fn rotated_root_save_chain() {
+    let base = test_data().join("rotated-root");
+    let chain_dir = TempDir::new().unwrap();
+    let chain_path = chain_dir.path().join("chain");
+
+    RepositoryLoader::new(
+        File::open(base.join("1.root.json")).unwrap(),
+        dir_url(&base),
+        dir_url(base.join("targets")),
+    )
+    .save_root_chain(&chain_path)
+    .load()
+    .unwrap();
+
+    // Only the root that was fetched is saved, byte for byte.
+    let saved: Vec<_> = std::fs::read_dir(&chain_path)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name())
+        .collect();
+    assert_eq!(saved, vec!["2.root.json"]);
+    assert_eq!(
+        std::fs::read(chain_path.join("2.root.json")).unwrap(),
+        std::fs::read(base.join("2.root.json")).unwrap()
+    );
+}
+
+#[test]
+fn rotated_root_key_rotation_callback() {
+    let base = test_data().join("rotated-root");
+    let rotations = Arc::new(Mutex::new(Vec::new()));
+    let recorded = Arc::clone(&rotations);
+
+    RepositoryLoader::new(
+        File::open(base.join("1.root.json")).unwrap(),
+        dir_url(&base),
+        dir_url(base.join("targets")),
+    )
+    .on_key_rotation(move |role, old_keys, new_keys| {
+        assert_ne!(old_keys, new_keys);
+        recorded.lock().unwrap().push(role);
+    })
+    .load()
+    .unwrap();
+
+    // Every top-level role has new keys in 2.root.json, and each is reported once.
+    assert_eq!(
+        *rotations.lock().unwrap(),
+        vec![
+            RoleType::Root,
+            RoleType::Snapshot,
+            RoleType::Targets,
+            RoleType::Timestamp
+        ]
+    );
+}
+
+#[test]
+fn no_root_update_no_key_rotation_callback() {
+    let base = test_data().join("rotated-root");
+
+    RepositoryLoader::new(
+        File::open(base.join("2.root.json")).unwrap(),
+        dir_url(&base),
+        dir_url(base.join("targets")),
+    )
+    .on_key_rotation(|role, _, _| panic!("unexpected key rotation for {}", role))
+    .load()
+    .unwrap();
+}
+
+#[test]
+fn root_update_outcome() {
+    let base = test_data().join("rotated-root");
+    let load = |metadata: &std::path::Path| {
+        RepositoryLoader::new(
+            File::open(base.join("1.root.json")).unwrap(),
+            dir_url(metadata),
+            dir_url(base.join("targets")),
+        )
+        .load()
+        .unwrap()
+    };
+
+    let outcome = load(&base).root_update_outcome();
+    assert_eq!(outcome.initial_version.get(), 1);
+    assert_eq!(outcome.final_version.get(), 2);
+    assert_eq!(outcome.reason, RootUpdateStopReason::ReachedLatest);
+
+    // A 3.root.json that lists version 2 is ignored.
+    let metadata = TempDir::new().unwrap();
+    for entry in std::fs::read_dir(&base).unwrap() {
+        let entry = entry.unwrap();
+        if entry.file_type().unwrap().is_file() {
+            std::fs::copy(entry.path(), metadata.path().join(entry.file_name())).unwrap();
+        }
+    }
+    std::fs::copy(
+        base.join("2.root.json"),
+        metadata.path().join("3.root.json"),
+    )
+    .unwrap();
+    let outcome = load(metadata.path()).root_update_outcome();
+    assert_eq!(outcome.final_version.get(), 2);
+    assert_eq!(outcome.reason, RootUpdateStopReason::SameVersion);
 }
@@ -0,0 +1,98 @@
+// Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use ring::rand::SystemRandom;
+use std::fs::File;
+use test_utils::test_data;
+use tough::editor::signed::SignedRole;
+use tough::key_source::{KeySource, LocalKeySource};
+use tough::schema::key::{Key, RsaScheme};
+use tough::schema::{KeyHolder, Root, Signed};
+
+mod test_utils;
+
+fn simple_rsa_root() -> Signed<Root> {
+    serde_json::from_reader(File::open(test_data().join("simple-rsa").join("root.json")).unwrap())
+        .unwrap()
+}
+
+/// Sets the scheme of the root's only key to `scheme`, and updates the roles to its new key ID.
+fn set_scheme(root: &mut Root, scheme: RsaScheme) {
+    let (old_key_id, mut key) = root.keys.drain().next().unwrap();
+    match &mut key {
+        Key::Rsa { scheme: s, .. } => *s = scheme,
+        _ => panic!("expected an RSA key"),
+    }
+    let key_id = key.key_id().unwrap();
+    root.keys.insert(key_id.clone(), key);
+    for role_keys in root.roles.values_mut() {
+        for id in &mut role_keys.keyids {
+            if *id == old_key_id {
+                *id = key_id.clone();
+            }
+        }
+    }
+}
+
+fn sign(root: Root) -> Signed<Root> {
+    let keys: Vec<Box<dyn KeySource>> = vec![Box::new(LocalKeySource {
+        path: test_data().join("snakeoil.pem"),
+    })];
+    let key_holder = KeyHolder::Root(root.clone());
+    SignedRole::new(root, &key_holder, &keys, &SystemRandom::new())
+        .unwrap()
+        .signed()
+        .clone()
+}
+
+/// A root whose RSA key declares `rsassa-pss-sha256` keeps that scheme when serialized, and
+/// verifies.
+#[test]
+fn pss_root_round_trips() {
+    let root = simple_rsa_root();
+    let key = root.signed.keys.values().next().unwrap();
+    assert!(matches!(
+        key,
+        Key::Rsa {
+            scheme: RsaScheme::RsassaPssSha256,
+            ..
+        }
+    ));
+    let json = serde_json::to_value(key).unwrap();
+    assert_eq!(json["scheme"], "rsassa-pss-sha256");
+
+    let reparsed: Signed<Root> =
+        serde_json::from_slice(&serde_json::to_vec(&root).unwrap()).unwrap();
+    assert_eq!(reparsed, root);
+    reparsed.signed.verify_role(&reparsed).unwrap();
+
+    // Re-signing with the editor uses PSS too
+    let resigned = sign(root.signed);
+    resigned.signed.verify_role(&resigned).unwrap();
+}
+
+/// A root whose RSA key declares `rsa-pkcs1v15-sha256` is signed with that scheme, and a
+/// verifier that expects a different scheme rejects the signature.
+#[test]
+fn pkcs1v15_root() {
+    let mut root = simple_rsa_root().signed;
+    set_scheme(&mut root, RsaScheme::RsaPkcs1v15Sha256);
+    let signed = sign(root);
+    let key = signed.signed.keys.values().next().unwrap();
+    assert_eq!(
+        serde_json::to_value(key).unwrap()["scheme"],
+        "rsa-pkcs1v15-sha256"
+    );
+    signed.signed.verify_role(&signed).unwrap();
+
+    // Verify the same signature with the key declared as PSS, under the same ID
+    let mut verifier = signed.signed.clone();
+    for key in verifier.keys.values_mut() {
+        if let Key::Rsa { scheme, .. } = key {
+            *scheme = RsaScheme::RsassaPssSha256;
+        }
+    }
+    verifier
+        .verify_role(&signed)
+        .expect_err("signature made with a different scheme should not verify");
+}
@@ -0,0 +1,31 @@
+// Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use std::fs::File;
+use test_utils::test_data;
+use tough::key_source::LocalKeySource;
+use tough::schema::{canonical_json, KeyHolder, Root, Signed};
+use tough::sign::sign_bytes;
+
+mod test_utils;
+
+/// A detached signature of a role's canonical form can be assembled into a `Signed` role that
+/// verifies.
+#[test]
+fn sign_bytes_verifies() {
+    let mut root: Signed<Root> = serde_json::from_reader(
+        File::open(test_data().join("simple-rsa").join("root.json")).unwrap(),
+    )
+    .unwrap();
+    let expected_keyid = root.signatures[0].keyid.clone();
+    root.signatures.clear();
+
+    let key = LocalKeySource {
+        path: test_data().join("snakeoil.pem"),
+    };
+    let key_holder = KeyHolder::Root(root.signed.clone());
+    let signature = sign_bytes(&key, &key_holder, &canonical_json(&root.signed).unwrap()).unwrap();
+    assert_eq!(signature.keyid, expected_keyid);
+    root.signatures.push(signature);
+    root.signed.verify_role(&root).unwrap();
+}
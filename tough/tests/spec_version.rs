@@ -0,0 +1,40 @@
+// Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+// These tests read the system clock, which needs the `clock` feature.
+#![cfg(feature = "clock")]
+
+use semver::VersionReq;
+use test_utils::{reference_impl_loader, test_data};
+use tough::error::Error;
+
+mod test_utils;
+
+fn load(requirement: Option<&str>) -> tough::error::Result<tough::Repository> {
+    let base = test_data().join("tuf-reference-impl");
+    let mut loader = reference_impl_loader(base.join("metadata"));
+    if let Some(requirement) = requirement {
+        loader = loader.require_spec_version(VersionReq::parse(requirement).unwrap());
+    }
+    loader.load()
+}
+
+/// The metadata declares spec version 1.0.0, which is loaded with or without a matching
+/// requirement.
+#[test]
+fn compatible_spec_version() {
+    load(None).unwrap();
+    load(Some("^1")).unwrap();
+}
+
+#[test]
+fn incompatible_spec_version() {
+    match load(Some("^2")) {
+        Err(Error::IncompatibleSpecVersion { role, given, .. }) => {
+            assert_eq!(role, "root");
+            assert_eq!(given, "1.0.0");
+        }
+        Err(e) => panic!("expected an incompatible spec version error, got: {}", e),
+        Ok(_) => panic!("loading should fail with an incompatible spec version"),
+    }
+}
@@ -0,0 +1,86 @@
+// Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+// These tests read the system clock, which needs the `clock` feature.
+#![cfg(feature = "clock")]
+
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+use test_utils::{read_to_end, reference_impl_loader_with_targets, test_data};
+use tough::Repository;
+
+mod test_utils;
+
+const FILE1: &[u8] = b"This is an example target file.";
+
+/// Loads `tuf-reference-impl` with its targets served from `targets_dir` and cached in `cache`.
+fn load(targets_dir: &Path, cache: &TempDir) -> Repository {
+    let metadata_dir = test_data().join("tuf-reference-impl").join("metadata");
+    reference_impl_loader_with_targets(metadata_dir, targets_dir)
+        .target_cache_dir(cache.path())
+        .load()
+        .unwrap()
+}
+
+fn cached_path(repo: &Repository, cache: &TempDir, name: &str) -> PathBuf {
+    let sha256 = repo.targets().signed.targets[name].hashes.sha256().unwrap();
+    cache
+        .path()
+        .join(format!("{}.{}", hex::encode(sha256), name))
+}
+
+/// A target that was read to the end is cached, and read from the cache afterward, even when it
+/// can no longer be downloaded.
+#[test]
+fn cached_after_download() {
+    let targets = test_data().join("tuf-reference-impl").join("targets");
+    let cache = TempDir::new().unwrap();
+    let repo = load(&targets, &cache);
+    assert_eq!(
+        read_to_end(repo.read_target("file1.txt").unwrap().unwrap()),
+        FILE1
+    );
+    assert_eq!(
+        fs::read(cached_path(&repo, &cache, "file1.txt")).unwrap(),
+        FILE1
+    );
+
+    let empty = TempDir::new().unwrap();
+    let repo = load(empty.path(), &cache);
+    assert_eq!(
+        read_to_end(repo.read_target("file1.txt").unwrap().unwrap()),
+        FILE1
+    );
+    assert!(repo.read_target("file2.txt").is_err());
+}
+
+/// A cached copy that doesn't match the target's metadata is downloaded again and replaced, rather
+/// than being an error.
+#[test]
+fn corrupt_cached_target() {
+    let targets = test_data().join("tuf-reference-impl").join("targets");
+    let cache = TempDir::new().unwrap();
+    let repo = load(&targets, &cache);
+    let path = cached_path(&repo, &cache, "file1.txt");
+    fs::write(&path, b"This is an example target file!").unwrap();
+    assert_eq!(
+        read_to_end(repo.read_target("file1.txt").unwrap().unwrap()),
+        FILE1
+    );
+    assert_eq!(fs::read(&path).unwrap(), FILE1);
+}
+
+/// A target that isn't read to the end isn't verified, so it isn't cached.
+#[test]
+fn partial_read_not_cached() {
+    let targets = test_data().join("tuf-reference-impl").join("targets");
+    let cache = TempDir::new().unwrap();
+    let repo = load(&targets, &cache);
+    let mut reader = repo.read_target("file1.txt").unwrap().unwrap();
+    reader.read_exact(&mut [0; 4]).unwrap();
+    drop(reader);
+    assert!(!cached_path(&repo, &cache, "file1.txt").exists());
+    assert_eq!(fs::read_dir(cache.path()).unwrap().count(), 0);
+}
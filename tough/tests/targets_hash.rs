@@ -0,0 +1,47 @@
+// Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+// These tests read the system clock, which needs the `clock` feature.
+#![cfg(feature = "clock")]
+
+use std::fs::File;
+use std::path::Path;
+use test_utils::{dir_url, test_data};
+use tough::error::Error;
+use tough::schema::RoleType;
+use tough::RepositoryLoader;
+
+mod test_utils;
+
+fn load(root: &Path, metadata: &Path, require: bool) -> tough::error::Result<tough::Repository> {
+    RepositoryLoader::new(
+        File::open(root).unwrap(),
+        dir_url(metadata),
+        dir_url(metadata.join("targets")),
+    )
+    .require_targets_hash(require)
+    .load()
+}
+
+/// The snapshot metadata of `tuf-reference-impl` lists the targets metadata without hashes.
+#[test]
+fn targets_hash_missing() {
+    let base = test_data().join("tuf-reference-impl").join("metadata");
+    let root = base.join("1.root.json");
+    load(&root, &base, false).unwrap();
+    match load(&root, &base, true) {
+        Err(Error::MetaHashesMissing { file, role, .. }) => {
+            assert_eq!(file, "targets.json");
+            assert_eq!(role, RoleType::Snapshot);
+        }
+        Err(e) => panic!("expected a missing hashes error, got: {}", e),
+        Ok(_) => panic!("loading should fail without a targets hash"),
+    }
+}
+
+/// The snapshot metadata of `rotated-root` lists the targets metadata with hashes.
+#[test]
+fn targets_hash_present() {
+    let base = test_data().join("rotated-root");
+    load(&base.join("1.root.json"), &base, true).unwrap();
+}
@@ -1,8 +1,17 @@
 // Copyright 2020 Amazon.com, Inc. or its affiliates. All Rights Reserved.
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
+#[cfg(feature = "clock")]
+use chrono::{Duration, Utc};
+use std::fs::File;
 use std::io::Read;
+#[cfg(feature = "clock")]
+use std::num::NonZeroU64;
 use std::path::{Path, PathBuf};
+#[cfg(feature = "clock")]
+use tough::editor::RepositoryEditor;
+use tough::key_source::{KeySource, LocalKeySource};
+use tough::RepositoryLoader;
 use url::Url;
 
 /// Utilities for tests. Not every test module uses every function, so we suppress unused warnings.
@@ -28,3 +37,74 @@ pub fn read_to_end<R: Read>(mut reader: R) -> Vec<u8> {
     reader.read_to_end(&mut v).unwrap();
     v
 }
+
+/// Returns the key `name` in our test data directory, in the form the editor signs with
+#[allow(unused)]
+pub fn key(name: &str) -> Vec<Box<dyn KeySource>> {
+    vec![Box::new(LocalKeySource {
+        path: test_data().join(name),
+    })]
+}
+
+/// Returns an editor for a repository with the `simple-rsa` root, whose top-level roles are all at
+/// `version` and expire in a week. Its roles are signed with `key("snakeoil.pem")`.
+#[cfg(feature = "clock")]
+#[allow(unused)]
+pub fn simple_rsa_editor(version: u64) -> RepositoryEditor {
+    let expiration = Utc::now().checked_add_signed(Duration::days(7)).unwrap();
+    let version = NonZeroU64::new(version).unwrap();
+    let mut editor =
+        RepositoryEditor::new(test_data().join("simple-rsa").join("root.json")).unwrap();
+    editor
+        .targets_expires(expiration)
+        .unwrap()
+        .targets_version(version)
+        .unwrap()
+        .snapshot_expires(expiration)
+        .snapshot_version(version)
+        .timestamp_expires(expiration)
+        .timestamp_version(version);
+    editor
+}
+
+/// Returns a loader that trusts the `simple-rsa` root, for a repository with its metadata in
+/// `metadata_dir` and its targets in `targets_dir`
+#[allow(unused)]
+pub fn simple_rsa_loader<P: AsRef<Path>, Q: AsRef<Path>>(
+    metadata_dir: P,
+    targets_dir: Q,
+) -> RepositoryLoader<File> {
+    RepositoryLoader::new(
+        File::open(test_data().join("simple-rsa").join("root.json")).unwrap(),
+        dir_url(metadata_dir),
+        dir_url(targets_dir),
+    )
+}
+
+/// Returns a loader that trusts the first root of `tuf-reference-impl`, for a copy of its metadata
+/// in `metadata_dir`, with its targets
+#[allow(unused)]
+pub fn reference_impl_loader<P: AsRef<Path>>(metadata_dir: P) -> RepositoryLoader<File> {
+    let targets_dir = test_data().join("tuf-reference-impl").join("targets");
+    reference_impl_loader_with_targets(metadata_dir, targets_dir)
+}
+
+/// Returns a loader that trusts the first root of `tuf-reference-impl`, for a copy of its metadata
+/// in `metadata_dir` and its targets in `targets_dir`
+#[allow(unused)]
+pub fn reference_impl_loader_with_targets<P: AsRef<Path>, Q: AsRef<Path>>(
+    metadata_dir: P,
+    targets_dir: Q,
+) -> RepositoryLoader<File> {
+    RepositoryLoader::new(
+        File::open(
+            test_data()
+                .join("tuf-reference-impl")
+                .join("metadata")
+                .join("1.root.json"),
+        )
+        .unwrap(),
+        dir_url(metadata_dir),
+        dir_url(targets_dir),
+    )
+}
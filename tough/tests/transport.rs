@@ -1,8 +1,14 @@
-use std::fs;
+use std::fs::{self, File};
+#[cfg(feature = "clock")]
+use std::io::Read;
 use std::str::FromStr;
 use tempfile::TempDir;
-use test_utils::read_to_end;
-use tough::{DefaultTransport, Transport, TransportErrorKind};
+use test_utils::{dir_url, read_to_end, test_data};
+use tough::{
+    DefaultTransport, FilesystemTransport, RepositoryLoader, Transport, TransportErrorKind,
+};
+#[cfg(feature = "clock")]
+use tough::{Repository, TarTransport, TransportError};
 use url::Url;
 
 mod test_utils;
@@ -47,3 +53,292 @@ fn default_transport_file() {
     let contents = String::from_utf8_lossy(&temp_vec);
     assert_eq!(contents, "123123987");
 }
+
+/// A `Transport` that serves files from the local filesystem, except for `2.root.json`, which it
+/// fails to fetch as if the server had returned a `500 Internal Server Error`.
+#[cfg(feature = "clock")]
+#[derive(Debug, Clone, Copy)]
+struct RootServerErrorTransport;
+
+#[cfg(feature = "clock")]
+impl Transport for RootServerErrorTransport {
+    fn fetch(&self, url: Url) -> Result<Box<dyn Read + Send>, TransportError> {
+        if url.path().ends_with("/2.root.json") {
+            return Err(TransportError::new_with_cause(
+                TransportErrorKind::Other,
+                url,
+                "500 Internal Server Error",
+            ));
+        }
+        FilesystemTransport.fetch(url)
+    }
+}
+
+/// A transport failure other than `FileNotFound` while looking for the next root version must fail
+/// the load rather than being treated as the end of the root chain.
+#[cfg(feature = "clock")]
+#[test]
+fn root_update_transport_error_is_err() {
+    let base = test_data().join("tuf-reference-impl");
+    let result = RepositoryLoader::new(
+        File::open(base.join("metadata").join("1.root.json")).unwrap(),
+        dir_url(base.join("metadata")),
+        dir_url(base.join("targets")),
+    )
+    .transport(RootServerErrorTransport)
+    .load();
+    match result {
+        Err(tough::error::Error::Transport { source, .. }) => {
+            assert!(matches!(source.kind(), TransportErrorKind::Other))
+        }
+        Err(e) => panic!("expected a transport error, got: {}", e),
+        Ok(_) => panic!("load should fail when the next root cannot be fetched"),
+    }
+}
+
+/// Loads `tuf-reference-impl` and archives it with `cache_to_tar`.
+#[cfg(feature = "clock")]
+fn reference_impl_tar() -> Vec<u8> {
+    let base = test_data().join("tuf-reference-impl");
+    RepositoryLoader::new(
+        File::open(base.join("metadata").join("1.root.json")).unwrap(),
+        dir_url(base.join("metadata")),
+        dir_url(base.join("targets")),
+    )
+    .load()
+    .unwrap()
+    .cache_to_tar(Vec::new(), None::<&[&str]>, false)
+    .unwrap()
+}
+
+#[cfg(feature = "clock")]
+fn load_from_tar(transport: TarTransport) -> Repository {
+    let base = test_data().join("tuf-reference-impl");
+    RepositoryLoader::new(
+        File::open(base.join("metadata").join("1.root.json")).unwrap(),
+        TarTransport::metadata_url(),
+        TarTransport::targets_url(),
+    )
+    .transport(transport)
+    .load()
+    .unwrap()
+}
+
+/// A repository can be loaded, and its targets read, directly from a tar archive.
+#[cfg(feature = "clock")]
+#[test]
+fn tar_transport() {
+    let repo = load_from_tar(TarTransport::from_reader(reference_impl_tar().as_slice()).unwrap());
+    assert_eq!(
+        read_to_end(repo.read_target("file1.txt").unwrap().unwrap()),
+        &b"This is an example target file."[..]
+    );
+    assert_eq!(
+        read_to_end(repo.read_target("file3.txt").unwrap().unwrap()),
+        &b"This is role1's target file."[..]
+    );
+}
+
+/// A gzip-compressed archive is detected and decompressed when read from a file.
+#[cfg(feature = "clock")]
+#[test]
+fn tar_transport_gzip() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("repo.tar.gz");
+    let mut encoder =
+        flate2::write::GzEncoder::new(File::create(&path).unwrap(), flate2::Compression::default());
+    std::io::copy(&mut reference_impl_tar().as_slice(), &mut encoder).unwrap();
+    encoder.finish().unwrap();
+
+    let repo = load_from_tar(TarTransport::from_path(&path).unwrap());
+    assert_eq!(
+        read_to_end(repo.read_target("file2.txt").unwrap().unwrap()),
+        &b"This is an another example target file."[..]
+    );
+}
+
+/// A zstd-compressed archive is detected and decompressed when read from a file.
+#[cfg(all(feature = "clock", feature = "zstd"))]
+#[test]
+fn tar_transport_zstd() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("repo.tar.zst");
+    zstd::stream::copy_encode(
+        reference_impl_tar().as_slice(),
+        File::create(&path).unwrap(),
+        0,
+    )
+    .unwrap();
+
+    let repo = load_from_tar(TarTransport::from_path(&path).unwrap());
+    assert_eq!(
+        read_to_end(repo.read_target("file2.txt").unwrap().unwrap()),
+        &b"This is an another example target file."[..]
+    );
+}
+
+/// Files are read from the archive when fetched, so readers of different files can be read in turns
+/// without affecting each other.
+#[cfg(feature = "clock")]
+#[test]
+fn tar_transport_interleaved_reads() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("repo.tar");
+    fs::write(&path, reference_impl_tar()).unwrap();
+    let transport = TarTransport::from_path(&path).unwrap();
+
+    let targets = TarTransport::targets_url();
+    let mut file1 = transport.fetch(targets.join("file1.txt").unwrap()).unwrap();
+    let mut file2 = transport.fetch(targets.join("file2.txt").unwrap()).unwrap();
+    let (mut data1, mut data2) = (Vec::new(), Vec::new());
+    let mut buf = [0; 4];
+    loop {
+        let n1 = file1.read(&mut buf).unwrap();
+        data1.extend_from_slice(&buf[..n1]);
+        let n2 = file2.read(&mut buf).unwrap();
+        data2.extend_from_slice(&buf[..n2]);
+        if n1 == 0 && n2 == 0 {
+            break;
+        }
+    }
+    assert_eq!(data1, &b"This is an example target file."[..]);
+    assert_eq!(data2, &b"This is an another example target file."[..]);
+}
+
+/// Files that are not in the archive, or URLs with other schemes, are reported as transport
+/// errors of the appropriate kind.
+#[cfg(feature = "clock")]
+#[test]
+fn tar_transport_errors() {
+    let transport = TarTransport::from_reader(reference_impl_tar().as_slice()).unwrap();
+    let err = transport
+        .fetch(TarTransport::metadata_url().join("2.root.json").unwrap())
+        .err()
+        .unwrap();
+    assert!(matches!(err.kind(), TransportErrorKind::FileNotFound));
+
+    let err = transport
+        .fetch(Url::from_str("file:///metadata/timestamp.json").unwrap())
+        .err()
+        .unwrap();
+    assert!(matches!(
+        err.kind(),
+        TransportErrorKind::UnsupportedUrlScheme
+    ));
+}
+
+/// Symbolic links within the base directory of a `ConfinedFilesystemTransport` are followed,
+/// including a base directory that is itself a link, but links that lead outside of it are not.
+#[cfg(unix)]
+#[test]
+fn confined_filesystem_transport_symlinks() {
+    use std::os::unix::fs::symlink;
+    use tough::ConfinedFilesystemTransport;
+
+    let dir = TempDir::new().unwrap();
+    let store = dir.path().join("store");
+    let repo = store.join("repo");
+    fs::create_dir_all(&repo).unwrap();
+    fs::write(store.join("object"), "in the store").unwrap();
+    fs::write(repo.join("file.txt"), "in the repo").unwrap();
+    fs::write(dir.path().join("secret.txt"), "outside").unwrap();
+    symlink(store.join("object"), repo.join("object.txt")).unwrap();
+    symlink(dir.path().join("secret.txt"), repo.join("secret.txt")).unwrap();
+    let link = dir.path().join("link");
+    symlink(&store, &link).unwrap();
+
+    let transport = ConfinedFilesystemTransport::new(&link);
+    let fetch = |path: &str| transport.fetch(Url::from_file_path(link.join(path)).unwrap());
+    assert_eq!(read_to_end(fetch("repo/file.txt").unwrap()), b"in the repo");
+    assert_eq!(
+        read_to_end(fetch("repo/object.txt").unwrap()),
+        b"in the store"
+    );
+
+    let err = fetch("repo/secret.txt").err().unwrap();
+    assert!(matches!(err.kind(), TransportErrorKind::FileNotFound));
+    let err = transport
+        .fetch(Url::from_file_path(dir.path().join("secret.txt")).unwrap())
+        .err()
+        .unwrap();
+    assert!(matches!(err.kind(), TransportErrorKind::FileNotFound));
+    let err = fetch("repo/missing.txt").err().unwrap();
+    assert!(matches!(err.kind(), TransportErrorKind::FileNotFound));
+}
+
+/// A `file://` URL with a relative path, whose first component is read as the host, is an error
+/// that says that the path must be absolute.
+#[cfg(unix)]
+#[test]
+fn filesystem_transport_relative_url() {
+    let url = Url::parse("file://./repo/metadata/timestamp.json").unwrap();
+    for transport in &[
+        Box::new(FilesystemTransport) as Box<dyn Transport>,
+        Box::new(DefaultTransport::new()),
+    ] {
+        let err = transport.fetch(url.clone()).err().unwrap();
+        assert!(matches!(err.kind(), TransportErrorKind::Other));
+        assert!(err.to_string().contains("must have an absolute path"));
+    }
+}
+
+/// With the default transport, a base URL with an unsupported scheme fails the load before
+/// anything is fetched, with an error that names the URL.
+#[cfg(feature = "clock")]
+#[test]
+fn default_transport_unsupported_scheme_at_load() {
+    let base = test_data().join("tuf-reference-impl");
+    let url = Url::parse("ftp://example.com/metadata/").unwrap();
+    let result = RepositoryLoader::new(
+        File::open(base.join("metadata").join("1.root.json")).unwrap(),
+        url.clone(),
+        dir_url(base.join("targets")),
+    )
+    .load();
+    match result {
+        Err(tough::error::Error::UnsupportedUrlScheme { url: err_url, .. }) => {
+            assert_eq!(err_url, url)
+        }
+        Err(e) => panic!("expected an unsupported URL scheme error, got: {}", e),
+        Ok(_) => panic!("load should fail for an ftp URL"),
+    }
+}
+
+/// Without the `http` feature, an `http` base URL fails the load with an error that says the
+/// feature is needed.
+#[cfg(not(feature = "http"))]
+#[test]
+fn default_transport_no_http_at_load() {
+    let base = test_data().join("tuf-reference-impl");
+    let loader = RepositoryLoader::new(
+        File::open(base.join("metadata").join("1.root.json")).unwrap(),
+        Url::parse("http://example.com/metadata/").unwrap(),
+        dir_url(base.join("targets")),
+    );
+    let err = loader.transport_from_url().err().unwrap();
+    assert!(matches!(
+        err,
+        tough::error::Error::HttpFeatureDisabled { .. }
+    ));
+    assert!(err.to_string().contains("http feature"));
+}
+
+/// `transport_from_url` picks a transport that can load a repository from `file` URLs.
+#[cfg(feature = "clock")]
+#[test]
+fn transport_from_file_url() {
+    let base = test_data().join("tuf-reference-impl");
+    let repo = RepositoryLoader::new(
+        File::open(base.join("metadata").join("1.root.json")).unwrap(),
+        dir_url(base.join("metadata")),
+        dir_url(base.join("targets")),
+    )
+    .transport_from_url()
+    .unwrap()
+    .load()
+    .unwrap();
+    assert_eq!(
+        read_to_end(repo.read_target("file1.txt").unwrap().unwrap()),
+        b"This is an example target file."
+    );
+}
@@ -0,0 +1,107 @@
+// Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+// These tests read the system clock, which needs the `clock` feature.
+#![cfg(feature = "clock")]
+
+use chrono::{TimeZone, Utc};
+use std::fs::{self, File};
+use tempfile::TempDir;
+use test_utils::{dir_url, test_data};
+use tough::schema::RoleType;
+use tough::{validate_metadata, validate_metadata_at, FilesystemTransport};
+
+mod test_utils;
+
+/// Test that the report covers every role of a valid repository.
+#[test]
+fn validate_tuf_reference_impl() {
+    let metadata = test_data().join("tuf-reference-impl").join("metadata");
+    let report = validate_metadata(
+        File::open(metadata.join("1.root.json")).unwrap(),
+        dir_url(&metadata),
+        &FilesystemTransport,
+    )
+    .unwrap();
+
+    let roles: Vec<_> = report
+        .roles
+        .iter()
+        .map(|role| (role.role_type, role.name.as_str(), role.version.get()))
+        .collect();
+    assert_eq!(
+        roles,
+        vec![
+            (RoleType::Root, "root", 1),
+            (RoleType::Timestamp, "timestamp", 1),
+            (RoleType::Snapshot, "snapshot", 1),
+            (RoleType::Targets, "targets", 1),
+            (RoleType::DelegatedTargets, "role1", 1),
+            (RoleType::DelegatedTargets, "role2", 1),
+        ]
+    );
+    for role in &report.roles {
+        assert!(role.valid_signatures >= role.threshold.get());
+        assert!(!role.expired);
+    }
+    assert!(!report.is_expired());
+}
+
+/// Test that expired metadata is reported rather than treated as an error.
+#[test]
+fn validate_expired_repository() {
+    let metadata = test_data().join("expired-repository").join("metadata");
+    let report = validate_metadata(
+        File::open(metadata.join("1.root.json")).unwrap(),
+        dir_url(&metadata),
+        &FilesystemTransport,
+    )
+    .unwrap();
+
+    assert!(report.is_expired());
+    let timestamp = report
+        .roles
+        .iter()
+        .find(|role| role.role_type == RoleType::Timestamp)
+        .unwrap();
+    assert!(timestamp.expired);
+}
+
+/// Test that expiration is checked against the time passed to `validate_metadata_at`.
+#[test]
+fn validate_at_time() {
+    let metadata = test_data().join("expired-repository").join("metadata");
+    let report = validate_metadata_at(
+        File::open(metadata.join("1.root.json")).unwrap(),
+        dir_url(&metadata),
+        &FilesystemTransport,
+        Utc.ymd(1998, 1, 1).and_hms(0, 0, 0),
+    )
+    .unwrap();
+    assert!(!report.is_expired());
+}
+
+/// Test that metadata that fails verification is an error.
+#[test]
+fn validate_tampered_metadata() {
+    let source = test_data().join("tuf-reference-impl").join("metadata");
+    let metadata = TempDir::new().unwrap();
+    for entry in fs::read_dir(&source).unwrap() {
+        let entry = entry.unwrap();
+        fs::copy(entry.path(), metadata.path().join(entry.file_name())).unwrap();
+    }
+    let targets_path = metadata.path().join("targets.json");
+    let targets = fs::read_to_string(&targets_path).unwrap();
+    fs::write(
+        &targets_path,
+        targets.replace("\"length\": 31", "\"length\": 32"),
+    )
+    .unwrap();
+
+    assert!(validate_metadata(
+        File::open(metadata.path().join("1.root.json")).unwrap(),
+        dir_url(metadata.path()),
+        &FilesystemTransport,
+    )
+    .is_err());
+}
@@ -0,0 +1,62 @@
+// Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+// These tests read the system clock, which needs the `clock` feature.
+#![cfg(feature = "clock")]
+
+use std::fs::File;
+use test_utils::{dir_url, test_data};
+use tough::error::Error;
+use tough::{Repository, RepositoryLoader};
+
+mod test_utils;
+
+fn load_reference_impl() -> Repository {
+    let base = test_data().join("tuf-reference-impl");
+    // Nothing is fetched from the targets URL, so it points somewhere with no targets.
+    RepositoryLoader::new(
+        File::open(base.join("metadata").join("1.root.json")).unwrap(),
+        dir_url(base.join("metadata")),
+        dir_url(base.join("no-such-targets")),
+    )
+    .load()
+    .unwrap()
+}
+
+/// Contents obtained without the repository are checked against the targets metadata, including
+/// for a target listed by a delegated role.
+#[test]
+fn verify_target_bytes() {
+    let repo = load_reference_impl();
+    assert!(repo
+        .verify_target_bytes("file1.txt", &b"This is an example target file."[..])
+        .unwrap());
+    assert!(repo
+        .verify_target_bytes("file3.txt", &b"This is role1's target file."[..])
+        .unwrap());
+}
+
+/// Contents with the wrong hash or length don't match.
+#[test]
+fn verify_target_bytes_mismatch() {
+    let repo = load_reference_impl();
+    assert!(!repo
+        .verify_target_bytes("file1.txt", &b"This is an example target FILE."[..])
+        .unwrap());
+    assert!(!repo
+        .verify_target_bytes("file1.txt", &b"This is an example target file"[..])
+        .unwrap());
+    assert!(!repo
+        .verify_target_bytes("file1.txt", &b"This is an example target file.\n"[..])
+        .unwrap());
+}
+
+/// A target that isn't in the metadata is an error.
+#[test]
+fn verify_target_bytes_unknown() {
+    let repo = load_reference_impl();
+    assert!(matches!(
+        repo.verify_target_bytes("no-such-file.txt", &b""[..]),
+        Err(Error::UnknownTarget { .. })
+    ));
+}
@@ -38,6 +38,11 @@ pub(crate) struct CloneArgs {
     #[structopt(long)]
     allow_root_download: bool,
 
+    /// Expected SHA-256 fingerprint, in hex, of the downloaded root.json file; the download is
+    /// aborted if it doesn't match
+    #[structopt(long, requires = "allow-root-download")]
+    root_fingerprint: Option<String>,
+
     /// Allow repo download for expired metadata (unsafe)
     #[structopt(long)]
     allow_expired_repo: bool,
@@ -74,7 +79,12 @@ impl CloneArgs {
             PathBuf::from(path)
         } else if self.allow_root_download {
             let outdir = std::env::current_dir().context(error::CurrentDir)?;
-            download_root(&self.metadata_base_url, self.root_version, outdir)?
+            download_root(
+                &self.metadata_base_url,
+                self.root_version,
+                self.root_fingerprint.as_deref(),
+                outdir,
+            )?
         } else {
             eprintln!("No root.json available");
             std::process::exit(1);
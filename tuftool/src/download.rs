@@ -3,10 +3,15 @@
 
 use crate::download_root::download_root;
 use crate::error::{self, Result};
-use snafu::{OptionExt, ResultExt};
-use std::fs::File;
-use std::io;
-use std::num::NonZeroU64;
+use crate::write_file;
+use chrono::SecondsFormat;
+use rayon::prelude::*;
+use ring::digest::{Context, SHA256};
+use serde_json::{json, Value};
+use snafu::{ensure, OptionExt, ResultExt};
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::num::{NonZeroU64, NonZeroUsize};
 use std::path::{Path, PathBuf};
 use structopt::StructOpt;
 use tough::{ExpirationEnforcement, Repository, RepositoryLoader};
@@ -34,26 +39,53 @@ pub(crate) struct DownloadArgs {
     #[structopt(long)]
     allow_root_download: bool,
 
+    /// Expected SHA-256 fingerprint, in hex, of the downloaded root.json file; the download is
+    /// aborted if it doesn't match
+    #[structopt(long, requires = "allow-root-download")]
+    root_fingerprint: Option<String>,
+
     /// Download only these targets, if specified
     #[structopt(short = "n", long = "target-name")]
     target_names: Vec<String>,
 
     /// Output directory of targets
-    outdir: PathBuf,
+    #[structopt(required_unless = "verify-only")]
+    outdir: Option<PathBuf>,
+
+    /// Number of targets to download concurrently
+    #[structopt(short = "j", long = "jobs", default_value = "1")]
+    jobs: NonZeroUsize,
 
     /// Allow repo download for expired metadata
     #[structopt(long)]
     allow_expired_repo: bool,
+
+    /// Fetch and verify targets without writing them to the output directory
+    #[structopt(long)]
+    verify_only: bool,
+
+    /// Download every target, even if a file that matches it is already in the output directory
+    #[structopt(long)]
+    force: bool,
+
+    /// Write a JSON report of the metadata versions and of each target's size, SHA-256 digest,
+    /// and error, if any, to this path once the repository is loaded
+    #[structopt(long = "report-json")]
+    report_json: Option<PathBuf>,
 }
 
-fn expired_repo_warning<P: AsRef<Path>>(path: P) {
+fn expired_repo_warning(path: Option<&Path>) {
+    let action = match path {
+        Some(path) => format!("Downloading repo to {}", path.display()),
+        None => "Verifying repo".to_string(),
+    };
     #[rustfmt::skip]
     eprintln!("\
 =================================================================
-Downloading repo to {}
+{}
 WARNING: `--allow-expired-repo` was passed; this is unsafe and will not establish trust, use only for testing!
 =================================================================",
-              path.as_ref().display());
+              action);
 }
 
 impl DownloadArgs {
@@ -63,7 +95,12 @@ impl DownloadArgs {
             PathBuf::from(path)
         } else if self.allow_root_download {
             let outdir = std::env::current_dir().context(error::CurrentDir)?;
-            download_root(&self.metadata_base_url, self.root_version, outdir)?
+            download_root(
+                &self.metadata_base_url,
+                self.root_version,
+                self.root_fingerprint.as_deref(),
+                outdir,
+            )?
         } else {
             eprintln!("No root.json available");
             std::process::exit(1);
@@ -71,7 +108,7 @@ impl DownloadArgs {
 
         // load repository
         let expiration_enforcement = if self.allow_expired_repo {
-            expired_repo_warning(&self.outdir);
+            expired_repo_warning(self.outdir.as_deref());
             ExpirationEnforcement::Unsafe
         } else {
             ExpirationEnforcement::Safe
@@ -85,41 +122,253 @@ impl DownloadArgs {
         .load()
         .context(error::RepoLoad)?;
 
-        // download targets
-        handle_download(&repository, &self.outdir, &self.target_names)
+        // copy requested targets, or all available targets (including delegated targets) if not
+        // specified
+        let targets = if self.target_names.is_empty() {
+            // Names that aren't safe paths are kept, so they're reported rather than skipped.
+            repository.all_targets().map(|(name, _)| name).collect()
+        } else {
+            self.target_names.clone()
+        };
+
+        let (results, downloaded) = match &self.outdir {
+            Some(outdir) if !self.verify_only => (
+                handle_download(&repository, outdir, &targets, self.jobs, self.force)?,
+                true,
+            ),
+            _ => (handle_verify(&repository, &targets), false),
+        };
+        if let Some(path) = &self.report_json {
+            write_file(path, &report(&repository, &targets, &results, downloaded))?;
+        }
+
+        // Every target was attempted; report the first failure, or how many failed verification.
+        if downloaded {
+            for result in results {
+                result?;
+            }
+        } else {
+            let failed = results.iter().filter(|result| result.is_err()).count();
+            ensure!(
+                failed == 0,
+                error::VerifyFailed {
+                    failed,
+                    total: targets.len()
+                }
+            );
+        }
+        Ok(())
+    }
+}
+
+/// The size and SHA-256 digest of a target that was read in full.
+struct TargetDigest {
+    size: u64,
+    sha256: String,
+    /// Whether the target was fetched from the repository, rather than found in the output
+    /// directory already.
+    fetched: bool,
+}
+
+/// A reader that computes the size and SHA-256 digest of everything read through it.
+struct DigestReader<R> {
+    inner: R,
+    context: Context,
+    size: u64,
+}
+
+impl<R: Read> DigestReader<R> {
+    fn new(inner: R) -> Self {
+        Self {
+            inner,
+            context: Context::new(&SHA256),
+            size: 0,
+        }
+    }
+
+    /// Returns the size and digest of the data read so far.
+    fn finish(self, fetched: bool) -> TargetDigest {
+        TargetDigest {
+            size: self.size,
+            sha256: hex::encode(self.context.finish()),
+            fetched,
+        }
+    }
+}
+
+impl<R: Read> Read for DigestReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.context.update(&buf[..n]);
+        self.size += n as u64;
+        Ok(n)
+    }
+}
+
+/// Copies `reader` to `writer` like `io::copy`, also computing the SHA-256 digest of the data,
+/// which is taken to have been fetched from the repository.
+fn copy_with_digest(reader: &mut dyn Read, writer: &mut dyn Write) -> io::Result<TargetDigest> {
+    let mut reader = DigestReader::new(reader);
+    io::copy(&mut reader, writer)?;
+    Ok(reader.finish(true))
+}
+
+/// Builds the `--report-json` report: the repository's metadata versions and earliest expiration,
+/// whether every target succeeded, and each target's outcome, in the same order as `targets`.
+/// `downloaded` says whether the targets that succeeded were written to the output directory; those
+/// that were already there aren't reported as downloaded.
+fn report(
+    repository: &Repository,
+    targets: &[String],
+    results: &[Result<TargetDigest>],
+    downloaded: bool,
+) -> Value {
+    let versions = repository.metadata_versions();
+    let earliest_expiration = repository
+        .root()
+        .signed
+        .expires
+        .min(repository.timestamp().signed.expires)
+        .min(repository.snapshot().signed.expires)
+        .min(repository.targets().signed.expires);
+    let reports: Vec<Value> = targets
+        .iter()
+        .zip(results)
+        .map(|(target, result)| match result {
+            Ok(digest) => json!({
+                "name": target,
+                "size": digest.size,
+                "sha256": digest.sha256,
+                "downloaded": downloaded && digest.fetched,
+                "error": null,
+            }),
+            Err(err) => json!({
+                "name": target,
+                "size": null,
+                "sha256": null,
+                "downloaded": false,
+                "error": err.to_string(),
+            }),
+        })
+        .collect();
+    json!({
+        "metadata": {
+            "root_version": versions.root,
+            "timestamp_version": versions.timestamp,
+            "snapshot_version": versions.snapshot,
+            "targets_version": versions.targets,
+            "delegated_versions": versions.delegated,
+            "earliest_expiration": earliest_expiration.to_rfc3339_opts(SecondsFormat::Secs, true),
+        },
+        "success": results.iter().all(Result::is_ok),
+        "targets": reports,
+    })
+}
+
+/// Returns the size and SHA-256 digest of the file at `path` if it matches the target's length and
+/// hashes in the repository metadata. Returns `None` if it doesn't, or if it can't be read, so that
+/// the target is downloaded again.
+fn existing_target(repository: &Repository, target: &str, path: &Path) -> Option<TargetDigest> {
+    // The file is only read once; its digest is taken from the bytes that were verified.
+    let mut reader = DigestReader::new(File::open(path).ok()?);
+    if !repository.verify_target_bytes(target, &mut reader).ok()? {
+        return None;
     }
+    Some(reader.finish(false))
 }
 
-fn handle_download(repository: &Repository, outdir: &Path, target_names: &[String]) -> Result<()> {
-    let download_target = |target: &str| -> Result<()> {
-        let path = PathBuf::from(outdir).join(target);
+/// Downloads each target to `outdir`, returning the result for each target in the same order as
+/// `targets`. Unless `force` is set, a target that's already in `outdir` and matches the repository
+/// metadata isn't downloaded again. Fails early only if `outdir` or the thread pool can't be set up.
+fn handle_download(
+    repository: &Repository,
+    outdir: &Path,
+    targets: &[String],
+    jobs: NonZeroUsize,
+    force: bool,
+) -> Result<Vec<Result<TargetDigest>>> {
+    let download_target = |target: &str| -> Result<TargetDigest> {
+        // Target names come from the repository, so make sure they can't point outside `outdir`.
+        let path =
+            outdir.join(tough::safe_target_path(target).context(error::TargetPath { target })?);
+        if !force && path.is_file() {
+            if let Some(digest) = existing_target(repository, target, &path) {
+                println!("\t-> {} (already present)", &target);
+                return Ok(digest);
+            }
+        }
         println!("\t-> {}", &target);
         let mut reader = repository
             .read_target(target)
             .context(error::Metadata)?
             .context(error::TargetNotFound { target })?;
-        let mut f = File::create(&path).context(error::OpenFile { path: &path })?;
-        io::copy(&mut reader, &mut f).context(error::WriteTarget)?;
-        Ok(())
+        // Target names may contain path separators, so create any intermediate directories.
+        let parent = path.parent().unwrap_or(outdir);
+        fs::create_dir_all(parent).context(error::DirCreate { path: parent })?;
+        // Write to a temporary file next to the destination and rename it into place, so that a
+        // failed or interrupted download never leaves a partial target in `outdir`.
+        let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+        let part_path = parent.join(format!(".{}.part", file_name));
+        let write_result = File::create(&part_path)
+            .context(error::OpenFile { path: &part_path })
+            .and_then(|mut f| copy_with_digest(&mut reader, &mut f).context(error::WriteTarget))
+            .and_then(|digest| {
+                fs::rename(&part_path, &path).context(error::FileRename {
+                    from: &part_path,
+                    to: &path,
+                })?;
+                Ok(digest)
+            });
+        if write_result.is_err() {
+            let _ = fs::remove_file(&part_path);
+        }
+        write_result
     };
 
-    // copy requested targets, or all available targets if not specified
-    let targets = if target_names.is_empty() {
-        repository
-            .targets()
-            .signed
-            .targets
-            .keys()
-            .cloned()
+    println!("Downloading targets to {:?}", outdir);
+    fs::create_dir_all(outdir).context(error::DirCreate { path: outdir })?;
+
+    // Download on a dedicated pool so that `--jobs` bounds the number of concurrent downloads.
+    // Every download is attempted; results are collected in the same order as `targets`, so the
+    // error that we report does not depend on which thread finished first.
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(usize::from(jobs))
+        .build()
+        .context(error::InitializeThreadPool)?;
+    Ok(pool.install(|| {
+        targets
+            .par_iter()
+            .map(|target| download_target(target))
             .collect()
-    } else {
-        target_names.to_owned()
-    };
+    }))
+}
 
-    println!("Downloading targets to {:?}", outdir);
-    std::fs::create_dir_all(outdir).context(error::DirCreate { path: outdir })?;
-    for target in targets {
-        download_target(&target)?;
+/// Reads every target to the end, discarding the data, so that `read_target` checks its length and
+/// hash. All targets are attempted; the result for each target is returned in the same order as
+/// `targets`.
+fn handle_verify(repository: &Repository, targets: &[String]) -> Vec<Result<TargetDigest>> {
+    println!("Verifying targets");
+    let names: Vec<&str> = targets.iter().map(String::as_str).collect();
+    let mut results = Vec::with_capacity(targets.len());
+    for (target, reader) in repository.read_targets(&names) {
+        let verified = reader.context(error::Metadata).and_then(|reader| {
+            let mut reader = reader.context(error::TargetNotFound { target })?;
+            copy_with_digest(&mut reader, &mut io::sink()).context(error::VerifyTarget { target })
+        });
+        match &verified {
+            Ok(_) => println!("\t-> {}: ok", target),
+            Err(err) => {
+                println!("\t-> {}: FAILED", target);
+                eprintln!("{}", err);
+            }
+        }
+        results.push(verified);
     }
-    Ok(())
+    let failed = results.iter().filter(|result| result.is_err()).count();
+    println!(
+        "{} targets verified, {} failed",
+        targets.len() - failed,
+        failed
+    );
+    results
 }
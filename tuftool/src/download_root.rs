@@ -3,17 +3,20 @@
 //! The `download_root` module owns the logic for downloading a given version of `root.json`.
 
 use crate::error::{self, Result};
-use snafu::ResultExt;
-use std::fs::File;
+use ring::digest::{digest, SHA256};
+use snafu::{ensure, ResultExt};
+use std::fs;
 use std::num::NonZeroU64;
 use std::path::{Path, PathBuf};
 use url::Url;
 
 /// Download the given version of `root.json`
-/// This is an unsafe operation, and doesn't establish trust. It should only be used for testing!
+/// Unless `fingerprint`, the expected hex SHA-256 digest of the file, is given, this is an unsafe
+/// operation, and doesn't establish trust. It should only be used for testing!
 pub(crate) fn download_root<P>(
     metadata_base_url: &Url,
     version: NonZeroU64,
+    fingerprint: Option<&str>,
     outdir: P,
 ) -> Result<PathBuf>
 where
@@ -22,18 +25,40 @@ where
     let name = format!("{}.root.json", version);
 
     let path = outdir.as_ref().join(&name);
-    let url = metadata_base_url.join(&name).context(error::UrlParse {
-        url: format!("{}/{}", metadata_base_url.as_str(), name),
+    // Without a trailing slash, joining would replace the last segment of the base URL.
+    let mut base = metadata_base_url.clone();
+    if !base.path().ends_with('/') {
+        base.set_path(&format!("{}/", base.path()));
+    }
+    let url = base.join(&name).context(error::UrlParse {
+        url: format!("{}{}", base.as_str(), name),
     })?;
-    root_warning(&path);
+    if fingerprint.is_none() {
+        root_warning(&path);
+    }
 
-    let mut root_request = reqwest::blocking::get(url.as_str())
+    let root = reqwest::blocking::get(url.as_str())
         .context(error::ReqwestGet)?
         .error_for_status()
-        .context(error::BadResponse { url })?;
+        .context(error::BadResponse { url })?
+        .bytes()
+        .context(error::ReqwestCopy)?;
 
-    let mut f = File::create(&path).context(error::OpenFile { path: &path })?;
-    root_request.copy_to(&mut f).context(error::ReqwestCopy)?;
+    // Check the fingerprint before writing the file, so that a root that doesn't match is never
+    // available to be trusted.
+    let calculated = hex::encode(digest(&SHA256, &root));
+    if let Some(expected) = fingerprint {
+        ensure!(
+            expected.eq_ignore_ascii_case(&calculated),
+            error::RootFingerprint {
+                expected,
+                calculated,
+            }
+        );
+    }
+
+    fs::write(&path, &root).context(error::FileWrite { path: &path })?;
+    println!("{} SHA-256 fingerprint: {}", name, calculated);
 
     Ok(path)
 }
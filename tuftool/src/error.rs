@@ -117,6 +117,14 @@ pub(crate) enum Error {
         backtrace: Backtrace,
     },
 
+    #[snafu(display("Failed to rename {} to {}: {}", from.display(), to.display(), source))]
+    FileRename {
+        from: PathBuf,
+        to: PathBuf,
+        source: std::io::Error,
+        backtrace: Backtrace,
+    },
+
     #[snafu(display("Failed to create temporary file in {}: {}", path.display(), source))]
     FileTempCreate {
         path: PathBuf,
@@ -256,6 +264,17 @@ pub(crate) enum Error {
         backtrace: Backtrace,
     },
 
+    #[snafu(display(
+        "Downloaded root.json does not match the expected fingerprint: calculated {}, expected {}",
+        calculated,
+        expected
+    ))]
+    RootFingerprint {
+        expected: String,
+        calculated: String,
+        backtrace: Backtrace,
+    },
+
     #[snafu(display("Failed to sign repository: {}", source))]
     SignRepo {
         source: tough::error::Error,
@@ -299,6 +318,13 @@ pub(crate) enum Error {
         backtrace: Backtrace,
     },
 
+    #[snafu(display("Refusing to download target '{}': {}", target, source))]
+    TargetPath {
+        target: String,
+        source: tough::error::Error,
+        backtrace: Backtrace,
+    },
+
     #[snafu(display("Failed to create temporary directory: {}", source))]
     TempDir {
         source: std::io::Error,
@@ -331,6 +357,20 @@ pub(crate) enum Error {
         backtrace: Backtrace,
     },
 
+    #[snafu(display("{} of {} targets failed verification", failed, total))]
+    VerifyFailed {
+        failed: usize,
+        total: usize,
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display("Failed to verify target '{}': {}", target, source))]
+    VerifyTarget {
+        target: String,
+        source: std::io::Error,
+        backtrace: Backtrace,
+    },
+
     #[snafu(display("Version number overflow"))]
     VersionOverflow { backtrace: Backtrace },
 
@@ -160,8 +160,8 @@ impl Command {
     fn init(path: &Path) -> Result<()> {
         write_file(
             path,
-            &Signed {
-                signed: Root {
+            &Signed::new(
+                Root {
                     spec_version: crate::SPEC_VERSION.to_owned(),
                     consistent_snapshot: true,
                     version: NonZeroU64::new(1).unwrap(),
@@ -175,8 +175,8 @@ impl Command {
                     },
                     _extra: HashMap::new(),
                 },
-                signatures: Vec::new(),
-            },
+                Vec::new(),
+            ),
         )
     }
 
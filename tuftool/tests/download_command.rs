@@ -2,19 +2,25 @@ mod test_utils;
 
 use assert_cmd::assert::Assert;
 use assert_cmd::Command;
+use chrono::{Duration, Utc};
 use httptest::{matchers::*, responders::*, Expectation, Server};
 use std::fs::{read_to_string, OpenOptions};
 use std::io::Write;
+use std::num::NonZeroU64;
 use std::str::FromStr;
 use tempfile::TempDir;
+use tough::editor::RepositoryEditor;
+use tough::key_source::{KeySource, LocalKeySource};
+use tough::schema::Target;
 use url::Url;
 
-/// Set an expectation in a test HTTP server which serves a file from `tuf-reference-impl`.
-fn create_successful_get(relative_path: &str) -> httptest::Expectation {
+/// Set an expectation in a test HTTP server which serves a file from `tuf-reference-impl` `times`
+/// times.
+fn create_successful_get(relative_path: &str, times: usize) -> httptest::Expectation {
     let repo_dir = test_utils::test_data().join("tuf-reference-impl");
     let file_bytes = std::fs::read(&repo_dir.join(relative_path)).unwrap();
     Expectation::matching(request::method_path("GET", format!("/{}", relative_path)))
-        .times(2)
+        .times(times)
         .respond_with(
             status_code(200)
                 .append_header("content-type", "application/octet-stream")
@@ -68,9 +74,10 @@ fn download_command(metadata_base_url: Url, targets_base_url: Url) {
         .assert()
         .success();
 
-    // Assert the files are exactly correct
+    // Assert the files are exactly correct, including the target delegated to `role1`
     assert_file_match(&outdir, "file1.txt");
     assert_file_match(&outdir, "file2.txt");
+    assert_file_match(&outdir, "file3.txt");
 
     // Add "bloop" to the end of file1.txt so that we can prove that the file is truncated when we
     // download the repo a second time into the same outdir.
@@ -99,6 +106,7 @@ fn download_command(metadata_base_url: Url, targets_base_url: Url) {
     // Assert the files are exactly correct
     assert_file_match(&outdir, "file1.txt");
     assert_file_match(&outdir, "file2.txt");
+    assert_file_match(&outdir, "file3.txt");
 }
 
 #[test]
@@ -106,13 +114,16 @@ fn download_command(metadata_base_url: Url, targets_base_url: Url) {
 // empty directory (i.e. that issue #173 is fixed).
 fn download_command_truncates_http() {
     let server = Server::run();
-    server.expect(create_successful_get("metadata/role1.json"));
-    server.expect(create_successful_get("metadata/role2.json"));
-    server.expect(create_successful_get("metadata/snapshot.json"));
-    server.expect(create_successful_get("metadata/targets.json"));
-    server.expect(create_successful_get("metadata/timestamp.json"));
-    server.expect(create_successful_get("targets/file1.txt"));
-    server.expect(create_successful_get("targets/file2.txt"));
+    server.expect(create_successful_get("metadata/role1.json", 2));
+    server.expect(create_successful_get("metadata/role2.json", 2));
+    server.expect(create_successful_get("metadata/snapshot.json", 2));
+    server.expect(create_successful_get("metadata/targets.json", 2));
+    server.expect(create_successful_get("metadata/timestamp.json", 2));
+    // Only `file1.txt` is changed in the output directory, so the other targets are only fetched
+    // once.
+    server.expect(create_successful_get("targets/file1.txt", 2));
+    server.expect(create_successful_get("targets/file2.txt", 1));
+    server.expect(create_successful_get("targets/file3.txt", 1));
     server.expect(create_unsuccessful_get("metadata/2.root.json"));
     let metadata_base_url = Url::from_str(server.url_str("/metadata").as_str()).unwrap();
     let targets_base_url = Url::from_str(server.url_str("/targets").as_str()).unwrap();
@@ -175,3 +186,370 @@ fn download_command_expired_repo_allow() {
     assert_file_match(&outdir, "file1.txt");
     assert_file_match(&outdir, "file2.txt");
 }
+
+fn verify_only(targets_base_url: &Url, outdir: Option<&TempDir>) -> Assert {
+    let repo_dir = test_utils::test_data().join("tuf-reference-impl");
+    let root_json = repo_dir.join("metadata").join("root.json");
+    let metadata_base_url = test_utils::dir_url(repo_dir.join("metadata"));
+    let mut cmd = Command::cargo_bin("tuftool").unwrap();
+    cmd.args(&[
+        "download",
+        "-r",
+        root_json.to_str().unwrap(),
+        "--metadata-url",
+        metadata_base_url.as_str(),
+        "--targets-url",
+        targets_base_url.as_str(),
+        "--verify-only",
+    ]);
+    if let Some(outdir) = outdir {
+        cmd.arg(outdir.path());
+    }
+    cmd.assert()
+}
+
+#[test]
+// Ensure that --verify-only checks every target without writing anything to the output directory
+fn download_command_verify_only() {
+    let targets_base_url = test_utils::dir_url(
+        test_utils::test_data()
+            .join("tuf-reference-impl")
+            .join("targets"),
+    );
+    let assert = verify_only(&targets_base_url, None).success();
+    let stdout = String::from_utf8_lossy(&assert.get_output().stdout);
+    assert!(stdout.contains("3 targets verified, 0 failed"));
+
+    let outdir = TempDir::new().unwrap();
+    verify_only(&targets_base_url, Some(&outdir)).success();
+    assert_eq!(std::fs::read_dir(outdir.path()).unwrap().count(), 0);
+}
+
+#[test]
+// Ensure that --verify-only fails, after checking all targets, when a target does not match its
+// metadata
+fn download_command_verify_only_bad_target() {
+    let src = test_utils::test_data()
+        .join("tuf-reference-impl")
+        .join("targets");
+    let targets_dir = TempDir::new().unwrap();
+    for name in &["file1.txt", "file2.txt", "file3.txt"] {
+        std::fs::copy(src.join(name), targets_dir.path().join(name)).unwrap();
+    }
+    // Same length as the original, different contents, so only the hash check catches it
+    std::fs::write(
+        targets_dir.path().join("file2.txt"),
+        b"This is an another example target file!",
+    )
+    .unwrap();
+
+    let assert = verify_only(&test_utils::dir_url(targets_dir.path()), None).failure();
+    let stdout = String::from_utf8_lossy(&assert.get_output().stdout);
+    assert!(stdout.contains("2 targets verified, 1 failed"));
+}
+
+#[test]
+// Ensure that --report-json describes the metadata and every target, including one that failed
+fn download_command_report_json() {
+    let repo_dir = test_utils::test_data().join("tuf-reference-impl");
+    let root_json = repo_dir.join("metadata").join("root.json");
+    let metadata_base_url = test_utils::dir_url(repo_dir.join("metadata"));
+    let targets_dir = TempDir::new().unwrap();
+    for name in &["file1.txt", "file3.txt"] {
+        std::fs::copy(
+            repo_dir.join("targets").join(name),
+            targets_dir.path().join(name),
+        )
+        .unwrap();
+    }
+    let targets_base_url = test_utils::dir_url(targets_dir.path());
+    let outdir = TempDir::new().unwrap();
+    let report_dir = TempDir::new().unwrap();
+    let report_path = report_dir.path().join("report.json");
+
+    Command::cargo_bin("tuftool")
+        .unwrap()
+        .args(&[
+            "download",
+            "-r",
+            root_json.to_str().unwrap(),
+            "--metadata-url",
+            metadata_base_url.as_str(),
+            "--targets-url",
+            targets_base_url.as_str(),
+            "--report-json",
+            report_path.to_str().unwrap(),
+            outdir.path().to_str().unwrap(),
+        ])
+        .assert()
+        .failure();
+
+    let report: serde_json::Value =
+        serde_json::from_slice(&std::fs::read(&report_path).unwrap()).unwrap();
+    assert_eq!(report["success"], false);
+    assert_eq!(report["metadata"]["root_version"], 1);
+    assert!(report["metadata"]["earliest_expiration"].is_string());
+    let targets = report["targets"].as_array().unwrap();
+    assert_eq!(targets.len(), 3);
+
+    let file1 = targets.iter().find(|t| t["name"] == "file1.txt").unwrap();
+    let contents = std::fs::read(repo_dir.join("targets").join("file1.txt")).unwrap();
+    assert_eq!(file1["size"], contents.len());
+    assert_eq!(
+        file1["sha256"],
+        hex::encode(ring::digest::digest(&ring::digest::SHA256, &contents))
+    );
+    assert_eq!(file1["downloaded"], true);
+    assert!(file1["error"].is_null());
+
+    let file2 = targets.iter().find(|t| t["name"] == "file2.txt").unwrap();
+    assert_eq!(file2["downloaded"], false);
+    assert!(file2["sha256"].is_null());
+    assert!(file2["error"].is_string());
+}
+
+/// Runs `tuftool download` of `tuf-reference-impl` into `outdir`, with the targets served from
+/// `targets_dir`, passing `--force` if `force` is set, and `--report-json` if `report` is given.
+fn download_targets_from(
+    targets_dir: &std::path::Path,
+    outdir: &TempDir,
+    force: bool,
+    report: Option<&std::path::Path>,
+) -> Assert {
+    let repo_dir = test_utils::test_data().join("tuf-reference-impl");
+    let root_json = repo_dir.join("metadata").join("root.json");
+    let metadata_base_url = test_utils::dir_url(repo_dir.join("metadata"));
+    let targets_base_url = test_utils::dir_url(targets_dir);
+    let mut command = Command::cargo_bin("tuftool").unwrap();
+    command.args(&[
+        "download",
+        "-r",
+        root_json.to_str().unwrap(),
+        "--metadata-url",
+        metadata_base_url.as_str(),
+        "--targets-url",
+        targets_base_url.as_str(),
+        outdir.path().to_str().unwrap(),
+    ]);
+    if force {
+        command.arg("--force");
+    }
+    if let Some(report) = report {
+        command.arg("--report-json").arg(report);
+    }
+    command.assert()
+}
+
+#[test]
+// Ensure that targets already in the output directory aren't downloaded again, unless they don't
+// match the metadata or `--force` is passed
+fn download_command_skips_existing() {
+    let targets_dir = TempDir::new().unwrap();
+    for name in &["file1.txt", "file2.txt", "file3.txt"] {
+        std::fs::copy(
+            test_utils::test_data()
+                .join("tuf-reference-impl")
+                .join("targets")
+                .join(name),
+            targets_dir.path().join(name),
+        )
+        .unwrap();
+    }
+    let outdir = TempDir::new().unwrap();
+    download_targets_from(targets_dir.path(), &outdir, false, None).success();
+
+    // With the served targets gone, the targets that are already present are skipped.
+    for name in &["file2.txt", "file3.txt"] {
+        std::fs::remove_file(targets_dir.path().join(name)).unwrap();
+    }
+    let assert = download_targets_from(targets_dir.path(), &outdir, false, None).success();
+    let stdout = String::from_utf8_lossy(&assert.get_output().stdout);
+    assert!(stdout.contains("file2.txt (already present)"));
+    assert!(stdout.contains("file3.txt (already present)"));
+
+    // A target that doesn't match is downloaded again, and only it is reported as downloaded.
+    std::fs::write(outdir.path().join("file1.txt"), "bloop").unwrap();
+    let report_dir = TempDir::new().unwrap();
+    let report_path = report_dir.path().join("report.json");
+    download_targets_from(targets_dir.path(), &outdir, false, Some(&report_path)).success();
+    assert_file_match(&outdir, "file1.txt");
+    let report: serde_json::Value =
+        serde_json::from_str(&read_to_string(&report_path).unwrap()).unwrap();
+    for target in report["targets"].as_array().unwrap() {
+        assert_eq!(target["downloaded"], target["name"] == "file1.txt");
+        assert!(target["sha256"].is_string());
+    }
+    assert_file_match(&outdir, "file2.txt");
+    assert_file_match(&outdir, "file3.txt");
+
+    // `--force` downloads every target, so the missing ones fail.
+    download_targets_from(targets_dir.path(), &outdir, true, None).failure();
+    assert_file_match(&outdir, "file2.txt");
+}
+
+#[test]
+// Ensure that downloading with several jobs produces the same files, and leaves nothing else behind
+fn download_command_jobs() {
+    let repo_dir = test_utils::test_data().join("tuf-reference-impl");
+    let root_json = repo_dir.join("metadata").join("root.json");
+    let metadata_base_url = test_utils::dir_url(repo_dir.join("metadata"));
+    let targets_base_url = test_utils::dir_url(repo_dir.join("targets"));
+    let outdir = TempDir::new().unwrap();
+
+    Command::cargo_bin("tuftool")
+        .unwrap()
+        .args(&[
+            "download",
+            "-r",
+            root_json.to_str().unwrap(),
+            "--metadata-url",
+            metadata_base_url.as_str(),
+            "--targets-url",
+            targets_base_url.as_str(),
+            "--jobs",
+            "4",
+            outdir.path().to_str().unwrap(),
+        ])
+        .assert()
+        .success();
+
+    assert_file_match(&outdir, "file1.txt");
+    assert_file_match(&outdir, "file2.txt");
+    assert_file_match(&outdir, "file3.txt");
+    assert_eq!(std::fs::read_dir(outdir.path()).unwrap().count(), 3);
+}
+
+#[test]
+// Ensure that a target whose name would escape the output directory is never written
+fn download_command_unsafe_target_name() {
+    let root_json = test_utils::test_data().join("simple-rsa").join("root.json");
+    let target = Target::from_path(
+        test_utils::test_data()
+            .join("tuf-reference-impl")
+            .join("targets")
+            .join("file1.txt"),
+    )
+    .unwrap();
+    let expiration = Utc::now().checked_add_signed(Duration::days(7)).unwrap();
+    let version = NonZeroU64::new(1).unwrap();
+    let mut editor = RepositoryEditor::new(&root_json).unwrap();
+    editor
+        .targets_expires(expiration)
+        .unwrap()
+        .targets_version(version)
+        .unwrap()
+        .snapshot_expires(expiration)
+        .snapshot_version(version)
+        .timestamp_expires(expiration)
+        .timestamp_version(version)
+        .add_target("../escaped.txt", target)
+        .unwrap();
+    let keys: &[Box<dyn KeySource>] = &[Box::new(LocalKeySource {
+        path: test_utils::test_data().join("snakeoil.pem"),
+    })];
+    let repo_dir = TempDir::new().unwrap();
+    editor
+        .sign(keys)
+        .unwrap()
+        .write(repo_dir.path().join("metadata"))
+        .unwrap();
+
+    let workdir = TempDir::new().unwrap();
+    let outdir = workdir.path().join("out");
+    let assert = Command::cargo_bin("tuftool")
+        .unwrap()
+        .args(&[
+            "download",
+            "-r",
+            root_json.to_str().unwrap(),
+            "--metadata-url",
+            test_utils::dir_url(repo_dir.path().join("metadata")).as_str(),
+            "--targets-url",
+            test_utils::dir_url(repo_dir.path().join("targets")).as_str(),
+            outdir.to_str().unwrap(),
+        ])
+        .assert()
+        .failure();
+    let stderr = String::from_utf8_lossy(&assert.get_output().stderr);
+    assert!(stderr.contains("Refusing to download target '../escaped.txt'"));
+    assert!(!workdir.path().join("escaped.txt").exists());
+    assert_eq!(std::fs::read_dir(&outdir).unwrap().count(), 0);
+}
+
+/// Runs `tuftool download --allow-root-download --root-fingerprint FINGERPRINT` in `workdir`, with
+/// the metadata served by `server`, verifying the targets without writing them.
+fn download_root_with_fingerprint(server: &Server, workdir: &TempDir, fingerprint: &str) -> Assert {
+    let targets_base_url = test_utils::dir_url(
+        test_utils::test_data()
+            .join("tuf-reference-impl")
+            .join("targets"),
+    );
+    Command::cargo_bin("tuftool")
+        .unwrap()
+        .current_dir(workdir.path())
+        .args(&[
+            "download",
+            "--allow-root-download",
+            "--root-fingerprint",
+            fingerprint,
+            "--metadata-url",
+            server.url_str("/metadata").as_str(),
+            "--targets-url",
+            targets_base_url.as_str(),
+            "--verify-only",
+        ])
+        .assert()
+}
+
+/// Serves `relative_path` from `tuf-reference-impl` once.
+fn serve_once(server: &Server, relative_path: &str) {
+    let repo_dir = test_utils::test_data().join("tuf-reference-impl");
+    let file_bytes = std::fs::read(&repo_dir.join(relative_path)).unwrap();
+    server.expect(
+        Expectation::matching(request::method_path("GET", format!("/{}", relative_path)))
+            .respond_with(status_code(200).body(file_bytes)),
+    );
+}
+
+const ROOT_FINGERPRINT: &str = "d5ffc36fbc55934878c89e00d00da4cfc6b7b46bcffec9f0de2615f238637d7c";
+
+#[test]
+// Ensure that a downloaded root.json that matches --root-fingerprint is used, and its fingerprint
+// is printed
+fn download_command_root_fingerprint() {
+    let server = Server::run();
+    for file in &[
+        "1.root.json",
+        "timestamp.json",
+        "snapshot.json",
+        "targets.json",
+        "role1.json",
+        "role2.json",
+    ] {
+        serve_once(&server, &format!("metadata/{}", file));
+    }
+    server.expect(
+        Expectation::matching(request::method_path("GET", "/metadata/2.root.json"))
+            .respond_with(status_code(403)),
+    );
+    let workdir = TempDir::new().unwrap();
+
+    let assert = download_root_with_fingerprint(&server, &workdir, ROOT_FINGERPRINT).success();
+    let stdout = String::from_utf8_lossy(&assert.get_output().stdout);
+    assert!(stdout.contains(ROOT_FINGERPRINT));
+    assert!(workdir.path().join("1.root.json").exists());
+}
+
+#[test]
+// Ensure that a downloaded root.json that doesn't match --root-fingerprint is neither written nor
+// used
+fn download_command_root_fingerprint_mismatch() {
+    let server = Server::run();
+    serve_once(&server, "metadata/1.root.json");
+    let workdir = TempDir::new().unwrap();
+
+    let assert = download_root_with_fingerprint(&server, &workdir, &"0".repeat(64)).failure();
+    let stderr = String::from_utf8_lossy(&assert.get_output().stderr);
+    assert!(stderr.contains(ROOT_FINGERPRINT), "{}", stderr);
+    assert!(!workdir.path().join("1.root.json").exists());
+}